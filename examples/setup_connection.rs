@@ -1,10 +1,9 @@
-use std::borrow::Cow;
 use std::io;
-use stratumv2::common::NetworkFrame;
+use stratumv2::common::{NetworkFrame, VersionRange};
 use stratumv2::mining;
-use stratumv2::types::MessageTypes;
+use stratumv2::types::{FlagSet, MessageTypes};
 use stratumv2::util::frame;
-use stratumv2::{Deserializable, Frameable, Protocol};
+use stratumv2::{Deserializable, Frameable};
 use tokio::net::{TcpListener, TcpStream};
 
 // Addreses and ports for the example.
@@ -24,7 +23,7 @@ async fn main() {
     let setup_connection_msg = mining::SetupConnection::new(
         2,
         2,
-        Cow::Borrowed(&[mining::SetupConnectionFlags::RequiresStandardJobs]),
+        FlagSet::from([mining::SetupConnectionFlags::RequiresStandardJobs]),
         "0.0.0.0",
         8545,
         "Bitmain",
@@ -44,28 +43,74 @@ async fn main() {
     miner.listen().await;
 }
 
+/// PoolPolicy is a convenience struct to demonstrate the decisions a Mining
+/// Pool makes when opening channels.
+struct PoolPolicy {
+    /// Assigns a unique extranonce_prefix to each channel.
+    extranonce: mining::ExtranonceAllocator,
+}
+
+impl mining::ChannelPolicy for PoolPolicy {
+    fn authorize_user(&mut self, _user_identity: &str) -> bool {
+        true
+    }
+
+    fn choose_target(
+        &mut self,
+        _nominal_hash_rate: f32,
+        max_target: &[u8; 32],
+    ) -> Option<[u8; 32]> {
+        Some(*max_target)
+    }
+
+    fn allocate_extranonce_prefix(
+        &mut self,
+        _channel_id: u32,
+        kind: mining::ChannelKind,
+        min_extranonce_size: u16,
+    ) -> Option<(Vec<u8>, u16)> {
+        match kind {
+            mining::ChannelKind::Standard => Some((self.extranonce.allocate_standard().ok()?, 0)),
+            mining::ChannelKind::Extended => {
+                let prefix = self.extranonce.allocate(min_extranonce_size).ok()?;
+                Some((prefix, self.extranonce.extranonce_size()))
+            }
+        }
+    }
+
+    fn release_channel(&mut self, channel: &mining::Channel) {
+        self.extranonce.release(&channel.extranonce_prefix);
+    }
+}
+
 /// Pool is a convenience struct to demonstrate simple behaviour of a Mining Pool.
 struct Pool<'a> {
     /// Listening address of the Mining Pool to accept incoming connections.
     listening_addr: &'a str,
 
-    /// The required feature flags for the mining sub protocol. These flags
-    /// should be sent on a SetupConnectionSuccess.
-    required_mining_feature_flags: &'a [mining::SetupConnectionSuccessFlags],
+    /// The sans-IO server handling the Mining Protocol connection.
+    server: mining::MiningServer<PoolPolicy>,
 }
 
 impl<'a> Pool<'a> {
     fn new(listening_addr: &'a str) -> Pool<'a> {
         Pool {
             listening_addr,
-            required_mining_feature_flags: &[
-                mining::SetupConnectionSuccessFlags::RequiresFixedVersion,
-            ],
+            server: mining::MiningServer::new(
+                PoolPolicy {
+                    extranonce: mining::ExtranonceAllocator::new(16, 4).unwrap(),
+                },
+                VersionRange::new(2, 2).unwrap(),
+                mining::FlagNegotiator::new(
+                    FlagSet::from([mining::SetupConnectionFlags::RequiresStandardJobs]),
+                    FlagSet::from([mining::SetupConnectionSuccessFlags::RequiresFixedVersion]),
+                ),
+            ),
         }
     }
 
     /// Listen on the port and handle the messages.
-    async fn listen(&self) {
+    async fn listen(&mut self) {
         let listener = TcpListener::bind(&self.listening_addr).await.unwrap();
         let mut buffer = [0u8; 1024];
 
@@ -86,34 +131,30 @@ impl<'a> Pool<'a> {
         }
     }
 
-    async fn handle_recv_bytes(&self, buffer: &[u8]) {
+    async fn handle_recv_bytes(&mut self, buffer: &[u8]) {
         let network_frame = NetworkFrame::deserialize(&buffer).unwrap();
 
         match network_frame.msg_type {
             MessageTypes::SetupConnection => {
-                // The first byte in a SetupConnection message defines the
-                // protocol.
-                match Protocol::from(network_frame.payload[0]) {
-                    Protocol::Mining => {
-                        let setup_conn =
-                            mining::SetupConnection::deserialize(&network_frame.payload).unwrap();
-
-                        let conn_success = mining::SetupConnectionSuccess::new(
-                            setup_conn.min_version,
-                            Cow::Borrowed(self.required_mining_feature_flags),
-                        );
+                let setup_conn =
+                    mining::SetupConnection::deserialize(&network_frame.payload).unwrap();
 
+                let buffer = match self.server.handle_setup_connection(&setup_conn).unwrap() {
+                    mining::SetupConnectionResponse::Success(conn_success) => {
                         println!("Pool: sending SetupConnectionSuccess message");
-                        let buffer = frame(conn_success).unwrap();
-
-                        TcpStream::connect(&MINER_ADDR)
-                            .await
-                            .unwrap()
-                            .try_write(&buffer)
-                            .unwrap();
+                        frame(conn_success).unwrap()
                     }
-                    _ => (),
-                }
+                    mining::SetupConnectionResponse::Error(conn_error) => {
+                        println!("Pool: sending SetupConnectionError message");
+                        frame(conn_error).unwrap()
+                    }
+                };
+
+                TcpStream::connect(&MINER_ADDR)
+                    .await
+                    .unwrap()
+                    .try_write(&buffer)
+                    .unwrap();
             }
             _ => (),
         }
@@ -0,0 +1,78 @@
+use crate::Result;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Args contains the `--name value` options and `--name` switches passed to
+/// a role binary.
+///
+/// # Examples
+///
+/// ```rust
+/// use roles::args::Args;
+///
+/// let args = Args::parse(vec!["--listen", "127.0.0.1:3333", "--verbose"]).unwrap();
+/// assert_eq!(args.value("listen", "0.0.0.0:0".to_string()).unwrap(), "127.0.0.1:3333");
+/// assert_eq!(args.value("difficulty", 1.0).unwrap(), 1.0);
+/// assert!(args.switch("verbose"));
+/// ```
+#[derive(Debug, Default)]
+pub struct Args {
+    values: HashMap<String, String>,
+    switches: Vec<String>,
+}
+
+impl Args {
+    /// Parses the arguments of the running process.
+    pub fn from_env() -> Result<Args> {
+        Args::parse(std::env::args().skip(1))
+    }
+
+    /// Parses a list of arguments. An option is followed by its value unless
+    /// the next argument is another option, in which case it is a switch.
+    pub fn parse<I, T>(args: I) -> Result<Args>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        let mut parsed = Args::default();
+        let mut args = args.into_iter().map(Into::into).peekable();
+
+        while let Some(arg) = args.next() {
+            let name = match arg.strip_prefix("--") {
+                Some(name) if !name.is_empty() => name.to_string(),
+                _ => return Err(format!("unexpected argument: {}", arg).into()),
+            };
+
+            match args.peek() {
+                Some(value) if !value.starts_with("--") => {
+                    let value = args.next().unwrap_or_default();
+                    parsed.values.insert(name, value);
+                }
+                _ => parsed.switches.push(name),
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    /// Returns the parsed value of an option, or `default` if the option was
+    /// not passed.
+    pub fn value<T: FromStr>(&self, name: &str, default: T) -> Result<T> {
+        match self.values.get(name) {
+            Some(value) => value
+                .parse()
+                .map_err(|_| format!("invalid value for --{}: {}", name, value).into()),
+            None => Ok(default),
+        }
+    }
+
+    /// Returns the raw value of an option, if it was passed.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(String::as_str)
+    }
+
+    /// Returns true if the switch was passed.
+    pub fn switch(&self, name: &str) -> bool {
+        self.switches.iter().any(|switch| switch == name)
+    }
+}
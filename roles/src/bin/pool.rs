@@ -0,0 +1,786 @@
+//! `stratumv2-pool` is a reference Mining Pool for local integration testing.
+//!
+//! The pool accepts Noise encrypted connections, signing its static key with
+//! the configured authority key, opens standard and extended channels for any
+//! user and sends synthetic jobs built from a template. A new prev hash is
+//! generated every `--job-interval` seconds. Shares are validated at the
+//! configured difficulty and every result is logged.
+use rand::rngs::OsRng;
+use rand::RngCore;
+use roles::args::Args;
+use roles::connection::Connection;
+use roles::keys;
+use roles::Result;
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryInto;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use stratumv2::common::{NetworkFrame, VersionRange};
+use stratumv2::mining::{
+    self, build_coinbase, coinbase_txid, merkle_root, validate_extended_share,
+    validate_standard_share, Channel, ChannelKind, ChannelPolicy, DuplicateShareDetector,
+    ExtranonceAllocator, FlagNegotiator, Job, MiningServer, NewExtendedMiningJob, NewMiningJob,
+    OpenExtendedMiningChannelResponse, OpenStandardMiningChannelResponse, SetNewPrevHash,
+    SetupConnectionFlags, SetupConnectionResponse, ShareOutcome, SubmitSharesError,
+    SubmitSharesErrorCodes, SubmitSharesSuccess,
+};
+use stratumv2::noise::{AuthorityKeyPair, StaticKeyPair};
+use stratumv2::target::Target;
+use stratumv2::types::{FlagSet, MessageTypes};
+use stratumv2::util::{hex, system_unix_time_to_u32};
+use stratumv2::Deserializable;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+
+const USAGE: &str = "\
+Usage: stratumv2-pool [OPTIONS]
+
+Options:
+    --listen ADDR               Address to listen on [default: 127.0.0.1:34254]
+    --authority-keypair FILE    File of the hex ed25519 authority keypair signing the
+                                static key, created if it does not exist. An ephemeral
+                                keypair is generated if omitted
+    --difficulty DIFFICULTY     Pool share difficulty of every channel [default: 1]
+    --template FILE             JSON template of the synthetic jobs
+    --job-interval SECONDS      Interval between new prev hashes [default: 30]
+    --certificate-validity SECONDS
+                                Validity of the signed static key [default: 86400]
+    --help                      Print this message
+
+The template file is a JSON object, every field is optional:
+    {
+        \"version\": 536870912,
+        \"nbits\": \"1d00ffff\",
+        \"coinbase_tx_prefix\": \"<hex>\",
+        \"coinbase_tx_suffix\": \"<hex>\",
+        \"merkle_path\": [\"<hex>\", ...]
+    }";
+
+/// The size of the extranonce prefix assigned to each channel.
+const EXTRANONCE_PREFIX_SIZE: u16 = 4;
+
+/// The extranonce space split between the channels, extended channels can
+/// search up to 8 bytes.
+const EXTRANONCE_SIZE: u16 = 12;
+
+/// The number of replaced jobs of a channel remembered, so late shares are
+/// rejected as stale instead of unknown.
+const STALE_JOBS: usize = 8;
+
+/// The coinbase of the default template, paying 50 BTC to a P2PK output.
+const COINBASE_TX_PREFIX: &str = concat!(
+    "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff",
+    "08044c86041b02"
+);
+const COINBASE_TX_SUFFIX: &str = concat!(
+    "ffffffff0100f2052a010000004341041b0e8c2567c12536aa13357b79a073dc4444acb83c4ec7a0e2",
+    "f99dd7457516c5817242da796924ca4e99947d087fedf9ce467cb9f7c6287078f801df276fdf84ac",
+    "00000000"
+);
+
+#[tokio::main]
+async fn main() {
+    if let Err(error) = run().await {
+        eprintln!("stratumv2-pool: {}", error);
+        std::process::exit(1);
+    }
+}
+
+async fn run() -> Result<()> {
+    let args = Args::from_env()?;
+    if args.switch("help") {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+
+    let config = Arc::new(Config::from_args(&args)?);
+    let listener = TcpListener::bind(&config.listen).await?;
+
+    println!(
+        "pool: listening on {} at difficulty {}",
+        config.listen, config.difficulty
+    );
+    println!(
+        "pool: authority public key {}",
+        hex::encode(config.authority_keypair.public.as_bytes())
+    );
+
+    let (work_sender, work) = watch::channel(Work::new(0)?);
+    let job_interval = config.job_interval;
+    tokio::spawn(async move {
+        let mut height = 0;
+        let mut interval = tokio::time::interval(job_interval);
+        interval.tick().await;
+
+        loop {
+            interval.tick().await;
+            height += 1;
+
+            match Work::new(height) {
+                Ok(work) => {
+                    println!(
+                        "pool: new prev hash {} at height {}",
+                        hex::encode(&work.prev_hash),
+                        work.height
+                    );
+                    if work_sender.send(work).is_err() {
+                        return;
+                    }
+                }
+                Err(error) => eprintln!("pool: {}", error),
+            }
+        }
+    });
+
+    loop {
+        let (stream, addr) = listener.accept().await?;
+        let config = config.clone();
+        let work = work.clone();
+
+        tokio::spawn(async move {
+            println!("pool: {} connected", addr);
+            match handle_connection(stream, config, work).await {
+                Ok(()) => println!("pool: {} disconnected", addr),
+                Err(error) => println!("pool: {} disconnected: {}", addr, error),
+            }
+        });
+    }
+}
+
+/// The configuration of the pool.
+struct Config {
+    listen: String,
+    difficulty: f64,
+    target: [u8; 32],
+    job_interval: Duration,
+    certificate_validity: Duration,
+    authority_keypair: AuthorityKeyPair,
+    static_keypair: StaticKeyPair,
+    template: Template,
+    extranonce: Arc<Mutex<ExtranonceAllocator>>,
+}
+
+impl Config {
+    fn from_args(args: &Args) -> Result<Config> {
+        let difficulty = args.value("difficulty", 1.0)?;
+
+        let authority_keypair = keys::authority_keypair(args.get("authority-keypair"))?;
+
+        let template = match args.get("template") {
+            Some(path) => Template::from_json(&fs::read_to_string(path)?)?,
+            None => Template::from_json("{}")?,
+        };
+
+        Ok(Config {
+            listen: args.value("listen", "127.0.0.1:34254".to_string())?,
+            difficulty,
+            target: Target::from_pool_difficulty(difficulty)?.into(),
+            job_interval: Duration::from_secs(args.value("job-interval", 30)?),
+            certificate_validity: Duration::from_secs(args.value("certificate-validity", 86400)?),
+            authority_keypair,
+            static_keypair: StaticKeyPair::default(),
+            template,
+            extranonce: Arc::new(Mutex::new(ExtranonceAllocator::new(
+                EXTRANONCE_SIZE,
+                EXTRANONCE_PREFIX_SIZE,
+            )?)),
+        })
+    }
+}
+
+/// The block template the synthetic jobs are built from.
+struct Template {
+    version: u32,
+    nbits: u32,
+    coinbase_tx_prefix: Vec<u8>,
+    coinbase_tx_suffix: Vec<u8>,
+    merkle_path: Vec<[u8; 32]>,
+}
+
+impl Template {
+    fn from_json(json: &str) -> Result<Template> {
+        let value: serde_json::Value = serde_json::from_str(json)?;
+        let hex_field = |name: &str, default: &str| -> Result<Vec<u8>> {
+            Ok(hex::decode(
+                value.get(name).and_then(|v| v.as_str()).unwrap_or(default),
+            )?)
+        };
+
+        let merkle_path = match value.get("merkle_path").and_then(|v| v.as_array()) {
+            Some(path) => path
+                .iter()
+                .map(|hash| {
+                    let bytes = hex::decode(hash.as_str().ok_or("merkle_path must contain hex")?)?;
+                    let hash: [u8; 32] = bytes
+                        .as_slice()
+                        .try_into()
+                        .map_err(|_| "merkle_path hashes must be 32 bytes")?;
+
+                    Ok(hash)
+                })
+                .collect::<Result<Vec<[u8; 32]>>>()?,
+            None => vec![],
+        };
+
+        Ok(Template {
+            version: value
+                .get("version")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(0x2000_0000) as u32,
+            nbits: u32::from_str_radix(
+                value
+                    .get("nbits")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("1d00ffff"),
+                16,
+            )?,
+            coinbase_tx_prefix: hex_field("coinbase_tx_prefix", COINBASE_TX_PREFIX)?,
+            coinbase_tx_suffix: hex_field("coinbase_tx_suffix", COINBASE_TX_SUFFIX)?,
+            merkle_path,
+        })
+    }
+}
+
+/// The synthetic chain tip every job is built on.
+#[derive(Debug, Clone)]
+struct Work {
+    height: u32,
+    prev_hash: [u8; 32],
+    min_ntime: u32,
+}
+
+impl Work {
+    fn new(height: u32) -> Result<Work> {
+        let mut prev_hash = [0u8; 32];
+        OsRng.fill_bytes(&mut prev_hash);
+
+        Ok(Work {
+            height,
+            prev_hash,
+            min_ntime: system_unix_time_to_u32(&SystemTime::now())?,
+        })
+    }
+}
+
+/// The ChannelPolicy of the pool, every user is authorized.
+struct PoolPolicy {
+    target: [u8; 32],
+    extranonce: Arc<Mutex<ExtranonceAllocator>>,
+}
+
+impl ChannelPolicy for PoolPolicy {
+    fn authorize_user(&mut self, _user_identity: &str) -> bool {
+        true
+    }
+
+    fn choose_target(
+        &mut self,
+        _nominal_hash_rate: f32,
+        max_target: &[u8; 32],
+    ) -> Option<[u8; 32]> {
+        if Target::from(*max_target) < Target::from(self.target) {
+            Some(*max_target)
+        } else {
+            Some(self.target)
+        }
+    }
+
+    fn allocate_extranonce_prefix(
+        &mut self,
+        _channel_id: u32,
+        kind: ChannelKind,
+        min_extranonce_size: u16,
+    ) -> Option<(Vec<u8>, u16)> {
+        let mut extranonce = self.extranonce.lock().ok()?;
+
+        match kind {
+            ChannelKind::Standard => Some((extranonce.allocate_standard().ok()?, 0)),
+            ChannelKind::Extended => {
+                let prefix = extranonce.allocate(min_extranonce_size).ok()?;
+                Some((prefix, extranonce.extranonce_size()))
+            }
+        }
+    }
+
+    fn release_channel(&mut self, channel: &Channel) {
+        if let Ok(mut extranonce) = self.extranonce.lock() {
+            extranonce.release(&channel.extranonce_prefix);
+        }
+    }
+}
+
+/// The current job of a channel, the prev hash it is built on and the ids of
+/// the jobs it replaced.
+struct ChannelJob {
+    job: Job,
+    prev_hash: SetNewPrevHash,
+    stale_job_ids: VecDeque<u32>,
+    accepted: u64,
+    rejected: u64,
+}
+
+/// Session handles a single connection to the pool.
+struct Session {
+    connection: Connection,
+    server: MiningServer<PoolPolicy>,
+    config: Arc<Config>,
+    jobs: HashMap<u32, ChannelJob>,
+    duplicates: DuplicateShareDetector,
+    next_job_id: u32,
+    addr: String,
+}
+
+async fn handle_connection(
+    stream: TcpStream,
+    config: Arc<Config>,
+    mut work: watch::Receiver<Work>,
+) -> Result<()> {
+    stream.set_nodelay(true)?;
+    let connection = Connection::accept(
+        stream,
+        &config.static_keypair,
+        &config.authority_keypair,
+        config.certificate_validity,
+    )
+    .await?;
+
+    let server = MiningServer::new(
+        PoolPolicy {
+            target: config.target,
+            extranonce: config.extranonce.clone(),
+        },
+        VersionRange::new(2, 2)?,
+        FlagNegotiator::new(
+            FlagSet::from([
+                SetupConnectionFlags::RequiresStandardJobs,
+                SetupConnectionFlags::RequiresVersionRolling,
+            ]),
+            FlagSet::empty(),
+        ),
+    );
+
+    let mut session = Session {
+        addr: connection.peer_addr()?.to_string(),
+        connection,
+        server,
+        config,
+        jobs: HashMap::new(),
+        duplicates: DuplicateShareDetector::new(1 << 16)?,
+        next_job_id: 0,
+    };
+
+    let result = loop {
+        tokio::select! {
+            frame = session.connection.recv() => match frame {
+                Ok(Some(frame)) => {
+                    let tip = work.borrow().clone();
+                    if let Err(error) = session.handle_frame(frame, &tip).await {
+                        break Err(error);
+                    }
+                }
+                Ok(None) => break Ok(()),
+                Err(error) => break Err(error),
+            },
+            changed = work.changed() => {
+                if changed.is_err() {
+                    break Ok(());
+                }
+
+                let tip = work.borrow().clone();
+                if let Err(error) = session.new_work(&tip).await {
+                    break Err(error);
+                }
+            }
+        }
+    };
+
+    session.close();
+    result
+}
+
+impl Session {
+    async fn handle_frame(&mut self, frame: NetworkFrame, work: &Work) -> Result<()> {
+        match frame.msg_type {
+            MessageTypes::SetupConnection => {
+                let message = mining::SetupConnection::deserialize(&frame.payload)?;
+
+                match self.server.handle_setup_connection(&message)? {
+                    SetupConnectionResponse::Success(success) => {
+                        println!(
+                            "pool: {} setup connection with versions {} to {}",
+                            self.addr, message.min_version, message.max_version
+                        );
+                        self.connection.send(&success).await
+                    }
+                    SetupConnectionResponse::Error(error) => {
+                        println!(
+                            "pool: {} setup connection rejected: {}",
+                            self.addr, error.error_code
+                        );
+                        self.connection.send(&error).await?;
+                        Err("the setup connection was rejected".into())
+                    }
+                }
+            }
+            MessageTypes::OpenStandardMiningChannel => {
+                let message = mining::OpenStandardMiningChannel::deserialize(&frame.payload)?;
+
+                match self.server.handle_open_standard_mining_channel(&message)? {
+                    OpenStandardMiningChannelResponse::Success(success) => {
+                        let channel_id = success.channel_id;
+                        self.connection.send(&success).await?;
+                        self.log_channel(channel_id);
+                        self.send_job(channel_id, work).await
+                    }
+                    OpenStandardMiningChannelResponse::Error(error) => {
+                        println!(
+                            "pool: {} standard channel rejected: {}",
+                            self.addr, error.error_code
+                        );
+                        self.connection.send(&error).await
+                    }
+                }
+            }
+            MessageTypes::OpenExtendedMiningChannel => {
+                let message = mining::OpenExtendedMiningChannel::deserialize(&frame.payload)?;
+
+                match self.server.handle_open_extended_mining_channel(&message)? {
+                    OpenExtendedMiningChannelResponse::Success(success) => {
+                        let channel_id = success.channel_id;
+                        self.connection.send(&success).await?;
+                        self.log_channel(channel_id);
+                        self.send_job(channel_id, work).await
+                    }
+                    OpenExtendedMiningChannelResponse::Error(error) => {
+                        println!(
+                            "pool: {} extended channel rejected: {}",
+                            self.addr, error.error_code
+                        );
+                        self.connection.send(&error).await
+                    }
+                }
+            }
+            MessageTypes::UpdateChannel => {
+                let message = mining::UpdateChannel::deserialize(&frame.payload)?;
+                println!(
+                    "pool: {} channel {} nominal hash rate {:.0} h/s",
+                    self.addr, message.channel_id, message.nominal_hash_rate
+                );
+                Ok(())
+            }
+            MessageTypes::CloseChannel => {
+                let message = mining::CloseChannel::deserialize(&frame.payload)?;
+                self.server.handle_close_channel(&message)?;
+                self.log_stats(message.channel_id, "closed");
+                self.jobs.remove(&message.channel_id);
+                Ok(())
+            }
+            MessageTypes::SubmitSharesStandard => {
+                let share = mining::SubmitSharesStandard::deserialize(&frame.payload)?;
+                let outcome = self.validate_standard_share(&share);
+
+                let outcome = outcome.and_then(|outcome| match outcome {
+                    ShareOutcome::Valid { .. } | ShareOutcome::BlockFound { .. } => {
+                        match self.duplicates.check_standard(&share) {
+                            Some(error) => Err(error.error_code),
+                            None => Ok(outcome),
+                        }
+                    }
+                    _ => Ok(outcome),
+                });
+
+                self.reply_to_share(share.channel_id, share.sequence_number, outcome)
+                    .await
+            }
+            MessageTypes::SubmitSharesExtended => {
+                let share = mining::SubmitSharesExtended::deserialize(&frame.payload)?;
+                let outcome = self.validate_extended_share(&share);
+
+                let outcome = outcome.and_then(|outcome| match outcome {
+                    ShareOutcome::Valid { .. } | ShareOutcome::BlockFound { .. } => {
+                        match self.duplicates.check_extended(&share) {
+                            Some(error) => Err(error.error_code),
+                            None => Ok(outcome),
+                        }
+                    }
+                    _ => Ok(outcome),
+                });
+
+                self.reply_to_share(share.channel_id, share.sequence_number, outcome)
+                    .await
+            }
+            msg_type => {
+                println!("pool: {} ignoring unexpected {:?}", self.addr, msg_type);
+                Ok(())
+            }
+        }
+    }
+
+    /// Sends a new job to every channel, built on the new chain tip.
+    async fn new_work(&mut self, work: &Work) -> Result<()> {
+        self.duplicates.reset();
+
+        let channel_ids: Vec<u32> = self.jobs.keys().copied().collect();
+        for channel_id in channel_ids {
+            self.send_job(channel_id, work).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Sends a future job to a channel followed by the SetNewPrevHash
+    /// activating it.
+    async fn send_job(&mut self, channel_id: u32, work: &Work) -> Result<()> {
+        let channel = self
+            .server
+            .channel(channel_id)
+            .ok_or("the channel is not open")?;
+
+        let job_id = self.next_job_id;
+        self.next_job_id = self.next_job_id.wrapping_add(1);
+
+        let template = &self.config.template;
+        let job: Job = match channel.kind {
+            ChannelKind::Standard => {
+                let coinbase = build_coinbase(
+                    &template.coinbase_tx_prefix,
+                    &channel.extranonce_prefix,
+                    &[],
+                    0,
+                    &template.coinbase_tx_suffix,
+                )?;
+
+                NewMiningJob::new(
+                    channel_id,
+                    job_id,
+                    true,
+                    template.version,
+                    merkle_root(&coinbase_txid(&coinbase), &template.merkle_path),
+                )
+                .into()
+            }
+            ChannelKind::Extended => NewExtendedMiningJob::new(
+                channel_id,
+                job_id,
+                true,
+                template.version,
+                true,
+                template.merkle_path.clone(),
+                template.coinbase_tx_prefix.clone(),
+                template.coinbase_tx_suffix.clone(),
+            )?
+            .into(),
+        };
+
+        let prev_hash = SetNewPrevHash::new(
+            channel_id,
+            job_id,
+            work.prev_hash,
+            work.min_ntime,
+            template.nbits,
+        );
+
+        match &job {
+            Job::Standard(job) => self.connection.send(job).await?,
+            Job::Extended(job) => self.connection.send(job).await?,
+        }
+        self.connection.send(&prev_hash).await?;
+
+        let (mut stale_job_ids, accepted, rejected) = match self.jobs.remove(&channel_id) {
+            Some(current) => {
+                let mut stale_job_ids = current.stale_job_ids;
+                stale_job_ids.push_back(current.job.job_id());
+                (stale_job_ids, current.accepted, current.rejected)
+            }
+            None => (VecDeque::new(), 0, 0),
+        };
+        if stale_job_ids.len() > STALE_JOBS {
+            stale_job_ids.pop_front();
+        }
+
+        self.jobs.insert(
+            channel_id,
+            ChannelJob {
+                job,
+                prev_hash,
+                stale_job_ids,
+                accepted,
+                rejected,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Validates a standard share, returning the error code to reply with if
+    /// the share does not reference the current job of an open channel.
+    fn validate_standard_share(
+        &self,
+        share: &mining::SubmitSharesStandard,
+    ) -> std::result::Result<ShareOutcome, SubmitSharesErrorCodes> {
+        let (channel, current) = self
+            .channel_job(share.channel_id)
+            .ok_or(SubmitSharesErrorCodes::InvalidChannelId)?;
+
+        match &current.job {
+            Job::Standard(job) if job.job_id == share.job_id => validate_standard_share(
+                job,
+                &current.prev_hash,
+                share,
+                self.server.version_rolling(),
+                &channel.target,
+            )
+            .map_err(|_| SubmitSharesErrorCodes::InvalidJobId),
+            _ => Err(unknown_job(current, share.job_id)),
+        }
+    }
+
+    /// Validates an extended share, returning the error code to reply with
+    /// if the share does not reference the current job of an open channel.
+    fn validate_extended_share(
+        &self,
+        share: &mining::SubmitSharesExtended,
+    ) -> std::result::Result<ShareOutcome, SubmitSharesErrorCodes> {
+        let (channel, current) = self
+            .channel_job(share.channel_id)
+            .ok_or(SubmitSharesErrorCodes::InvalidChannelId)?;
+
+        match &current.job {
+            // The specification has no error code for an extranonce of the
+            // wrong size, the share cannot be built from the job it references.
+            Job::Extended(job) if job.job_id == share.job_id => validate_extended_share(
+                job,
+                &current.prev_hash,
+                share,
+                &channel.extranonce_prefix,
+                channel.extranonce_size,
+                self.server.version_rolling(),
+                &channel.target,
+            )
+            .map_err(|_| SubmitSharesErrorCodes::InvalidJobId),
+            _ => Err(unknown_job(current, share.job_id)),
+        }
+    }
+
+    fn channel_job(&self, channel_id: u32) -> Option<(&Channel, &ChannelJob)> {
+        Some((
+            self.server.channel(channel_id)?,
+            self.jobs.get(&channel_id)?,
+        ))
+    }
+
+    async fn reply_to_share(
+        &mut self,
+        channel_id: u32,
+        sequence_number: u32,
+        outcome: std::result::Result<ShareOutcome, SubmitSharesErrorCodes>,
+    ) -> Result<()> {
+        let error_code = match outcome {
+            Ok(ShareOutcome::Valid { hash }) | Ok(ShareOutcome::BlockFound { hash }) => {
+                let difficulty = self
+                    .server
+                    .channel(channel_id)
+                    .map(|channel| Target::from(channel.target).pool_difficulty())
+                    .unwrap_or(0.0);
+
+                if matches!(outcome, Ok(ShareOutcome::BlockFound { .. })) {
+                    println!(
+                        "pool: {} channel {} found a block {}",
+                        self.addr,
+                        channel_id,
+                        hex::encode(&hash.iter().rev().copied().collect::<Vec<u8>>())
+                    );
+                }
+
+                if let Some(current) = self.jobs.get_mut(&channel_id) {
+                    current.accepted += 1;
+                }
+                println!(
+                    "pool: {} channel {} share {} accepted",
+                    self.addr, channel_id, sequence_number
+                );
+
+                return self
+                    .connection
+                    .send(&SubmitSharesSuccess::new(
+                        channel_id,
+                        sequence_number,
+                        1,
+                        difficulty.max(1.0) as u64,
+                    ))
+                    .await;
+            }
+            Ok(ShareOutcome::LowDifficulty { .. }) => SubmitSharesErrorCodes::DifficultyTooLow,
+            Ok(ShareOutcome::Stale) => SubmitSharesErrorCodes::StaleShare,
+            // The specification has no error code for an invalid version or
+            // nTime, the share does not match the job it references.
+            Ok(ShareOutcome::InvalidVersion) | Ok(ShareOutcome::InvalidTime) => {
+                SubmitSharesErrorCodes::InvalidJobId
+            }
+            Err(error_code) => error_code,
+        };
+
+        if let Some(current) = self.jobs.get_mut(&channel_id) {
+            current.rejected += 1;
+        }
+        println!(
+            "pool: {} channel {} share {} rejected: {}",
+            self.addr, channel_id, sequence_number, error_code
+        );
+
+        self.connection
+            .send(&SubmitSharesError::new(
+                channel_id,
+                sequence_number,
+                error_code,
+            ))
+            .await
+    }
+
+    fn log_channel(&self, channel_id: u32) {
+        if let Some(channel) = self.server.channel(channel_id) {
+            println!(
+                "pool: {} opened {:?} channel {} for {} with extranonce prefix {}",
+                self.addr,
+                channel.kind,
+                channel_id,
+                channel.user_identity,
+                hex::encode(&channel.extranonce_prefix)
+            );
+        }
+    }
+
+    fn log_stats(&self, channel_id: u32, event: &str) {
+        if let Some(current) = self.jobs.get(&channel_id) {
+            println!(
+                "pool: {} channel {} {}: {} shares accepted, {} rejected",
+                self.addr, channel_id, event, current.accepted, current.rejected
+            );
+        }
+    }
+
+    /// Closes every open channel, releasing their extranonce prefixes.
+    fn close(&mut self) {
+        let channel_ids: Vec<u32> = self
+            .server
+            .channels()
+            .map(|channel| channel.channel_id)
+            .collect();
+
+        for channel_id in channel_ids {
+            self.log_stats(channel_id, "closed");
+            let _ = self.server.close_channel(channel_id);
+        }
+    }
+}
+
+/// Recently replaced jobs of the channel are stale, other job ids, including
+/// the jobs of other channels, are unknown.
+fn unknown_job(current: &ChannelJob, job_id: u32) -> SubmitSharesErrorCodes {
+    if current.stale_job_ids.contains(&job_id) {
+        SubmitSharesErrorCodes::StaleShare
+    } else {
+        SubmitSharesErrorCodes::InvalidJobId
+    }
+}
@@ -0,0 +1,827 @@
+//! `stratumv2-sim-miner` simulates mining devices for load and conformance
+//! testing of a pool.
+//!
+//! The simulator opens `--channels` standard or extended channels on each of
+//! `--connections` connections. Every channel either grinds real shares at
+//! the target set by the pool, which is only practical at a low difficulty,
+//! or submits shares at the rate a device of `--hash-rate` would find them
+//! without hashing. SetTarget messages update the target of a channel and a
+//! Reconnect moves the connection to the new endpoint. The acceptance of the
+//! shares of every channel is reported every `--report-interval` seconds.
+use rand::rngs::OsRng;
+use rand::RngCore;
+use roles::args::Args;
+use roles::connection::Connection;
+use roles::Result;
+use std::collections::{BTreeMap, HashMap};
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use stratumv2::common::NetworkFrame;
+use stratumv2::mining::{
+    self, block_header, build_coinbase, coinbase_txid, header_hash, merkle_root, Job,
+    OpenExtendedMiningChannel, OpenStandardMiningChannel, SetNewPrevHash, SetupConnection,
+    SetupConnectionFlags, SubmitSharesExtended, SubmitSharesStandard,
+};
+use stratumv2::noise::AuthorityPublicKey;
+use stratumv2::target::Target;
+use stratumv2::types::{FlagSet, MessageTypes};
+use stratumv2::util::hex;
+use stratumv2::Deserializable;
+use tokio::task::JoinSet;
+
+const USAGE: &str = "\
+Usage: stratumv2-sim-miner [OPTIONS]
+
+Options:
+    --pool ADDR                 Address of the pool [default: 127.0.0.1:34254]
+    --authority-public-key HEX  Authority public key the static key of the pool
+                                must be signed by, not verified if omitted
+    --connections N             Number of connections to the pool [default: 1]
+    --channels N                Number of channels opened on each connection [default: 1]
+    --extended                  Open extended channels instead of standard channels
+    --extranonce-size SIZE      Minimum extranonce size of extended channels [default: 4]
+    --user NAME                 User identity of the channels [default: sim-miner]
+    --hash-rate H/S             Hash rate of every channel [default: 1000000]
+    --grind                     Hash block headers to find shares meeting the target,
+                                otherwise shares with random nonces are submitted at
+                                the rate the hash rate would find them
+    --duration SECONDS          Stop after the duration, runs until interrupted if 0
+                                [default: 0]
+    --report-interval SECONDS   Interval between reports [default: 10]
+    --help                      Print this message";
+
+/// The interval at which every channel performs the work of its hash rate.
+const TICK: Duration = Duration::from_millis(100);
+
+/// The maximum number of headers hashed by a channel in a single tick, so
+/// grinding at a high hash rate does not delay the shares of the connection.
+const MAX_HASHES_PER_TICK: u64 = 100_000;
+
+/// The maximum number of shares submitted by a channel in a single tick.
+const MAX_SHARES_PER_TICK: usize = 1000;
+
+#[tokio::main]
+async fn main() {
+    if let Err(error) = run().await {
+        eprintln!("stratumv2-sim-miner: {}", error);
+        std::process::exit(1);
+    }
+}
+
+async fn run() -> Result<()> {
+    let args = Args::from_env()?;
+    if args.switch("help") {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+
+    let config = Arc::new(Config::from_args(&args)?);
+    let connections: u32 = args.value("connections", 1)?;
+    let duration = Duration::from_secs(args.value("duration", 0)?);
+    let report_interval = Duration::from_secs(args.value("report-interval", 10)?);
+
+    let stats: Stats = Arc::new(Mutex::new(vec![
+        ChannelStats::default();
+        (connections * config.channels) as usize
+    ]));
+
+    let mut tasks = JoinSet::new();
+    for index in 0..connections {
+        tasks.spawn(run_connection(index, config.clone(), stats.clone()));
+    }
+
+    let stop = async {
+        if duration.as_secs() > 0 {
+            tokio::time::sleep(duration).await
+        } else {
+            std::future::pending().await
+        }
+    };
+    tokio::pin!(stop);
+
+    let mut report = tokio::time::interval(report_interval);
+    report.tick().await;
+
+    loop {
+        tokio::select! {
+            _ = report.tick() => print_report(&stats),
+            _ = &mut stop => break,
+            joined = tasks.join_next() => if joined.is_none() {
+                break;
+            },
+        }
+    }
+
+    print_report(&stats);
+    Ok(())
+}
+
+/// The configuration shared by every connection.
+struct Config {
+    pool: String,
+    authority_public_key: Option<AuthorityPublicKey>,
+    channels: u32,
+    extended: bool,
+    extranonce_size: u16,
+    user: String,
+    hash_rate: f64,
+    grind: bool,
+}
+
+impl Config {
+    fn from_args(args: &Args) -> Result<Config> {
+        let authority_public_key = match args.get("authority-public-key") {
+            Some(key) => Some(AuthorityPublicKey::from_bytes(&hex::decode(key)?)?),
+            None => None,
+        };
+
+        let hash_rate: f64 = args.value("hash-rate", 1e6)?;
+        if hash_rate.is_nan() || hash_rate <= 0.0 {
+            return Err("--hash-rate must be greater than 0".into());
+        }
+
+        Ok(Config {
+            pool: args.value("pool", "127.0.0.1:34254".to_string())?,
+            authority_public_key,
+            channels: args.value("channels", 1)?,
+            extended: args.switch("extended"),
+            extranonce_size: args.value("extranonce-size", 4)?,
+            user: args.value("user", "sim-miner".to_string())?,
+            hash_rate,
+            grind: args.switch("grind"),
+        })
+    }
+}
+
+/// The share statistics of a channel, kept across reconnections.
+#[derive(Debug, Clone, Default)]
+struct ChannelStats {
+    connection: u32,
+    channel_id: Option<u32>,
+    difficulty: f64,
+    submitted: u64,
+    accepted: u64,
+    rejected: BTreeMap<String, u64>,
+}
+
+/// The statistics of every channel, indexed by connection and request id.
+type Stats = Arc<Mutex<Vec<ChannelStats>>>;
+
+fn print_report(stats: &Stats) {
+    let stats = match stats.lock() {
+        Ok(stats) => stats,
+        Err(_) => return,
+    };
+
+    for stats in stats.iter() {
+        let channel_id = match stats.channel_id {
+            Some(channel_id) => channel_id,
+            None => continue,
+        };
+
+        let rejected: u64 = stats.rejected.values().sum();
+        let acknowledged = stats.accepted + rejected;
+        let acceptance = if acknowledged > 0 {
+            stats.accepted as f64 * 100.0 / acknowledged as f64
+        } else {
+            0.0
+        };
+
+        let reasons: String = stats
+            .rejected
+            .iter()
+            .map(|(reason, count)| format!(", {}: {}", reason, count))
+            .collect();
+
+        println!(
+            "sim-miner: connection {} channel {} at difficulty {}: {} submitted, {} accepted, {} rejected ({:.1}% accepted{})",
+            stats.connection,
+            channel_id,
+            stats.difficulty,
+            stats.submitted,
+            stats.accepted,
+            rejected,
+            acceptance,
+            reasons
+        );
+    }
+}
+
+/// Runs a connection to the pool, following Reconnect messages until the
+/// connection is closed.
+async fn run_connection(index: u32, config: Arc<Config>, stats: Stats) {
+    let mut addr = config.pool.clone();
+
+    loop {
+        match Session::run(index, &addr, &config, &stats).await {
+            Ok(Some(new_addr)) => {
+                println!(
+                    "sim-miner: connection {} reconnecting to {}",
+                    index, new_addr
+                );
+                addr = new_addr;
+            }
+            Ok(None) => {
+                println!("sim-miner: connection {} closed by the pool", index);
+                return;
+            }
+            Err(error) => {
+                println!("sim-miner: connection {} failed: {}", index, error);
+                return;
+            }
+        }
+    }
+}
+
+/// The job a channel is mining and the prev hash it is built on.
+struct Work {
+    job: Job,
+    prev_hash: SetNewPrevHash,
+}
+
+/// A share found by a channel.
+enum Share {
+    Standard(SubmitSharesStandard),
+    Extended(SubmitSharesExtended),
+}
+
+/// A simulated mining device on a channel.
+struct Device {
+    stats_index: usize,
+    target: [u8; 32],
+    extranonce_prefix: Vec<u8>,
+    extranonce_size: u16,
+    future_jobs: HashMap<u32, Job>,
+    prev_hash: Option<SetNewPrevHash>,
+    work: Option<Work>,
+    search: Search,
+}
+
+impl Device {
+    fn new(stats_index: usize, target: [u8; 32], extranonce_prefix: &[u8], size: u16) -> Device {
+        Device {
+            stats_index,
+            target,
+            extranonce_prefix: extranonce_prefix.to_vec(),
+            extranonce_size: size,
+            future_jobs: HashMap::new(),
+            prev_hash: None,
+            work: None,
+            search: Search::default(),
+        }
+    }
+
+    /// Starts mining a job if it is not a future job, otherwise keeps it for
+    /// the SetNewPrevHash activating it.
+    fn add_job(&mut self, job: Job) {
+        if job.future_job() {
+            self.future_jobs.insert(job.job_id(), job);
+            return;
+        }
+
+        if let Some(prev_hash) = &self.prev_hash {
+            self.work = Some(Work {
+                job,
+                prev_hash: copy_prev_hash(prev_hash),
+            });
+            self.search.restart();
+        }
+    }
+
+    /// Starts mining the future job activated by the prev hash. Every other
+    /// future job is discarded.
+    fn set_new_prev_hash(&mut self, prev_hash: SetNewPrevHash) {
+        self.work = self.future_jobs.remove(&prev_hash.job_id).map(|job| Work {
+            job,
+            prev_hash: copy_prev_hash(&prev_hash),
+        });
+        self.future_jobs.clear();
+        self.prev_hash = Some(prev_hash);
+        self.search.restart();
+    }
+
+    /// Performs `hashes` hashes of work, returning the shares found.
+    fn mine(&mut self, channel_id: u32, hashes: f64, grind: bool) -> Result<Vec<Share>> {
+        let work = match &self.work {
+            Some(work) => work,
+            None => return Ok(vec![]),
+        };
+
+        let target = Target::from(self.target);
+        let search = &mut self.search;
+        let mut shares = vec![];
+
+        if grind {
+            search.pending += hashes;
+            let count = (search.pending as u64).min(MAX_HASHES_PER_TICK);
+
+            for hashed in 1..=count {
+                let merkle_root =
+                    search.merkle_root(work, &self.extranonce_prefix, self.extranonce_size)?;
+                let header = block_header(
+                    job_version(&work.job),
+                    &work.prev_hash.prev_hash,
+                    &merkle_root,
+                    work.prev_hash.min_ntime,
+                    work.prev_hash.nbits,
+                    search.nonce,
+                );
+
+                if target.is_met_by(&header_hash(&header)) {
+                    let nonce = search.nonce;
+                    shares.push(search.share(channel_id, work, nonce, self.extranonce_size)?);
+                }
+
+                search.next_nonce(self.extranonce_size);
+                if hashed == count || shares.len() >= MAX_SHARES_PER_TICK {
+                    search.pending -= hashed as f64;
+                    break;
+                }
+            }
+
+            // Hashes exceeding the limits are dropped rather than performed in
+            // bursts on the next ticks, the fraction of a hash is kept.
+            search.pending = search.pending.min(1.0);
+        } else {
+            let hashes_per_share = 2f64.powi(256) / (target.to_f64() + 1.0);
+            search.pending += hashes / hashes_per_share;
+
+            while search.pending >= 1.0 && shares.len() < MAX_SHARES_PER_TICK {
+                search.pending -= 1.0;
+                shares.push(search.share(
+                    channel_id,
+                    work,
+                    OsRng.next_u32(),
+                    self.extranonce_size,
+                )?);
+            }
+
+            // Shares exceeding the limit are dropped rather than submitted in
+            // bursts on the next ticks.
+            search.pending = search.pending.min(1.0);
+        }
+
+        Ok(shares)
+    }
+}
+
+/// The progress of a device through the search space of its job.
+#[derive(Default)]
+struct Search {
+    nonce: u32,
+    extranonce: u64,
+    merkle_root: Option<[u8; 32]>,
+    sequence_number: u32,
+    pending: f64,
+}
+
+impl Search {
+    fn restart(&mut self) {
+        self.nonce = 0;
+        self.extranonce = 0;
+        self.merkle_root = None;
+        self.pending = 0.0;
+    }
+
+    /// Moves to the next nonce, rolling the extranonce once every nonce was
+    /// searched. The extranonce wraps around within the `size` bytes of the
+    /// channel, so it always fits the channel.
+    fn next_nonce(&mut self, size: u16) {
+        self.nonce = self.nonce.wrapping_add(1);
+        if self.nonce == 0 {
+            let max = match size {
+                0..=7 => (1u64 << (8 * size)) - 1,
+                _ => u64::MAX,
+            };
+
+            self.extranonce = match self.extranonce.checked_add(1) {
+                Some(extranonce) if extranonce <= max => extranonce,
+                _ => 0,
+            };
+            self.merkle_root = None;
+        }
+    }
+
+    /// Returns the extranonce searched by the device, padded to the
+    /// extranonce size of the channel.
+    fn extranonce(&self, size: u16) -> Vec<u8> {
+        let mut extranonce = self.extranonce.to_le_bytes().to_vec();
+        extranonce.resize(size as usize, 0);
+
+        extranonce
+    }
+
+    /// Returns the merkle root of the job, building the coinbase of extended
+    /// jobs from the current extranonce.
+    fn merkle_root(
+        &mut self,
+        work: &Work,
+        extranonce_prefix: &[u8],
+        size: u16,
+    ) -> Result<[u8; 32]> {
+        if let Some(merkle_root) = self.merkle_root {
+            return Ok(merkle_root);
+        }
+
+        let root = match &work.job {
+            Job::Standard(job) => job.merkle_root,
+            Job::Extended(job) => {
+                let coinbase = build_coinbase(
+                    job.coinbase_tx_prefix.as_ref(),
+                    extranonce_prefix,
+                    &self.extranonce(size),
+                    size,
+                    job.coinbase_tx_suffix.as_ref(),
+                )?;
+
+                merkle_root(&coinbase_txid(&coinbase), &job.merkle_path)
+            }
+        };
+
+        self.merkle_root = Some(root);
+        Ok(root)
+    }
+
+    fn share(&mut self, channel_id: u32, work: &Work, nonce: u32, size: u16) -> Result<Share> {
+        let sequence_number = self.sequence_number;
+        self.sequence_number = self.sequence_number.wrapping_add(1);
+
+        let ntime = work.prev_hash.min_ntime;
+        Ok(match &work.job {
+            Job::Standard(job) => Share::Standard(SubmitSharesStandard::new(
+                channel_id,
+                sequence_number,
+                job.job_id,
+                nonce,
+                ntime,
+                job.version,
+            )),
+            Job::Extended(job) => Share::Extended(SubmitSharesExtended::new(
+                channel_id,
+                sequence_number,
+                job.job_id,
+                nonce,
+                ntime,
+                job.version,
+                self.extranonce(size),
+            )?),
+        })
+    }
+}
+
+fn job_version(job: &Job) -> u32 {
+    match job {
+        Job::Standard(job) => job.version,
+        Job::Extended(job) => job.version,
+    }
+}
+
+/// SetNewPrevHash is not Clone, every device of a connection keeps a copy.
+fn copy_prev_hash(prev_hash: &SetNewPrevHash) -> SetNewPrevHash {
+    SetNewPrevHash::new(
+        prev_hash.channel_id,
+        prev_hash.job_id,
+        prev_hash.prev_hash,
+        prev_hash.min_ntime,
+        prev_hash.nbits,
+    )
+}
+
+/// Session is a single connection to the pool and the devices mining on its
+/// channels.
+struct Session<'a> {
+    index: u32,
+    addr: &'a str,
+    config: &'a Config,
+    stats: &'a Stats,
+    connection: Connection,
+    devices: HashMap<u32, Device>,
+}
+
+impl<'a> Session<'a> {
+    /// Connects to the pool and mines until the connection is closed, or a
+    /// Reconnect is received in which case the new address is returned.
+    async fn run(
+        index: u32,
+        addr: &'a str,
+        config: &'a Config,
+        stats: &'a Stats,
+    ) -> Result<Option<String>> {
+        let (host, port) = split_addr(addr)?;
+        let mut connection =
+            Connection::connect(addr, config.authority_public_key.as_ref()).await?;
+
+        let flags = if config.extended {
+            FlagSet::empty()
+        } else {
+            FlagSet::from([SetupConnectionFlags::RequiresStandardJobs])
+        };
+        let device_id = format!("sim-miner-{}", index);
+        connection
+            .send(&SetupConnection::new(
+                2,
+                2,
+                flags,
+                host,
+                port,
+                "stratumv2",
+                "sim-miner",
+                env!("CARGO_PKG_VERSION"),
+                device_id.as_str(),
+            )?)
+            .await?;
+
+        let mut session = Session {
+            index,
+            addr,
+            config,
+            stats,
+            connection,
+            devices: HashMap::new(),
+        };
+        session.setup_connection().await?;
+        session.open_channels().await?;
+
+        let mut tick = tokio::time::interval(TICK);
+        loop {
+            tokio::select! {
+                frame = session.connection.recv() => match frame? {
+                    Some(frame) => {
+                        if let Some(addr) = session.handle_frame(frame)? {
+                            return Ok(Some(addr));
+                        }
+                    }
+                    None => return Ok(None),
+                },
+                _ = tick.tick() => session.mine().await?,
+            }
+        }
+    }
+
+    async fn setup_connection(&mut self) -> Result<()> {
+        let frame = self
+            .connection
+            .recv()
+            .await?
+            .ok_or("the connection was closed during setup")?;
+
+        match frame.msg_type {
+            MessageTypes::SetupConnectionSuccess => {
+                let success = mining::SetupConnectionSuccess::deserialize(&frame.payload)?;
+                println!(
+                    "sim-miner: connection {} setup with version {}",
+                    self.index, success.used_version
+                );
+                Ok(())
+            }
+            MessageTypes::SetupConnectionError => {
+                let error = mining::SetupConnectionError::deserialize(&frame.payload)?;
+                Err(format!("the setup connection was rejected: {}", error.error_code).into())
+            }
+            msg_type => Err(format!("unexpected {:?} during setup", msg_type).into()),
+        }
+    }
+
+    async fn open_channels(&mut self) -> Result<()> {
+        for request_id in 0..self.config.channels {
+            if self.config.extended {
+                self.connection
+                    .send(&OpenExtendedMiningChannel::new(
+                        request_id,
+                        self.config.user.as_str(),
+                        self.config.hash_rate as f32,
+                        [0xff; 32],
+                        self.config.extranonce_size,
+                    )?)
+                    .await?;
+            } else {
+                self.connection
+                    .send(&OpenStandardMiningChannel::new(
+                        request_id,
+                        self.config.user.as_str(),
+                        self.config.hash_rate as f32,
+                        [0xff; 32],
+                    )?)
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handles a message from the pool, returning the new address if the
+    /// pool asked to reconnect.
+    fn handle_frame(&mut self, frame: NetworkFrame) -> Result<Option<String>> {
+        match frame.msg_type {
+            MessageTypes::OpenStandardMiningChannelSuccess => {
+                let success =
+                    mining::OpenStandardMiningChannelSuccess::deserialize(&frame.payload)?;
+                self.add_device(
+                    success.request_id,
+                    success.channel_id,
+                    success.target,
+                    success.extranonce_prefix.as_ref(),
+                    0,
+                );
+            }
+            MessageTypes::OpenExtendedMiningChannelSuccess => {
+                let success =
+                    mining::OpenExtendedMiningChannelSuccess::deserialize(&frame.payload)?;
+                self.add_device(
+                    success.request_id,
+                    success.channel_id,
+                    success.target,
+                    success.extranonce_prefix.as_ref(),
+                    success.extranonce_size,
+                );
+            }
+            MessageTypes::OpenStandardMiningChannelError => {
+                let error = mining::OpenStandardMiningChannelError::deserialize(&frame.payload)?;
+                println!(
+                    "sim-miner: connection {} channel request {} rejected: {}",
+                    self.index, error.request_id, error.error_code
+                );
+            }
+            MessageTypes::OpenExtendedMiningChannelError => {
+                let error = mining::OpenExtendedMiningChannelError::deserialize(&frame.payload)?;
+                println!(
+                    "sim-miner: connection {} channel request {} rejected: {}",
+                    self.index, error.request_id, error.error_code
+                );
+            }
+            MessageTypes::NewMiningJob => {
+                let job = mining::NewMiningJob::deserialize(&frame.payload)?;
+                if let Some(device) = self.devices.get_mut(&job.channel_id) {
+                    device.add_job(job.into());
+                }
+            }
+            MessageTypes::NewExtendedMiningJob => {
+                let job = mining::NewExtendedMiningJob::deserialize(&frame.payload)?;
+                if let Some(device) = self.devices.get_mut(&job.channel_id) {
+                    device.add_job(job.into());
+                }
+            }
+            MessageTypes::SetNewPrevHash => {
+                let prev_hash = SetNewPrevHash::deserialize(&frame.payload)?;
+                if let Some(device) = self.devices.get_mut(&prev_hash.channel_id) {
+                    device.set_new_prev_hash(prev_hash);
+                }
+            }
+            MessageTypes::SetTarget => {
+                let message = mining::SetTarget::deserialize(&frame.payload)?;
+                if let Some(device) = self.devices.get_mut(&message.channel_id) {
+                    device.target = message.maximum_target;
+
+                    let difficulty = Target::from(device.target).pool_difficulty();
+                    update_stats(self.stats, device.stats_index, |stats| {
+                        stats.difficulty = difficulty
+                    });
+                    println!(
+                        "sim-miner: connection {} channel {} set to difficulty {}",
+                        self.index, message.channel_id, difficulty
+                    );
+                }
+            }
+            MessageTypes::SubmitSharesSuccess => {
+                let success = mining::SubmitSharesSuccess::deserialize(&frame.payload)?;
+                if let Some(device) = self.devices.get(&success.channel_id) {
+                    let accepted = success.new_submits_accepted_count as u64;
+                    update_stats(self.stats, device.stats_index, |stats| {
+                        stats.accepted += accepted
+                    });
+                }
+            }
+            MessageTypes::SubmitSharesError => {
+                let error = mining::SubmitSharesError::deserialize(&frame.payload)?;
+                if let Some(device) = self.devices.get(&error.channel_id) {
+                    let reason = error.error_code.to_string();
+                    update_stats(self.stats, device.stats_index, |stats| {
+                        *stats.rejected.entry(reason).or_insert(0) += 1
+                    });
+                }
+            }
+            MessageTypes::CloseChannel => {
+                let message = mining::CloseChannel::deserialize(&frame.payload)?;
+                if let Some(device) = self.devices.remove(&message.channel_id) {
+                    update_stats(self.stats, device.stats_index, |stats| {
+                        stats.channel_id = None
+                    });
+                    println!(
+                        "sim-miner: connection {} channel {} closed by the pool",
+                        self.index, message.channel_id
+                    );
+                }
+            }
+            MessageTypes::Reconnect => {
+                let message = mining::Reconnect::deserialize(&frame.payload)?;
+                let (host, port) = split_addr(self.addr)?;
+
+                let new_host: String = message.new_host.into();
+                let host = if new_host.is_empty() { host } else { &new_host };
+                let port = if message.new_port == 0 {
+                    port
+                } else {
+                    message.new_port
+                };
+
+                return Ok(Some(format!("{}:{}", host, port)));
+            }
+            msg_type => println!(
+                "sim-miner: connection {} ignoring unexpected {:?}",
+                self.index, msg_type
+            ),
+        }
+
+        Ok(None)
+    }
+
+    fn add_device(
+        &mut self,
+        request_id: u32,
+        channel_id: u32,
+        target: [u8; 32],
+        extranonce_prefix: &[u8],
+        extranonce_size: u16,
+    ) {
+        let stats_index = (self.index * self.config.channels + request_id) as usize;
+        let difficulty = Target::from(target).pool_difficulty();
+
+        let connection = self.index;
+        update_stats(self.stats, stats_index, |stats| {
+            stats.connection = connection;
+            stats.channel_id = Some(channel_id);
+            stats.difficulty = difficulty;
+        });
+        self.devices.insert(
+            channel_id,
+            Device::new(stats_index, target, extranonce_prefix, extranonce_size),
+        );
+
+        println!(
+            "sim-miner: connection {} opened channel {} at difficulty {}",
+            self.index, channel_id, difficulty
+        );
+    }
+
+    /// Performs a tick of work on every channel and submits the shares found.
+    /// The work is performed on a blocking thread, so grinding does not
+    /// starve the other connections.
+    async fn mine(&mut self) -> Result<()> {
+        let hashes = self.config.hash_rate * TICK.as_secs_f64();
+        let grind = self.config.grind;
+        let mut devices = mem::take(&mut self.devices);
+
+        let (devices, found) = tokio::task::spawn_blocking(move || {
+            let found = devices
+                .iter_mut()
+                .map(|(channel_id, device)| {
+                    Ok((device.stats_index, device.mine(*channel_id, hashes, grind)?))
+                })
+                .collect::<Result<Vec<(usize, Vec<Share>)>>>();
+
+            (devices, found)
+        })
+        .await?;
+        self.devices = devices;
+
+        let mut shares = vec![];
+        for (stats_index, found) in found? {
+            let submitted = found.len() as u64;
+            if submitted > 0 {
+                update_stats(self.stats, stats_index, |stats| {
+                    stats.submitted += submitted
+                });
+            }
+
+            shares.extend(found);
+        }
+
+        for share in shares {
+            match share {
+                Share::Standard(share) => self.connection.send(&share).await?,
+                Share::Extended(share) => self.connection.send(&share).await?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Updates the statistics of a channel.
+fn update_stats<F: FnOnce(&mut ChannelStats)>(stats: &Stats, index: usize, update: F) {
+    if let Ok(mut stats) = stats.lock() {
+        if let Some(stats) = stats.get_mut(index) {
+            update(stats);
+        }
+    }
+}
+
+/// Splits an address into its host and port.
+fn split_addr(addr: &str) -> Result<(&str, u16)> {
+    let (host, port) = addr
+        .rsplit_once(':')
+        .ok_or_else(|| format!("the address has no port: {}", addr))?;
+
+    Ok((host, port.parse()?))
+}
@@ -0,0 +1,645 @@
+//! `stratumv2-template-provider` is a stand-in Template Provider for local
+//! development of the Template Distribution protocol.
+//!
+//! Templates are loaded from serialized blocks, as raw bytes or hex, and from
+//! JSON getblocktemplate results. Every connection is sent the current
+//! template as a future NewTemplate followed by a SetNewPrevHash activating
+//! it, and the next template is served every `--interval` seconds, wrapping
+//! around after the last one. The transactions of a template are returned on
+//! RequestTransactionData, and the block assembled from a SubmitSolution is
+//! written to the `--output` directory as hex.
+use roles::args::Args;
+use roles::block::{Block, Transaction, TxOut};
+use roles::connection::Connection;
+use roles::keys;
+use roles::Result;
+use std::convert::TryInto;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use stratumv2::common::{NetworkFrame, SetupConnectionErrorCodes};
+use stratumv2::mining::{block_header, header_hash, merkle_path, merkle_root};
+use stratumv2::noise::{AuthorityKeyPair, StaticKeyPair};
+use stratumv2::target::Target;
+use stratumv2::template_distribution::{
+    CoinbaseOutputDataSize, NewTemplate, RequestTransactionData, RequestTransactionDataError,
+    RequestTransactionDataErrorCodes, RequestTransactionDataSuccess, SetNewPrevHash,
+    SetupConnection, SetupConnectionError, SetupConnectionSuccess, SubmitSolution,
+};
+use stratumv2::types::{FlagSet, MessageTypes};
+use stratumv2::util::hex;
+use stratumv2::{Deserializable, Protocol};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+
+const USAGE: &str = "\
+Usage: stratumv2-template-provider --templates PATH [OPTIONS]
+
+Options:
+    --templates PATH            A block or getblocktemplate file, or a directory of
+                                them served in file name order. Files ending in .json
+                                are getblocktemplate results, other files are blocks
+                                serialized as raw bytes or hex
+    --listen ADDR               Address to listen on [default: 127.0.0.1:8442]
+    --authority-keypair FILE    File of the hex ed25519 authority keypair signing the
+                                static key, created if it does not exist. An ephemeral
+                                keypair is generated if omitted
+    --interval SECONDS          Interval between new templates [default: 30]
+    --output DIR                Directory the submitted blocks are written to [default: .]
+    --certificate-validity SECONDS
+                                Validity of the signed static key [default: 86400]
+    --help                      Print this message";
+
+/// The protocol version supported by the Template Provider.
+const PROTOCOL_VERSION: u16 = 2;
+
+/// The start of the script of the output committing to the witness data of a
+/// block, as defined by BIP141.
+const WITNESS_COMMITMENT_HEADER: [u8; 6] = [0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
+
+#[tokio::main]
+async fn main() {
+    if let Err(error) = run().await {
+        eprintln!("stratumv2-template-provider: {}", error);
+        std::process::exit(1);
+    }
+}
+
+async fn run() -> Result<()> {
+    let args = Args::from_env()?;
+    if args.switch("help") {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+
+    let config = Arc::new(Config::from_args(&args)?);
+    let listener = TcpListener::bind(&config.listen).await?;
+
+    println!(
+        "template-provider: listening on {} with {} templates",
+        config.listen,
+        config.templates.len()
+    );
+    println!(
+        "template-provider: authority public key {}",
+        hex::encode(config.authority_keypair.public.as_bytes())
+    );
+    config.log_template(1);
+
+    let (sender, current) = watch::channel(1u64);
+    let ticker_config = config.clone();
+    tokio::spawn(async move {
+        let mut template_id = 1;
+        let mut interval = tokio::time::interval(ticker_config.interval);
+        interval.tick().await;
+
+        loop {
+            interval.tick().await;
+            template_id += 1;
+
+            ticker_config.log_template(template_id);
+            if sender.send(template_id).is_err() {
+                return;
+            }
+        }
+    });
+
+    loop {
+        let (stream, addr) = listener.accept().await?;
+        let config = config.clone();
+        let current = current.clone();
+
+        tokio::spawn(async move {
+            println!("template-provider: {} connected", addr);
+            match handle_connection(stream, config, current).await {
+                Ok(()) => println!("template-provider: {} disconnected", addr),
+                Err(error) => println!("template-provider: {} disconnected: {}", addr, error),
+            }
+        });
+    }
+}
+
+/// The configuration of the Template Provider.
+struct Config {
+    listen: String,
+    interval: Duration,
+    output: PathBuf,
+    certificate_validity: Duration,
+    authority_keypair: AuthorityKeyPair,
+    static_keypair: StaticKeyPair,
+    templates: Vec<Template>,
+}
+
+impl Config {
+    fn from_args(args: &Args) -> Result<Config> {
+        let path = args.get("templates").ok_or("--templates is required")?;
+        let templates = load_templates(Path::new(path))?;
+        if templates.is_empty() {
+            return Err(format!("no templates found in {}", path).into());
+        }
+
+        let authority_keypair = keys::authority_keypair(args.get("authority-keypair"))?;
+
+        Ok(Config {
+            listen: args.value("listen", "127.0.0.1:8442".to_string())?,
+            interval: Duration::from_secs(args.value("interval", 30)?),
+            output: PathBuf::from(args.value("output", ".".to_string())?),
+            certificate_validity: Duration::from_secs(args.value("certificate-validity", 86400)?),
+            authority_keypair,
+            static_keypair: StaticKeyPair::default(),
+            templates,
+        })
+    }
+
+    /// Returns the template served with a template id. Template ids start at
+    /// 1 and each new template served increments the id, so ids above the
+    /// current one are unknown.
+    fn template(&self, template_id: u64, current: u64) -> Option<&Template> {
+        if template_id == 0 || template_id > current {
+            return None;
+        }
+
+        let index = (template_id - 1) % self.templates.len() as u64;
+        self.templates.get(index as usize)
+    }
+
+    fn log_template(&self, template_id: u64) {
+        if let Some(template) = self.template(template_id, template_id) {
+            println!(
+                "template-provider: serving template {} from {} on prev hash {} with {} transactions",
+                template_id,
+                template.name,
+                hex::encode(&reversed(&template.prev_hash)),
+                template.transactions.len()
+            );
+        }
+    }
+}
+
+/// Loads the templates of a file, or of every file of a directory in file
+/// name order.
+fn load_templates(path: &Path) -> Result<Vec<Template>> {
+    if !path.is_dir() {
+        return Ok(vec![Template::load(path)?]);
+    }
+
+    let mut paths = fs::read_dir(path)?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Result<Vec<PathBuf>>>()?;
+    paths.retain(|path| path.is_file());
+    paths.sort();
+
+    paths.iter().map(|path| Template::load(path)).collect()
+}
+
+/// Template is a block template served to the Clients.
+struct Template {
+    name: String,
+    version: u32,
+    prev_hash: [u8; 32],
+    timestamp: u32,
+    nbits: u32,
+    coinbase_tx_version: u32,
+    coinbase_prefix: Vec<u8>,
+    coinbase_tx_input_sequence: u32,
+    coinbase_tx_value_remaining: u64,
+    coinbase_tx_outputs: Vec<TxOut>,
+    coinbase_tx_locktime: u32,
+    transactions: Vec<Transaction>,
+    excess_data: Vec<u8>,
+}
+
+impl Template {
+    fn load(path: &Path) -> Result<Template> {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let bytes = fs::read(path)?;
+
+        let template = if path.extension().is_some_and(|ext| ext == "json") {
+            Template::from_block_template(name, &serde_json::from_slice(&bytes)?)
+        } else {
+            let text = String::from_utf8_lossy(&bytes);
+            let block = match hex::decode(text.trim()) {
+                Ok(bytes) => Block::parse(&bytes)?,
+                Err(_) => Block::parse(&bytes)?,
+            };
+
+            Template::from_block(name, &block)
+        };
+
+        template.map_err(|error| format!("{}: {}", path.display(), error).into())
+    }
+
+    /// Builds a template from a block. The outputs of the coinbase, except
+    /// the witness commitment, are removed and their value is left to the
+    /// Client.
+    fn from_block(name: String, block: &Block) -> Result<Template> {
+        let coinbase = &block.transactions[0];
+        let input = coinbase
+            .inputs
+            .first()
+            .ok_or("the coinbase transaction has no input")?;
+
+        let (required, removed): (Vec<TxOut>, Vec<TxOut>) = coinbase
+            .outputs
+            .iter()
+            .cloned()
+            .partition(|output| output.script_pubkey.starts_with(&WITNESS_COMMITMENT_HEADER));
+
+        Ok(Template {
+            name,
+            version: block.version(),
+            prev_hash: block.prev_hash(),
+            timestamp: block.time(),
+            nbits: block.nbits(),
+            coinbase_tx_version: coinbase.version,
+            coinbase_prefix: height_push(&input.script_sig),
+            coinbase_tx_input_sequence: input.sequence,
+            coinbase_tx_value_remaining: removed.iter().map(|output| output.value).sum(),
+            coinbase_tx_outputs: required,
+            coinbase_tx_locktime: coinbase.lock_time,
+            transactions: block.transactions[1..].to_vec(),
+            excess_data: input.witness.first().cloned().unwrap_or_default(),
+        })
+    }
+
+    /// Builds a template from the result of a getblocktemplate call.
+    fn from_block_template(name: String, value: &serde_json::Value) -> Result<Template> {
+        let field = |name: &str| value.get(name).ok_or(format!("missing field {}", name));
+        let u64_field = |name: &str| -> Result<u64> {
+            Ok(field(name)?
+                .as_u64()
+                .ok_or(format!("{} must be a number", name))?)
+        };
+        let str_field = |name: &str| -> Result<&str> {
+            Ok(field(name)?
+                .as_str()
+                .ok_or(format!("{} must be a string", name))?)
+        };
+
+        let prev_hash: [u8; 32] = reversed(&hex::decode(str_field("previousblockhash")?)?)
+            .as_slice()
+            .try_into()
+            .map_err(|_| "previousblockhash must be 32 bytes")?;
+
+        let transactions = field("transactions")?
+            .as_array()
+            .ok_or("transactions must be an array")?
+            .iter()
+            .map(|transaction| {
+                let data = transaction
+                    .get("data")
+                    .and_then(|data| data.as_str())
+                    .ok_or("transactions must contain data")?;
+
+                Transaction::parse(&hex::decode(data)?)
+            })
+            .collect::<Result<Vec<Transaction>>>()?;
+
+        // The witness reserved value of the coinbase is all zeros, as assumed
+        // by the default witness commitment.
+        let (coinbase_tx_outputs, excess_data) = match value
+            .get("default_witness_commitment")
+            .and_then(|commitment| commitment.as_str())
+        {
+            Some(commitment) => (
+                vec![TxOut {
+                    value: 0,
+                    script_pubkey: hex::decode(commitment)?,
+                }],
+                vec![0u8; 32],
+            ),
+            None => (vec![], vec![]),
+        };
+
+        Ok(Template {
+            name,
+            version: u64_field("version")? as u32,
+            prev_hash,
+            timestamp: u64_field("curtime")? as u32,
+            nbits: u32::from_str_radix(str_field("bits")?, 16)?,
+            coinbase_tx_version: 2,
+            coinbase_prefix: script_number(u64_field("height")?),
+            coinbase_tx_input_sequence: 0xffff_ffff,
+            coinbase_tx_value_remaining: u64_field("coinbasevalue")?,
+            coinbase_tx_outputs,
+            coinbase_tx_locktime: 0,
+            transactions,
+            excess_data,
+        })
+    }
+
+    fn merkle_path(&self) -> Vec<[u8; 32]> {
+        let txids: Vec<[u8; 32]> = self
+            .transactions
+            .iter()
+            .map(|transaction| transaction.txid())
+            .collect();
+
+        merkle_path(&txids)
+    }
+
+    fn new_template(&self, template_id: u64) -> Result<NewTemplate> {
+        let outputs: Vec<u8> = self
+            .coinbase_tx_outputs
+            .iter()
+            .flat_map(|output| output.serialize())
+            .collect();
+
+        Ok(NewTemplate::new(
+            template_id,
+            true,
+            self.version,
+            self.coinbase_tx_version,
+            self.coinbase_prefix.clone(),
+            self.coinbase_tx_input_sequence,
+            self.coinbase_tx_value_remaining,
+            self.coinbase_tx_outputs.len() as u32,
+            outputs,
+            self.coinbase_tx_locktime,
+            self.merkle_path(),
+        )?)
+    }
+
+    fn set_new_prev_hash(&self, template_id: u64) -> Result<SetNewPrevHash> {
+        Ok(SetNewPrevHash::new(
+            template_id,
+            self.prev_hash,
+            self.timestamp,
+            self.nbits,
+            Target::from_compact(self.nbits)?.into(),
+        ))
+    }
+
+    /// Assembles the block of a solution, returning it with its hash.
+    fn assemble(&self, solution: &SubmitSolution) -> Result<(Block, [u8; 32])> {
+        let coinbase = Transaction::parse(solution.coinbase_tx.as_ref())?;
+        let header = block_header(
+            solution.version,
+            &self.prev_hash,
+            &merkle_root(&coinbase.txid(), &self.merkle_path()),
+            solution.header_timestamp,
+            self.nbits,
+            solution.header_nonce,
+        );
+
+        let mut transactions = vec![coinbase];
+        transactions.extend_from_slice(&self.transactions);
+
+        Ok((
+            Block {
+                header,
+                transactions,
+            },
+            header_hash(&header),
+        ))
+    }
+}
+
+/// Returns the push of the block height at the start of a coinbase script,
+/// as required by BIP34, or an empty prefix if the script does not start
+/// with a push of at most 8 bytes.
+fn height_push(script_sig: &[u8]) -> Vec<u8> {
+    match script_sig.first() {
+        // OP_0 and OP_1 to OP_16.
+        Some(0x00) | Some(0x51..=0x60) => script_sig[..1].to_vec(),
+        Some(&length) if (1..=7).contains(&length) && script_sig.len() > length as usize => {
+            script_sig[..=length as usize].to_vec()
+        }
+        _ => vec![],
+    }
+}
+
+/// Returns the script pushing a block height, as serialized by Bitcoin Core
+/// for BIP34.
+fn script_number(height: u64) -> Vec<u8> {
+    match height {
+        0 => vec![0x00],
+        1..=16 => vec![0x50 + height as u8],
+        _ => {
+            let mut number = height.to_le_bytes().to_vec();
+            while number.last() == Some(&0) {
+                number.pop();
+            }
+            if number.last().is_some_and(|byte| byte & 0x80 != 0) {
+                number.push(0x00);
+            }
+
+            let mut script = vec![number.len() as u8];
+            script.extend_from_slice(&number);
+            script
+        }
+    }
+}
+
+fn reversed(bytes: &[u8]) -> Vec<u8> {
+    bytes.iter().rev().copied().collect()
+}
+
+async fn handle_connection(
+    stream: TcpStream,
+    config: Arc<Config>,
+    mut current: watch::Receiver<u64>,
+) -> Result<()> {
+    stream.set_nodelay(true)?;
+    let mut connection = Connection::accept(
+        stream,
+        &config.static_keypair,
+        &config.authority_keypair,
+        config.certificate_validity,
+    )
+    .await?;
+    let addr = connection.peer_addr()?;
+
+    let frame = connection
+        .recv()
+        .await?
+        .ok_or("the connection was closed during setup")?;
+    if frame.msg_type != MessageTypes::SetupConnection {
+        return Err(format!("unexpected {:?} during setup", frame.msg_type).into());
+    }
+
+    let setup = SetupConnection::deserialize(&frame.payload)?;
+    let error_code = if setup.protocol() != Protocol::TemplateDistribution {
+        Some(SetupConnectionErrorCodes::UnsupportedProtocol)
+    } else if setup.min_version > PROTOCOL_VERSION || setup.max_version < PROTOCOL_VERSION {
+        Some(SetupConnectionErrorCodes::ProtocolVersionMismatch)
+    } else {
+        None
+    };
+
+    if let Some(error_code) = error_code {
+        connection
+            .send(&SetupConnectionError::new(FlagSet::empty(), error_code)?)
+            .await?;
+        return Err(format!("the setup connection was rejected: {}", error_code).into());
+    }
+
+    connection
+        .send(&SetupConnectionSuccess::new(
+            PROTOCOL_VERSION,
+            FlagSet::empty(),
+        ))
+        .await?;
+
+    let template_id = *current.borrow();
+    send_template(&mut connection, &config, template_id).await?;
+
+    loop {
+        tokio::select! {
+            frame = connection.recv() => match frame? {
+                Some(frame) => {
+                    let template_id = *current.borrow();
+                    handle_frame(&mut connection, &config, frame, template_id, &addr.to_string()).await?;
+                }
+                None => return Ok(()),
+            },
+            changed = current.changed() => {
+                if changed.is_err() {
+                    return Ok(());
+                }
+
+                let template_id = *current.borrow();
+                send_template(&mut connection, &config, template_id).await?;
+            }
+        }
+    }
+}
+
+/// Sends a template as a future NewTemplate followed by the SetNewPrevHash
+/// activating it.
+async fn send_template(
+    connection: &mut Connection,
+    config: &Config,
+    template_id: u64,
+) -> Result<()> {
+    let template = config
+        .template(template_id, template_id)
+        .ok_or("the current template is unknown")?;
+
+    connection
+        .send(&template.new_template(template_id)?)
+        .await?;
+    connection
+        .send(&template.set_new_prev_hash(template_id)?)
+        .await
+}
+
+async fn handle_frame(
+    connection: &mut Connection,
+    config: &Config,
+    frame: NetworkFrame,
+    current: u64,
+    addr: &str,
+) -> Result<()> {
+    match frame.msg_type {
+        MessageTypes::CoinbaseOutputDataSize => {
+            let message = CoinbaseOutputDataSize::deserialize(&frame.payload)?;
+
+            // The templates are served as loaded, their transactions are not
+            // reduced to make space for the outputs of the Client.
+            println!(
+                "template-provider: {} coinbase output max additional size {}",
+                addr, message.coinbase_output_max_additional_size
+            );
+            Ok(())
+        }
+        MessageTypes::RequestTransactionData => {
+            let request = RequestTransactionData::deserialize(&frame.payload)?;
+            let template_id = request.template_id;
+
+            match config.template(template_id, current) {
+                Some(template) if template_id == current => {
+                    let transactions = template
+                        .transactions
+                        .iter()
+                        .map(|transaction| transaction.serialize())
+                        .collect();
+
+                    connection
+                        .send(&RequestTransactionDataSuccess::new(
+                            template_id,
+                            template.excess_data.clone(),
+                            transactions,
+                        )?)
+                        .await
+                }
+                Some(_) => {
+                    connection
+                        .send(&RequestTransactionDataError::new(
+                            template_id,
+                            RequestTransactionDataErrorCodes::StaleTemplateId,
+                        ))
+                        .await
+                }
+                None => {
+                    connection
+                        .send(&RequestTransactionDataError::new(
+                            template_id,
+                            RequestTransactionDataErrorCodes::TemplateIdNotFound,
+                        ))
+                        .await
+                }
+            }
+        }
+        MessageTypes::SubmitSolution => {
+            let solution = SubmitSolution::deserialize(&frame.payload)?;
+            let template = match config.template(solution.template_id, current) {
+                Some(template) => template,
+                None => {
+                    println!(
+                        "template-provider: {} submitted a solution for unknown template {}",
+                        addr, solution.template_id
+                    );
+                    return Ok(());
+                }
+            };
+
+            let (block, hash) = match template.assemble(&solution) {
+                Ok(assembled) => assembled,
+                Err(error) => {
+                    println!(
+                        "template-provider: {} submitted an invalid solution: {}",
+                        addr, error
+                    );
+                    return Ok(());
+                }
+            };
+
+            let hash = hex::encode(&reversed(&hash));
+            let path = config.output.join(format!("{}.hex", hash));
+            fs::write(&path, format!("{}\n", hex::encode(&block.serialize())))?;
+
+            let meets_target =
+                Target::from_compact(template.nbits)?.is_met_by(&header_hash(&block.header));
+            println!(
+                "template-provider: {} submitted block {} on template {}{}{}, written to {}",
+                addr,
+                hash,
+                solution.template_id,
+                if solution.template_id == current {
+                    ""
+                } else {
+                    " (stale)"
+                },
+                if meets_target {
+                    ""
+                } else {
+                    " not meeting the network target"
+                },
+                path.display()
+            );
+            Ok(())
+        }
+        msg_type => {
+            println!(
+                "template-provider: {} ignoring unexpected {:?}",
+                addr, msg_type
+            );
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,400 @@
+//! `stratumv2-translator` bridges Stratum V1 miners to a Stratum V2 pool.
+//!
+//! The translator connects to the pool over a Noise encrypted connection and
+//! opens a single extended channel, whose extranonce space is split between
+//! the V1 miners connecting to `--listen`. Jobs and targets received from the
+//! pool are sent to every authorized miner and the shares of the miners are
+//! submitted on the extended channel.
+use roles::args::Args;
+use roles::connection::Connection;
+use roles::Result;
+use std::collections::HashMap;
+use std::time::Instant;
+use stratumv2::common::NetworkFrame;
+use stratumv2::mining::{self, SetupConnection, SetupConnectionFlags};
+use stratumv2::noise::AuthorityPublicKey;
+use stratumv2::translator::{Translator, TranslatorConfig};
+use stratumv2::types::{FlagSet, MessageTypes};
+use stratumv2::util::hex;
+use stratumv2::v1::{self, LineCodec, Message, Notification, Request, Response, RpcError};
+use stratumv2::Deserializable;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+const USAGE: &str = "\
+Usage: stratumv2-translator [OPTIONS]
+
+Options:
+    --listen ADDR               Address the V1 miners connect to [default: 127.0.0.1:34255]
+    --pool ADDR                 Address of the V2 pool [default: 127.0.0.1:34254]
+    --authority-public-key HEX  Authority public key the static key of the pool
+                                must be signed by, not verified if omitted
+    --user NAME                 User identity of the extended channel [default: translator]
+    --hash-rate H/S             Expected hash rate of every miner combined
+                                [default: 1000000000000]
+    --help                      Print this message";
+
+/// The error code of a request rejected by the translator, a V1 share
+/// rejected upstream is only logged since the miner was already answered.
+const REJECTED: i64 = 20;
+
+#[tokio::main]
+async fn main() {
+    if let Err(error) = run().await {
+        eprintln!("stratumv2-translator: {}", error);
+        std::process::exit(1);
+    }
+}
+
+async fn run() -> Result<()> {
+    let args = Args::from_env()?;
+    if args.switch("help") {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+
+    let listen = args.value("listen", "127.0.0.1:34255".to_string())?;
+    let pool = args.value("pool", "127.0.0.1:34254".to_string())?;
+    let authority_public_key = match args.get("authority-public-key") {
+        Some(key) => Some(AuthorityPublicKey::from_bytes(&hex::decode(key)?)?),
+        None => None,
+    };
+    let config = TranslatorConfig::new(
+        args.value("user", "translator".to_string())?,
+        args.value("hash-rate", 1e12)?,
+    );
+
+    let mut bridge = Bridge::connect(&pool, authority_public_key.as_ref(), config).await?;
+    let listener = TcpListener::bind(&listen).await?;
+    println!("translator: listening on {} for V1 miners", listen);
+
+    let (events, mut received) = mpsc::unbounded_channel();
+    tokio::spawn(accept_miners(listener, events));
+
+    loop {
+        tokio::select! {
+            frame = bridge.connection.recv() => match frame? {
+                Some(frame) => bridge.handle_frame(frame).await?,
+                None => return Err("the pool closed the connection".into()),
+            },
+            event = received.recv() => match event {
+                Some(event) => bridge.handle_event(event).await?,
+                None => return Err("stopped accepting miners".into()),
+            },
+        }
+    }
+}
+
+/// An event of a V1 miner connection, sent to the task owning the
+/// translator.
+enum Event {
+    Connected(u32, UnboundedSender<String>),
+    Message(u32, Message),
+    Disconnected(u32),
+}
+
+/// Accepts V1 miners, numbering every connection.
+async fn accept_miners(listener: TcpListener, events: UnboundedSender<Event>) {
+    let mut next_id = 0u32;
+
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, addr)) => {
+                println!("translator: miner {} connected from {}", next_id, addr);
+                stream
+            }
+            Err(error) => {
+                println!("translator: failed to accept a miner: {}", error);
+                continue;
+            }
+        };
+
+        let (sender, lines) = mpsc::unbounded_channel();
+        if events.send(Event::Connected(next_id, sender)).is_err() {
+            return;
+        }
+
+        tokio::spawn(run_miner(next_id, stream, lines, events.clone()));
+        next_id = next_id.wrapping_add(1);
+    }
+}
+
+/// Reads the messages of a V1 miner and writes the lines sent to it, until
+/// either side closes the connection.
+async fn run_miner(
+    downstream_id: u32,
+    stream: TcpStream,
+    mut lines: UnboundedReceiver<String>,
+    events: UnboundedSender<Event>,
+) {
+    let (mut reader, mut writer) = stream.into_split();
+    let mut codec = LineCodec::new();
+    let mut bytes = [0u8; 4096];
+
+    'connection: loop {
+        tokio::select! {
+            read = reader.read(&mut bytes) => {
+                let read = match read {
+                    Ok(0) | Err(_) => break,
+                    Ok(read) => read,
+                };
+
+                codec.push(&bytes[..read]);
+                while let Some(message) = codec.next_message() {
+                    match message {
+                        Ok(message) => {
+                            if events.send(Event::Message(downstream_id, message)).is_err() {
+                                break 'connection;
+                            }
+                        }
+                        Err(error) => {
+                            println!(
+                                "translator: miner {} sent an invalid message: {}",
+                                downstream_id, error
+                            );
+                            break 'connection;
+                        }
+                    }
+                }
+            }
+            line = lines.recv() => match line {
+                Some(line) => if writer.write_all(line.as_bytes()).await.is_err() {
+                    break;
+                },
+                None => break,
+            },
+        }
+    }
+
+    println!("translator: miner {} disconnected", downstream_id);
+    let _ = events.send(Event::Disconnected(downstream_id));
+}
+
+/// Bridge owns the connection to the pool and the translator shared by
+/// every V1 miner.
+struct Bridge {
+    connection: Connection,
+    translator: Translator,
+    miners: HashMap<u32, UnboundedSender<String>>,
+    authorized: Vec<u32>,
+}
+
+impl Bridge {
+    /// Connects to the pool and opens the extended channel.
+    async fn connect(
+        addr: &str,
+        authority_public_key: Option<&AuthorityPublicKey>,
+        config: TranslatorConfig,
+    ) -> Result<Bridge> {
+        let (host, port) = split_addr(addr)?;
+        let mut connection = Connection::connect(addr, authority_public_key).await?;
+
+        connection
+            .send(&SetupConnection::new(
+                2,
+                2,
+                FlagSet::from([SetupConnectionFlags::RequiresVersionRolling]),
+                host,
+                port,
+                "stratumv2",
+                "translator",
+                env!("CARGO_PKG_VERSION"),
+                "translator",
+            )?)
+            .await?;
+
+        let frame = recv(&mut connection).await?;
+        match frame.msg_type {
+            MessageTypes::SetupConnectionSuccess => {
+                mining::SetupConnectionSuccess::deserialize(&frame.payload)?;
+            }
+            MessageTypes::SetupConnectionError => {
+                let error = mining::SetupConnectionError::deserialize(&frame.payload)?;
+                return Err(
+                    format!("the setup connection was rejected: {}", error.error_code).into(),
+                );
+            }
+            msg_type => return Err(format!("unexpected {:?} during setup", msg_type).into()),
+        }
+
+        let mut translator = Translator::new(config)?;
+        connection
+            .send(&translator.open_extended_mining_channel(0)?)
+            .await?;
+
+        let frame = recv(&mut connection).await?;
+        match frame.msg_type {
+            MessageTypes::OpenExtendedMiningChannelSuccess => {
+                let success =
+                    mining::OpenExtendedMiningChannelSuccess::deserialize(&frame.payload)?;
+                translator.handle_open_extended_mining_channel_success(&success)?;
+                println!(
+                    "translator: opened channel {} with {} bytes of extranonce",
+                    success.channel_id, success.extranonce_size
+                );
+            }
+            MessageTypes::OpenExtendedMiningChannelError => {
+                let error = mining::OpenExtendedMiningChannelError::deserialize(&frame.payload)?;
+                return Err(format!("the channel was rejected: {}", error.error_code).into());
+            }
+            msg_type => return Err(format!("unexpected {:?} opening the channel", msg_type).into()),
+        }
+
+        Ok(Bridge {
+            connection,
+            translator,
+            miners: HashMap::new(),
+            authorized: vec![],
+        })
+    }
+
+    /// Handles a message from the pool, forwarding jobs and targets to every
+    /// authorized miner.
+    async fn handle_frame(&mut self, frame: NetworkFrame) -> Result<()> {
+        let now = Instant::now();
+
+        match frame.msg_type {
+            MessageTypes::NewExtendedMiningJob => {
+                let job = mining::NewExtendedMiningJob::deserialize(&frame.payload)?;
+                if let Some(notify) = self.translator.handle_new_extended_mining_job(job, now)? {
+                    self.broadcast(Notification::Notify(notify))?;
+                }
+            }
+            MessageTypes::SetNewPrevHash => {
+                let prev_hash = mining::SetNewPrevHash::deserialize(&frame.payload)?;
+                let notify = self.translator.handle_set_new_prev_hash(prev_hash, now)?;
+                self.broadcast(Notification::Notify(notify))?;
+            }
+            MessageTypes::SetTarget => {
+                let message = mining::SetTarget::deserialize(&frame.payload)?;
+                let difficulty = self.translator.handle_set_target(&message)?;
+                println!("translator: difficulty set to {}", difficulty.difficulty);
+                self.broadcast(Notification::SetDifficulty(difficulty))?;
+            }
+            MessageTypes::SubmitSharesSuccess => {
+                let success = mining::SubmitSharesSuccess::deserialize(&frame.payload)?;
+                self.translator.handle_submit_shares_success(&success)?;
+            }
+            MessageTypes::SubmitSharesError => {
+                let error = mining::SubmitSharesError::deserialize(&frame.payload)?;
+                match self.translator.handle_submit_shares_error(&error)? {
+                    Some(downstream_id) => println!(
+                        "translator: share of miner {} rejected: {}",
+                        downstream_id, error.error_code
+                    ),
+                    None => println!("translator: share rejected: {}", error.error_code),
+                }
+            }
+            MessageTypes::CloseChannel => return Err("the pool closed the channel".into()),
+            msg_type => println!("translator: ignoring unexpected {:?}", msg_type),
+        }
+
+        Ok(())
+    }
+
+    async fn handle_event(&mut self, event: Event) -> Result<()> {
+        match event {
+            Event::Connected(downstream_id, sender) => {
+                self.miners.insert(downstream_id, sender);
+            }
+            Event::Message(downstream_id, Message::Request { id, request }) => {
+                let response = match self.handle_request(downstream_id, &request).await {
+                    Ok(response) => response,
+                    Err(error) => Response::Error(RpcError::new(REJECTED, error.to_string())),
+                };
+                self.send(downstream_id, &Message::Response { id, response })?;
+
+                if let Request::Authorize(_) = request {
+                    self.start_mining(downstream_id)?;
+                }
+            }
+            Event::Message(downstream_id, _) => {
+                println!(
+                    "translator: miner {} sent an unexpected message",
+                    downstream_id
+                );
+            }
+            Event::Disconnected(downstream_id) => {
+                self.miners.remove(&downstream_id);
+                self.authorized.retain(|id| *id != downstream_id);
+                self.translator.remove_downstream(downstream_id);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn handle_request(&mut self, downstream_id: u32, request: &Request) -> Result<Response> {
+        Ok(match request {
+            Request::Configure(message) => {
+                Response::Configure(self.translator.handle_configure(downstream_id, message)?)
+            }
+            Request::Subscribe(message) => {
+                Response::Subscribe(self.translator.handle_subscribe(downstream_id, message)?)
+            }
+            Request::Authorize(message) => {
+                Response::Boolean(self.translator.handle_authorize(downstream_id, message)?)
+            }
+            Request::Submit(message) => {
+                let share = self.translator.handle_submit(downstream_id, message)?;
+                self.connection.send(&share).await?;
+                Response::Boolean(true)
+            }
+        })
+    }
+
+    /// Sends the difficulty and the current job to a newly authorized miner.
+    fn start_mining(&mut self, downstream_id: u32) -> Result<()> {
+        if self.authorized.contains(&downstream_id) {
+            return Ok(());
+        }
+        self.authorized.push(downstream_id);
+
+        if let Some(difficulty) = self.translator.set_difficulty() {
+            let message = Message::Notification(Notification::SetDifficulty(difficulty));
+            self.send(downstream_id, &message)?;
+        }
+        if let Some(notify) = self.translator.current_notify() {
+            let message = Message::Notification(Notification::Notify(notify));
+            self.send(downstream_id, &message)?;
+        }
+
+        Ok(())
+    }
+
+    fn broadcast(&self, notification: Notification) -> Result<()> {
+        let line = v1::encode(&Message::Notification(notification))?;
+        for downstream_id in &self.authorized {
+            if let Some(miner) = self.miners.get(downstream_id) {
+                let _ = miner.send(line.clone());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn send(&self, downstream_id: u32, message: &Message) -> Result<()> {
+        if let Some(miner) = self.miners.get(&downstream_id) {
+            let _ = miner.send(v1::encode(message)?);
+        }
+
+        Ok(())
+    }
+}
+
+async fn recv(connection: &mut Connection) -> Result<NetworkFrame> {
+    connection
+        .recv()
+        .await?
+        .ok_or_else(|| "the pool closed the connection".into())
+}
+
+/// Splits an address into its host and port.
+fn split_addr(addr: &str) -> Result<(&str, u16)> {
+    let (host, port) = addr
+        .rsplit_once(':')
+        .ok_or_else(|| format!("the address has no port: {}", addr))?;
+
+    Ok((host, port.parse()?))
+}
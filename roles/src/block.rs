@@ -0,0 +1,393 @@
+use crate::Result;
+use std::convert::TryInto;
+use stratumv2::util::{compact_size, double_sha256, read_compact_size};
+
+/// The segwit marker and flag following the version of a transaction with
+/// witness data.
+const SEGWIT_MARKER: [u8; 2] = [0x00, 0x01];
+
+/// TxIn is an input of a transaction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TxIn {
+    /// The txid and output index spent by the input.
+    pub previous_output: [u8; 36],
+    pub script_sig: Vec<u8>,
+    pub sequence: u32,
+    pub witness: Vec<Vec<u8>>,
+}
+
+/// TxOut is an output of a transaction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TxOut {
+    /// The value of the output in satoshis.
+    pub value: u64,
+    pub script_pubkey: Vec<u8>,
+}
+
+impl TxOut {
+    /// Returns the serialized output.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut buffer = self.value.to_le_bytes().to_vec();
+        buffer.extend(compact_size(self.script_pubkey.len() as u64));
+        buffer.extend_from_slice(&self.script_pubkey);
+
+        buffer
+    }
+}
+
+/// Transaction is a Bitcoin transaction, with or without witness data.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transaction {
+    pub version: u32,
+    pub inputs: Vec<TxIn>,
+    pub outputs: Vec<TxOut>,
+    pub lock_time: u32,
+}
+
+impl Transaction {
+    /// Parses a serialized transaction, which must not contain trailing
+    /// bytes.
+    pub fn parse(bytes: &[u8]) -> Result<Transaction> {
+        let mut reader = Reader::new(bytes);
+        let transaction = Transaction::read(&mut reader)?;
+        reader.finish()?;
+
+        Ok(transaction)
+    }
+
+    fn read(reader: &mut Reader) -> Result<Transaction> {
+        let version = reader.read_u32()?;
+
+        let mut segwit = false;
+        let mut input_count = reader.read_compact_size()?;
+        if input_count == 0 {
+            if reader.read(1)?[0] != SEGWIT_MARKER[1] {
+                return Err("invalid segwit flag".into());
+            }
+            segwit = true;
+            input_count = reader.read_compact_size()?;
+        }
+
+        let mut inputs = Vec::new();
+        for _ in 0..input_count {
+            let previous_output = reader.read(36)?.try_into()?;
+            let script_length = reader.read_compact_size()?;
+            let script_sig = reader.read(script_length as usize)?.to_vec();
+            let sequence = reader.read_u32()?;
+
+            inputs.push(TxIn {
+                previous_output,
+                script_sig,
+                sequence,
+                witness: vec![],
+            });
+        }
+
+        let mut outputs = Vec::new();
+        for _ in 0..reader.read_compact_size()? {
+            let value = reader.read_u64()?;
+            let script_length = reader.read_compact_size()?;
+            let script_pubkey = reader.read(script_length as usize)?.to_vec();
+
+            outputs.push(TxOut {
+                value,
+                script_pubkey,
+            });
+        }
+
+        if segwit {
+            for input in inputs.iter_mut() {
+                for _ in 0..reader.read_compact_size()? {
+                    let length = reader.read_compact_size()?;
+                    input.witness.push(reader.read(length as usize)?.to_vec());
+                }
+            }
+        }
+
+        Ok(Transaction {
+            version,
+            inputs,
+            outputs,
+            lock_time: reader.read_u32()?,
+        })
+    }
+
+    /// Returns true if any input has witness data.
+    pub fn has_witness(&self) -> bool {
+        self.inputs.iter().any(|input| !input.witness.is_empty())
+    }
+
+    /// Returns the serialized transaction, including the witness data.
+    pub fn serialize(&self) -> Vec<u8> {
+        self.serialize_with(self.has_witness())
+    }
+
+    /// Returns the serialized transaction without witness data, as hashed by
+    /// the txid.
+    pub fn serialize_without_witness(&self) -> Vec<u8> {
+        self.serialize_with(false)
+    }
+
+    /// Returns the txid in the internal byte order used by merkle trees.
+    pub fn txid(&self) -> [u8; 32] {
+        double_sha256(&self.serialize_without_witness())
+    }
+
+    fn serialize_with(&self, witness: bool) -> Vec<u8> {
+        let mut buffer = self.version.to_le_bytes().to_vec();
+        if witness {
+            buffer.extend_from_slice(&SEGWIT_MARKER);
+        }
+
+        buffer.extend(compact_size(self.inputs.len() as u64));
+        for input in &self.inputs {
+            buffer.extend_from_slice(&input.previous_output);
+            buffer.extend(compact_size(input.script_sig.len() as u64));
+            buffer.extend_from_slice(&input.script_sig);
+            buffer.extend_from_slice(&input.sequence.to_le_bytes());
+        }
+
+        buffer.extend(compact_size(self.outputs.len() as u64));
+        for output in &self.outputs {
+            buffer.extend_from_slice(&output.serialize());
+        }
+
+        if witness {
+            for input in &self.inputs {
+                buffer.extend(compact_size(input.witness.len() as u64));
+                for item in &input.witness {
+                    buffer.extend(compact_size(item.len() as u64));
+                    buffer.extend_from_slice(item);
+                }
+            }
+        }
+
+        buffer.extend_from_slice(&self.lock_time.to_le_bytes());
+        buffer
+    }
+}
+
+/// Block is a Bitcoin block, the 80 byte header followed by its
+/// transactions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub header: [u8; 80],
+    pub transactions: Vec<Transaction>,
+}
+
+impl Block {
+    /// Parses a serialized block, which must not contain trailing bytes.
+    pub fn parse(bytes: &[u8]) -> Result<Block> {
+        let mut reader = Reader::new(bytes);
+        let header = reader.read(80)?.try_into()?;
+
+        let mut transactions = Vec::new();
+        for _ in 0..reader.read_compact_size()? {
+            transactions.push(Transaction::read(&mut reader)?);
+        }
+        reader.finish()?;
+
+        if transactions.is_empty() {
+            return Err("the block has no coinbase transaction".into());
+        }
+
+        Ok(Block {
+            header,
+            transactions,
+        })
+    }
+
+    /// Returns the serialized block.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut buffer = self.header.to_vec();
+        buffer.extend(compact_size(self.transactions.len() as u64));
+        for transaction in &self.transactions {
+            buffer.extend_from_slice(&transaction.serialize());
+        }
+
+        buffer
+    }
+
+    pub fn version(&self) -> u32 {
+        u32::from_le_bytes([
+            self.header[0],
+            self.header[1],
+            self.header[2],
+            self.header[3],
+        ])
+    }
+
+    /// Returns the hash of the previous block in internal byte order.
+    pub fn prev_hash(&self) -> [u8; 32] {
+        let mut prev_hash = [0u8; 32];
+        prev_hash.copy_from_slice(&self.header[4..36]);
+
+        prev_hash
+    }
+
+    pub fn merkle_root(&self) -> [u8; 32] {
+        let mut merkle_root = [0u8; 32];
+        merkle_root.copy_from_slice(&self.header[36..68]);
+
+        merkle_root
+    }
+
+    pub fn time(&self) -> u32 {
+        u32::from_le_bytes([
+            self.header[68],
+            self.header[69],
+            self.header[70],
+            self.header[71],
+        ])
+    }
+
+    pub fn nbits(&self) -> u32 {
+        u32::from_le_bytes([
+            self.header[72],
+            self.header[73],
+            self.header[74],
+            self.header[75],
+        ])
+    }
+}
+
+/// Reader reads the fields of serialized Bitcoin data.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Reader<'a> {
+        Reader { bytes, position: 0 }
+    }
+
+    fn read(&mut self, length: usize) -> Result<&'a [u8]> {
+        let end = self
+            .position
+            .checked_add(length)
+            .ok_or("length overflows the data")?;
+        let bytes = self
+            .bytes
+            .get(self.position..end)
+            .ok_or("unexpected end of data")?;
+        self.position = end;
+
+        Ok(bytes)
+    }
+
+    fn read_u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.read(4)?.try_into()?))
+    }
+
+    fn read_u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.read(8)?.try_into()?))
+    }
+
+    fn read_compact_size(&mut self) -> Result<u64> {
+        let (value, size) = read_compact_size(&self.bytes[self.position..])?;
+        self.position += size;
+
+        Ok(value)
+    }
+
+    fn finish(&self) -> Result<()> {
+        if self.position != self.bytes.len() {
+            return Err("unexpected trailing data".into());
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use stratumv2::util::hex;
+
+    const GENESIS_BLOCK: &str = concat!(
+        "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd",
+        "7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c",
+        "0101000000010000000000000000000000000000000000000000000000000000000000000000ffff",
+        "ffff4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c",
+        "6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73",
+        "ffffffff0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a6",
+        "7962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5f",
+        "ac00000000"
+    );
+
+    #[test]
+    fn parse_genesis_block() {
+        let bytes = hex::decode(GENESIS_BLOCK).unwrap();
+        let block = Block::parse(&bytes).unwrap();
+
+        assert_eq!(block.version(), 1);
+        assert_eq!(block.prev_hash(), [0u8; 32]);
+        assert_eq!(block.time(), 1231006505);
+        assert_eq!(block.nbits(), 0x1d00ffff);
+        assert_eq!(block.transactions.len(), 1);
+        assert_eq!(block.transactions[0].outputs[0].value, 5_000_000_000);
+
+        // The merkle root of a block with a single transaction is its txid.
+        assert_eq!(block.transactions[0].txid(), block.merkle_root());
+        assert_eq!(block.serialize(), bytes);
+    }
+
+    #[test]
+    fn segwit_transaction() {
+        let transaction = Transaction {
+            version: 2,
+            inputs: vec![TxIn {
+                previous_output: [0x11; 36],
+                script_sig: vec![],
+                sequence: 0xffff_fffd,
+                witness: vec![vec![0x22; 72], vec![0x33; 33]],
+            }],
+            outputs: vec![TxOut {
+                value: 1000,
+                script_pubkey: vec![0x00, 0x14],
+            }],
+            lock_time: 0,
+        };
+
+        let bytes = transaction.serialize();
+        assert_eq!(bytes[4..6], SEGWIT_MARKER);
+        assert_eq!(Transaction::parse(&bytes).unwrap(), transaction);
+
+        // The txid does not commit to the witness data.
+        let stripped = Transaction::parse(&transaction.serialize_without_witness()).unwrap();
+        assert!(!stripped.has_witness());
+        assert_eq!(stripped.txid(), transaction.txid());
+    }
+
+    #[test]
+    fn compact_size() {
+        let buffer = [
+            super::compact_size(0xfc),
+            super::compact_size(0xfd),
+            super::compact_size(0x1_0000),
+        ]
+        .concat();
+        assert_eq!(
+            buffer,
+            [0xfc, 0xfd, 0xfd, 0x00, 0xfe, 0x00, 0x00, 0x01, 0x00]
+        );
+
+        let mut reader = Reader::new(&buffer);
+        assert_eq!(reader.read_compact_size().unwrap(), 0xfc);
+        assert_eq!(reader.read_compact_size().unwrap(), 0xfd);
+        assert_eq!(reader.read_compact_size().unwrap(), 0x1_0000);
+        assert!(reader.finish().is_ok());
+    }
+
+    #[test]
+    fn rejects_overflowing_length() {
+        // A transaction with a script length of u64::MAX.
+        let bytes = [&[0x01, 0x00, 0x00, 0x00, 0x01][..], &[0x00; 36], &[0xff; 9]].concat();
+        assert!(Transaction::parse(&bytes).is_err());
+
+        let mut reader = Reader::new(&[0x00]);
+        reader.read(1).unwrap();
+        assert!(reader.read(usize::MAX).is_err());
+    }
+}
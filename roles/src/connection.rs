@@ -0,0 +1,212 @@
+use crate::Result;
+use std::net::SocketAddr;
+use std::time::{Duration, SystemTime};
+use stratumv2::common::NetworkFrame;
+use stratumv2::noise::{
+    new_noise_initiator, new_noise_responder, AuthorityKeyPair, AuthorityPublicKey,
+    CertificateFormat, NoiseSession, SignatureNoiseMessage, SignedCertificate, StaticKeyPair,
+};
+use stratumv2::util::{serialize, system_unix_time_to_u32};
+use stratumv2::{Deserializable, Frameable};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// The length of the MAC appended to every encrypted Noise message.
+const MAC_LENGTH: usize = 16;
+
+/// The length of the first handshake message, the ephemeral key of the
+/// initiator followed by an empty payload.
+const HANDSHAKE_A_LENGTH: usize = 32 + MAC_LENGTH;
+
+/// The length of the second handshake message, the ephemeral and encrypted
+/// static keys of the responder followed by an empty encrypted payload.
+const HANDSHAKE_B_LENGTH: usize = 32 + 32 + MAC_LENGTH + MAC_LENGTH;
+
+/// Connection is a Stratum V2 connection encrypted with the Noise NX
+/// handshake. Every Noise message is prefixed on the wire with its length as
+/// a u16 little endian, and every transport message contains a single
+/// framed Stratum V2 message.
+///
+/// After the handshake the responder sends a
+/// [SignatureNoiseMessage](../../stratumv2/noise/struct.SignatureNoiseMessage.html)
+/// proving its static key was signed by the authority key of the pool.
+pub struct Connection {
+    stream: TcpStream,
+    noise: NoiseSession,
+    buffer: Vec<u8>,
+}
+
+impl Connection {
+    /// Performs the handshake as the responder on an accepted stream, signing
+    /// the static key with the authority key for `validity`.
+    pub async fn accept(
+        stream: TcpStream,
+        static_keypair: &StaticKeyPair,
+        authority_keypair: &AuthorityKeyPair,
+        validity: Duration,
+    ) -> Result<Connection> {
+        let mut connection =
+            Connection::new(stream, new_noise_responder(Some(static_keypair.clone())));
+
+        let mut message = connection.read_message().await?;
+        if message.len() != HANDSHAKE_A_LENGTH {
+            return Err("unexpected handshake message length".into());
+        }
+        connection.recv_noise(&mut message)?;
+
+        let mut message = vec![0u8; HANDSHAKE_B_LENGTH];
+        connection.send_noise(&mut message)?;
+        connection.write_message(&message).await?;
+
+        let valid_from = system_unix_time_to_u32(&SystemTime::now())?;
+        let not_valid_after = system_unix_time_to_u32(&(SystemTime::now() + validity))?;
+        let public_key = static_keypair.get_public_key();
+        let certificate = SignedCertificate::new(0, valid_from, not_valid_after, &public_key)?;
+        let signature = SignatureNoiseMessage::from_auth_key(authority_keypair, &certificate)?;
+
+        connection.send_bytes(&serialize(signature)?).await?;
+        Ok(connection)
+    }
+
+    /// Connects to a responder and performs the handshake as the initiator.
+    /// If an `authority_public_key` is given, the static key of the
+    /// responder must be signed by it.
+    pub async fn connect(
+        addr: &str,
+        authority_public_key: Option<&AuthorityPublicKey>,
+    ) -> Result<Connection> {
+        let stream = TcpStream::connect(addr).await?;
+        stream.set_nodelay(true)?;
+        let mut connection = Connection::new(stream, new_noise_initiator());
+
+        let mut message = vec![0u8; HANDSHAKE_A_LENGTH];
+        connection.send_noise(&mut message)?;
+        connection.write_message(&message).await?;
+
+        let mut message = connection.read_message().await?;
+        if message.len() != HANDSHAKE_B_LENGTH {
+            return Err("unexpected handshake message length".into());
+        }
+        connection.recv_noise(&mut message)?;
+
+        let signature = SignatureNoiseMessage::deserialize(&connection.recv_bytes().await?)?;
+        if let Some(authority_public_key) = authority_public_key {
+            let static_key = connection
+                .noise
+                .get_remote_static_public_key()
+                .ok_or("the handshake did not complete")?;
+
+            CertificateFormat::new(authority_public_key, &static_key, &signature).verify()?;
+        }
+
+        Ok(connection)
+    }
+
+    pub fn peer_addr(&self) -> Result<SocketAddr> {
+        Ok(self.stream.peer_addr()?)
+    }
+
+    /// Frames, encrypts and sends a message.
+    pub async fn send<T: Frameable>(&mut self, message: &T) -> Result<()> {
+        let mut buffer = vec![];
+        message.frame(&mut buffer)?;
+
+        self.send_bytes(&buffer).await
+    }
+
+    /// Receives and decrypts the next message. Returns None if the peer
+    /// closed the connection.
+    ///
+    /// This method is cancellation safe, it can be used in `tokio::select!`
+    /// without losing received bytes.
+    pub async fn recv(&mut self) -> Result<Option<NetworkFrame>> {
+        match self.try_read_message().await? {
+            Some(mut message) => {
+                self.recv_noise(&mut message)?;
+                message.truncate(message.len() - MAC_LENGTH);
+
+                Ok(Some(NetworkFrame::deserialize(&message)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn new(stream: TcpStream, noise: NoiseSession) -> Connection {
+        Connection {
+            stream,
+            noise,
+            buffer: vec![],
+        }
+    }
+
+    async fn send_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        let mut message = bytes.to_vec();
+        message.resize(bytes.len() + MAC_LENGTH, 0);
+        self.send_noise(&mut message)?;
+
+        self.write_message(&message).await
+    }
+
+    async fn recv_bytes(&mut self) -> Result<Vec<u8>> {
+        let mut message = self.read_message().await?;
+        self.recv_noise(&mut message)?;
+        message.truncate(message.len() - MAC_LENGTH);
+
+        Ok(message)
+    }
+
+    fn send_noise(&mut self, message: &mut [u8]) -> Result<()> {
+        self.noise
+            .send_message(message)
+            .map_err(|error| format!("noise error: {}", error).into())
+    }
+
+    fn recv_noise(&mut self, message: &mut [u8]) -> Result<()> {
+        self.noise
+            .recv_message(message)
+            .map_err(|error| format!("noise error: {}", error).into())
+    }
+
+    async fn write_message(&mut self, message: &[u8]) -> Result<()> {
+        if message.len() > u16::MAX as usize {
+            return Err("the message is too large".into());
+        }
+
+        let mut buffer = (message.len() as u16).to_le_bytes().to_vec();
+        buffer.extend_from_slice(message);
+        self.stream.write_all(&buffer).await?;
+
+        Ok(())
+    }
+
+    async fn read_message(&mut self) -> Result<Vec<u8>> {
+        self.try_read_message()
+            .await?
+            .ok_or_else(|| "the connection was closed".into())
+    }
+
+    /// Returns the next length prefixed message, buffering the received bytes
+    /// so no data is lost if the future is cancelled.
+    async fn try_read_message(&mut self) -> Result<Option<Vec<u8>>> {
+        loop {
+            if self.buffer.len() >= 2 {
+                let length = u16::from_le_bytes([self.buffer[0], self.buffer[1]]) as usize;
+
+                if self.buffer.len() >= 2 + length {
+                    let message = self.buffer[2..2 + length].to_vec();
+                    self.buffer.drain(..2 + length);
+
+                    return Ok(Some(message));
+                }
+            }
+
+            let mut bytes = [0u8; 4096];
+            let read = self.stream.read(&mut bytes).await?;
+            if read == 0 {
+                return Ok(None);
+            }
+
+            self.buffer.extend_from_slice(&bytes[..read]);
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use crate::Result;
+use rand::rngs::OsRng;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::Path;
+use stratumv2::noise::AuthorityKeyPair;
+use stratumv2::util::hex;
+
+/// Returns the authority keypair of a role. Without a path an ephemeral
+/// keypair is generated. Otherwise the keypair is read from the file as hex,
+/// or generated and written to the file if it does not exist. The file is
+/// created readable only by its owner, the secret key is never printed.
+pub fn authority_keypair(path: Option<&str>) -> Result<AuthorityKeyPair> {
+    let path = match path {
+        Some(path) => Path::new(path),
+        None => return Ok(AuthorityKeyPair::generate(&mut OsRng {})),
+    };
+
+    match fs::read_to_string(path) {
+        Ok(key) => Ok(AuthorityKeyPair::from_bytes(&hex::decode(key.trim())?)?),
+        Err(error) if error.kind() == ErrorKind::NotFound => {
+            let keypair = AuthorityKeyPair::generate(&mut OsRng {});
+            write_secret(path, &hex::encode(&keypair.to_bytes()))?;
+            Ok(keypair)
+        }
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Creates a file that only its owner can read and write.
+fn write_secret(path: &Path, contents: &str) -> Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(path)?;
+    file.write_all(contents.as_bytes())?;
+    Ok(())
+}
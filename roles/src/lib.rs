@@ -0,0 +1,17 @@
+//! Runnable Stratum V2 roles built on the `stratumv2` crate, intended for
+//! local integration testing. These are not hardened for production use.
+
+/// Parsing of the command line arguments of the role binaries.
+pub mod args;
+
+/// Parsing and serialization of Bitcoin blocks and transactions.
+pub mod block;
+
+/// Noise encrypted Stratum V2 connections over TCP.
+pub mod connection;
+
+/// Loading and generation of the authority keypairs of the roles.
+pub mod keys;
+
+/// Result type returned by the roles.
+pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
@@ -0,0 +1,52 @@
+//! Measures the throughput and memory usage of the DuplicateShareDetector for
+//! a Server receiving 100k shares per second.
+//!
+//! Run with `cargo bench --bench duplicate_shares`.
+use std::time::Instant;
+use stratumv2::mining::{DuplicateShareDetector, SubmitSharesExtended};
+
+const SHARES_PER_SECOND: usize = 100_000;
+const CHANNELS: u32 = 1_000;
+
+fn main() {
+    // Remember the shares of the last 1s, 10s and 60s.
+    for seconds in [1, 10, 60].iter() {
+        let capacity = SHARES_PER_SECOND * seconds;
+        let mut detector = DuplicateShareDetector::new(capacity).unwrap();
+
+        // Submit twice the capacity, so half of the shares are evicted.
+        let shares: Vec<SubmitSharesExtended> = (0..capacity as u32 * 2)
+            .map(|i| {
+                SubmitSharesExtended::new(
+                    i % CHANNELS,
+                    i,
+                    1,
+                    i,
+                    0x5f00_0000,
+                    0x2000_0000,
+                    i.to_le_bytes().to_vec(),
+                )
+                .unwrap()
+            })
+            .collect();
+
+        let start = Instant::now();
+        let duplicates = shares
+            .iter()
+            .filter(|share| detector.check_extended(share).is_some())
+            .count();
+        let elapsed = start.elapsed();
+
+        assert_eq!(duplicates, 0);
+        assert_eq!(detector.remembered(), capacity);
+
+        println!(
+            "window {:>2}s: capacity {:>8} shares, {:>7.2} MiB, {:>6.0} ns/share, {:>5.1}M shares/s",
+            seconds,
+            capacity,
+            detector.memory_usage() as f64 / (1024.0 * 1024.0),
+            elapsed.as_nanos() as f64 / shares.len() as f64,
+            shares.len() as f64 / elapsed.as_secs_f64() / 1e6,
+        );
+    }
+}
@@ -0,0 +1,246 @@
+//! Conversions between the types of this library and the types of the
+//! [bitcoin](https://docs.rs/bitcoin) crate, enabled by the `bitcoin` feature.
+//!
+//! Hashes are sent in messages as a `U256` in the internal byte order used by
+//! block headers. Since a `U256` is a byte array, hashes are converted through
+//! the [Hash256](struct.Hash256.html) wrapper.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use bitcoin::BlockHash;
+//! use stratumv2::bitcoin::Hash256;
+//! use stratumv2::mining::SetNewPrevHash;
+//!
+//! let message = SetNewPrevHash::new(1, 1, [0x01; 32], 1231006505, 0x1d00ffff);
+//!
+//! let prev_hash = BlockHash::from(Hash256(message.prev_hash));
+//! assert_eq!(Hash256::from(prev_hash).0, message.prev_hash);
+//! ```
+use crate::error::{Error, Result};
+use crate::mining::BlockHeader;
+use crate::target::Target;
+use crate::types::{B0_16M, B0_64K, U256};
+use ::bitcoin::block::{Header, Version};
+use ::bitcoin::consensus::encode;
+use ::bitcoin::hashes::Hash;
+use ::bitcoin::{BlockHash, CompactTarget, Transaction, TxMerkleNode};
+use std::convert::TryFrom;
+
+/// Hash256 wraps a `U256` hash in the internal byte order, converting to and
+/// from the hash types of the bitcoin crate.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Hash256(pub U256);
+
+impl From<U256> for Hash256 {
+    fn from(hash: U256) -> Self {
+        Hash256(hash)
+    }
+}
+
+impl From<Hash256> for U256 {
+    fn from(hash: Hash256) -> Self {
+        hash.0
+    }
+}
+
+macro_rules! impl_hash_conversions {
+    ($($hash:ty),*) => {
+        $(
+            impl From<Hash256> for $hash {
+                fn from(hash: Hash256) -> Self {
+                    <$hash>::from_byte_array(hash.0)
+                }
+            }
+
+            impl From<$hash> for Hash256 {
+                fn from(hash: $hash) -> Self {
+                    Hash256(hash.to_byte_array())
+                }
+            }
+        )*
+    };
+}
+
+impl_hash_conversions!(BlockHash, TxMerkleNode);
+
+impl From<Target> for ::bitcoin::Target {
+    fn from(target: Target) -> Self {
+        ::bitcoin::Target::from_le_bytes(target.to_le_bytes())
+    }
+}
+
+impl From<::bitcoin::Target> for Target {
+    fn from(target: ::bitcoin::Target) -> Self {
+        Target::from_le_bytes(target.to_le_bytes())
+    }
+}
+
+/// Encodes the Target in its compact `nBits` representation, losing the
+/// precision beyond the mantissa.
+impl From<Target> for CompactTarget {
+    fn from(target: Target) -> Self {
+        CompactTarget::from_consensus(target.to_compact())
+    }
+}
+
+/// Decodes a compact `nBits` target, a RequirementError is returned for a
+/// negative or overflowing target.
+impl TryFrom<CompactTarget> for Target {
+    type Error = Error;
+
+    fn try_from(nbits: CompactTarget) -> Result<Self> {
+        Target::from_compact(nbits.to_consensus())
+    }
+}
+
+impl From<BlockHeader> for Header {
+    fn from(header: BlockHeader) -> Self {
+        Header {
+            version: Version::from_consensus(header.version as i32),
+            prev_blockhash: Hash256(header.prev_hash).into(),
+            merkle_root: Hash256(header.merkle_root).into(),
+            time: header.ntime,
+            bits: CompactTarget::from_consensus(header.nbits),
+            nonce: header.nonce,
+        }
+    }
+}
+
+impl From<Header> for BlockHeader {
+    fn from(header: Header) -> Self {
+        BlockHeader {
+            version: header.version.to_consensus() as u32,
+            prev_hash: Hash256::from(header.prev_blockhash).0,
+            merkle_root: Hash256::from(header.merkle_root).0,
+            ntime: header.time,
+            nbits: header.bits.to_consensus(),
+            nonce: header.nonce,
+        }
+    }
+}
+
+macro_rules! impl_transaction_conversions {
+    ($($bytes:ident),*) => {
+        $(
+            /// Decodes a serialized transaction, a DeserializationError is
+            /// returned if the bytes are not exactly one transaction.
+            impl TryFrom<&$bytes> for Transaction {
+                type Error = Error;
+
+                fn try_from(bytes: &$bytes) -> Result<Self> {
+                    encode::deserialize(bytes.as_ref())
+                        .map_err(|e| Error::DeserializationError(e.to_string()))
+                }
+            }
+
+            /// Serializes a transaction, a RequirementError is returned if the
+            /// transaction exceeds the maximum size of the byte type.
+            impl TryFrom<&Transaction> for $bytes {
+                type Error = Error;
+
+                fn try_from(transaction: &Transaction) -> Result<Self> {
+                    $bytes::new(encode::serialize(transaction))
+                }
+            }
+        )*
+    };
+}
+
+impl_transaction_conversions!(B0_64K, B0_16M);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mining::header_hash;
+    use crate::util::hex;
+    use std::convert::TryInto;
+
+    // The Bitcoin mainnet genesis block header.
+    const GENESIS_HEADER: &str = concat!(
+        "01000000",
+        "0000000000000000000000000000000000000000000000000000000000000000",
+        "3ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a",
+        "29ab5f49",
+        "ffff001d",
+        "1dac2b7c",
+    );
+
+    // The coinbase transaction of the genesis block.
+    const GENESIS_COINBASE: &str = concat!(
+        "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff",
+        "4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72",
+        "206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff",
+        "0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f",
+        "61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000",
+    );
+
+    fn genesis_header() -> BlockHeader {
+        BlockHeader::from_bytes(&hex::decode(GENESIS_HEADER).unwrap().try_into().unwrap())
+    }
+
+    #[test]
+    fn header_conversion() {
+        let header = genesis_header();
+        let converted = Header::from(header);
+
+        assert_eq!(
+            encode::serialize(&converted),
+            hex::decode(GENESIS_HEADER).unwrap()
+        );
+        assert_eq!(
+            Hash256::from(converted.block_hash()).0,
+            header_hash(&header.to_bytes())
+        );
+        assert_eq!(
+            converted.block_hash().to_string(),
+            "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"
+        );
+        assert_eq!(BlockHeader::from(converted), header);
+    }
+
+    #[test]
+    fn hash_conversion() {
+        let header = genesis_header();
+        let merkle_root = TxMerkleNode::from(Hash256(header.merkle_root));
+
+        assert_eq!(
+            merkle_root.to_string(),
+            "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b"
+        );
+        assert_eq!(U256::from(Hash256::from(merkle_root)), header.merkle_root);
+    }
+
+    #[test]
+    fn target_conversion() {
+        let target = Target::from_compact(0x1d00ffff).unwrap();
+        let converted = ::bitcoin::Target::from(target);
+
+        assert_eq!(converted, ::bitcoin::Target::MAX_ATTAINABLE_MAINNET);
+        assert_eq!(Target::from(converted), target);
+
+        let nbits = CompactTarget::from(target);
+        assert_eq!(nbits.to_consensus(), 0x1d00ffff);
+        assert_eq!(Target::try_from(nbits).unwrap(), target);
+
+        // A negative compact target.
+        assert!(Target::try_from(CompactTarget::from_consensus(0x1d80ffff)).is_err());
+    }
+
+    #[test]
+    fn transaction_conversion() {
+        let bytes = B0_64K::new(hex::decode(GENESIS_COINBASE).unwrap()).unwrap();
+        let transaction = Transaction::try_from(&bytes).unwrap();
+
+        assert_eq!(
+            transaction.compute_txid().to_string(),
+            "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b"
+        );
+        assert_eq!(B0_16M::try_from(&transaction).unwrap(), bytes.0);
+
+        // Trailing bytes are rejected.
+        let trailing =
+            B0_16M::new([hex::decode(GENESIS_COINBASE).unwrap(), vec![0x00]].concat()).unwrap();
+        assert!(Transaction::try_from(&trailing).is_err());
+    }
+}
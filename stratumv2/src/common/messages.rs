@@ -17,8 +17,9 @@ pub enum SetupConnectionErrorCodes {
     /// the server does not support.
     UnsupportedProtocol,
 
-    // TODO: What is the difference between protocol version mismatch
-    // and unsupported protocol?
+    /// Indicates the server supports the requested protocol, but none of the
+    /// versions between the `min_version` and `max_version` of the
+    /// SetupConnection message. See [VersionRange](struct.VersionRange.html).
     ProtocolVersionMismatch,
 }
 
@@ -31,10 +32,12 @@ impl_error_codes_enum!(
 
 /// Used to deserialize a received network frame. The payload would be further
 /// deserialized according to the received MessageTypes.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NetworkFrame {
     pub extension_type: u16,
     pub msg_type: MessageTypes,
     pub msg_length: u32,
+    #[cfg_attr(feature = "serde", serde(with = "crate::util::hex::bytes"))]
     pub payload: Vec<u8>,
 }
 
@@ -46,7 +49,12 @@ impl Deserializable for NetworkFrame {
         let msg_type = parser.next_by(1)?[0];
 
         let msg_length_bytes = parser.next_by(3)?;
-        let msg_length = (msg_length_bytes[1] | msg_length_bytes[0]) as u32;
+        let msg_length = u32::from_le_bytes([
+            msg_length_bytes[0],
+            msg_length_bytes[1],
+            msg_length_bytes[2],
+            0,
+        ]);
 
         let payload = parser.next_by(msg_length as usize)?;
 
@@ -63,6 +71,7 @@ impl Deserializable for NetworkFrame {
 mod test {
     use super::*;
     use crate::mining;
+    use crate::util::frame;
 
     #[test]
     fn deserialize_network_frame() {
@@ -98,4 +107,30 @@ mod test {
 
         assert!(mining::SetupConnection::deserialize(&network_frame.payload).is_ok());
     }
+
+    #[test]
+    fn deserialize_large_network_frame() {
+        let job = mining::NewExtendedMiningJob::new(
+            1,
+            2,
+            true,
+            0x2000_0000,
+            true,
+            vec![[0x11; 32]; 8],
+            vec![0x01; 300],
+            vec![0x02; 100],
+        )
+        .unwrap();
+
+        let network_frame = NetworkFrame::deserialize(&frame(job).unwrap()).unwrap();
+        assert_eq!(network_frame.msg_type, MessageTypes::NewExtendedMiningJob);
+        assert!(network_frame.msg_length > 0xff);
+        assert_eq!(
+            network_frame.payload.len(),
+            network_frame.msg_length as usize
+        );
+
+        let job = mining::NewExtendedMiningJob::deserialize(&network_frame.payload).unwrap();
+        assert_eq!(job.coinbase_tx_prefix, vec![0x01; 300]);
+    }
 }
@@ -1,3 +1,8 @@
 /// Messages common to all Stratum V2 protocols.
 mod messages;
+
+/// Protocol version negotiation common to all Stratum V2 protocols.
+mod version;
+
 pub use messages::{NetworkFrame, SetupConnectionErrorCodes};
+pub use version::VersionRange;
@@ -0,0 +1,104 @@
+use crate::error::{Error, Result};
+
+/// VersionRange is an inclusive range of Stratum V2 protocol versions. It is
+/// used to represent the versions supported by a node and to agree on the
+/// `used_version` of a connection.
+///
+/// # Examples
+///
+/// ```rust
+/// use stratumv2::common::VersionRange;
+///
+/// let server = VersionRange::new(2, 3).unwrap();
+/// assert_eq!(server.negotiate(2, 4), Some(3));
+/// assert_eq!(server.negotiate(4, 5), None);
+/// ```
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct VersionRange {
+    min: u16,
+    max: u16,
+}
+
+impl VersionRange {
+    /// The lowest protocol version defined by Stratum V2.
+    pub const MIN_VERSION: u16 = 2;
+
+    /// The constructor enforces that the range is not empty and that the
+    /// minimum version is at least [MIN_VERSION](#associatedconstant.MIN_VERSION).
+    pub fn new(min: u16, max: u16) -> Result<VersionRange> {
+        if min < Self::MIN_VERSION {
+            return Err(Error::VersionError("min_version must be atleast 2".into()));
+        }
+
+        if min > max {
+            return Err(Error::VersionError(
+                "min_version cannot be greater than max_version".into(),
+            ));
+        }
+
+        Ok(VersionRange { min, max })
+    }
+
+    /// Returns the minimum version in the range.
+    pub fn min(&self) -> u16 {
+        self.min
+    }
+
+    /// Returns the maximum version in the range.
+    pub fn max(&self) -> u16 {
+        self.max
+    }
+
+    /// Returns true if the version is within the range.
+    pub fn contains(&self, version: u16) -> bool {
+        self.min <= version && version <= self.max
+    }
+
+    /// Returns the highest version that is in this range and in the range
+    /// received from the other node. None is returned if the ranges do not
+    /// overlap, meaning the connection MUST be rejected with a
+    /// `protocol-version-mismatch` error.
+    pub fn negotiate(&self, min_version: u16, max_version: u16) -> Option<u16> {
+        let version = self.max.min(max_version);
+        if version < self.min.max(min_version) {
+            return None;
+        }
+
+        Some(version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_range_init() {
+        assert!(VersionRange::new(2, 2).is_ok());
+        assert!(VersionRange::new(2, 5).is_ok());
+        assert!(VersionRange::new(1, 2).is_err());
+        assert!(VersionRange::new(3, 2).is_err());
+    }
+
+    #[test]
+    fn version_range_contains() {
+        let range = VersionRange::new(2, 4).unwrap();
+        assert!(!range.contains(1));
+        assert!(range.contains(2));
+        assert!(range.contains(4));
+        assert!(!range.contains(5));
+    }
+
+    #[test]
+    fn version_range_negotiate() {
+        let range = VersionRange::new(2, 4).unwrap();
+
+        assert_eq!(range.negotiate(2, 2), Some(2));
+        assert_eq!(range.negotiate(3, 6), Some(4));
+        assert_eq!(range.negotiate(4, 4), Some(4));
+        assert_eq!(range.negotiate(5, 6), None);
+
+        // An empty range received from the other node can never be agreed.
+        assert_eq!(range.negotiate(3, 2), None);
+    }
+}
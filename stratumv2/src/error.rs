@@ -12,6 +12,7 @@ pub enum Error {
     RequirementError(String),
     DeserializationError(String),
     ParseError(String),
+    JsonError(serde_json::Error),
     AuthorityKeyError(ed25519_dalek::ed25519::Error),
     SystemTimeError(std::time::SystemTimeError),
     TryFromSliceError(std::array::TryFromSliceError),
@@ -30,6 +31,7 @@ impl fmt::Display for Error {
             Error::RequirementError(ref message) => write!(f, "{}", message),
             Error::DeserializationError(ref message) => write!(f, "{}", message),
             Error::ParseError(ref message) => write!(f, "{}", message),
+            Error::JsonError(ref message) => write!(f, "{}", message),
             Error::AuthorityKeyError(ref message) => write!(f, "{}", message),
             Error::SystemTimeError(ref message) => write!(f, "{}", message),
             Error::TryFromSliceError(ref message) => write!(f, "{}", message),
@@ -39,13 +41,16 @@ impl fmt::Display for Error {
     }
 }
 
+impl std::error::Error for Error {}
+
 impl_error_conversions!(
     std::str::Utf8Error => Error::Utf8Error,
     std::string::FromUtf8Error => Error::FromUtf8Error,
     io::Error => Error::IOError,
     ed25519_dalek::ed25519::Error => Error::AuthorityKeyError,
     std::time::SystemTimeError => Error::SystemTimeError,
-    std::array::TryFromSliceError => Error::TryFromSliceError
+    std::array::TryFromSliceError => Error::TryFromSliceError,
+    serde_json::Error => Error::JsonError
 );
 
 /// Alias Result type for the library.
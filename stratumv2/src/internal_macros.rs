@@ -87,5 +87,26 @@ macro_rules! impl_sized_STR0 {
                 s.0
             }
         }
+
+        #[cfg(feature = "serde")]
+        impl serde::Serialize for $type {
+            fn serialize<S: serde::Serializer>(
+                &self,
+                serializer: S,
+            ) -> std::result::Result<S::Ok, S::Error> {
+                serializer.serialize_str(&self.0)
+            }
+        }
+
+        /// Deserialization enforces the MAX_SIZE of the STR0 type.
+        #[cfg(feature = "serde")]
+        impl<'de> serde::Deserialize<'de> for $type {
+            fn deserialize<D: serde::Deserializer<'de>>(
+                deserializer: D,
+            ) -> std::result::Result<Self, D::Error> {
+                let value: String = serde::Deserialize::deserialize(deserializer)?;
+                $type::new(value).map_err(serde::de::Error::custom)
+            }
+        }
     };
 }
@@ -12,14 +12,14 @@ macro_rules! impl_setup_connection {
         /// # Examples
         ///
         /// ```rust
-        /// use std::borrow::Cow;
         /// use stratumv2::mining;
         /// use stratumv2::job_negotiation;
+        /// use stratumv2::types::FlagSet;
         ///
         /// let mining_connection = mining::SetupConnection::new(
         ///    2,
         ///    2,
-        ///    Cow::Borrowed(&[
+        ///    FlagSet::from([
         ///        mining::SetupConnectionFlags::RequiresStandardJobs,
         ///        mining::SetupConnectionFlags::RequiresVersionRolling
         ///     ]),
@@ -31,15 +31,15 @@ macro_rules! impl_setup_connection {
         ///    "some-device-uuid",
         /// );
         /// assert!(mining_connection.is_ok());
-        /// assert_eq!(
-        ///     mining_connection.unwrap().flags[0],
-        ///     mining::SetupConnectionFlags::RequiresStandardJobs
-        /// );
+        /// assert!(mining_connection
+        ///     .unwrap()
+        ///     .flags
+        ///     .contains(mining::SetupConnectionFlags::RequiresStandardJobs));
         ///
         /// let job_negotiation_connection = job_negotiation::SetupConnection::new(
         ///    2,
         ///    2,
-        ///    Cow::Borrowed(&[
+        ///    FlagSet::from([
         ///        job_negotiation::SetupConnectionFlags::RequiresAsyncJobMining,
         ///     ]),
         ///    "0.0.0.0",
@@ -52,7 +52,8 @@ macro_rules! impl_setup_connection {
         /// assert!(job_negotiation_connection.is_ok());
         /// ```
         #[derive(Debug, Clone)]
-        pub struct SetupConnection<'a> {
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+        pub struct SetupConnection {
             /// Used to indicate the protocol the client wants to use on the new connection.
             protocol: Protocol,
 
@@ -63,7 +64,7 @@ macro_rules! impl_setup_connection {
             pub max_version: u16,
 
             /// Flags indicating the optional protocol features the client supports.
-            pub flags: Cow<'a, [$flags]>,
+            pub flags: FlagSet<$flags>,
 
             /// Used to indicate the hostname or IP address of the endpoint.
             pub endpoint_host: STR0_255,
@@ -87,11 +88,11 @@ macro_rules! impl_setup_connection {
             pub device_id: STR0_255,
         }
 
-        impl<'a> SetupConnection<'a> {
+        impl SetupConnection {
             pub fn new<T: Into<String>>(
                 min_version: u16,
                 max_version: u16,
-                flags: Cow<'a, [$flags]>,
+                flags: FlagSet<$flags>,
                 endpoint_host: T,
                 endpoint_port: u16,
                 vendor: T,
@@ -134,18 +135,19 @@ macro_rules! impl_setup_connection {
                     device_id: STR0_255::new(device_id)?,
                 })
             }
+
+            /// Returns the sub protocol the client wants to use on the new
+            /// connection.
+            pub fn protocol(&self) -> Protocol {
+                self.protocol
+            }
         }
 
         /// Implementation of the Serializable trait to serialize the contents
         /// of the SetupConnection message to the valid message format.
-        impl<'a> Serializable for SetupConnection<'a> {
+        impl Serializable for SetupConnection {
             fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<usize> {
-                let byte_flags = self
-                    .flags
-                    .iter()
-                    .map(|x| x.as_bit_flag())
-                    .fold(0, |accumulator, byte| (accumulator | byte))
-                    .to_le_bytes();
+                let byte_flags = self.flags.bits().to_le_bytes();
 
                 let buffer = serialize_slices!(
                     &[self.protocol as u8],
@@ -164,8 +166,8 @@ macro_rules! impl_setup_connection {
             }
         }
 
-        impl<'a> Deserializable for SetupConnection<'a> {
-            fn deserialize(bytes: &[u8]) -> Result<SetupConnection<'a>> {
+        impl Deserializable for SetupConnection {
+            fn deserialize(bytes: &[u8]) -> Result<SetupConnection> {
                 let mut parser = ByteParser::new(bytes, 0);
 
                 let protocol = parser.next_by(1)?[0];
@@ -178,11 +180,7 @@ macro_rules! impl_setup_connection {
                 let min_version = parser.next_by(2)?;
                 let max_version = parser.next_by(2)?;
 
-                let set_flags = parser
-                    .next_by(4)?
-                    .iter()
-                    .map(|x| *x as u32)
-                    .fold(0, |accumulator, byte| (accumulator | byte));
+                let set_flags = u32::from_le_bytes(parser.next_by(4)?.try_into()?);
 
                 let endpoint_host_length = parser.next_by(1)?[0] as usize;
                 let endpoint_host = parser.next_by(endpoint_host_length)?;
@@ -201,21 +199,28 @@ macro_rules! impl_setup_connection {
                 let device_id_length = parser.next_by(1)?[0] as usize;
                 let device_id = parser.next_by(device_id_length)?;
 
-                SetupConnection::new(
+                let mut setup_connection = SetupConnection::new(
                     u16::from_le_bytes(min_version.try_into()?),
                     u16::from_le_bytes(max_version.try_into()?),
-                    Cow::from($flags::deserialize_flags(set_flags)),
+                    FlagSet::from_bits(set_flags),
                     str::from_utf8(endpoint_host)?,
                     u16::from_le_bytes(endpoint_port.try_into()?),
                     str::from_utf8(vendor)?,
                     str::from_utf8(hardware_version)?,
                     str::from_utf8(firmware)?,
                     str::from_utf8(device_id)?,
-                )
+                )?;
+
+                // Keep the received protocol so that a Server can respond with
+                // an unsupported-protocol error instead of silently assuming
+                // the sub protocol.
+                setup_connection.protocol = Protocol::from(protocol);
+
+                Ok(setup_connection)
             }
         }
 
-        impl_frameable_trait_with_lifetime!(SetupConnection, MessageTypes::SetupConnection, false, 'a);
+        impl_frameable_trait!(SetupConnection, MessageTypes::SetupConnection, false);
     };
 }
 
@@ -227,33 +232,33 @@ macro_rules! impl_setup_connection_success {
         /// # Examples
         ///
         /// ```rust
-        /// use std::borrow::Cow;
         /// use stratumv2::mining;
+        /// use stratumv2::types::FlagSet;
         ///
         /// let conn_success = mining::SetupConnectionSuccess::new(
         ///    2,
-        ///    Cow::Borrowed(&[
+        ///    FlagSet::from([
         ///        mining::SetupConnectionSuccessFlags::RequiresFixedVersion,
         ///     ]),
         /// );
-        /// assert_eq!(
-        ///     conn_success.flags[0],
-        ///     mining::SetupConnectionSuccessFlags::RequiresFixedVersion
-        /// );
+        /// assert!(conn_success
+        ///     .flags
+        ///     .contains(mining::SetupConnectionSuccessFlags::RequiresFixedVersion));
         /// ```
-        pub struct SetupConnectionSuccess<'a> {
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+        pub struct SetupConnectionSuccess {
             /// Version proposed by the connecting node as one of the verions supported
             /// by the upstream node. The version will be used during the lifetime of
             /// the connection.
             pub used_version: u16,
 
             /// Indicates the optional features the server supports.
-            pub flags: Cow<'a, [$flags]>,
+            pub flags: FlagSet<$flags>,
         }
 
-        impl<'a> SetupConnectionSuccess<'a> {
+        impl SetupConnectionSuccess {
             /// Constructor for the SetupConnectionSuccess message.
-            pub fn new(used_version: u16, flags: Cow<'a, [$flags]>) -> SetupConnectionSuccess {
+            pub fn new(used_version: u16, flags: FlagSet<$flags>) -> SetupConnectionSuccess {
                 SetupConnectionSuccess {
                     used_version,
                     flags,
@@ -261,39 +266,34 @@ macro_rules! impl_setup_connection_success {
             }
         }
 
-        impl Serializable for SetupConnectionSuccess<'_> {
+        impl Serializable for SetupConnectionSuccess {
             fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<usize> {
-                let byte_flags = self
-                    .flags
-                    .iter()
-                    .map(|x| x.as_bit_flag())
-                    .fold(0, |accumulator, byte| (accumulator | byte))
-                    .to_le_bytes();
+                let byte_flags = self.flags.bits().to_le_bytes();
 
                 let buffer = serialize_slices!(&self.used_version.to_le_bytes(), &byte_flags);
                 Ok(writer.write(&buffer)?)
             }
         }
 
-        impl<'a> Deserializable for SetupConnectionSuccess<'a> {
-            fn deserialize(bytes: &[u8]) -> Result<SetupConnectionSuccess<'a>> {
+        impl Deserializable for SetupConnectionSuccess {
+            fn deserialize(bytes: &[u8]) -> Result<SetupConnectionSuccess> {
                 let mut parser = ByteParser::new(bytes, 0);
 
                 let used_version_bytes = parser.next_by(2)?;
-                let set_flags = parser
-                    .next_by(4)?
-                    .iter()
-                    .map(|x| *x as u32)
-                    .fold(0, |accumulator, byte| (accumulator | byte));
+                let set_flags = u32::from_le_bytes(parser.next_by(4)?.try_into()?);
 
                 Ok(SetupConnectionSuccess {
                     used_version: u16::from_le_bytes(used_version_bytes.try_into()?),
-                    flags: Cow::from($flags::deserialize_flags(set_flags)),
+                    flags: FlagSet::from_bits(set_flags),
                 })
             }
         }
 
-        impl_frameable_trait_with_lifetime!(SetupConnectionSuccess, MessageTypes::SetupConnectionSuccess, false, 'a);
+        impl_frameable_trait!(
+            SetupConnectionSuccess,
+            MessageTypes::SetupConnectionSuccess,
+            false
+        );
     };
 }
 
@@ -313,12 +313,12 @@ macro_rules! impl_setup_connection_error {
         /// # Examples
         ///
         /// ```rust
-        /// use std::borrow::Cow;
         /// use stratumv2::mining;
         /// use stratumv2::common::SetupConnectionErrorCodes;
+        /// use stratumv2::types::FlagSet;
         ///
         /// let conn_error = mining::SetupConnectionError::new(
-        ///    Cow::Borrowed(&[
+        ///    FlagSet::from([
         ///        mining::SetupConnectionFlags::RequiresVersionRolling,
         ///     ]),
         ///        SetupConnectionErrorCodes::UnsupportedFeatureFlags
@@ -330,18 +330,20 @@ macro_rules! impl_setup_connection_error {
         ///     SetupConnectionErrorCodes::UnsupportedFeatureFlags
         /// );
         /// ```
-        pub struct SetupConnectionError<'a> {
-            /// Indicates all the flags that the server does NOT support.
-            pub flags: Cow<'a, [$flag_type]>,
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+        pub struct SetupConnectionError {
+            /// Indicates all the flags that the server does NOT support,
+            /// including any bits unknown to the server.
+            pub flags: FlagSet<$flag_type>,
 
             /// Error code is a predefined STR0_255 error code.
             pub error_code: SetupConnectionErrorCodes,
         }
 
-        impl<'a> SetupConnectionError<'a> {
+        impl SetupConnectionError {
             /// Constructor for the SetupConnectionError message.
             pub fn new(
-                flags: Cow<'a, [$flag_type]>,
+                flags: FlagSet<$flag_type>,
                 error_code: SetupConnectionErrorCodes,
             ) -> Result<SetupConnectionError> {
                 if flags.is_empty()
@@ -356,14 +358,9 @@ macro_rules! impl_setup_connection_error {
             }
         }
 
-        impl Serializable for SetupConnectionError<'_> {
+        impl Serializable for SetupConnectionError {
             fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<usize> {
-                let byte_flags = self
-                    .flags
-                    .iter()
-                    .map(|x| x.as_bit_flag())
-                    .fold(0, |accumulator, byte| (accumulator | byte))
-                    .to_le_bytes();
+                let byte_flags = self.flags.bits().to_le_bytes();
 
                 let result = serialize_slices!(
                     &byte_flags,
@@ -374,27 +371,27 @@ macro_rules! impl_setup_connection_error {
             }
         }
 
-        impl<'a> Deserializable for SetupConnectionError<'a> {
-            fn deserialize(bytes: &[u8]) -> Result<SetupConnectionError<'a>> {
+        impl Deserializable for SetupConnectionError {
+            fn deserialize(bytes: &[u8]) -> Result<SetupConnectionError> {
                 let mut parser = ByteParser::new(bytes, 0);
 
-                let set_flags = parser
-                    .next_by(4)?
-                    .iter()
-                    .map(|x| *x as u32)
-                    .fold(0, |accumulator, byte| (accumulator | byte));
+                let set_flags = u32::from_le_bytes(parser.next_by(4)?.try_into()?);
 
                 let error_code_length = parser.next_by(1)?[0] as usize;
                 let error_code = str::from_utf8(parser.next_by(error_code_length)?)?;
 
                 Ok(SetupConnectionError {
-                    flags: Cow::from($flag_type::deserialize_flags(set_flags)),
+                    flags: FlagSet::from_bits(set_flags),
                     error_code: SetupConnectionErrorCodes::from_str(error_code)?,
                 })
             }
         }
 
-        impl_frameable_trait_with_lifetime!(SetupConnectionError, MessageTypes::SetupConnectionError, false, 'a);
+        impl_frameable_trait!(
+            SetupConnectionError,
+            MessageTypes::SetupConnectionError,
+            false
+        );
     };
 }
 
@@ -402,9 +399,10 @@ macro_rules! impl_setup_connection_error {
 /// Standard Mining Channels and Extended Mining Channels.
 macro_rules! impl_open_mining_channel_error {
     ($name:ident, $msg_type:path) => {
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
         pub struct $name {
-            request_id: u32,
-            error_code: OpenMiningChannelErrorCodes,
+            pub request_id: u32,
+            pub error_code: OpenMiningChannelErrorCodes,
         }
 
         impl $name {
@@ -452,24 +450,23 @@ macro_rules! impl_message_flag {
     ($flag_type:ident, $($variant:path => $shift:expr),*) => {
 
         impl BitFlag for $flag_type {
-            /// Gets the set bit representation of a SetupConnectionFlag as a u32.
+            /// Every known variant of the flag type, used by a
+            /// [FlagSet](../types/struct.FlagSet.html) to iterate set flags.
             ///
             /// # Example
             ///
             /// ```rust
-            /// use stratumv2::BitFlag;
             /// use stratumv2::mining;
+            /// use stratumv2::types::FlagSet;
             ///
-            /// let standard_job = mining::SetupConnectionFlags::RequiresStandardJobs.as_bit_flag();
-            /// assert_eq!(standard_job, 0x01);
+            /// let flags = FlagSet::<mining::SetupConnectionFlags>::from_bits(3);
+            /// let flags: Vec<_> = flags.iter().collect();
+            /// assert_eq!(flags[0], mining::SetupConnectionFlags::RequiresStandardJobs);
+            /// assert_eq!(flags[1], mining::SetupConnectionFlags::RequiresWorkSelection);
             /// ```
-            fn as_bit_flag(&self) -> u32 {
-                match self {
-                    $($variant => (1 << $shift)),*
-                }
-            }
+            const FLAGS: &'static [$flag_type] = &[$($variant),*];
 
-            /// Gets a vector of enums representing message flags.
+            /// Gets the set bit representation of a SetupConnectionFlag as a u32.
             ///
             /// # Example
             ///
@@ -477,18 +474,13 @@ macro_rules! impl_message_flag {
             /// use stratumv2::BitFlag;
             /// use stratumv2::mining;
             ///
-            /// let flags = mining::SetupConnectionFlags::deserialize_flags(3);
-            /// assert_eq!(flags[0], mining::SetupConnectionFlags::RequiresStandardJobs);
-            /// assert_eq!(flags[1], mining::SetupConnectionFlags::RequiresWorkSelection);
+            /// let standard_job = mining::SetupConnectionFlags::RequiresStandardJobs.as_bit_flag();
+            /// assert_eq!(standard_job, 0x01);
             /// ```
-            fn deserialize_flags(flags: u32) -> Vec<$flag_type> {
-                let mut result = Vec::new();
-
-                $(if flags & $variant.as_bit_flag() != 0 {
-                    result.push($variant)
-                })*
-
-                result
+            fn as_bit_flag(&self) -> u32 {
+                match self {
+                    $($variant => (1 << $shift)),*
+                }
             }
         }
     };
@@ -497,8 +489,6 @@ macro_rules! impl_message_flag {
 /// Implemenation of all the common traits for ErrorCode enums.
 macro_rules! impl_error_codes_enum {
     ($name:ident, $($variant:path => $str:expr),*) => {
-        use std::str::FromStr;
-
         impl fmt::Display for $name {
             fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
                 match *self {
@@ -508,7 +498,7 @@ macro_rules! impl_error_codes_enum {
         }
 
 
-        impl FromStr for $name {
+        impl std::str::FromStr for $name {
             type Err = Error;
 
             fn from_str(s: &str) -> Result<Self> {
@@ -518,6 +508,27 @@ macro_rules! impl_error_codes_enum {
                 }
             }
         }
+
+        /// Serializes the error code as its STR0 representation.
+        #[cfg(feature = "serde")]
+        impl serde::Serialize for $name {
+            fn serialize<S: serde::Serializer>(
+                &self,
+                serializer: S,
+            ) -> std::result::Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<'de> serde::Deserialize<'de> for $name {
+            fn deserialize<D: serde::Deserializer<'de>>(
+                deserializer: D,
+            ) -> std::result::Result<Self, D::Error> {
+                let value: String = serde::Deserialize::deserialize(deserializer)?;
+                value.parse().map_err(serde::de::Error::custom)
+            }
+        }
     };
 }
 
@@ -533,14 +544,6 @@ macro_rules! impl_frameable_trait {
     };
 }
 
-macro_rules! impl_frameable_trait_with_lifetime {
-    ($msg:ident, $msg_type:path, $has_channel_msg_bit:expr, $lt:lifetime) => {
-        impl<$lt> Frameable for $msg<$lt> {
-            internal_frameable_trait!($msg_type, $has_channel_msg_bit);
-        }
-    };
-}
-
 // TODO: Implement a conditional branch to set the channel msg bit.
 macro_rules! internal_frameable_trait {
     ($msg_type:path, $has_channel_msg_bit:expr) => {
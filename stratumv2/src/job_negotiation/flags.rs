@@ -2,7 +2,8 @@ use crate::BitFlag;
 
 /// Feature flags that can be passed to a SetupConnection message for the
 /// job negotiation protocol. Each flag corresponds to a set bit.
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SetupConnectionFlags {
     // TODO: Add hyperlinks to all everything between ``
     /// Flag indicating that the `mining_job_token` from `AllocateMiningJobToken.Success`
@@ -12,3 +13,19 @@ pub enum SetupConnectionFlags {
 }
 
 impl_message_flag!(SetupConnectionFlags, SetupConnectionFlags::RequiresAsyncJobMining => 0);
+
+/// Feature flags for the SetupConnectionSuccess message for the job
+/// negotiation protocol. The protocol does not define any flags, bits set by
+/// newer versions of the protocol are kept by the
+/// [FlagSet](../types/struct.FlagSet.html) as unknown bits.
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SetupConnectionSuccessFlags {}
+
+impl BitFlag for SetupConnectionSuccessFlags {
+    const FLAGS: &'static [SetupConnectionSuccessFlags] = &[];
+
+    fn as_bit_flag(&self) -> u32 {
+        match *self {}
+    }
+}
@@ -1,13 +1,775 @@
+use crate::common::SetupConnectionErrorCodes;
 use crate::error::{Error, Result};
-use crate::job_negotiation::SetupConnectionFlags;
-use crate::types::{MessageTypes, STR0_255};
-use crate::util::ByteParser;
-use crate::{BitFlag, Deserializable, Frameable, Protocol, Serializable};
-use std::borrow::Cow;
+use crate::job_negotiation::{SetupConnectionFlags, SetupConnectionSuccessFlags};
+use crate::types::{FlagSet, MessageTypes, B0_16M, B0_255, B0_64K, STR0_255, U256};
+use crate::util::{deserialize_bool, ByteParser};
+use crate::{Deserializable, Frameable, Protocol, Serializable};
+use std::fmt;
+use std::str::FromStr;
 use std::{io, str};
 
-// Implementation of the SetupConenction message for the Job Negotiation Protocol.
+// Implementation of the SetupConnection, SetupConnectionSuccess and
+// SetupConnectionError messages for the Job Negotiation Protocol.
 impl_setup_connection!(Protocol::JobNegotiation, SetupConnectionFlags);
+impl_setup_connection_success!(SetupConnectionSuccessFlags);
+impl_setup_connection_error!(SetupConnectionFlags);
+
+/// The maximum number of items in a SEQ0_64K.
+const MAX_SEQ0_64K: usize = 65535;
+
+/// A short transaction id, the 6 least significant bytes of the SipHash-2-4
+/// of a transaction hash, used to identify transactions in a
+/// [CommitMiningJob](struct.CommitMiningJob.html).
+pub type ShortTxId = [u8; 6];
+
+/// AllocateMiningJobToken is a message sent by the Job Negotiator to the
+/// Mining Pool to request a token, which is used to commit a mining job.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AllocateMiningJobToken {
+    /// The user the mining job is negotiated for, as used in the mining
+    /// channels opened with the Mining Pool.
+    pub user_identifier: STR0_255,
+
+    /// A Job Negotiator identifier for the request.
+    pub request_id: u32,
+}
+
+impl AllocateMiningJobToken {
+    pub fn new<T: Into<String>>(
+        user_identifier: T,
+        request_id: u32,
+    ) -> Result<AllocateMiningJobToken> {
+        Ok(AllocateMiningJobToken {
+            user_identifier: STR0_255::new(user_identifier)?,
+            request_id,
+        })
+    }
+}
+
+impl Serializable for AllocateMiningJobToken {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<usize> {
+        let buffer = serialize_slices!(
+            &self.user_identifier.as_bytes(),
+            &self.request_id.to_le_bytes()
+        );
+
+        Ok(writer.write(&buffer)?)
+    }
+}
+
+impl Deserializable for AllocateMiningJobToken {
+    fn deserialize(bytes: &[u8]) -> Result<AllocateMiningJobToken> {
+        let mut parser = ByteParser::new(bytes, 0);
+
+        let user_identifier_length = parser.next_by(1)?[0] as usize;
+        let user_identifier = str::from_utf8(parser.next_by(user_identifier_length)?)?;
+        let request_id = parser.next_by(4)?;
+
+        AllocateMiningJobToken::new(user_identifier, u32::from_le_bytes(request_id.try_into()?))
+    }
+}
+
+impl_frameable_trait!(
+    AllocateMiningJobToken,
+    MessageTypes::AllocateMiningJobToken,
+    false
+);
+
+/// AllocateMiningJobTokenSuccess is a message sent by the Mining Pool to the
+/// Job Negotiator with an allocated token.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AllocateMiningJobTokenSuccess {
+    /// The request the token was allocated for.
+    pub request_id: u32,
+
+    /// The token, used once in a [CommitMiningJob](struct.CommitMiningJob.html).
+    pub mining_job_token: B0_255,
+
+    /// The maximum additional serialized bytes the Mining Pool will add to
+    /// the coinbase outputs, which the Template Provider must leave space
+    /// for.
+    pub coinbase_output_max_additional_size: u32,
+
+    /// True if the token can be used on a mining connection before the
+    /// mining job is committed.
+    pub async_mining_allowed: bool,
+}
+
+impl AllocateMiningJobTokenSuccess {
+    pub fn new<T: Into<Vec<u8>>>(
+        request_id: u32,
+        mining_job_token: T,
+        coinbase_output_max_additional_size: u32,
+        async_mining_allowed: bool,
+    ) -> Result<AllocateMiningJobTokenSuccess> {
+        Ok(AllocateMiningJobTokenSuccess {
+            request_id,
+            mining_job_token: B0_255::new(mining_job_token)?,
+            coinbase_output_max_additional_size,
+            async_mining_allowed,
+        })
+    }
+}
+
+impl Serializable for AllocateMiningJobTokenSuccess {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<usize> {
+        let buffer = serialize_slices!(
+            &self.request_id.to_le_bytes(),
+            &self.mining_job_token.as_bytes(),
+            &self.coinbase_output_max_additional_size.to_le_bytes(),
+            &[self.async_mining_allowed as u8]
+        );
+
+        Ok(writer.write(&buffer)?)
+    }
+}
+
+impl Deserializable for AllocateMiningJobTokenSuccess {
+    fn deserialize(bytes: &[u8]) -> Result<AllocateMiningJobTokenSuccess> {
+        let mut parser = ByteParser::new(bytes, 0);
+
+        let request_id = parser.next_by(4)?;
+        let mining_job_token_length = parser.next_by(1)?[0] as usize;
+        let mining_job_token = parser.next_by(mining_job_token_length)?;
+        let coinbase_output_max_additional_size = parser.next_by(4)?;
+        let async_mining_allowed = deserialize_bool(parser.next_by(1)?[0])?;
+
+        AllocateMiningJobTokenSuccess::new(
+            u32::from_le_bytes(request_id.try_into()?),
+            mining_job_token.to_vec(),
+            u32::from_le_bytes(coinbase_output_max_additional_size.try_into()?),
+            async_mining_allowed,
+        )
+    }
+}
+
+impl_frameable_trait!(
+    AllocateMiningJobTokenSuccess,
+    MessageTypes::AllocateMiningJobTokenSuccess,
+    false
+);
+
+/// CommitMiningJob is a message sent by the Job Negotiator to the Mining Pool
+/// to propose a mining job. The coinbase transaction is sent in full, except
+/// for the extranonce, and the other transactions are identified by their
+/// short ids so the Mining Pool can rebuild the block from its mempool.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CommitMiningJob {
+    /// A Job Negotiator identifier for the request.
+    pub request_id: u32,
+
+    /// A token allocated by the Mining Pool, which is consumed by the
+    /// request.
+    pub mining_job_token: B0_255,
+
+    /// The version field of the block header.
+    pub version: u32,
+
+    /// The version field of the coinbase transaction.
+    pub coinbase_tx_version: u32,
+
+    /// Up to 8 bytes placed at the beginning of the script of the coinbase
+    /// input, before the extranonce.
+    pub coinbase_prefix: B0_255,
+
+    /// The sequence field of the coinbase input.
+    pub coinbase_tx_input_sequence: u32,
+
+    /// The value in satoshis of the coinbase not spent by
+    /// `coinbase_tx_outputs`.
+    pub coinbase_tx_value_remaining: u64,
+
+    /// The serialized outputs of the coinbase transaction.
+    pub coinbase_tx_outputs: Vec<B0_64K>,
+
+    /// The lock time field of the coinbase transaction.
+    pub coinbase_tx_locktime: u32,
+
+    /// The minimum extranonce size the mining job requires in the coinbase
+    /// input script.
+    pub min_extranonce_size: u16,
+
+    /// The nonce keying the SipHash of the short transaction ids.
+    pub tx_short_hash_nonce: u64,
+
+    /// The short ids of the transactions of the block, excluding the
+    /// coinbase transaction, in block order.
+    #[cfg_attr(feature = "serde", serde(with = "crate::util::hex::array_list"))]
+    pub tx_short_hash_list: Vec<ShortTxId>,
+
+    /// The SHA256 of the concatenated SHA256 of every serialized transaction
+    /// of `tx_short_hash_list`, used to detect short id collisions.
+    #[cfg_attr(feature = "serde", serde(with = "crate::util::hex::array"))]
+    pub tx_hash_list_hash: U256,
+
+    /// Extra data required to build the block, such as the witness reserved
+    /// value of the coinbase transaction.
+    pub excess_data: B0_64K,
+}
+
+impl CommitMiningJob {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new<T: Into<Vec<u8>>>(
+        request_id: u32,
+        mining_job_token: T,
+        version: u32,
+        coinbase_tx_version: u32,
+        coinbase_prefix: T,
+        coinbase_tx_input_sequence: u32,
+        coinbase_tx_value_remaining: u64,
+        coinbase_tx_outputs: Vec<Vec<u8>>,
+        coinbase_tx_locktime: u32,
+        min_extranonce_size: u16,
+        tx_short_hash_nonce: u64,
+        tx_short_hash_list: Vec<ShortTxId>,
+        tx_hash_list_hash: U256,
+        excess_data: T,
+    ) -> Result<CommitMiningJob> {
+        let coinbase_prefix = coinbase_prefix.into();
+        if coinbase_prefix.len() > 8 {
+            return Err(Error::RequirementError(
+                "coinbase_prefix cannot be greater than 8 bytes".into(),
+            ));
+        }
+
+        if coinbase_tx_outputs.len() > MAX_SEQ0_64K {
+            return Err(Error::RequirementError(
+                "coinbase_tx_outputs cannot contain more than 65535 outputs".into(),
+            ));
+        }
+
+        if tx_short_hash_list.len() > MAX_SEQ0_64K {
+            return Err(Error::RequirementError(
+                "tx_short_hash_list cannot contain more than 65535 transactions".into(),
+            ));
+        }
+
+        Ok(CommitMiningJob {
+            request_id,
+            mining_job_token: B0_255::new(mining_job_token)?,
+            version,
+            coinbase_tx_version,
+            coinbase_prefix: B0_255::new(coinbase_prefix)?,
+            coinbase_tx_input_sequence,
+            coinbase_tx_value_remaining,
+            coinbase_tx_outputs: coinbase_tx_outputs
+                .into_iter()
+                .map(B0_64K::new)
+                .collect::<Result<Vec<B0_64K>>>()?,
+            coinbase_tx_locktime,
+            min_extranonce_size,
+            tx_short_hash_nonce,
+            tx_short_hash_list,
+            tx_hash_list_hash,
+            excess_data: B0_64K::new(excess_data)?,
+        })
+    }
+}
+
+impl Serializable for CommitMiningJob {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<usize> {
+        let mut coinbase_tx_outputs = (self.coinbase_tx_outputs.len() as u16)
+            .to_le_bytes()
+            .to_vec();
+        for output in &self.coinbase_tx_outputs {
+            coinbase_tx_outputs.extend_from_slice(&output.as_bytes());
+        }
+
+        let mut tx_short_hash_list = (self.tx_short_hash_list.len() as u16)
+            .to_le_bytes()
+            .to_vec();
+        for short_id in &self.tx_short_hash_list {
+            tx_short_hash_list.extend_from_slice(short_id);
+        }
+
+        let buffer = serialize_slices!(
+            &self.request_id.to_le_bytes(),
+            &self.mining_job_token.as_bytes(),
+            &self.version.to_le_bytes(),
+            &self.coinbase_tx_version.to_le_bytes(),
+            &self.coinbase_prefix.as_bytes(),
+            &self.coinbase_tx_input_sequence.to_le_bytes(),
+            &self.coinbase_tx_value_remaining.to_le_bytes(),
+            &coinbase_tx_outputs,
+            &self.coinbase_tx_locktime.to_le_bytes(),
+            &self.min_extranonce_size.to_le_bytes(),
+            &self.tx_short_hash_nonce.to_le_bytes(),
+            &tx_short_hash_list,
+            &self.tx_hash_list_hash,
+            &self.excess_data.as_bytes()
+        );
+
+        Ok(writer.write(&buffer)?)
+    }
+}
+
+impl Deserializable for CommitMiningJob {
+    fn deserialize(bytes: &[u8]) -> Result<CommitMiningJob> {
+        let mut parser = ByteParser::new(bytes, 0);
+
+        let request_id = parser.next_by(4)?;
+        let mining_job_token_length = parser.next_by(1)?[0] as usize;
+        let mining_job_token = parser.next_by(mining_job_token_length)?;
+        let version = parser.next_by(4)?;
+        let coinbase_tx_version = parser.next_by(4)?;
+        let coinbase_prefix_length = parser.next_by(1)?[0] as usize;
+        let coinbase_prefix = parser.next_by(coinbase_prefix_length)?;
+        let coinbase_tx_input_sequence = parser.next_by(4)?;
+        let coinbase_tx_value_remaining = parser.next_by(8)?;
+
+        let coinbase_tx_outputs_length = u16::from_le_bytes(parser.next_by(2)?.try_into()?);
+        let mut coinbase_tx_outputs = Vec::with_capacity(coinbase_tx_outputs_length as usize);
+        for _ in 0..coinbase_tx_outputs_length {
+            let length = u16::from_le_bytes(parser.next_by(2)?.try_into()?);
+            coinbase_tx_outputs.push(parser.next_by(length as usize)?.to_vec());
+        }
+
+        let coinbase_tx_locktime = parser.next_by(4)?;
+        let min_extranonce_size = parser.next_by(2)?;
+        let tx_short_hash_nonce = parser.next_by(8)?;
+
+        let tx_short_hash_list_length = u16::from_le_bytes(parser.next_by(2)?.try_into()?);
+        let mut tx_short_hash_list = Vec::with_capacity(tx_short_hash_list_length as usize);
+        for _ in 0..tx_short_hash_list_length {
+            tx_short_hash_list.push(parser.next_by(6)?.try_into()?);
+        }
+
+        let tx_hash_list_hash = parser.next_by(32)?;
+        let excess_data_length = u16::from_le_bytes(parser.next_by(2)?.try_into()?);
+        let excess_data = parser.next_by(excess_data_length as usize)?;
+
+        CommitMiningJob::new(
+            u32::from_le_bytes(request_id.try_into()?),
+            mining_job_token.to_vec(),
+            u32::from_le_bytes(version.try_into()?),
+            u32::from_le_bytes(coinbase_tx_version.try_into()?),
+            coinbase_prefix.to_vec(),
+            u32::from_le_bytes(coinbase_tx_input_sequence.try_into()?),
+            u64::from_le_bytes(coinbase_tx_value_remaining.try_into()?),
+            coinbase_tx_outputs,
+            u32::from_le_bytes(coinbase_tx_locktime.try_into()?),
+            u16::from_le_bytes(min_extranonce_size.try_into()?),
+            u64::from_le_bytes(tx_short_hash_nonce.try_into()?),
+            tx_short_hash_list,
+            tx_hash_list_hash.try_into()?,
+            excess_data.to_vec(),
+        )
+    }
+}
+
+impl_frameable_trait!(CommitMiningJob, MessageTypes::CommitMiningJob, false);
+
+/// CommitMiningJobSuccess is a message sent by the Mining Pool to the Job
+/// Negotiator when a mining job is accepted.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CommitMiningJobSuccess {
+    /// The request the mining job was committed with.
+    pub request_id: u32,
+
+    /// The token identifying the committed mining job on mining connections,
+    /// used in SetCustomMiningJob.
+    pub new_mining_job_token: B0_255,
+}
+
+impl CommitMiningJobSuccess {
+    pub fn new<T: Into<Vec<u8>>>(
+        request_id: u32,
+        new_mining_job_token: T,
+    ) -> Result<CommitMiningJobSuccess> {
+        Ok(CommitMiningJobSuccess {
+            request_id,
+            new_mining_job_token: B0_255::new(new_mining_job_token)?,
+        })
+    }
+}
+
+impl Serializable for CommitMiningJobSuccess {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<usize> {
+        let buffer = serialize_slices!(
+            &self.request_id.to_le_bytes(),
+            &self.new_mining_job_token.as_bytes()
+        );
+
+        Ok(writer.write(&buffer)?)
+    }
+}
+
+impl Deserializable for CommitMiningJobSuccess {
+    fn deserialize(bytes: &[u8]) -> Result<CommitMiningJobSuccess> {
+        let mut parser = ByteParser::new(bytes, 0);
+
+        let request_id = parser.next_by(4)?;
+        let new_mining_job_token_length = parser.next_by(1)?[0] as usize;
+        let new_mining_job_token = parser.next_by(new_mining_job_token_length)?;
+
+        CommitMiningJobSuccess::new(
+            u32::from_le_bytes(request_id.try_into()?),
+            new_mining_job_token.to_vec(),
+        )
+    }
+}
+
+impl_frameable_trait!(
+    CommitMiningJobSuccess,
+    MessageTypes::CommitMiningJobSuccess,
+    false
+);
+
+/// CommitMiningJobError is a message sent by the Mining Pool to the Job
+/// Negotiator when a mining job is rejected.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CommitMiningJobError {
+    /// The request the mining job was committed with.
+    pub request_id: u32,
+
+    /// The reason the mining job was rejected.
+    pub error_code: CommitMiningJobErrorCodes,
+
+    /// Optional details about the error, such as the offending transaction.
+    pub error_details: B0_64K,
+}
+
+impl CommitMiningJobError {
+    pub fn new<T: Into<Vec<u8>>>(
+        request_id: u32,
+        error_code: CommitMiningJobErrorCodes,
+        error_details: T,
+    ) -> Result<CommitMiningJobError> {
+        Ok(CommitMiningJobError {
+            request_id,
+            error_code,
+            error_details: B0_64K::new(error_details)?,
+        })
+    }
+}
+
+impl Serializable for CommitMiningJobError {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<usize> {
+        let buffer = serialize_slices!(
+            &self.request_id.to_le_bytes(),
+            &STR0_255::new(self.error_code.to_string())?.as_bytes(),
+            &self.error_details.as_bytes()
+        );
+
+        Ok(writer.write(&buffer)?)
+    }
+}
+
+impl Deserializable for CommitMiningJobError {
+    fn deserialize(bytes: &[u8]) -> Result<CommitMiningJobError> {
+        let mut parser = ByteParser::new(bytes, 0);
+
+        let request_id = parser.next_by(4)?;
+        let error_code_length = parser.next_by(1)?[0] as usize;
+        let error_code = str::from_utf8(parser.next_by(error_code_length)?)?;
+        let error_details_length = u16::from_le_bytes(parser.next_by(2)?.try_into()?);
+        let error_details = parser.next_by(error_details_length as usize)?;
+
+        CommitMiningJobError::new(
+            u32::from_le_bytes(request_id.try_into()?),
+            CommitMiningJobErrorCodes::from_str(error_code)?,
+            error_details.to_vec(),
+        )
+    }
+}
+
+impl_frameable_trait!(
+    CommitMiningJobError,
+    MessageTypes::CommitMiningJobError,
+    false
+);
+
+/// Contains the error codes for the
+/// [CommitMiningJob.Error](struct.CommitMiningJobError.html) message. Each
+/// error code is serialized according to constraints of a
+/// [STR0_255](../types/struct.STR0_255.html).
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CommitMiningJobErrorCodes {
+    /// The token was not allocated by the Mining Pool or was already used.
+    InvalidMiningJobToken,
+
+    /// A field of the mining job has a value the Mining Pool does not
+    /// accept.
+    InvalidJobParamValue,
+
+    /// The transactions of the mining job could not be identified.
+    MissingTransactions,
+
+    /// The coinbase transaction does not contain an output paying the
+    /// Mining Pool.
+    MissingPayoutOutput,
+
+    /// The outputs paying the Mining Pool are greater than the
+    /// `coinbase_output_max_additional_size` of the token.
+    CoinbaseOutputsTooLarge,
+
+    /// The block would exceed the weight accepted by the Mining Pool.
+    BlockWeightTooLarge,
+
+    /// The block contains a transaction the Mining Pool does not mine.
+    ForbiddenTransaction,
+}
+
+impl_error_codes_enum!(
+    CommitMiningJobErrorCodes,
+    CommitMiningJobErrorCodes::InvalidMiningJobToken => "invalid-mining-job-token",
+    CommitMiningJobErrorCodes::InvalidJobParamValue => "invalid-job-param-value",
+    CommitMiningJobErrorCodes::MissingTransactions => "missing-transactions",
+    CommitMiningJobErrorCodes::MissingPayoutOutput => "missing-payout-output",
+    CommitMiningJobErrorCodes::CoinbaseOutputsTooLarge => "coinbase-outputs-too-large",
+    CommitMiningJobErrorCodes::BlockWeightTooLarge => "block-weight-too-large",
+    CommitMiningJobErrorCodes::ForbiddenTransaction => "forbidden-transaction"
+);
+
+/// IdentifyTransactions is a message sent by the Mining Pool to the Job
+/// Negotiator when the short ids of a
+/// [CommitMiningJob](struct.CommitMiningJob.html) collide or cannot be
+/// resolved, requesting the full hashes of the transactions.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IdentifyTransactions {
+    /// The request the mining job was committed with.
+    pub request_id: u32,
+}
+
+impl IdentifyTransactions {
+    pub fn new(request_id: u32) -> IdentifyTransactions {
+        IdentifyTransactions { request_id }
+    }
+}
+
+impl Serializable for IdentifyTransactions {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<usize> {
+        Ok(writer.write(&self.request_id.to_le_bytes())?)
+    }
+}
+
+impl Deserializable for IdentifyTransactions {
+    fn deserialize(bytes: &[u8]) -> Result<IdentifyTransactions> {
+        let mut parser = ByteParser::new(bytes, 0);
+
+        let request_id = parser.next_by(4)?;
+
+        Ok(IdentifyTransactions::new(u32::from_le_bytes(
+            request_id.try_into()?,
+        )))
+    }
+}
+
+impl_frameable_trait!(
+    IdentifyTransactions,
+    MessageTypes::IdentifyTransactions,
+    false
+);
+
+/// IdentifyTransactionsSuccess is a message sent by the Job Negotiator to the
+/// Mining Pool with the full hashes of the transactions of a mining job.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IdentifyTransactionsSuccess {
+    /// The request the mining job was committed with.
+    pub request_id: u32,
+
+    /// The wtxid of every transaction of the mining job, in the order of
+    /// `tx_short_hash_list`.
+    #[cfg_attr(feature = "serde", serde(with = "crate::util::hex::array_list"))]
+    pub tx_data_hashes: Vec<U256>,
+}
+
+impl IdentifyTransactionsSuccess {
+    pub fn new(request_id: u32, tx_data_hashes: Vec<U256>) -> Result<IdentifyTransactionsSuccess> {
+        if tx_data_hashes.len() > MAX_SEQ0_64K {
+            return Err(Error::RequirementError(
+                "tx_data_hashes cannot contain more than 65535 hashes".into(),
+            ));
+        }
+
+        Ok(IdentifyTransactionsSuccess {
+            request_id,
+            tx_data_hashes,
+        })
+    }
+}
+
+impl Serializable for IdentifyTransactionsSuccess {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<usize> {
+        let mut tx_data_hashes = (self.tx_data_hashes.len() as u16).to_le_bytes().to_vec();
+        for hash in &self.tx_data_hashes {
+            tx_data_hashes.extend_from_slice(hash);
+        }
+
+        let buffer = serialize_slices!(&self.request_id.to_le_bytes(), &tx_data_hashes);
+
+        Ok(writer.write(&buffer)?)
+    }
+}
+
+impl Deserializable for IdentifyTransactionsSuccess {
+    fn deserialize(bytes: &[u8]) -> Result<IdentifyTransactionsSuccess> {
+        let mut parser = ByteParser::new(bytes, 0);
+
+        let request_id = parser.next_by(4)?;
+        let tx_data_hashes_length = u16::from_le_bytes(parser.next_by(2)?.try_into()?);
+        let mut tx_data_hashes = Vec::with_capacity(tx_data_hashes_length as usize);
+        for _ in 0..tx_data_hashes_length {
+            tx_data_hashes.push(parser.next_by(32)?.try_into()?);
+        }
+
+        IdentifyTransactionsSuccess::new(u32::from_le_bytes(request_id.try_into()?), tx_data_hashes)
+    }
+}
+
+impl_frameable_trait!(
+    IdentifyTransactionsSuccess,
+    MessageTypes::IdentifyTransactionsSuccess,
+    false
+);
+
+/// ProvideMissingTransactions is a message sent by the Mining Pool to the Job
+/// Negotiator requesting the transactions of a mining job missing from its
+/// mempool.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProvideMissingTransactions {
+    /// The request the mining job was committed with.
+    pub request_id: u32,
+
+    /// The positions of the missing transactions in the
+    /// `tx_short_hash_list` of the mining job.
+    pub unknown_tx_position_list: Vec<u16>,
+}
+
+impl ProvideMissingTransactions {
+    pub fn new(
+        request_id: u32,
+        unknown_tx_position_list: Vec<u16>,
+    ) -> Result<ProvideMissingTransactions> {
+        if unknown_tx_position_list.len() > MAX_SEQ0_64K {
+            return Err(Error::RequirementError(
+                "unknown_tx_position_list cannot contain more than 65535 positions".into(),
+            ));
+        }
+
+        Ok(ProvideMissingTransactions {
+            request_id,
+            unknown_tx_position_list,
+        })
+    }
+}
+
+impl Serializable for ProvideMissingTransactions {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<usize> {
+        let mut positions = (self.unknown_tx_position_list.len() as u16)
+            .to_le_bytes()
+            .to_vec();
+        for position in &self.unknown_tx_position_list {
+            positions.extend_from_slice(&position.to_le_bytes());
+        }
+
+        let buffer = serialize_slices!(&self.request_id.to_le_bytes(), &positions);
+
+        Ok(writer.write(&buffer)?)
+    }
+}
+
+impl Deserializable for ProvideMissingTransactions {
+    fn deserialize(bytes: &[u8]) -> Result<ProvideMissingTransactions> {
+        let mut parser = ByteParser::new(bytes, 0);
+
+        let request_id = parser.next_by(4)?;
+        let positions_length = u16::from_le_bytes(parser.next_by(2)?.try_into()?);
+        let mut positions = Vec::with_capacity(positions_length as usize);
+        for _ in 0..positions_length {
+            positions.push(u16::from_le_bytes(parser.next_by(2)?.try_into()?));
+        }
+
+        ProvideMissingTransactions::new(u32::from_le_bytes(request_id.try_into()?), positions)
+    }
+}
+
+impl_frameable_trait!(
+    ProvideMissingTransactions,
+    MessageTypes::ProvideMissingTransactions,
+    false
+);
+
+/// ProvideMissingTransactionsSuccess is a message sent by the Job Negotiator
+/// to the Mining Pool with the transactions missing from its mempool.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProvideMissingTransactionsSuccess {
+    /// The request the mining job was committed with.
+    pub request_id: u32,
+
+    /// The serialized transactions, in the order of the requested
+    /// positions.
+    pub transaction_list: Vec<B0_16M>,
+}
+
+impl ProvideMissingTransactionsSuccess {
+    pub fn new(
+        request_id: u32,
+        transaction_list: Vec<Vec<u8>>,
+    ) -> Result<ProvideMissingTransactionsSuccess> {
+        if transaction_list.len() > MAX_SEQ0_64K {
+            return Err(Error::RequirementError(
+                "transaction_list cannot contain more than 65535 transactions".into(),
+            ));
+        }
+
+        Ok(ProvideMissingTransactionsSuccess {
+            request_id,
+            transaction_list: transaction_list
+                .into_iter()
+                .map(B0_16M::new)
+                .collect::<Result<Vec<B0_16M>>>()?,
+        })
+    }
+}
+
+impl Serializable for ProvideMissingTransactionsSuccess {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<usize> {
+        let mut transaction_list = (self.transaction_list.len() as u16).to_le_bytes().to_vec();
+        for transaction in &self.transaction_list {
+            transaction_list.extend_from_slice(&transaction.as_bytes());
+        }
+
+        let buffer = serialize_slices!(&self.request_id.to_le_bytes(), &transaction_list);
+
+        Ok(writer.write(&buffer)?)
+    }
+}
+
+impl Deserializable for ProvideMissingTransactionsSuccess {
+    fn deserialize(bytes: &[u8]) -> Result<ProvideMissingTransactionsSuccess> {
+        let mut parser = ByteParser::new(bytes, 0);
+
+        let request_id = parser.next_by(4)?;
+        let transaction_list_length = u16::from_le_bytes(parser.next_by(2)?.try_into()?);
+        let mut transaction_list = Vec::with_capacity(transaction_list_length as usize);
+        for _ in 0..transaction_list_length {
+            let length = parser.next_by(3)?;
+            let length = u32::from_le_bytes([length[0], length[1], length[2], 0]);
+            transaction_list.push(parser.next_by(length as usize)?.to_vec());
+        }
+
+        ProvideMissingTransactionsSuccess::new(
+            u32::from_le_bytes(request_id.try_into()?),
+            transaction_list,
+        )
+    }
+}
+
+impl_frameable_trait!(
+    ProvideMissingTransactionsSuccess,
+    MessageTypes::ProvideMissingTransactionsSuccess,
+    false
+);
 
 #[cfg(test)]
 mod tests {
@@ -18,7 +780,7 @@ mod tests {
         let message = SetupConnection::new(
             2,
             2,
-            Cow::Borrowed(&[SetupConnectionFlags::RequiresAsyncJobMining]),
+            FlagSet::from([SetupConnectionFlags::RequiresAsyncJobMining]),
             "0.0.0.0",
             8545,
             "Bitmain",
@@ -35,7 +797,7 @@ mod tests {
         let message = SetupConnection::new(
             2,
             2,
-            Cow::Borrowed(&[SetupConnectionFlags::RequiresAsyncJobMining]),
+            FlagSet::from([SetupConnectionFlags::RequiresAsyncJobMining]),
             "0.0.0.0",
             8545,
             "Bitmain",
@@ -58,7 +820,7 @@ mod tests {
         let message = SetupConnection::new(
             2,
             2,
-            Cow::Borrowed(&[]),
+            FlagSet::empty(),
             "0.0.0.0",
             8545,
             "Bitmain",
@@ -75,4 +837,231 @@ mod tests {
         assert_eq!(buffer[0], 0x01);
         assert_eq!(buffer[5], 0x00);
     }
+
+    #[test]
+    fn allocate_mining_job_token_round_trip() {
+        let message = AllocateMiningJobToken::new("miner1", 7).unwrap();
+
+        let mut buffer: Vec<u8> = Vec::new();
+        let size = message.serialize(&mut buffer).unwrap();
+        assert_eq!(size, 11);
+
+        let message = AllocateMiningJobToken::deserialize(&buffer).unwrap();
+        assert_eq!(message.user_identifier, "miner1".to_string());
+        assert_eq!(message.request_id, 7);
+
+        let success = AllocateMiningJobTokenSuccess::new(7, vec![0xaa, 0xbb], 100, true).unwrap();
+        let mut buffer: Vec<u8> = Vec::new();
+        assert_eq!(success.serialize(&mut buffer).unwrap(), 12);
+
+        let success = AllocateMiningJobTokenSuccess::deserialize(&buffer).unwrap();
+        assert_eq!(success.request_id, 7);
+        assert_eq!(success.mining_job_token, vec![0xaa, 0xbb]);
+        assert_eq!(success.coinbase_output_max_additional_size, 100);
+        assert!(success.async_mining_allowed);
+    }
+
+    #[test]
+    fn allocate_mining_job_token_success_invalid_bool() {
+        let mut buffer = vec![0x07, 0x00, 0x00, 0x00, 0x00, 0x64, 0x00, 0x00, 0x00];
+        buffer.push(0x02);
+
+        assert!(AllocateMiningJobTokenSuccess::deserialize(&buffer).is_err());
+    }
+
+    fn commit_mining_job() -> CommitMiningJob {
+        CommitMiningJob::new(
+            1,
+            vec![0x01, 0x02],
+            0x2000_0000,
+            2,
+            vec![0x03, 0x40, 0x0d, 0x03],
+            0xffff_ffff,
+            0,
+            vec![vec![0x00; 9], vec![0x01; 43]],
+            0,
+            8,
+            42,
+            vec![[0x0a; 6], [0x0b; 6]],
+            [0xcc; 32],
+            vec![0x00; 32],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn commit_mining_job_round_trip() {
+        let message = commit_mining_job();
+
+        let mut buffer: Vec<u8> = Vec::new();
+        let size = message.serialize(&mut buffer).unwrap();
+        assert_eq!(size, buffer.len());
+
+        let message = CommitMiningJob::deserialize(&buffer).unwrap();
+        assert_eq!(message.request_id, 1);
+        assert_eq!(message.mining_job_token, vec![0x01, 0x02]);
+        assert_eq!(message.coinbase_prefix, vec![0x03, 0x40, 0x0d, 0x03]);
+        assert_eq!(message.coinbase_tx_outputs.len(), 2);
+        assert_eq!(message.coinbase_tx_outputs[1], vec![0x01; 43]);
+        assert_eq!(message.min_extranonce_size, 8);
+        assert_eq!(message.tx_short_hash_nonce, 42);
+        assert_eq!(message.tx_short_hash_list, vec![[0x0a; 6], [0x0b; 6]]);
+        assert_eq!(message.tx_hash_list_hash, [0xcc; 32]);
+        assert_eq!(message.excess_data, vec![0x00; 32]);
+
+        let mut reserialized: Vec<u8> = Vec::new();
+        message.serialize(&mut reserialized).unwrap();
+        assert_eq!(buffer, reserialized);
+    }
+
+    #[test]
+    fn commit_mining_job_prefix_too_long() {
+        let message = CommitMiningJob::new(
+            1,
+            vec![0x01],
+            0,
+            2,
+            vec![0x00; 9],
+            0,
+            0,
+            vec![],
+            0,
+            0,
+            0,
+            vec![],
+            [0u8; 32],
+            vec![],
+        );
+
+        assert!(message.is_err());
+    }
+
+    #[test]
+    fn commit_mining_job_responses_round_trip() {
+        let success = CommitMiningJobSuccess::new(1, vec![0x05; 4]).unwrap();
+        let mut buffer: Vec<u8> = Vec::new();
+        success.serialize(&mut buffer).unwrap();
+
+        let success = CommitMiningJobSuccess::deserialize(&buffer).unwrap();
+        assert_eq!(success.request_id, 1);
+        assert_eq!(success.new_mining_job_token, vec![0x05; 4]);
+
+        let error = CommitMiningJobError::new(
+            2,
+            CommitMiningJobErrorCodes::InvalidMiningJobToken,
+            vec![0x01],
+        )
+        .unwrap();
+        let mut buffer: Vec<u8> = Vec::new();
+        error.serialize(&mut buffer).unwrap();
+        assert_eq!(buffer[4] as usize, "invalid-mining-job-token".len());
+
+        let error = CommitMiningJobError::deserialize(&buffer).unwrap();
+        assert_eq!(error.request_id, 2);
+        assert_eq!(
+            error.error_code,
+            CommitMiningJobErrorCodes::InvalidMiningJobToken
+        );
+        assert_eq!(error.error_details, vec![0x01]);
+    }
+
+    #[test]
+    fn transaction_requests_round_trip() {
+        let mut buffer: Vec<u8> = Vec::new();
+        IdentifyTransactions::new(3).serialize(&mut buffer).unwrap();
+        assert_eq!(
+            IdentifyTransactions::deserialize(&buffer)
+                .unwrap()
+                .request_id,
+            3
+        );
+
+        let success = IdentifyTransactionsSuccess::new(3, vec![[0x01; 32], [0x02; 32]]).unwrap();
+        let mut buffer: Vec<u8> = Vec::new();
+        assert_eq!(success.serialize(&mut buffer).unwrap(), 70);
+
+        let success = IdentifyTransactionsSuccess::deserialize(&buffer).unwrap();
+        assert_eq!(success.tx_data_hashes, vec![[0x01; 32], [0x02; 32]]);
+
+        let request = ProvideMissingTransactions::new(3, vec![0, 2]).unwrap();
+        let mut buffer: Vec<u8> = Vec::new();
+        assert_eq!(request.serialize(&mut buffer).unwrap(), 10);
+
+        let request = ProvideMissingTransactions::deserialize(&buffer).unwrap();
+        assert_eq!(request.unknown_tx_position_list, vec![0, 2]);
+
+        let success =
+            ProvideMissingTransactionsSuccess::new(3, vec![vec![0xaa; 3], vec![0xbb]]).unwrap();
+        let mut buffer: Vec<u8> = Vec::new();
+        assert_eq!(success.serialize(&mut buffer).unwrap(), 16);
+
+        let success = ProvideMissingTransactionsSuccess::deserialize(&buffer).unwrap();
+        assert_eq!(success.transaction_list[0], vec![0xaa; 3]);
+        assert_eq!(success.transaction_list[1], vec![0xbb]);
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod serde_tests {
+    use super::*;
+
+    #[test]
+    fn commit_mining_job_json() {
+        let message = CommitMiningJob::new(
+            1,
+            vec![0x0a],
+            0x2000_0000,
+            2,
+            vec![0x51],
+            0xffff_ffff,
+            0,
+            vec![vec![0x00; 2]],
+            0,
+            8,
+            42,
+            vec![[0x01, 0x02, 0x03, 0x04, 0x05, 0x06]],
+            [0xff; 32],
+            vec![],
+        )
+        .unwrap();
+
+        let json = serde_json::to_value(&message).unwrap();
+        assert_eq!(json["mining_job_token"], "0a");
+        assert_eq!(json["coinbase_tx_outputs"], serde_json::json!(["0000"]));
+        assert_eq!(
+            json["tx_short_hash_list"],
+            serde_json::json!(["010203040506"])
+        );
+        assert_eq!(json["tx_hash_list_hash"], "ff".repeat(32));
+        assert_eq!(json["excess_data"], "");
+
+        let message: CommitMiningJob = serde_json::from_value(json).unwrap();
+        assert_eq!(
+            message.tx_short_hash_list,
+            vec![[0x01, 0x02, 0x03, 0x04, 0x05, 0x06]]
+        );
+        assert_eq!(message.coinbase_tx_outputs[0], vec![0x00; 2]);
+    }
+
+    #[test]
+    fn commit_mining_job_error_json() {
+        let message = CommitMiningJobError::new(
+            1,
+            CommitMiningJobErrorCodes::MissingPayoutOutput,
+            vec![0x01],
+        )
+        .unwrap();
+
+        let json = serde_json::to_string(&message).unwrap();
+        assert_eq!(
+            json,
+            r#"{"request_id":1,"error_code":"missing-payout-output","error_details":"01"}"#
+        );
+
+        let message: CommitMiningJobError = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            message.error_code,
+            CommitMiningJobErrorCodes::MissingPayoutOutput
+        );
+    }
 }
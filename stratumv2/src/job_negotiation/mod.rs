@@ -9,7 +9,20 @@
 
 mod flags;
 mod messages;
+mod negotiator;
+mod policy;
+mod reconciliation;
+mod short_id;
 
 /// Job Negotiation subprotocol messages.
-pub use flags::SetupConnectionFlags;
-pub use messages::SetupConnection;
+pub use flags::{SetupConnectionFlags, SetupConnectionSuccessFlags};
+pub use messages::{
+    AllocateMiningJobToken, AllocateMiningJobTokenSuccess, CommitMiningJob, CommitMiningJobError,
+    CommitMiningJobErrorCodes, CommitMiningJobSuccess, IdentifyTransactions,
+    IdentifyTransactionsSuccess, ProvideMissingTransactions, ProvideMissingTransactionsSuccess,
+    SetupConnection, SetupConnectionError, SetupConnectionSuccess, ShortTxId,
+};
+pub use negotiator::{JobNegotiator, JobNegotiatorConfig, NegotiatedJob};
+pub use policy::{DefaultTemplatePolicy, TemplatePolicy, TemplateRejection};
+pub use reconciliation::{MempoolView, Reconciliation, TransactionReconciler};
+pub use short_id::{tx_hash_list_hash, wtxid, ShortTxIdHasher};
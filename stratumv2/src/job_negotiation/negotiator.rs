@@ -0,0 +1,1120 @@
+//! The Job Negotiator negotiates the templates of a Template Provider with a
+//! Mining Pool:
+//! - Tokens are allocated by the Mining Pool ahead of the templates with
+//!   `AllocateMiningJobToken`.
+//! - The transactions of every template received from the Template Provider
+//!   are requested with `RequestTransactionData`.
+//! - A template with its transactions is committed to the Mining Pool with
+//!   `CommitMiningJob`, consuming a token. The value of the coinbase is paid
+//!   to the configured payout script.
+//! - `IdentifyTransactions` and `ProvideMissingTransactions` from the Mining
+//!   Pool are answered from the transactions of the committed template.
+//! - Committed jobs are returned as a [NegotiatedJob](struct.NegotiatedJob.html),
+//!   which downstream proxies mine as an extended job.
+//!
+//! The [JobNegotiator](struct.JobNegotiator.html) is sans-IO, messages are
+//! passed in by the caller and the messages to send are returned.
+use crate::error::{Error, Result};
+use crate::job_negotiation::short_id::{tx_hash_list_hash, wtxid, ShortTxIdHasher};
+use crate::job_negotiation::{
+    AllocateMiningJobToken, AllocateMiningJobTokenSuccess, CommitMiningJob, CommitMiningJobError,
+    CommitMiningJobSuccess, IdentifyTransactions, IdentifyTransactionsSuccess,
+    ProvideMissingTransactions, ProvideMissingTransactionsSuccess,
+};
+use crate::mining::NewExtendedMiningJob;
+use crate::template_distribution::{
+    CoinbaseOutputDataSize, NewTemplate, RequestTransactionData, RequestTransactionDataError,
+    RequestTransactionDataSuccess, SetNewPrevHash,
+};
+use crate::types::U256;
+use crate::util::{compact_size, ByteParser};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::convert::TryInto;
+
+/// The maximum size of the script of a coinbase input, enforced by
+/// consensus.
+const MAX_COINBASE_SCRIPT_SIZE: usize = 100;
+
+/// JobNegotiatorConfig contains the parameters of a
+/// [JobNegotiator](struct.JobNegotiator.html).
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobNegotiatorConfig {
+    /// The user the mining jobs are negotiated for.
+    pub user_identifier: String,
+
+    /// The script the value of the coinbase is paid to, typically provided
+    /// by the Mining Pool.
+    pub payout_script: Vec<u8>,
+
+    /// The minimum extranonce size committed for the mining jobs.
+    pub min_extranonce_size: u16,
+
+    /// The number of tokens kept allocated ahead of the templates.
+    pub token_reserve: usize,
+
+    /// The maximum number of templates kept, the oldest templates are
+    /// dropped first.
+    pub max_templates: usize,
+}
+
+impl JobNegotiatorConfig {
+    /// Creates a JobNegotiatorConfig with a `min_extranonce_size` of 8, a
+    /// `token_reserve` of 2 and a `max_templates` of 16.
+    pub fn new<T: Into<String>>(user_identifier: T, payout_script: Vec<u8>) -> JobNegotiatorConfig {
+        JobNegotiatorConfig {
+            user_identifier: user_identifier.into(),
+            payout_script,
+            min_extranonce_size: 8,
+            token_reserve: 2,
+            max_templates: 16,
+        }
+    }
+}
+
+/// NegotiatedJob is a mining job committed to the Mining Pool, identified on
+/// mining connections by its `mining_job_token`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NegotiatedJob {
+    /// The template the job was built from.
+    pub template_id: u64,
+
+    /// The token returned by the Mining Pool for the committed job.
+    pub mining_job_token: Vec<u8>,
+
+    /// True if the job is for a future prev hash.
+    pub future_job: bool,
+
+    /// The version field of the block header.
+    pub version: u32,
+
+    /// The version field of the coinbase transaction.
+    pub coinbase_tx_version: u32,
+
+    /// The bytes at the beginning of the script of the coinbase input,
+    /// before the extranonce.
+    pub coinbase_prefix: Vec<u8>,
+
+    /// The sequence field of the coinbase input.
+    pub coinbase_tx_input_sequence: u32,
+
+    /// The serialized outputs of the coinbase transaction.
+    pub coinbase_tx_outputs: Vec<Vec<u8>>,
+
+    /// The lock time field of the coinbase transaction.
+    pub coinbase_tx_locktime: u32,
+
+    /// The minimum extranonce size committed for the job.
+    pub min_extranonce_size: u16,
+
+    /// The merkle path of the coinbase transaction.
+    pub merkle_path: Vec<U256>,
+}
+
+impl NegotiatedJob {
+    /// Returns the job as a NewExtendedMiningJob for an extended channel. The
+    /// coinbase is split around the extranonce, placed in the script of the
+    /// coinbase input after the `coinbase_prefix`.
+    ///
+    /// A RequirementError is returned if the `extranonce_size` is smaller
+    /// than the committed `min_extranonce_size` or the script of the coinbase
+    /// input would exceed 100 bytes.
+    pub fn new_extended_mining_job(
+        &self,
+        channel_id: u32,
+        job_id: u32,
+        extranonce_size: u16,
+    ) -> Result<NewExtendedMiningJob> {
+        if extranonce_size < self.min_extranonce_size {
+            return Err(Error::RequirementError(
+                "extranonce_size is smaller than the committed min_extranonce_size".into(),
+            ));
+        }
+
+        let script_size = self.coinbase_prefix.len() + extranonce_size as usize;
+        if script_size > MAX_COINBASE_SCRIPT_SIZE {
+            return Err(Error::RequirementError(
+                "the coinbase script cannot be greater than 100 bytes".into(),
+            ));
+        }
+
+        let mut coinbase_tx_prefix = self.coinbase_tx_version.to_le_bytes().to_vec();
+        coinbase_tx_prefix.push(0x01);
+        coinbase_tx_prefix.extend_from_slice(&[0x00; 32]);
+        coinbase_tx_prefix.extend_from_slice(&0xffff_ffffu32.to_le_bytes());
+        coinbase_tx_prefix.push(script_size as u8);
+        coinbase_tx_prefix.extend_from_slice(&self.coinbase_prefix);
+
+        let mut coinbase_tx_suffix = self.coinbase_tx_input_sequence.to_le_bytes().to_vec();
+        coinbase_tx_suffix.extend(compact_size(self.coinbase_tx_outputs.len() as u64));
+        for output in &self.coinbase_tx_outputs {
+            coinbase_tx_suffix.extend_from_slice(output);
+        }
+        coinbase_tx_suffix.extend_from_slice(&self.coinbase_tx_locktime.to_le_bytes());
+
+        NewExtendedMiningJob::new(
+            channel_id,
+            job_id,
+            self.future_job,
+            self.version,
+            true,
+            self.merkle_path.clone(),
+            coinbase_tx_prefix,
+            coinbase_tx_suffix,
+        )
+    }
+}
+
+/// A template received from the Template Provider.
+#[derive(Debug)]
+struct TemplateState {
+    template: NewTemplate,
+    transactions: Option<RequestTransactionDataSuccess>,
+    committed: bool,
+}
+
+/// A mining job committed to the Mining Pool, waiting for the response.
+#[derive(Debug)]
+struct PendingCommit {
+    job: NegotiatedJob,
+    transactions: Vec<Vec<u8>>,
+}
+
+/// JobNegotiator commits the templates of a Template Provider to a Mining
+/// Pool and hands the negotiated jobs to downstream proxies.
+///
+/// Negotiated jobs are only returned once the Mining Pool accepts the
+/// commit, asynchronous mining allowed by the token is not used.
+///
+/// # Examples
+///
+/// ```rust
+/// use stratumv2::job_negotiation::{
+///     AllocateMiningJobTokenSuccess, CommitMiningJobSuccess, JobNegotiator, JobNegotiatorConfig,
+/// };
+/// use stratumv2::template_distribution::{
+///     NewTemplate, RequestTransactionDataSuccess, SetNewPrevHash,
+/// };
+///
+/// let config = JobNegotiatorConfig::new("miner", vec![0x51]);
+/// let mut negotiator = JobNegotiator::new(config).unwrap();
+///
+/// // Tokens are allocated by the Mining Pool ahead of the templates.
+/// let requests = negotiator.allocate_mining_job_tokens().unwrap();
+/// assert_eq!(requests.len(), 2);
+///
+/// let token = AllocateMiningJobTokenSuccess::new(requests[0].request_id, vec![0x01], 100, false).unwrap();
+/// let output_size = negotiator.handle_allocate_mining_job_token_success(&token).unwrap();
+/// assert_eq!(output_size.unwrap().coinbase_output_max_additional_size, 100);
+///
+/// // The transactions of every template are requested from the Template Provider.
+/// let template = NewTemplate::new(
+///     1, true, 0x2000_0000, 2, vec![0x51], 0xffff_ffff, 5_000_000_000, 0, vec![], 0, vec![],
+/// ).unwrap();
+/// let request = negotiator.handle_new_template(template).unwrap();
+/// assert_eq!(request.template_id, 1);
+///
+/// let transactions = RequestTransactionDataSuccess::new(1, vec![], vec![]).unwrap();
+/// negotiator.handle_request_transaction_data_success(transactions).unwrap();
+///
+/// // The template is committed to the Mining Pool with the token.
+/// let commits = negotiator.poll_commits().unwrap();
+/// assert_eq!(commits[0].mining_job_token, vec![0x01]);
+///
+/// let success = CommitMiningJobSuccess::new(commits[0].request_id, vec![0x02]).unwrap();
+/// let job = negotiator.handle_commit_mining_job_success(&success).unwrap();
+/// assert!(job.future_job);
+///
+/// // The job is activated by the new prev hash and mined downstream.
+/// let prev_hash = SetNewPrevHash::new(1, [0u8; 32], 1_600_000_000, 0x1d00_ffff, [0xff; 32]);
+/// let job = negotiator.handle_set_new_prev_hash(prev_hash).unwrap().unwrap();
+/// assert!(!job.future_job);
+///
+/// let extended_job = job.new_extended_mining_job(1, 1, 8).unwrap();
+/// assert_eq!(extended_job.merkle_path.len(), 0);
+/// ```
+#[derive(Debug)]
+pub struct JobNegotiator {
+    config: JobNegotiatorConfig,
+    payout_output_size: usize,
+    next_request_id: u32,
+    token_requests: HashSet<u32>,
+    tokens: VecDeque<Vec<u8>>,
+    announced_output_size: Option<u32>,
+    templates: BTreeMap<u64, TemplateState>,
+    prev_hash: Option<SetNewPrevHash>,
+    commits: HashMap<u32, PendingCommit>,
+    jobs: HashMap<u64, NegotiatedJob>,
+}
+
+impl JobNegotiator {
+    /// Creates a JobNegotiator. A RequirementError is returned if the
+    /// `user_identifier` is greater than 255 bytes or the `payout_script` is
+    /// empty.
+    pub fn new(config: JobNegotiatorConfig) -> Result<JobNegotiator> {
+        if config.user_identifier.len() > 255 {
+            return Err(Error::RequirementError(
+                "user_identifier cannot be greater than 255 bytes".into(),
+            ));
+        }
+
+        if config.payout_script.is_empty() {
+            return Err(Error::RequirementError(
+                "payout_script cannot be empty".into(),
+            ));
+        }
+
+        Ok(JobNegotiator {
+            payout_output_size: output(0, &config.payout_script).len(),
+            config,
+            next_request_id: 0,
+            token_requests: HashSet::new(),
+            tokens: VecDeque::new(),
+            announced_output_size: None,
+            templates: BTreeMap::new(),
+            prev_hash: None,
+            commits: HashMap::new(),
+            jobs: HashMap::new(),
+        })
+    }
+
+    /// Returns the configuration of the JobNegotiator.
+    pub fn config(&self) -> &JobNegotiatorConfig {
+        &self.config
+    }
+
+    /// Returns the number of allocated tokens not used by a commit.
+    pub fn available_tokens(&self) -> usize {
+        self.tokens.len()
+    }
+
+    /// Returns the negotiated job of a template.
+    pub fn job(&self, template_id: u64) -> Option<&NegotiatedJob> {
+        self.jobs.get(&template_id)
+    }
+
+    /// Returns the negotiated job of the template activated by the last
+    /// prev hash.
+    pub fn current_job(&self) -> Option<&NegotiatedJob> {
+        self.prev_hash
+            .as_ref()
+            .and_then(|prev_hash| self.job(prev_hash.template_id))
+    }
+
+    /// Returns the AllocateMiningJobToken requests to send to the Mining Pool
+    /// to keep `token_reserve` tokens allocated or requested.
+    pub fn allocate_mining_job_tokens(&mut self) -> Result<Vec<AllocateMiningJobToken>> {
+        let allocated = self.tokens.len() + self.token_requests.len();
+        let mut requests = vec![];
+
+        for _ in allocated..self.config.token_reserve {
+            let request_id = self.next_request_id();
+            requests.push(AllocateMiningJobToken::new(
+                self.config.user_identifier.as_str(),
+                request_id,
+            )?);
+            self.token_requests.insert(request_id);
+        }
+
+        Ok(requests)
+    }
+
+    /// Stores a token allocated by the Mining Pool. The CoinbaseOutputDataSize
+    /// to send to the Template Provider is returned when the space required
+    /// by the Mining Pool changes.
+    ///
+    /// A RequirementError is returned if the token was not requested or the
+    /// payout output does not fit in the space allowed by the Mining Pool.
+    pub fn handle_allocate_mining_job_token_success(
+        &mut self,
+        message: &AllocateMiningJobTokenSuccess,
+    ) -> Result<Option<CoinbaseOutputDataSize>> {
+        if !self.token_requests.remove(&message.request_id) {
+            return Err(Error::RequirementError(
+                "the token was not requested".into(),
+            ));
+        }
+
+        if self.payout_output_size > message.coinbase_output_max_additional_size as usize {
+            return Err(Error::RequirementError(
+                "the payout output is greater than the coinbase_output_max_additional_size".into(),
+            ));
+        }
+
+        self.tokens
+            .push_back(message.mining_job_token.as_ref().to_vec());
+
+        let size = message.coinbase_output_max_additional_size;
+        if self.announced_output_size == Some(size) {
+            return Ok(None);
+        }
+
+        self.announced_output_size = Some(size);
+        Ok(Some(CoinbaseOutputDataSize::new(size)))
+    }
+
+    /// Stores a template received from the Template Provider, returning the
+    /// request for its transactions.
+    ///
+    /// A DeserializationError is returned if the `coinbase_tx_outputs` do not
+    /// match the `coinbase_tx_outputs_count`.
+    pub fn handle_new_template(&mut self, template: NewTemplate) -> Result<RequestTransactionData> {
+        let template_id = template.template_id;
+        if self.templates.contains_key(&template_id) {
+            return Err(Error::RequirementError(
+                "the template id is already known".into(),
+            ));
+        }
+
+        split_outputs(
+            template.coinbase_tx_outputs.as_ref(),
+            template.coinbase_tx_outputs_count,
+        )?;
+
+        self.templates.insert(
+            template_id,
+            TemplateState {
+                template,
+                transactions: None,
+                committed: false,
+            },
+        );
+        self.prune_templates();
+
+        Ok(RequestTransactionData::new(template_id))
+    }
+
+    /// Stores the transactions of a template, which can then be committed
+    /// with [poll_commits](struct.JobNegotiator.html#method.poll_commits).
+    pub fn handle_request_transaction_data_success(
+        &mut self,
+        message: RequestTransactionDataSuccess,
+    ) -> Result<()> {
+        let state = self
+            .templates
+            .get_mut(&message.template_id)
+            .ok_or_else(|| Error::RequirementError("the template is unknown".into()))?;
+
+        if state.committed {
+            return Err(Error::RequirementError(
+                "the template is already committed".into(),
+            ));
+        }
+
+        state.transactions = Some(message);
+        Ok(())
+    }
+
+    /// Drops a template whose transactions cannot be provided by the
+    /// Template Provider.
+    pub fn handle_request_transaction_data_error(&mut self, message: &RequestTransactionDataError) {
+        self.templates.remove(&message.template_id);
+    }
+
+    /// Applies a new prev hash from the Template Provider. Templates, commits
+    /// and jobs other than the activated template are stale and dropped. The
+    /// job of the activated template is returned if it was already
+    /// negotiated.
+    pub fn handle_set_new_prev_hash(
+        &mut self,
+        message: SetNewPrevHash,
+    ) -> Result<Option<NegotiatedJob>> {
+        let template_id = message.template_id;
+        if !self.templates.contains_key(&template_id) {
+            return Err(Error::RequirementError(
+                "the prev hash activates an unknown template".into(),
+            ));
+        }
+
+        self.templates.retain(|id, _| *id == template_id);
+        self.commits
+            .retain(|_, commit| commit.job.template_id == template_id);
+        self.jobs.retain(|id, _| *id == template_id);
+        self.prev_hash = Some(message);
+
+        Ok(self.jobs.get_mut(&template_id).map(|job| {
+            job.future_job = false;
+            job.clone()
+        }))
+    }
+
+    /// Returns the CommitMiningJob of every template whose transactions are
+    /// known, in template order, while tokens are available. A template
+    /// that cannot be committed is dropped without consuming a token.
+    pub fn poll_commits(&mut self) -> Result<Vec<CommitMiningJob>> {
+        let template_ids: Vec<u64> = self
+            .templates
+            .iter()
+            .filter(|(_, state)| !state.committed && state.transactions.is_some())
+            .map(|(template_id, _)| *template_id)
+            .collect();
+
+        let mut commits = vec![];
+        for template_id in template_ids {
+            let token = match self.tokens.pop_front() {
+                Some(token) => token,
+                None => break,
+            };
+
+            // A template that cannot be committed is dropped and its token
+            // is kept for the next template.
+            match self.commit(template_id, token.clone()) {
+                Ok(commit) => commits.push(commit),
+                Err(_) => {
+                    self.templates.remove(&template_id);
+                    self.tokens.push_front(token);
+                }
+            }
+        }
+
+        Ok(commits)
+    }
+
+    /// Returns the job negotiated by a commit accepted by the Mining Pool.
+    pub fn handle_commit_mining_job_success(
+        &mut self,
+        message: &CommitMiningJobSuccess,
+    ) -> Result<NegotiatedJob> {
+        let mut commit = self.take_commit(message.request_id)?;
+        commit.job.mining_job_token = message.new_mining_job_token.as_ref().to_vec();
+        commit.job.future_job = self
+            .prev_hash
+            .as_ref()
+            .is_none_or(|prev_hash| prev_hash.template_id != commit.job.template_id);
+
+        self.jobs.insert(commit.job.template_id, commit.job.clone());
+        Ok(commit.job)
+    }
+
+    /// Drops a commit rejected by the Mining Pool. The template is not
+    /// committed again.
+    pub fn handle_commit_mining_job_error(&mut self, message: &CommitMiningJobError) -> Result<()> {
+        self.take_commit(message.request_id).map(|_| ())
+    }
+
+    /// Returns the wtxid of every transaction of a pending commit.
+    pub fn handle_identify_transactions(
+        &self,
+        message: &IdentifyTransactions,
+    ) -> Result<IdentifyTransactionsSuccess> {
+        let commit = self.pending_commit(message.request_id)?;
+
+        IdentifyTransactionsSuccess::new(
+            message.request_id,
+            commit
+                .transactions
+                .iter()
+                .map(|transaction| wtxid(transaction))
+                .collect(),
+        )
+    }
+
+    /// Returns the transactions of a pending commit missing from the mempool
+    /// of the Mining Pool.
+    pub fn handle_provide_missing_transactions(
+        &self,
+        message: &ProvideMissingTransactions,
+    ) -> Result<ProvideMissingTransactionsSuccess> {
+        let commit = self.pending_commit(message.request_id)?;
+
+        let transactions = message
+            .unknown_tx_position_list
+            .iter()
+            .map(|position| {
+                commit
+                    .transactions
+                    .get(*position as usize)
+                    .cloned()
+                    .ok_or_else(|| {
+                        Error::RequirementError("the transaction position is unknown".into())
+                    })
+            })
+            .collect::<Result<Vec<Vec<u8>>>>()?;
+
+        ProvideMissingTransactionsSuccess::new(message.request_id, transactions)
+    }
+
+    /// Drops the oldest templates, with their commits and jobs, beyond
+    /// `max_templates`. The template activated by the last prev hash is
+    /// kept.
+    fn prune_templates(&mut self) {
+        let active = self
+            .prev_hash
+            .as_ref()
+            .map(|prev_hash| prev_hash.template_id);
+        let excess = self
+            .templates
+            .len()
+            .saturating_sub(self.config.max_templates);
+        let pruned: Vec<u64> = self
+            .templates
+            .keys()
+            .filter(|template_id| Some(**template_id) != active)
+            .take(excess)
+            .copied()
+            .collect();
+
+        for template_id in pruned {
+            self.templates.remove(&template_id);
+            self.commits
+                .retain(|_, commit| commit.job.template_id != template_id);
+            self.jobs.remove(&template_id);
+        }
+    }
+
+    fn commit(&mut self, template_id: u64, mining_job_token: Vec<u8>) -> Result<CommitMiningJob> {
+        let request_id = self.next_request_id();
+        let state = self
+            .templates
+            .get_mut(&template_id)
+            .ok_or_else(|| Error::RequirementError("the template is unknown".into()))?;
+        let data = state
+            .transactions
+            .as_ref()
+            .ok_or_else(|| Error::RequirementError("the transactions are unknown".into()))?;
+        let template = &state.template;
+
+        // The payout output spends the value remaining and is placed first,
+        // in the space reserved by the Template Provider.
+        let mut coinbase_tx_outputs = vec![output(
+            template.coinbase_tx_value_remaining,
+            &self.config.payout_script,
+        )];
+        coinbase_tx_outputs.extend(split_outputs(
+            template.coinbase_tx_outputs.as_ref(),
+            template.coinbase_tx_outputs_count,
+        )?);
+
+        let transactions: Vec<Vec<u8>> = data
+            .transaction_list
+            .iter()
+            .map(|transaction| transaction.as_ref().to_vec())
+            .collect();
+        let tx_short_hash_nonce = rand::random::<u64>();
+        let hasher = ShortTxIdHasher::new(tx_short_hash_nonce);
+
+        let commit = CommitMiningJob::new(
+            request_id,
+            mining_job_token,
+            template.version,
+            template.coinbase_tx_version,
+            template.coinbase_prefix.as_ref().to_vec(),
+            template.coinbase_tx_input_sequence,
+            0,
+            coinbase_tx_outputs.clone(),
+            template.coinbase_tx_locktime,
+            self.config.min_extranonce_size,
+            tx_short_hash_nonce,
+            transactions
+                .iter()
+                .map(|transaction| hasher.transaction_short_id(transaction))
+                .collect(),
+            tx_hash_list_hash(&transactions),
+            data.excess_data.as_ref().to_vec(),
+        )?;
+
+        let job = NegotiatedJob {
+            template_id,
+            mining_job_token: vec![],
+            future_job: true,
+            version: template.version,
+            coinbase_tx_version: template.coinbase_tx_version,
+            coinbase_prefix: template.coinbase_prefix.as_ref().to_vec(),
+            coinbase_tx_input_sequence: template.coinbase_tx_input_sequence,
+            coinbase_tx_outputs,
+            coinbase_tx_locktime: template.coinbase_tx_locktime,
+            min_extranonce_size: self.config.min_extranonce_size,
+            merkle_path: template.merkle_path.clone(),
+        };
+
+        state.committed = true;
+        self.commits
+            .insert(request_id, PendingCommit { job, transactions });
+
+        Ok(commit)
+    }
+
+    fn pending_commit(&self, request_id: u32) -> Result<&PendingCommit> {
+        self.commits
+            .get(&request_id)
+            .ok_or_else(|| Error::RequirementError("the commit is unknown".into()))
+    }
+
+    fn take_commit(&mut self, request_id: u32) -> Result<PendingCommit> {
+        self.commits
+            .remove(&request_id)
+            .ok_or_else(|| Error::RequirementError("the commit is unknown".into()))
+    }
+
+    fn next_request_id(&mut self) -> u32 {
+        let request_id = self.next_request_id;
+        self.next_request_id = self.next_request_id.wrapping_add(1);
+
+        request_id
+    }
+}
+
+/// Serializes a transaction output.
+fn output(value: u64, script_pubkey: &[u8]) -> Vec<u8> {
+    let mut output = value.to_le_bytes().to_vec();
+    output.extend(compact_size(script_pubkey.len() as u64));
+    output.extend_from_slice(script_pubkey);
+
+    output
+}
+
+/// Splits the serialized outputs of a template into separate outputs.
+fn split_outputs(bytes: &[u8], count: u32) -> Result<Vec<Vec<u8>>> {
+    let mut parser = ByteParser::new(bytes, 0);
+    let mut outputs = vec![];
+
+    for _ in 0..count {
+        let mut output = parser.next_by(8)?.to_vec();
+        let prefix = parser.next_by(1)?[0];
+        output.push(prefix);
+
+        let script_length = match prefix {
+            0xfd => {
+                let length = parser.next_by(2)?;
+                output.extend_from_slice(length);
+                u16::from_le_bytes(length.try_into()?) as usize
+            }
+            0xfe | 0xff => {
+                return Err(Error::DeserializationError(
+                    "the script of a coinbase output is too large".into(),
+                ))
+            }
+            length => length as usize,
+        };
+
+        output.extend_from_slice(parser.next_by(script_length)?);
+        outputs.push(output);
+    }
+
+    if outputs.iter().map(|output| output.len()).sum::<usize>() != bytes.len() {
+        return Err(Error::DeserializationError(
+            "coinbase_tx_outputs contains more than coinbase_tx_outputs_count outputs".into(),
+        ));
+    }
+
+    Ok(outputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job_negotiation::{CommitMiningJobErrorCodes, ShortTxId};
+    use crate::mining::{build_coinbase, coinbase_txid, merkle_root};
+    use crate::template_distribution::RequestTransactionDataErrorCodes;
+    use crate::util::double_sha256;
+
+    const PAYOUT_SCRIPT: [u8; 3] = [0x51, 0x52, 0x53];
+
+    fn witness_commitment() -> Vec<u8> {
+        let mut script = vec![0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
+        script.extend_from_slice(&[0x11; 32]);
+        output(0, &script)
+    }
+
+    fn new_template(template_id: u64, merkle_path: Vec<U256>) -> NewTemplate {
+        NewTemplate::new(
+            template_id,
+            true,
+            0x2000_0000,
+            2,
+            vec![0x03, 0x40, 0x0d, 0x03],
+            0xffff_fffe,
+            625_000_000,
+            1,
+            witness_commitment(),
+            0,
+            merkle_path,
+        )
+        .unwrap()
+    }
+
+    /// Returns a JobNegotiator with two tokens allocated.
+    fn negotiator() -> JobNegotiator {
+        let config = JobNegotiatorConfig::new("miner", PAYOUT_SCRIPT.to_vec());
+        let mut negotiator = JobNegotiator::new(config).unwrap();
+
+        for (i, request) in negotiator
+            .allocate_mining_job_tokens()
+            .unwrap()
+            .iter()
+            .enumerate()
+        {
+            let token =
+                AllocateMiningJobTokenSuccess::new(request.request_id, vec![i as u8], 64, false)
+                    .unwrap();
+            negotiator
+                .handle_allocate_mining_job_token_success(&token)
+                .unwrap();
+        }
+
+        negotiator
+    }
+
+    fn transactions() -> Vec<Vec<u8>> {
+        vec![vec![0x01; 60], vec![0x02; 80], vec![0x03; 100]]
+    }
+
+    fn short_ids(nonce: u64) -> Vec<ShortTxId> {
+        let hasher = ShortTxIdHasher::new(nonce);
+        transactions()
+            .iter()
+            .map(|transaction| hasher.transaction_short_id(transaction))
+            .collect()
+    }
+
+    fn txids() -> Vec<U256> {
+        transactions().iter().map(|tx| double_sha256(tx)).collect()
+    }
+
+    /// Stores a template with three transactions and commits it.
+    fn commit_template(negotiator: &mut JobNegotiator, template_id: u64) -> CommitMiningJob {
+        let merkle_path = crate::mining::merkle_path(&txids());
+
+        negotiator
+            .handle_new_template(new_template(template_id, merkle_path))
+            .unwrap();
+        let data = RequestTransactionDataSuccess::new(template_id, vec![0x00; 32], transactions())
+            .unwrap();
+        negotiator
+            .handle_request_transaction_data_success(data)
+            .unwrap();
+
+        negotiator.poll_commits().unwrap().remove(0)
+    }
+
+    #[test]
+    fn allocates_token_reserve() {
+        let config = JobNegotiatorConfig::new("miner", PAYOUT_SCRIPT.to_vec());
+        let mut negotiator = JobNegotiator::new(config).unwrap();
+
+        let requests = negotiator.allocate_mining_job_tokens().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].user_identifier, "miner".to_string());
+
+        // Requested tokens count towards the reserve.
+        assert!(negotiator.allocate_mining_job_tokens().unwrap().is_empty());
+
+        let token =
+            AllocateMiningJobTokenSuccess::new(requests[0].request_id, vec![0x01], 64, false)
+                .unwrap();
+        let output_size = negotiator
+            .handle_allocate_mining_job_token_success(&token)
+            .unwrap();
+        assert_eq!(output_size.unwrap().coinbase_output_max_additional_size, 64);
+
+        // The output size is only announced when it changes.
+        let token =
+            AllocateMiningJobTokenSuccess::new(requests[1].request_id, vec![0x02], 64, false)
+                .unwrap();
+        assert!(negotiator
+            .handle_allocate_mining_job_token_success(&token)
+            .unwrap()
+            .is_none());
+        assert_eq!(negotiator.available_tokens(), 2);
+
+        // Unrequested tokens are rejected.
+        assert!(negotiator
+            .handle_allocate_mining_job_token_success(&token)
+            .is_err());
+    }
+
+    #[test]
+    fn rejects_payout_output_too_large() {
+        let config = JobNegotiatorConfig::new("miner", vec![0x51; 40]);
+        let mut negotiator = JobNegotiator::new(config).unwrap();
+
+        let request = negotiator.allocate_mining_job_tokens().unwrap().remove(0);
+        let token =
+            AllocateMiningJobTokenSuccess::new(request.request_id, vec![0x01], 40, false).unwrap();
+        assert!(negotiator
+            .handle_allocate_mining_job_token_success(&token)
+            .is_err());
+        assert_eq!(negotiator.available_tokens(), 0);
+    }
+
+    #[test]
+    fn commits_template_with_payout_output() {
+        let mut negotiator = negotiator();
+        let commit = commit_template(&mut negotiator, 1);
+
+        assert_eq!(commit.mining_job_token, vec![0x00]);
+        assert_eq!(commit.coinbase_prefix, vec![0x03, 0x40, 0x0d, 0x03]);
+        assert_eq!(commit.coinbase_tx_input_sequence, 0xffff_fffe);
+        assert_eq!(commit.coinbase_tx_value_remaining, 0);
+        assert_eq!(commit.coinbase_tx_outputs.len(), 2);
+        assert_eq!(
+            commit.coinbase_tx_outputs[0],
+            output(625_000_000, &PAYOUT_SCRIPT)
+        );
+        assert_eq!(commit.coinbase_tx_outputs[1], witness_commitment());
+        assert_eq!(commit.min_extranonce_size, 8);
+        assert_eq!(commit.excess_data, vec![0x00; 32]);
+
+        assert_eq!(
+            commit.tx_short_hash_list,
+            short_ids(commit.tx_short_hash_nonce)
+        );
+        assert_eq!(commit.tx_hash_list_hash, tx_hash_list_hash(&transactions()));
+        assert_eq!(negotiator.available_tokens(), 1);
+
+        // A template is only committed once.
+        assert!(negotiator.poll_commits().unwrap().is_empty());
+    }
+
+    #[test]
+    fn waits_for_tokens_and_transactions() {
+        let config = JobNegotiatorConfig::new("miner", PAYOUT_SCRIPT.to_vec());
+        let mut negotiator = JobNegotiator::new(config).unwrap();
+        let requests = negotiator.allocate_mining_job_tokens().unwrap();
+
+        let request = negotiator
+            .handle_new_template(new_template(1, vec![]))
+            .unwrap();
+        assert_eq!(request.template_id, 1);
+        assert!(negotiator.poll_commits().unwrap().is_empty());
+
+        let data = RequestTransactionDataSuccess::new(1, vec![], vec![]).unwrap();
+        negotiator
+            .handle_request_transaction_data_success(data)
+            .unwrap();
+        assert!(negotiator.poll_commits().unwrap().is_empty());
+
+        let token =
+            AllocateMiningJobTokenSuccess::new(requests[0].request_id, vec![0x01], 64, false)
+                .unwrap();
+        negotiator
+            .handle_allocate_mining_job_token_success(&token)
+            .unwrap();
+        assert_eq!(negotiator.poll_commits().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn drops_templates_without_transactions() {
+        let mut negotiator = negotiator();
+        negotiator
+            .handle_new_template(new_template(1, vec![]))
+            .unwrap();
+
+        let error =
+            RequestTransactionDataError::new(1, RequestTransactionDataErrorCodes::StaleTemplateId);
+        negotiator.handle_request_transaction_data_error(&error);
+
+        let data = RequestTransactionDataSuccess::new(1, vec![], vec![]).unwrap();
+        assert!(negotiator
+            .handle_request_transaction_data_success(data)
+            .is_err());
+    }
+
+    #[test]
+    fn rejects_template_with_invalid_outputs() {
+        let mut negotiator = negotiator();
+        let template = NewTemplate::new(
+            1,
+            true,
+            0x2000_0000,
+            2,
+            vec![0x03, 0x40, 0x0d, 0x03],
+            0xffff_fffe,
+            625_000_000,
+            2,
+            witness_commitment(),
+            0,
+            vec![],
+        )
+        .unwrap();
+        assert!(negotiator.handle_new_template(template).is_err());
+
+        // The tokens are kept for the next template.
+        let commit = commit_template(&mut negotiator, 2);
+        assert_eq!(commit.mining_job_token, vec![0x00]);
+        assert_eq!(negotiator.available_tokens(), 1);
+    }
+
+    #[test]
+    fn rejects_transactions_of_committed_template() {
+        let mut negotiator = negotiator();
+        commit_template(&mut negotiator, 1);
+
+        let data = RequestTransactionDataSuccess::new(1, vec![], vec![]).unwrap();
+        assert!(negotiator
+            .handle_request_transaction_data_success(data)
+            .is_err());
+    }
+
+    #[test]
+    fn prunes_oldest_templates() {
+        let mut negotiator = negotiator();
+        negotiator.config.max_templates = 2;
+
+        for template_id in 1..=3 {
+            negotiator
+                .handle_new_template(new_template(template_id, vec![]))
+                .unwrap();
+        }
+
+        let data = RequestTransactionDataSuccess::new(1, vec![], vec![]).unwrap();
+        assert!(negotiator
+            .handle_request_transaction_data_success(data)
+            .is_err());
+        for template_id in 2..=3 {
+            let data = RequestTransactionDataSuccess::new(template_id, vec![], vec![]).unwrap();
+            assert!(negotiator
+                .handle_request_transaction_data_success(data)
+                .is_ok());
+        }
+    }
+
+    #[test]
+    fn answers_transaction_requests() {
+        let mut negotiator = negotiator();
+        let commit = commit_template(&mut negotiator, 1);
+
+        let identified = negotiator
+            .handle_identify_transactions(&IdentifyTransactions::new(commit.request_id))
+            .unwrap();
+        assert_eq!(identified.tx_data_hashes, txids());
+
+        let request = ProvideMissingTransactions::new(commit.request_id, vec![1]).unwrap();
+        let provided = negotiator
+            .handle_provide_missing_transactions(&request)
+            .unwrap();
+        assert_eq!(provided.transaction_list.len(), 1);
+        assert_eq!(provided.transaction_list[0], transactions()[1]);
+
+        let request = ProvideMissingTransactions::new(commit.request_id, vec![3]).unwrap();
+        assert!(negotiator
+            .handle_provide_missing_transactions(&request)
+            .is_err());
+
+        assert!(negotiator
+            .handle_identify_transactions(&IdentifyTransactions::new(commit.request_id + 1))
+            .is_err());
+    }
+
+    #[test]
+    fn hands_negotiated_job_downstream() {
+        let mut negotiator = negotiator();
+        let commit = commit_template(&mut negotiator, 1);
+
+        let success = CommitMiningJobSuccess::new(commit.request_id, vec![0x0f; 4]).unwrap();
+        let job = negotiator
+            .handle_commit_mining_job_success(&success)
+            .unwrap();
+        assert_eq!(job.mining_job_token, vec![0x0f; 4]);
+        assert!(job.future_job);
+        assert!(negotiator.current_job().is_none());
+
+        // The commit is no longer pending.
+        assert!(negotiator
+            .handle_commit_mining_job_success(&success)
+            .is_err());
+
+        let prev_hash = SetNewPrevHash::new(1, [0x22; 32], 1_600_000_000, 0x1d00_ffff, [0xff; 32]);
+        let job = negotiator
+            .handle_set_new_prev_hash(prev_hash)
+            .unwrap()
+            .unwrap();
+        assert!(!job.future_job);
+        assert_eq!(negotiator.current_job(), Some(&job));
+
+        // The coinbase of the extended job contains the committed fields
+        // around the extranonce and the merkle root commits to the block
+        // transactions.
+        assert!(job.new_extended_mining_job(1, 1, 7).is_err());
+        let extended_job = job.new_extended_mining_job(1, 1, 8).unwrap();
+        let coinbase = build_coinbase(
+            extended_job.coinbase_tx_prefix.as_ref(),
+            &[],
+            &[0x01; 8],
+            8,
+            extended_job.coinbase_tx_suffix.as_ref(),
+        )
+        .unwrap();
+
+        let mut expected = 2u32.to_le_bytes().to_vec();
+        expected.push(0x01);
+        expected.extend_from_slice(&[0x00; 32]);
+        expected.extend_from_slice(&[0xff; 4]);
+        expected.push(12);
+        expected.extend_from_slice(&[0x03, 0x40, 0x0d, 0x03]);
+        expected.extend_from_slice(&[0x01; 8]);
+        expected.extend_from_slice(&0xffff_fffeu32.to_le_bytes());
+        expected.push(0x02);
+        expected.extend(output(625_000_000, &PAYOUT_SCRIPT));
+        expected.extend(witness_commitment());
+        expected.extend_from_slice(&[0x00; 4]);
+        assert_eq!(coinbase, expected);
+
+        let txids = [&[coinbase_txid(&coinbase)][..], &txids()].concat();
+        let level = [
+            double_sha256(&[txids[0], txids[1]].concat()),
+            double_sha256(&[txids[2], txids[3]].concat()),
+        ];
+        assert_eq!(
+            merkle_root(&txids[0], &extended_job.merkle_path),
+            double_sha256(&level.concat())
+        );
+    }
+
+    #[test]
+    fn new_prev_hash_drops_stale_templates() {
+        let mut negotiator = negotiator();
+        let stale = commit_template(&mut negotiator, 1);
+        let current = commit_template(&mut negotiator, 2);
+
+        let prev_hash = SetNewPrevHash::new(2, [0x22; 32], 1_600_000_000, 0x1d00_ffff, [0xff; 32]);
+        assert!(negotiator
+            .handle_set_new_prev_hash(prev_hash)
+            .unwrap()
+            .is_none());
+
+        let success = CommitMiningJobSuccess::new(stale.request_id, vec![0x01]).unwrap();
+        assert!(negotiator
+            .handle_commit_mining_job_success(&success)
+            .is_err());
+
+        // The job of the current template is active once committed.
+        let success = CommitMiningJobSuccess::new(current.request_id, vec![0x02]).unwrap();
+        let job = negotiator
+            .handle_commit_mining_job_success(&success)
+            .unwrap();
+        assert!(!job.future_job);
+
+        let prev_hash = SetNewPrevHash::new(1, [0x33; 32], 1_600_000_000, 0x1d00_ffff, [0xff; 32]);
+        assert!(negotiator.handle_set_new_prev_hash(prev_hash).is_err());
+    }
+
+    #[test]
+    fn rejected_commit_is_dropped() {
+        let mut negotiator = negotiator();
+        let commit = commit_template(&mut negotiator, 1);
+
+        let error = CommitMiningJobError::new(
+            commit.request_id,
+            CommitMiningJobErrorCodes::InvalidMiningJobToken,
+            vec![],
+        )
+        .unwrap();
+        negotiator.handle_commit_mining_job_error(&error).unwrap();
+
+        assert!(negotiator
+            .handle_identify_transactions(&IdentifyTransactions::new(commit.request_id))
+            .is_err());
+        assert!(negotiator.poll_commits().unwrap().is_empty());
+    }
+
+    #[test]
+    fn splits_outputs() {
+        let outputs = [witness_commitment(), output(1, &[0x51; 300])].concat();
+        let split = split_outputs(&outputs, 2).unwrap();
+
+        assert_eq!(split[0], witness_commitment());
+        assert_eq!(split[1].len(), 8 + 3 + 300);
+        assert!(split_outputs(&outputs, 1).is_err());
+        assert!(split_outputs(&outputs, 3).is_err());
+    }
+}
@@ -0,0 +1,487 @@
+use crate::error::{Error, Result};
+use crate::job_negotiation::{CommitMiningJob, CommitMiningJobError, CommitMiningJobErrorCodes};
+use crate::types::U256;
+use crate::util::{compact_size, double_sha256, ByteParser};
+use std::collections::HashSet;
+use std::convert::TryFrom;
+
+/// The maximum weight of a block, enforced by consensus.
+const MAX_BLOCK_WEIGHT: u64 = 4_000_000;
+
+/// TemplatePolicy is implemented by a Mining Pool to decide whether a mining
+/// job proposed by a Job Negotiator with a
+/// [CommitMiningJob](struct.CommitMiningJob.html) is acceptable.
+pub trait TemplatePolicy {
+    /// Checks a mining job with the transactions of its block, excluding the
+    /// coinbase transaction, in block order. Returns None if the mining job
+    /// is accepted, otherwise the reason it is rejected.
+    fn check_commit(
+        &mut self,
+        commit: &CommitMiningJob,
+        transactions: &[Vec<u8>],
+    ) -> Option<TemplateRejection>;
+}
+
+/// TemplateRejection is the reason a [TemplatePolicy](trait.TemplatePolicy.html)
+/// rejects a mining job, sent to the Job Negotiator as a
+/// [CommitMiningJob.Error](struct.CommitMiningJobError.html).
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemplateRejection {
+    pub error_code: CommitMiningJobErrorCodes,
+    pub error_details: Vec<u8>,
+}
+
+impl TemplateRejection {
+    pub fn new(error_code: CommitMiningJobErrorCodes, error_details: Vec<u8>) -> TemplateRejection {
+        TemplateRejection {
+            error_code,
+            error_details,
+        }
+    }
+
+    /// Returns the CommitMiningJobError rejecting the request.
+    pub fn commit_mining_job_error(&self, request_id: u32) -> Result<CommitMiningJobError> {
+        CommitMiningJobError::new(request_id, self.error_code, self.error_details.clone())
+    }
+}
+
+/// DefaultTemplatePolicy accepts mining jobs that:
+/// - Pay the Mining Pool with an output to the `payout_script`, the outputs
+///   paying the Mining Pool fitting in `coinbase_output_max_additional_size`.
+/// - Fit in `max_block_weight`, including the coinbase transaction.
+/// - Contain no transaction in the `blacklist` and, if a `whitelist` is set,
+///   only transactions in the `whitelist`.
+///
+/// Rejected mining jobs are reported with the `missing-payout-output`,
+/// `coinbase-outputs-too-large`, `block-weight-too-large` and
+/// `forbidden-transaction` error codes, the details of a forbidden
+/// transaction are its txid. Transactions that cannot be parsed are rejected
+/// with `invalid-job-param-value`.
+///
+/// # Examples
+///
+/// ```rust
+/// use stratumv2::job_negotiation::{
+///     CommitMiningJob, CommitMiningJobErrorCodes, DefaultTemplatePolicy, TemplatePolicy,
+/// };
+///
+/// let payout_script = vec![0x51];
+/// let mut policy = DefaultTemplatePolicy::new(payout_script, 100);
+///
+/// let payout_output = vec![0x00, 0xf2, 0x05, 0x2a, 0x01, 0x00, 0x00, 0x00, 0x01, 0x51];
+/// let commit = |outputs| {
+///     CommitMiningJob::new(
+///         1, vec![0x01], 0x2000_0000, 2, vec![0x51], 0xffff_ffff, 0, outputs, 0, 8, 0,
+///         vec![], [0u8; 32], vec![],
+///     )
+///     .unwrap()
+/// };
+///
+/// assert!(policy.check_commit(&commit(vec![payout_output]), &[]).is_none());
+///
+/// let rejection = policy.check_commit(&commit(vec![]), &[]).unwrap();
+/// assert_eq!(rejection.error_code, CommitMiningJobErrorCodes::MissingPayoutOutput);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct DefaultTemplatePolicy {
+    /// The script of the output paying the Mining Pool.
+    pub payout_script: Vec<u8>,
+
+    /// The maximum serialized size of the outputs paying the Mining Pool, as
+    /// sent in the AllocateMiningJobTokenSuccess.
+    pub coinbase_output_max_additional_size: u32,
+
+    /// The maximum weight of the block.
+    pub max_block_weight: u64,
+
+    /// The txids of the transactions that are never mined.
+    pub blacklist: HashSet<U256>,
+
+    /// The txids of the only transactions that can be mined, or None to
+    /// allow any transaction not in the `blacklist`.
+    pub whitelist: Option<HashSet<U256>>,
+}
+
+impl DefaultTemplatePolicy {
+    pub fn new(
+        payout_script: Vec<u8>,
+        coinbase_output_max_additional_size: u32,
+    ) -> DefaultTemplatePolicy {
+        DefaultTemplatePolicy {
+            payout_script,
+            coinbase_output_max_additional_size,
+            max_block_weight: MAX_BLOCK_WEIGHT,
+            blacklist: HashSet::new(),
+            whitelist: None,
+        }
+    }
+
+    /// Returns true if a serialized output pays to the `payout_script`.
+    fn is_payout_output(&self, output: &[u8]) -> bool {
+        let script = [
+            compact_size(self.payout_script.len() as u64),
+            self.payout_script.clone(),
+        ]
+        .concat();
+
+        output.len() > 8 && output[8..] == script[..]
+    }
+}
+
+impl TemplatePolicy for DefaultTemplatePolicy {
+    fn check_commit(
+        &mut self,
+        commit: &CommitMiningJob,
+        transactions: &[Vec<u8>],
+    ) -> Option<TemplateRejection> {
+        let payout_outputs: Vec<&[u8]> = commit
+            .coinbase_tx_outputs
+            .iter()
+            .map(|output| output.as_ref())
+            .filter(|output| self.is_payout_output(output))
+            .collect();
+
+        if payout_outputs.is_empty() {
+            return Some(TemplateRejection::new(
+                CommitMiningJobErrorCodes::MissingPayoutOutput,
+                vec![],
+            ));
+        }
+
+        let payout_size: usize = payout_outputs.iter().map(|output| output.len()).sum();
+        if payout_size > self.coinbase_output_max_additional_size as usize {
+            return Some(TemplateRejection::new(
+                CommitMiningJobErrorCodes::CoinbaseOutputsTooLarge,
+                vec![],
+            ));
+        }
+
+        let mut weight = 4 * (80 + compact_size(transactions.len() as u64 + 1).len() as u64)
+            + coinbase_weight(commit);
+
+        for transaction in transactions {
+            let (txid, transaction_weight) = match parse_transaction(transaction) {
+                Ok(parsed) => parsed,
+                Err(_) => {
+                    return Some(TemplateRejection::new(
+                        CommitMiningJobErrorCodes::InvalidJobParamValue,
+                        vec![],
+                    ))
+                }
+            };
+
+            let allowed = self
+                .whitelist
+                .as_ref()
+                .is_none_or(|whitelist| whitelist.contains(&txid));
+            if self.blacklist.contains(&txid) || !allowed {
+                return Some(TemplateRejection::new(
+                    CommitMiningJobErrorCodes::ForbiddenTransaction,
+                    txid.to_vec(),
+                ));
+            }
+
+            weight += transaction_weight;
+        }
+
+        if weight > self.max_block_weight {
+            return Some(TemplateRejection::new(
+                CommitMiningJobErrorCodes::BlockWeightTooLarge,
+                vec![],
+            ));
+        }
+
+        None
+    }
+}
+
+/// Returns the weight of the coinbase transaction of a mining job, with an
+/// extranonce of `min_extranonce_size` bytes. The coinbase has a witness if
+/// the mining job has `excess_data`, the witness reserved value.
+fn coinbase_weight(commit: &CommitMiningJob) -> u64 {
+    let script_size = commit.coinbase_prefix.as_ref().len() + commit.min_extranonce_size as usize;
+    let outputs_size: usize = commit
+        .coinbase_tx_outputs
+        .iter()
+        .map(|output| output.as_ref().len())
+        .sum();
+
+    let base_size = 4
+        + 1
+        + 36
+        + compact_size(script_size as u64).len()
+        + script_size
+        + 4
+        + compact_size(commit.coinbase_tx_outputs.len() as u64).len()
+        + outputs_size
+        + 4;
+
+    let excess_data = commit.excess_data.as_ref();
+    let witness_size = if excess_data.is_empty() {
+        0
+    } else {
+        2 + 1 + compact_size(excess_data.len() as u64).len() + excess_data.len()
+    };
+
+    4 * base_size as u64 + witness_size as u64
+}
+
+/// Parses a serialized transaction, returning its txid and weight.
+fn parse_transaction(bytes: &[u8]) -> Result<(U256, u64)> {
+    let has_witness = bytes.get(4..6) == Some(&[0x00, 0x01]);
+    let body_start = if has_witness { 6 } else { 4 };
+    let mut parser = ByteParser::new(bytes, body_start);
+
+    let inputs = parser.next_compact_size()?;
+    for _ in 0..inputs {
+        parser.next_by(36)?;
+        let script_length = read_length(&mut parser)?;
+        parser.next_by(script_length)?;
+        parser.next_by(4)?;
+    }
+
+    let outputs = parser.next_compact_size()?;
+    for _ in 0..outputs {
+        parser.next_by(8)?;
+        let script_length = read_length(&mut parser)?;
+        parser.next_by(script_length)?;
+    }
+    let body_end = parser.offset();
+
+    if has_witness {
+        for _ in 0..inputs {
+            let items = parser.next_compact_size()?;
+            for _ in 0..items {
+                let item_length = read_length(&mut parser)?;
+                parser.next_by(item_length)?;
+            }
+        }
+    }
+
+    let lock_time = parser.next_by(4)?;
+    if parser.offset() != bytes.len() {
+        return Err(Error::ParseError(
+            "unexpected bytes after the transaction".into(),
+        ));
+    }
+
+    let stripped = [&bytes[..4], &bytes[body_start..body_end], lock_time].concat();
+    let weight = 3 * stripped.len() as u64 + bytes.len() as u64;
+
+    Ok((double_sha256(&stripped), weight))
+}
+
+/// Parses a compact size length prefix, a ParseError is returned if the
+/// length exceeds the remaining bytes.
+fn read_length(parser: &mut ByteParser) -> Result<usize> {
+    let length = parser.next_compact_size()?;
+
+    usize::try_from(length)
+        .ok()
+        .filter(|length| *length <= parser.remaining())
+        .ok_or_else(|| Error::ParseError("length exceeds the remaining bytes".into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::hex;
+
+    const GENESIS_COINBASE: &str = concat!(
+        "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff",
+        "4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72",
+        "206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff",
+        "0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f",
+        "61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000"
+    );
+    const GENESIS_COINBASE_TXID: &str =
+        "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b";
+
+    const PAYOUT_SCRIPT: [u8; 3] = [0x51, 0x52, 0x53];
+
+    /// A segwit transaction with one input, one output and a witness of one
+    /// 2 byte item.
+    fn segwit_transaction() -> Vec<u8> {
+        let mut transaction = vec![0x02, 0x00, 0x00, 0x00, 0x00, 0x01, 0x01];
+        transaction.extend_from_slice(&[0x11; 36]);
+        transaction.extend_from_slice(&[0x00, 0xff, 0xff, 0xff, 0xff, 0x01]);
+        transaction.extend_from_slice(&[0x00; 8]);
+        transaction.extend_from_slice(&[0x01, 0x51, 0x01, 0x02, 0xaa, 0xbb]);
+        transaction.extend_from_slice(&[0x00; 4]);
+        transaction
+    }
+
+    fn payout_output() -> Vec<u8> {
+        let mut output = 625_000_000u64.to_le_bytes().to_vec();
+        output.push(PAYOUT_SCRIPT.len() as u8);
+        output.extend_from_slice(&PAYOUT_SCRIPT);
+        output
+    }
+
+    fn commit(outputs: Vec<Vec<u8>>) -> CommitMiningJob {
+        CommitMiningJob::new(
+            1,
+            vec![0x01],
+            0x2000_0000,
+            2,
+            vec![0x03, 0x40, 0x0d, 0x03],
+            0xffff_ffff,
+            0,
+            outputs,
+            0,
+            8,
+            0,
+            vec![],
+            [0u8; 32],
+            vec![0x00; 32],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn parses_transactions() {
+        let (txid, weight) = parse_transaction(&hex::decode(GENESIS_COINBASE).unwrap()).unwrap();
+        let mut expected = hex::decode(GENESIS_COINBASE_TXID).unwrap();
+        expected.reverse();
+        assert_eq!(txid.to_vec(), expected);
+        assert_eq!(weight, 4 * 204);
+
+        let (txid, weight) = parse_transaction(&segwit_transaction()).unwrap();
+        let stripped = [
+            &segwit_transaction()[..4],
+            &segwit_transaction()[6..59],
+            &[0x00; 4][..],
+        ]
+        .concat();
+        assert_eq!(stripped.len(), 61);
+        assert_eq!(txid, double_sha256(&stripped));
+        assert_eq!(weight, 3 * 61 + 67);
+
+        let mut trailing = segwit_transaction();
+        trailing.push(0x00);
+        assert!(parse_transaction(&trailing).is_err());
+        assert!(parse_transaction(&segwit_transaction()[..40]).is_err());
+
+        // A script length of u64::MAX must not overflow the parser offset.
+        let overflowing = [&[0x01, 0x00, 0x00, 0x00, 0x01][..], &[0x00; 36], &[0xff; 9]].concat();
+        assert!(parse_transaction(&overflowing).is_err());
+
+        // The input count is truncated.
+        assert!(parse_transaction(&[0x01, 0x00, 0x00, 0x00, 0xfd, 0x01]).is_err());
+    }
+
+    #[test]
+    fn coinbase_weight_includes_witness() {
+        let commit = commit(vec![payout_output()]);
+
+        // 4 version, 1 input count, 36 outpoint, 1 + 12 script, 4 sequence,
+        // 1 output count, 12 output and 4 lock time bytes.
+        let base_size = 75;
+        // 2 marker and flag, 1 item count and 1 + 32 reserved value bytes.
+        let witness_size = 36;
+        assert_eq!(coinbase_weight(&commit), 4 * base_size + witness_size);
+    }
+
+    #[test]
+    fn accepts_commit() {
+        let mut policy = DefaultTemplatePolicy::new(PAYOUT_SCRIPT.to_vec(), 20);
+        let commit = commit(vec![payout_output(), vec![0x00; 9]]);
+
+        let transactions = vec![hex::decode(GENESIS_COINBASE).unwrap(), segwit_transaction()];
+        assert!(policy.check_commit(&commit, &transactions).is_none());
+    }
+
+    #[test]
+    fn rejects_missing_payout_output() {
+        let mut policy = DefaultTemplatePolicy::new(PAYOUT_SCRIPT.to_vec(), 20);
+
+        let mut other_output = payout_output();
+        other_output[11] = 0x54;
+        let rejection = policy
+            .check_commit(&commit(vec![other_output]), &[])
+            .unwrap();
+        assert_eq!(
+            rejection.error_code,
+            CommitMiningJobErrorCodes::MissingPayoutOutput
+        );
+
+        let error = rejection.commit_mining_job_error(7).unwrap();
+        assert_eq!(error.request_id, 7);
+        assert_eq!(
+            error.error_code,
+            CommitMiningJobErrorCodes::MissingPayoutOutput
+        );
+    }
+
+    #[test]
+    fn rejects_payout_outputs_too_large() {
+        let mut policy = DefaultTemplatePolicy::new(PAYOUT_SCRIPT.to_vec(), 20);
+        let commit = commit(vec![payout_output(), payout_output()]);
+
+        let rejection = policy.check_commit(&commit, &[]).unwrap();
+        assert_eq!(
+            rejection.error_code,
+            CommitMiningJobErrorCodes::CoinbaseOutputsTooLarge
+        );
+
+        policy.coinbase_output_max_additional_size = 24;
+        assert!(policy.check_commit(&commit, &[]).is_none());
+    }
+
+    #[test]
+    fn rejects_block_weight_too_large() {
+        let mut policy = DefaultTemplatePolicy::new(PAYOUT_SCRIPT.to_vec(), 20);
+        let commit = commit(vec![payout_output()]);
+        let transactions = vec![segwit_transaction()];
+
+        // 4 * 81 header and transaction count, 336 coinbase and 250
+        // transaction weight units.
+        policy.max_block_weight = 910;
+        assert!(policy.check_commit(&commit, &transactions).is_none());
+
+        policy.max_block_weight = 909;
+        let rejection = policy.check_commit(&commit, &transactions).unwrap();
+        assert_eq!(
+            rejection.error_code,
+            CommitMiningJobErrorCodes::BlockWeightTooLarge
+        );
+    }
+
+    #[test]
+    fn rejects_forbidden_transactions() {
+        let mut policy = DefaultTemplatePolicy::new(PAYOUT_SCRIPT.to_vec(), 20);
+        let commit = commit(vec![payout_output()]);
+        let transactions = vec![hex::decode(GENESIS_COINBASE).unwrap(), segwit_transaction()];
+        let (txid, _) = parse_transaction(&segwit_transaction()).unwrap();
+
+        policy.blacklist.insert(txid);
+        let rejection = policy.check_commit(&commit, &transactions).unwrap();
+        assert_eq!(
+            rejection.error_code,
+            CommitMiningJobErrorCodes::ForbiddenTransaction
+        );
+        assert_eq!(rejection.error_details, txid.to_vec());
+
+        // Only whitelisted transactions are allowed.
+        policy.blacklist.clear();
+        policy.whitelist = Some([txid].iter().cloned().collect());
+        let rejection = policy.check_commit(&commit, &transactions).unwrap();
+        assert_eq!(
+            rejection.error_code,
+            CommitMiningJobErrorCodes::ForbiddenTransaction
+        );
+        assert!(policy.check_commit(&commit, &transactions[1..]).is_none());
+    }
+
+    #[test]
+    fn rejects_invalid_transactions() {
+        let mut policy = DefaultTemplatePolicy::new(PAYOUT_SCRIPT.to_vec(), 20);
+        let commit = commit(vec![payout_output()]);
+
+        let rejection = policy.check_commit(&commit, &[vec![0x01; 10]]).unwrap();
+        assert_eq!(
+            rejection.error_code,
+            CommitMiningJobErrorCodes::InvalidJobParamValue
+        );
+    }
+}
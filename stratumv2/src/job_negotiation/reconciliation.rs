@@ -0,0 +1,421 @@
+use crate::error::{Error, Result};
+use crate::job_negotiation::short_id::{tx_hash_list_hash, wtxid};
+use crate::job_negotiation::{
+    CommitMiningJob, IdentifyTransactions, IdentifyTransactionsSuccess, ProvideMissingTransactions,
+    ProvideMissingTransactionsSuccess, ShortTxId, ShortTxIdHasher,
+};
+use crate::types::U256;
+use std::collections::HashMap;
+
+/// MempoolView is implemented by a Mining Pool to look up the transactions
+/// of its mempool when rebuilding the block of a
+/// [CommitMiningJob](struct.CommitMiningJob.html).
+pub trait MempoolView {
+    /// Returns the wtxid of every transaction in the mempool.
+    fn wtxids(&self) -> Vec<U256>;
+
+    /// Returns the serialized transaction with a wtxid, including its
+    /// witness data.
+    fn transaction(&self, wtxid: &U256) -> Option<Vec<u8>>;
+}
+
+/// A mempool of serialized transactions indexed by wtxid.
+impl MempoolView for HashMap<U256, Vec<u8>> {
+    fn wtxids(&self) -> Vec<U256> {
+        self.keys().cloned().collect()
+    }
+
+    fn transaction(&self, wtxid: &U256) -> Option<Vec<u8>> {
+        self.get(wtxid).cloned()
+    }
+}
+
+/// The result of a step of a [TransactionReconciler](struct.TransactionReconciler.html).
+#[derive(Debug, Clone)]
+pub enum Reconciliation {
+    /// Every transaction of the mining job is known, in block order.
+    Complete(Vec<Vec<u8>>),
+
+    /// Transactions are missing from the mempool and are requested from the
+    /// Job Negotiator.
+    ProvideMissingTransactions(ProvideMissingTransactions),
+
+    /// Short ids collide in the mempool, or resolve to transactions not
+    /// matching the `tx_hash_list_hash`, and the full hashes are requested
+    /// from the Job Negotiator.
+    IdentifyTransactions(IdentifyTransactions),
+}
+
+/// TransactionReconciler rebuilds the transactions of a
+/// [CommitMiningJob](struct.CommitMiningJob.html) from the mempool of a
+/// Mining Pool:
+/// - Short ids matching exactly one mempool transaction are resolved.
+/// - Short ids matching several mempool transactions are colliding, the full
+///   hashes are requested with `IdentifyTransactions`.
+/// - Short ids matching no mempool transaction are requested with
+///   `ProvideMissingTransactions`.
+/// - The resolved transactions are verified against the `tx_hash_list_hash`,
+///   a mismatch caused by a collision with a transaction outside the block
+///   is resolved with `IdentifyTransactions`.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use stratumv2::job_negotiation::{
+///     tx_hash_list_hash, wtxid, CommitMiningJob, ProvideMissingTransactionsSuccess,
+///     Reconciliation, ShortTxIdHasher, TransactionReconciler,
+/// };
+///
+/// let transactions = vec![vec![0x01; 60], vec![0x02; 80]];
+/// let hasher = ShortTxIdHasher::new(7);
+/// let short_ids = transactions.iter().map(|tx| hasher.transaction_short_id(tx)).collect();
+///
+/// let commit = CommitMiningJob::new(
+///     1, vec![0x01], 0x2000_0000, 2, vec![0x51], 0xffff_ffff, 0, vec![], 0, 8, 7,
+///     short_ids, tx_hash_list_hash(&transactions), vec![],
+/// )
+/// .unwrap();
+///
+/// // The mempool of the Mining Pool only contains the first transaction.
+/// let mut mempool = HashMap::new();
+/// mempool.insert(wtxid(&transactions[0]), transactions[0].clone());
+///
+/// let mut reconciler = TransactionReconciler::new(&commit);
+/// let request = match reconciler.reconcile(&mempool).unwrap() {
+///     Reconciliation::ProvideMissingTransactions(request) => request,
+///     _ => panic!("expected missing transactions"),
+/// };
+/// assert_eq!(request.unknown_tx_position_list, vec![1]);
+///
+/// let provided = ProvideMissingTransactionsSuccess::new(1, vec![transactions[1].clone()]).unwrap();
+/// match reconciler.handle_provide_missing_transactions_success(&provided).unwrap() {
+///     Reconciliation::Complete(block_transactions) => assert_eq!(block_transactions, transactions),
+///     _ => panic!("expected the transactions to be reconciled"),
+/// }
+/// ```
+#[derive(Debug)]
+pub struct TransactionReconciler {
+    request_id: u32,
+    hasher: ShortTxIdHasher,
+    short_ids: Vec<ShortTxId>,
+    tx_hash_list_hash: U256,
+    wtxids: Option<Vec<U256>>,
+    transactions: Vec<Option<Vec<u8>>>,
+    requested: Vec<u16>,
+}
+
+impl TransactionReconciler {
+    /// Creates a TransactionReconciler for the short ids of a mining job
+    /// committed by a Job Negotiator.
+    pub fn new(commit: &CommitMiningJob) -> TransactionReconciler {
+        TransactionReconciler {
+            request_id: commit.request_id,
+            hasher: ShortTxIdHasher::new(commit.tx_short_hash_nonce),
+            short_ids: commit.tx_short_hash_list.clone(),
+            tx_hash_list_hash: commit.tx_hash_list_hash,
+            wtxids: None,
+            transactions: vec![None; commit.tx_short_hash_list.len()],
+            requested: vec![],
+        }
+    }
+
+    /// Resolves the short ids of the mining job from the mempool.
+    pub fn reconcile<M: MempoolView>(&mut self, mempool: &M) -> Result<Reconciliation> {
+        let mut candidates: HashMap<ShortTxId, Vec<U256>> = HashMap::new();
+        for wtxid in mempool.wtxids() {
+            candidates
+                .entry(self.hasher.short_id(&wtxid))
+                .or_default()
+                .push(wtxid);
+        }
+
+        for (position, short_id) in self.short_ids.iter().enumerate() {
+            match candidates.get(short_id).map(Vec::as_slice) {
+                Some([wtxid]) => self.transactions[position] = mempool.transaction(wtxid),
+                Some(_) => {
+                    return Ok(Reconciliation::IdentifyTransactions(
+                        IdentifyTransactions::new(self.request_id),
+                    ))
+                }
+                None => {}
+            }
+        }
+
+        self.status()
+    }
+
+    /// Resolves the transactions by the full hashes sent by the Job
+    /// Negotiator. Transactions already provided by the Job Negotiator are
+    /// kept.
+    ///
+    /// A RequirementError is returned if the hashes do not match the short
+    /// ids of the mining job.
+    pub fn handle_identify_transactions_success<M: MempoolView>(
+        &mut self,
+        message: &IdentifyTransactionsSuccess,
+        mempool: &M,
+    ) -> Result<Reconciliation> {
+        self.check_request_id(message.request_id)?;
+
+        if message.tx_data_hashes.len() != self.short_ids.len()
+            || message
+                .tx_data_hashes
+                .iter()
+                .zip(&self.short_ids)
+                .any(|(wtxid, short_id)| self.hasher.short_id(wtxid) != *short_id)
+        {
+            return Err(Error::RequirementError(
+                "the transaction hashes do not match the short ids".into(),
+            ));
+        }
+
+        for (position, hash) in message.tx_data_hashes.iter().enumerate() {
+            let known = self.transactions[position]
+                .as_ref()
+                .is_some_and(|transaction| wtxid(transaction) == *hash);
+
+            if !known {
+                self.transactions[position] = mempool.transaction(hash);
+            }
+        }
+
+        self.wtxids = Some(message.tx_data_hashes.clone());
+        self.status()
+    }
+
+    /// Adds the transactions provided by the Job Negotiator.
+    ///
+    /// A RequirementError is returned if the transactions are not the ones
+    /// requested.
+    pub fn handle_provide_missing_transactions_success(
+        &mut self,
+        message: &ProvideMissingTransactionsSuccess,
+    ) -> Result<Reconciliation> {
+        self.check_request_id(message.request_id)?;
+
+        if message.transaction_list.len() != self.requested.len() {
+            return Err(Error::RequirementError(
+                "the number of transactions does not match the request".into(),
+            ));
+        }
+
+        for (position, transaction) in self.requested.iter().zip(&message.transaction_list) {
+            let position = *position as usize;
+            let hash = wtxid(transaction.as_ref());
+
+            let matches = match &self.wtxids {
+                Some(wtxids) => wtxids[position] == hash,
+                None => self.hasher.short_id(&hash) == self.short_ids[position],
+            };
+            if !matches {
+                return Err(Error::RequirementError(
+                    "a provided transaction does not match the request".into(),
+                ));
+            }
+
+            self.transactions[position] = Some(transaction.as_ref().to_vec());
+        }
+
+        self.requested.clear();
+        self.status()
+    }
+
+    fn status(&mut self) -> Result<Reconciliation> {
+        let missing: Vec<u16> = self
+            .transactions
+            .iter()
+            .enumerate()
+            .filter(|(_, transaction)| transaction.is_none())
+            .map(|(position, _)| position as u16)
+            .collect();
+
+        if !missing.is_empty() {
+            self.requested = missing.clone();
+            return Ok(Reconciliation::ProvideMissingTransactions(
+                ProvideMissingTransactions::new(self.request_id, missing)?,
+            ));
+        }
+
+        let transactions: Vec<Vec<u8>> = self.transactions.iter().flatten().cloned().collect();
+        if tx_hash_list_hash(&transactions) == self.tx_hash_list_hash {
+            return Ok(Reconciliation::Complete(transactions));
+        }
+
+        // A short id matched a mempool transaction outside the block, the
+        // full hashes resolve the collision.
+        if self.wtxids.is_none() {
+            return Ok(Reconciliation::IdentifyTransactions(
+                IdentifyTransactions::new(self.request_id),
+            ));
+        }
+
+        Err(Error::RequirementError(
+            "the transactions do not match the tx_hash_list_hash".into(),
+        ))
+    }
+
+    fn check_request_id(&self, request_id: u32) -> Result<()> {
+        if request_id != self.request_id {
+            return Err(Error::RequirementError(
+                "the message is not for the reconciled mining job".into(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transactions() -> Vec<Vec<u8>> {
+        vec![vec![0x01; 60], vec![0x02; 80], vec![0x03; 100]]
+    }
+
+    fn commit(nonce: u64, short_ids: Vec<ShortTxId>) -> CommitMiningJob {
+        CommitMiningJob::new(
+            1,
+            vec![0x01],
+            0x2000_0000,
+            2,
+            vec![0x51],
+            0xffff_ffff,
+            0,
+            vec![],
+            0,
+            8,
+            nonce,
+            short_ids,
+            tx_hash_list_hash(&transactions()),
+            vec![],
+        )
+        .unwrap()
+    }
+
+    fn short_ids(nonce: u64) -> Vec<ShortTxId> {
+        let hasher = ShortTxIdHasher::new(nonce);
+        transactions()
+            .iter()
+            .map(|transaction| hasher.transaction_short_id(transaction))
+            .collect()
+    }
+
+    fn mempool(transactions: &[Vec<u8>]) -> HashMap<U256, Vec<u8>> {
+        transactions
+            .iter()
+            .map(|transaction| (wtxid(transaction), transaction.clone()))
+            .collect()
+    }
+
+    /// A mempool view listing every transaction twice, so that every short
+    /// id matches two candidates.
+    struct DuplicatingMempool(HashMap<U256, Vec<u8>>);
+
+    impl MempoolView for DuplicatingMempool {
+        fn wtxids(&self) -> Vec<U256> {
+            let wtxids = self.0.wtxids();
+            [wtxids.clone(), wtxids].concat()
+        }
+
+        fn transaction(&self, wtxid: &U256) -> Option<Vec<u8>> {
+            self.0.transaction(wtxid)
+        }
+    }
+
+    #[test]
+    fn reconciles_from_mempool() {
+        let mut mempool = mempool(&transactions());
+        mempool.insert(wtxid(&[0x04; 10]), vec![0x04; 10]);
+
+        let mut reconciler = TransactionReconciler::new(&commit(5, short_ids(5)));
+        match reconciler.reconcile(&mempool).unwrap() {
+            Reconciliation::Complete(reconciled) => assert_eq!(reconciled, transactions()),
+            _ => panic!("expected the transactions to be reconciled"),
+        }
+    }
+
+    #[test]
+    fn requests_missing_transactions() {
+        let mempool = mempool(&transactions()[1..2]);
+
+        let mut reconciler = TransactionReconciler::new(&commit(5, short_ids(5)));
+        match reconciler.reconcile(&mempool).unwrap() {
+            Reconciliation::ProvideMissingTransactions(request) => {
+                assert_eq!(request.request_id, 1);
+                assert_eq!(request.unknown_tx_position_list, vec![0, 2]);
+            }
+            _ => panic!("expected missing transactions"),
+        }
+
+        // Transactions other than the requested ones are rejected.
+        let wrong =
+            ProvideMissingTransactionsSuccess::new(1, vec![vec![0x05], vec![0x03; 100]]).unwrap();
+        assert!(reconciler
+            .handle_provide_missing_transactions_success(&wrong)
+            .is_err());
+
+        let provided = ProvideMissingTransactionsSuccess::new(
+            1,
+            vec![transactions()[0].clone(), transactions()[2].clone()],
+        )
+        .unwrap();
+        match reconciler
+            .handle_provide_missing_transactions_success(&provided)
+            .unwrap()
+        {
+            Reconciliation::Complete(reconciled) => assert_eq!(reconciled, transactions()),
+            _ => panic!("expected the transactions to be reconciled"),
+        }
+    }
+
+    #[test]
+    fn identifies_colliding_short_ids() {
+        let mempool = DuplicatingMempool(mempool(&transactions()[..2]));
+
+        let mut reconciler = TransactionReconciler::new(&commit(5, short_ids(5)));
+        match reconciler.reconcile(&mempool).unwrap() {
+            Reconciliation::IdentifyTransactions(request) => assert_eq!(request.request_id, 1),
+            _ => panic!("expected the transactions to be identified"),
+        }
+
+        // Hashes not matching the short ids are rejected.
+        let wrong = IdentifyTransactionsSuccess::new(1, vec![[0x00; 32]; 3]).unwrap();
+        assert!(reconciler
+            .handle_identify_transactions_success(&wrong, &mempool)
+            .is_err());
+
+        let wtxids = transactions().iter().map(|tx| wtxid(tx)).collect();
+        let identified = IdentifyTransactionsSuccess::new(1, wtxids).unwrap();
+        match reconciler
+            .handle_identify_transactions_success(&identified, &mempool)
+            .unwrap()
+        {
+            Reconciliation::ProvideMissingTransactions(request) => {
+                assert_eq!(request.unknown_tx_position_list, vec![2])
+            }
+            _ => panic!("expected missing transactions"),
+        }
+
+        let provided =
+            ProvideMissingTransactionsSuccess::new(1, vec![transactions()[2].clone()]).unwrap();
+        match reconciler
+            .handle_provide_missing_transactions_success(&provided)
+            .unwrap()
+        {
+            Reconciliation::Complete(reconciled) => assert_eq!(reconciled, transactions()),
+            _ => panic!("expected the transactions to be reconciled"),
+        }
+    }
+
+    #[test]
+    fn rejects_other_request_id() {
+        let mut reconciler = TransactionReconciler::new(&commit(5, short_ids(5)));
+        let provided = ProvideMissingTransactionsSuccess::new(2, vec![]).unwrap();
+
+        assert!(reconciler
+            .handle_provide_missing_transactions_success(&provided)
+            .is_err());
+    }
+}
@@ -0,0 +1,137 @@
+use crate::job_negotiation::ShortTxId;
+use crate::types::U256;
+use crate::util::double_sha256;
+use sha2::{Digest, Sha256};
+use siphasher::sip::SipHasher24;
+use std::convert::TryInto;
+use std::hash::Hasher;
+
+/// ShortTxIdHasher computes the short ids of the transactions of a
+/// [CommitMiningJob](struct.CommitMiningJob.html), the 6 least significant
+/// bytes of the SipHash-2-4 of their wtxid. As in BIP152, the SipHash keys
+/// are the first two little endian u64 of the SHA256 of the
+/// `tx_short_hash_nonce`.
+///
+/// # Examples
+///
+/// ```rust
+/// use stratumv2::job_negotiation::ShortTxIdHasher;
+///
+/// let hasher = ShortTxIdHasher::new(42);
+/// let short_id = hasher.short_id(&[0x11; 32]);
+///
+/// assert_eq!(short_id, [0xf8, 0x72, 0x1d, 0x6e, 0x66, 0x42]);
+/// assert_eq!(hasher.transaction_short_id(&[0x01, 0x02]).len(), 6);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShortTxIdHasher {
+    k0: u64,
+    k1: u64,
+}
+
+impl ShortTxIdHasher {
+    /// Creates a hasher with the keys derived from a `tx_short_hash_nonce`.
+    pub fn new(nonce: u64) -> ShortTxIdHasher {
+        let keys = Sha256::digest(&nonce.to_le_bytes());
+
+        ShortTxIdHasher::with_keys(
+            u64::from_le_bytes(keys[0..8].try_into().unwrap()),
+            u64::from_le_bytes(keys[8..16].try_into().unwrap()),
+        )
+    }
+
+    /// Creates a hasher with explicit SipHash keys.
+    pub fn with_keys(k0: u64, k1: u64) -> ShortTxIdHasher {
+        ShortTxIdHasher { k0, k1 }
+    }
+
+    /// Returns the short id of a wtxid.
+    pub fn short_id(&self, wtxid: &U256) -> ShortTxId {
+        let mut hasher = SipHasher24::new_with_keys(self.k0, self.k1);
+        hasher.write(wtxid);
+
+        let mut short_id = [0u8; 6];
+        short_id.copy_from_slice(&hasher.finish().to_le_bytes()[..6]);
+        short_id
+    }
+
+    /// Returns the short id of a serialized transaction, including its
+    /// witness data.
+    pub fn transaction_short_id(&self, transaction: &[u8]) -> ShortTxId {
+        self.short_id(&wtxid(transaction))
+    }
+}
+
+/// Returns the wtxid of a serialized transaction, including its witness
+/// data. The wtxid of a transaction without witness is its txid.
+pub fn wtxid(transaction: &[u8]) -> U256 {
+    double_sha256(transaction)
+}
+
+/// Returns the `tx_hash_list_hash` of a
+/// [CommitMiningJob](struct.CommitMiningJob.html), the SHA256 of the
+/// concatenated SHA256 of the serialized transactions.
+pub fn tx_hash_list_hash(transactions: &[Vec<u8>]) -> U256 {
+    let mut hasher = Sha256::new();
+    for transaction in transactions {
+        hasher.update(Sha256::digest(transaction));
+    }
+
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(&hasher.finalize());
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn siphash_reference_vector() {
+        // The reference vector of the SipHash paper for a 32 byte message.
+        let hasher = ShortTxIdHasher::with_keys(0x0706_0504_0302_0100, 0x0f0e_0d0c_0b0a_0908);
+
+        let mut message = [0u8; 32];
+        for (i, byte) in message.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+
+        assert_eq!(
+            hasher.short_id(&message),
+            [0xce, 0x7c, 0xf2, 0x72, 0x2f, 0x51]
+        );
+    }
+
+    #[test]
+    fn short_id_depends_on_nonce() {
+        let wtxid = [0x11; 32];
+
+        assert_eq!(
+            ShortTxIdHasher::new(42).short_id(&wtxid),
+            [0xf8, 0x72, 0x1d, 0x6e, 0x66, 0x42]
+        );
+        assert_ne!(
+            ShortTxIdHasher::new(43).short_id(&wtxid),
+            ShortTxIdHasher::new(42).short_id(&wtxid)
+        );
+    }
+
+    #[test]
+    fn hash_list_hash() {
+        let transactions = vec![vec![0x01], vec![0x02, 0x03]];
+        let concatenated = [
+            Sha256::digest(&[0x01]).to_vec(),
+            Sha256::digest(&[0x02, 0x03]).to_vec(),
+        ]
+        .concat();
+
+        assert_eq!(
+            tx_hash_list_hash(&transactions).to_vec(),
+            Sha256::digest(&concatenated).to_vec()
+        );
+        assert_ne!(
+            tx_hash_list_hash(&transactions),
+            tx_hash_list_hash(&transactions[..1])
+        );
+    }
+}
@@ -3,7 +3,7 @@
 //! Stratum V2 sources:
 //! - [Stratum V2 Overview](https://braiins.com/stratum-v2)
 //! - [Stratum V2 Specification](https://docs.google.com/document/d/1FadCWj-57dvhxsnFM_7X806qyvhR0u3i85607bGHxvg/edit)
-use crate::error::Result;
+pub use crate::error::{Error, Result};
 use std::io;
 
 #[macro_use]
@@ -30,14 +30,34 @@ pub mod mining;
 /// Job Negotiation is a sub protocol of Stratum V2.
 pub mod job_negotiation;
 
+/// Template Distribution is a sub protocol of Stratum V2.
+pub mod template_distribution;
+
 /// Noise contains all the required messages and functions to perform the Noise
 /// Handshake, creating a symmetric key to perform secure communication.
 /// This module contains functions to verify and generate signatures
 /// for both Client and Server to attest to the authenticty of an Upstream Node.
 pub mod noise;
 
+/// Target and difficulty arithmetic on 256-bit integers.
+pub mod target;
+
+/// Stratum V1 messages used to bridge legacy mining devices.
+pub mod v1;
+
+/// Translation between Stratum V1 miners and a Stratum V2 Upstream.
+pub mod translator;
+
+/// A mining proxy aggregating many standard channels into one extended channel.
+pub mod proxy;
+
+/// Conversions to the types of the bitcoin crate.
+#[cfg(feature = "bitcoin")]
+pub mod bitcoin;
+
 /// Protocol is an enum representing each sub protocol of Stratum V2.
 #[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Protocol {
     /// Mining is the main and only required sub protocol in Stratum V2.
     Mining,
@@ -88,12 +108,13 @@ pub trait Deserializable {
 }
 
 /// Trait for getting set bits of a flag as a u32 or enum representation
-/// according to the Stratum V2 protocol.
-pub trait BitFlag {
+/// according to the Stratum V2 protocol. Sets of flags are represented by a
+/// [FlagSet](types/struct.FlagSet.html).
+pub trait BitFlag: Copy + 'static {
+    /// Every known variant of the flag.
+    const FLAGS: &'static [Self];
+
     fn as_bit_flag(&self) -> u32;
-    fn deserialize_flags(flags: u32) -> Vec<Self>
-    where
-        Self: std::marker::Sized;
 }
 
 /// Trait for creating a serialized frame for networked messages. This trait
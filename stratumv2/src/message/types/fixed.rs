@@ -46,6 +46,28 @@ impl TryFrom<u32> for U24 {
     }
 }
 
+/// Serializes the U24 as an integer for the `serde` feature.
+#[cfg(feature = "serde")]
+impl serde::Serialize for U24 {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_u32(self.0)
+    }
+}
+
+/// Deserialization enforces the maximum value of a U24.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for U24 {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let value: u32 = serde::Deserialize::deserialize(deserializer)?;
+        U24::try_from(value).map_err(serde::de::Error::custom)
+    }
+}
+
 impl From<U24> for usize {
     fn from(u: U24) -> Self {
         u.0 as usize
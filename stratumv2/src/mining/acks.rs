@@ -0,0 +1,414 @@
+use crate::error::{Error, Result};
+use crate::mining::{SubmitSharesError, SubmitSharesSuccess};
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Returns true if `sequence_number` is at or before `last_sequence_number`,
+/// accounting for sequence numbers wrapping around.
+fn is_acknowledged(sequence_number: u32, last_sequence_number: u32) -> bool {
+    last_sequence_number.wrapping_sub(sequence_number) < 1 << 31
+}
+
+/// The share counters of a channel tracked by a
+/// [ShareTracker](struct.ShareTracker.html).
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ShareStats {
+    /// The number of shares accepted by the Server.
+    pub accepted: u64,
+
+    /// The sum of the difficulty of the accepted shares.
+    pub accepted_shares_sum: u64,
+
+    /// The number of shares rejected by the Server.
+    pub rejected: u64,
+
+    /// The number of submitted shares not yet acknowledged by the Server.
+    pub pending: usize,
+}
+
+#[derive(Debug, Default)]
+struct TrackedChannel {
+    next_sequence_number: u32,
+    pending: VecDeque<u32>,
+    stats: ShareStats,
+}
+
+/// ShareTracker is used by a Client to number the shares submitted on its
+/// channels and to match the replies of the Server.
+///
+/// Each submitted share is assigned a monotonically increasing
+/// `sequence_number`. The Server acknowledges accepted shares in batches with
+/// a [SubmitSharesSuccess](struct.SubmitSharesSuccess.html), covering every
+/// share up to its `last_sequence_number`, and rejects shares individually
+/// with a [SubmitSharesError](struct.SubmitSharesError.html).
+///
+/// # Examples
+///
+/// ```rust
+/// use stratumv2::mining::{
+///     ShareTracker, SubmitSharesError, SubmitSharesErrorCodes, SubmitSharesSuccess,
+/// };
+///
+/// let mut tracker = ShareTracker::new();
+/// tracker.add_channel(1);
+///
+/// assert_eq!(tracker.next_sequence_number(1).unwrap(), 0);
+/// assert_eq!(tracker.next_sequence_number(1).unwrap(), 1);
+/// assert_eq!(tracker.next_sequence_number(1).unwrap(), 2);
+///
+/// let error = SubmitSharesError::new(1, 1, SubmitSharesErrorCodes::StaleShare);
+/// tracker.on_error(&error).unwrap();
+///
+/// // A single SubmitSharesSuccess acknowledges both remaining shares.
+/// tracker.on_success(&SubmitSharesSuccess::new(1, 2, 2, 200)).unwrap();
+///
+/// let stats = tracker.stats(1).unwrap();
+/// assert_eq!(stats.accepted, 2);
+/// assert_eq!(stats.rejected, 1);
+/// assert_eq!(stats.pending, 0);
+/// ```
+#[derive(Debug, Default)]
+pub struct ShareTracker {
+    channels: HashMap<u32, TrackedChannel>,
+}
+
+impl ShareTracker {
+    pub fn new() -> ShareTracker {
+        ShareTracker::default()
+    }
+
+    /// Starts tracking the shares of a channel, e.g. when the channel is
+    /// opened. Sequence numbers of the channel start at 0.
+    pub fn add_channel(&mut self, channel_id: u32) {
+        self.channels.insert(channel_id, TrackedChannel::default());
+    }
+
+    /// Stops tracking the shares of a channel, returning its counters.
+    pub fn remove_channel(&mut self, channel_id: u32) -> Option<ShareStats> {
+        self.channels
+            .remove(&channel_id)
+            .map(|channel| channel.stats_with_pending())
+    }
+
+    /// Returns the counters of a channel.
+    pub fn stats(&self, channel_id: u32) -> Option<ShareStats> {
+        self.channels
+            .get(&channel_id)
+            .map(|channel| channel.stats_with_pending())
+    }
+
+    /// Assigns the sequence number of a share about to be submitted on a
+    /// channel. The share is pending until it is acknowledged or rejected.
+    ///
+    /// A RequirementError is returned if the channel is not tracked.
+    pub fn next_sequence_number(&mut self, channel_id: u32) -> Result<u32> {
+        let channel = self.channel_mut(channel_id)?;
+
+        let sequence_number = channel.next_sequence_number;
+        channel.next_sequence_number = sequence_number.wrapping_add(1);
+        channel.pending.push_back(sequence_number);
+
+        Ok(sequence_number)
+    }
+
+    /// Applies a SubmitSharesSuccess, resolving every pending share up to
+    /// `last_sequence_number`.
+    ///
+    /// A RequirementError is returned if the channel is not tracked.
+    pub fn on_success(&mut self, success: &SubmitSharesSuccess) -> Result<()> {
+        let channel = self.channel_mut(success.channel_id)?;
+
+        while let Some(&sequence_number) = channel.pending.front() {
+            if !is_acknowledged(sequence_number, success.last_sequence_number) {
+                break;
+            }
+            channel.pending.pop_front();
+        }
+
+        channel.stats.accepted += u64::from(success.new_submits_accepted_count);
+        channel.stats.accepted_shares_sum = channel
+            .stats
+            .accepted_shares_sum
+            .saturating_add(success.new_shares_sum);
+
+        Ok(())
+    }
+
+    /// Applies a SubmitSharesError, resolving the rejected share.
+    ///
+    /// A RequirementError is returned if the channel is not tracked.
+    pub fn on_error(&mut self, error: &SubmitSharesError) -> Result<()> {
+        let channel = self.channel_mut(error.channel_id)?;
+
+        // The share may already be resolved if the error was sent after a
+        // SubmitSharesSuccess covering its sequence number.
+        if let Some(index) = channel
+            .pending
+            .iter()
+            .position(|sequence_number| *sequence_number == error.sequence_number)
+        {
+            channel.pending.remove(index);
+        }
+
+        channel.stats.rejected += 1;
+        Ok(())
+    }
+
+    fn channel_mut(&mut self, channel_id: u32) -> Result<&mut TrackedChannel> {
+        self.channels.get_mut(&channel_id).ok_or_else(|| {
+            Error::RequirementError("the shares of the channel are not tracked".into())
+        })
+    }
+}
+
+impl TrackedChannel {
+    fn stats_with_pending(&self) -> ShareStats {
+        ShareStats {
+            pending: self.pending.len(),
+            ..self.stats
+        }
+    }
+}
+
+/// The accepted shares of a channel not yet acknowledged.
+#[derive(Debug)]
+struct PendingAck {
+    last_sequence_number: u32,
+    accepted_count: u32,
+    shares_sum: u64,
+    first_accepted: Instant,
+}
+
+impl PendingAck {
+    fn into_success(self, channel_id: u32) -> SubmitSharesSuccess {
+        SubmitSharesSuccess::new(
+            channel_id,
+            self.last_sequence_number,
+            self.accepted_count,
+            self.shares_sum,
+        )
+    }
+}
+
+/// ShareAckAggregator is used by a Server to acknowledge the accepted shares
+/// of its channels in batches. The aggregator is sans-IO, the current time is
+/// provided by the caller and the returned
+/// [SubmitSharesSuccess](struct.SubmitSharesSuccess.html) messages must be
+/// sent to the Client.
+///
+/// A channel is acknowledged once `interval` elapsed since its first
+/// unacknowledged share was accepted. Pending acknowledgements SHOULD be
+/// flushed before a [SubmitSharesError](struct.SubmitSharesError.html) is
+/// sent on the channel, so the Client receives the replies in order.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::{Duration, Instant};
+/// use stratumv2::mining::ShareAckAggregator;
+///
+/// let mut acks = ShareAckAggregator::new(Duration::from_secs(1));
+///
+/// let now = Instant::now();
+/// acks.on_share_accepted(1, 0, 100, now);
+/// acks.on_share_accepted(1, 1, 100, now);
+/// assert!(acks.poll(now).is_empty());
+///
+/// let success = acks.poll(now + Duration::from_secs(1));
+/// assert_eq!(success.len(), 1);
+/// assert_eq!(success[0].last_sequence_number, 1);
+/// assert_eq!(success[0].new_submits_accepted_count, 2);
+/// assert_eq!(success[0].new_shares_sum, 200);
+/// ```
+#[derive(Debug)]
+pub struct ShareAckAggregator {
+    interval: Duration,
+    channels: HashMap<u32, PendingAck>,
+}
+
+impl ShareAckAggregator {
+    /// Creates a ShareAckAggregator acknowledging shares every `interval`. An
+    /// interval of zero acknowledges shares on the next poll.
+    pub fn new(interval: Duration) -> ShareAckAggregator {
+        ShareAckAggregator {
+            interval,
+            channels: HashMap::new(),
+        }
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Records an accepted share with the difficulty credited to the share.
+    pub fn on_share_accepted(
+        &mut self,
+        channel_id: u32,
+        sequence_number: u32,
+        difficulty: u64,
+        now: Instant,
+    ) {
+        let ack = self.channels.entry(channel_id).or_insert(PendingAck {
+            last_sequence_number: sequence_number,
+            accepted_count: 0,
+            shares_sum: 0,
+            first_accepted: now,
+        });
+
+        if is_acknowledged(ack.last_sequence_number, sequence_number) {
+            ack.last_sequence_number = sequence_number;
+        }
+        ack.accepted_count = ack.accepted_count.saturating_add(1);
+        ack.shares_sum = ack.shares_sum.saturating_add(difficulty);
+    }
+
+    /// Returns the acknowledgement of every channel whose first pending share
+    /// was accepted at least `interval` ago.
+    pub fn poll(&mut self, now: Instant) -> Vec<SubmitSharesSuccess> {
+        let interval = self.interval;
+        let due: Vec<u32> = self
+            .channels
+            .iter()
+            .filter(|(_, ack)| now.saturating_duration_since(ack.first_accepted) >= interval)
+            .map(|(channel_id, _)| *channel_id)
+            .collect();
+
+        due.into_iter()
+            .filter_map(|channel_id| self.flush(channel_id))
+            .collect()
+    }
+
+    /// Returns the acknowledgement of the pending shares of a channel
+    /// immediately, regardless of the interval.
+    pub fn flush(&mut self, channel_id: u32) -> Option<SubmitSharesSuccess> {
+        self.channels
+            .remove(&channel_id)
+            .map(|ack| ack.into_success(channel_id))
+    }
+
+    /// Discards the pending shares of a channel, e.g. when the channel is
+    /// closed.
+    pub fn remove_channel(&mut self, channel_id: u32) {
+        self.channels.remove(&channel_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mining::SubmitSharesErrorCodes;
+
+    #[test]
+    fn track_sequence_numbers() {
+        let mut tracker = ShareTracker::new();
+        tracker.add_channel(1);
+        tracker.add_channel(2);
+
+        assert_eq!(tracker.next_sequence_number(1).unwrap(), 0);
+        assert_eq!(tracker.next_sequence_number(1).unwrap(), 1);
+        assert_eq!(tracker.next_sequence_number(2).unwrap(), 0);
+        assert!(tracker.next_sequence_number(3).is_err());
+
+        assert_eq!(tracker.stats(1).unwrap().pending, 2);
+        assert_eq!(tracker.stats(2).unwrap().pending, 1);
+    }
+
+    #[test]
+    fn match_batched_replies() {
+        let mut tracker = ShareTracker::new();
+        tracker.add_channel(1);
+        for _ in 0..5 {
+            tracker.next_sequence_number(1).unwrap();
+        }
+
+        tracker
+            .on_success(&SubmitSharesSuccess::new(1, 1, 2, 20))
+            .unwrap();
+        assert_eq!(tracker.stats(1).unwrap().pending, 3);
+
+        // The error is received after a success covering the share.
+        let error = SubmitSharesError::new(1, 3, SubmitSharesErrorCodes::DifficultyTooLow);
+        tracker
+            .on_success(&SubmitSharesSuccess::new(1, 3, 1, 10))
+            .unwrap();
+        tracker.on_error(&error).unwrap();
+
+        let error = SubmitSharesError::new(1, 4, SubmitSharesErrorCodes::DuplicateShare);
+        tracker.on_error(&error).unwrap();
+
+        let stats = tracker.stats(1).unwrap();
+        assert_eq!(
+            stats,
+            ShareStats {
+                accepted: 3,
+                accepted_shares_sum: 30,
+                rejected: 2,
+                pending: 0,
+            }
+        );
+
+        assert_eq!(tracker.remove_channel(1), Some(stats));
+        assert!(tracker
+            .on_success(&SubmitSharesSuccess::new(1, 0, 1, 1))
+            .is_err());
+    }
+
+    #[test]
+    fn sequence_numbers_wrap_around() {
+        let mut tracker = ShareTracker::new();
+        tracker.add_channel(1);
+        tracker.channels.get_mut(&1).unwrap().next_sequence_number = u32::MAX;
+
+        assert_eq!(tracker.next_sequence_number(1).unwrap(), u32::MAX);
+        assert_eq!(tracker.next_sequence_number(1).unwrap(), 0);
+        assert_eq!(tracker.next_sequence_number(1).unwrap(), 1);
+
+        tracker
+            .on_success(&SubmitSharesSuccess::new(1, 0, 2, 2))
+            .unwrap();
+        assert_eq!(tracker.stats(1).unwrap().pending, 1);
+    }
+
+    #[test]
+    fn aggregate_acks() {
+        let now = Instant::now();
+        let mut acks = ShareAckAggregator::new(Duration::from_secs(5));
+
+        acks.on_share_accepted(1, 0, 10, now);
+        acks.on_share_accepted(2, 0, 10, now + Duration::from_secs(2));
+        acks.on_share_accepted(1, 2, 10, now + Duration::from_secs(3));
+        // Shares may be validated out of order.
+        acks.on_share_accepted(1, 1, 10, now + Duration::from_secs(4));
+
+        let success = acks.poll(now + Duration::from_secs(5));
+        assert_eq!(success.len(), 1);
+        assert_eq!(success[0].channel_id, 1);
+        assert_eq!(success[0].last_sequence_number, 2);
+        assert_eq!(success[0].new_submits_accepted_count, 3);
+        assert_eq!(success[0].new_shares_sum, 30);
+
+        // The next batch of the channel starts a new interval.
+        acks.on_share_accepted(1, 3, 10, now + Duration::from_secs(6));
+        let success = acks.poll(now + Duration::from_secs(7));
+        assert_eq!(success.len(), 1);
+        assert_eq!(success[0].channel_id, 2);
+
+        assert!(acks.poll(now + Duration::from_secs(10)).is_empty());
+        assert_eq!(acks.poll(now + Duration::from_secs(11)).len(), 1);
+    }
+
+    #[test]
+    fn flush_acks() {
+        let now = Instant::now();
+        let mut acks = ShareAckAggregator::new(Duration::from_secs(5));
+
+        acks.on_share_accepted(1, 0, 10, now);
+        let success = acks.flush(1).unwrap();
+        assert_eq!(success.new_submits_accepted_count, 1);
+        assert!(acks.flush(1).is_none());
+
+        acks.on_share_accepted(1, 1, 10, now);
+        acks.remove_channel(1);
+        assert!(acks.poll(now + Duration::from_secs(5)).is_empty());
+    }
+}
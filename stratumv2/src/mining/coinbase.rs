@@ -0,0 +1,93 @@
+use crate::error::{Error, Result};
+use crate::types::U256;
+use crate::util::double_sha256;
+
+/// Rebuilds the coinbase transaction of an extended channel job.
+///
+/// The coinbase is the concatenation of the `coinbase_tx_prefix` of the job,
+/// the `extranonce_prefix` assigned to the channel, the `extranonce` searched
+/// by the Client and the `coinbase_tx_suffix` of the job.
+///
+/// A RequirementError is returned if the `extranonce` is not exactly
+/// `extranonce_size` bytes.
+///
+/// # Examples
+///
+/// ```rust
+/// use stratumv2::mining::build_coinbase;
+///
+/// let coinbase = build_coinbase(&[0x01], &[0x02], &[0x03, 0x04], 2, &[0x05]).unwrap();
+/// assert_eq!(coinbase, vec![0x01, 0x02, 0x03, 0x04, 0x05]);
+///
+/// assert!(build_coinbase(&[0x01], &[0x02], &[0x03], 2, &[0x05]).is_err());
+/// ```
+pub fn build_coinbase(
+    coinbase_tx_prefix: &[u8],
+    extranonce_prefix: &[u8],
+    extranonce: &[u8],
+    extranonce_size: u16,
+    coinbase_tx_suffix: &[u8],
+) -> Result<Vec<u8>> {
+    if extranonce.len() != extranonce_size as usize {
+        return Err(Error::RequirementError(
+            "extranonce length must be equal to the extranonce_size of the channel".into(),
+        ));
+    }
+
+    Ok(serialize_slices!(
+        coinbase_tx_prefix,
+        extranonce_prefix,
+        extranonce,
+        coinbase_tx_suffix
+    ))
+}
+
+/// Returns the txid of a serialized coinbase transaction. The coinbase is
+/// expected without the segwit marker, flag and witness data, as sent in a
+/// [NewExtendedMiningJob](struct.NewExtendedMiningJob.html).
+pub fn coinbase_txid(coinbase: &[u8]) -> U256 {
+    double_sha256(coinbase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::hex;
+
+    // The coinbase transaction of mainnet block 100000, split around the last
+    // two bytes pushed by its scriptSig.
+    const COINBASE_TX_PREFIX: &str = concat!(
+        "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff",
+        "08044c86041b02"
+    );
+    const COINBASE_TX_SUFFIX: &str = concat!(
+        "ffffffff0100f2052a010000004341041b0e8c2567c12536aa13357b79a073dc4444acb83c4ec7a0e2",
+        "f99dd7457516c5817242da796924ca4e99947d087fedf9ce467cb9f7c6287078f801df276fdf84ac",
+        "00000000"
+    );
+    const COINBASE_TXID: &str = "8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87";
+
+    #[test]
+    fn mainnet_coinbase_txid() {
+        let coinbase = build_coinbase(
+            &hex::decode(COINBASE_TX_PREFIX).unwrap(),
+            &[0x06],
+            &[0x02],
+            1,
+            &hex::decode(COINBASE_TX_SUFFIX).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(coinbase.len(), 135);
+
+        let mut expected = hex::decode(COINBASE_TXID).unwrap();
+        expected.reverse();
+        assert_eq!(coinbase_txid(&coinbase).to_vec(), expected);
+    }
+
+    #[test]
+    fn invalid_extranonce_size() {
+        assert!(build_coinbase(&[], &[], &[0x00, 0x01], 1, &[]).is_err());
+        assert!(build_coinbase(&[], &[], &[], 1, &[]).is_err());
+        assert!(build_coinbase(&[], &[], &[], 0, &[]).is_ok());
+    }
+}
@@ -0,0 +1,242 @@
+use crate::error::{Error, Result};
+use crate::mining::{
+    SubmitSharesError, SubmitSharesErrorCodes, SubmitSharesExtended, SubmitSharesStandard,
+};
+use std::collections::hash_map::RandomState;
+use std::collections::{HashSet, VecDeque};
+use std::hash::BuildHasher;
+use std::mem;
+
+/// DuplicateShareDetector detects shares submitted more than once, keyed on
+/// the channel id, job id, nonce, ntime, version and extranonce of the share.
+///
+/// The memory used by the detector is bounded by its capacity. Each share is
+/// recorded as a 64 bit hash, keyed with random state so a Client cannot
+/// craft colliding shares, and the oldest shares are evicted once the
+/// capacity is reached. The detector SHOULD be reset on every
+/// [SetNewPrevHash](struct.SetNewPrevHash.html), since shares on the previous
+/// prev hash are rejected as stale.
+///
+/// Shares SHOULD only be checked once they have been validated, so invalid
+/// shares do not evict valid ones.
+///
+/// # Examples
+///
+/// ```rust
+/// use stratumv2::mining::{DuplicateShareDetector, SubmitSharesErrorCodes, SubmitSharesStandard};
+///
+/// let mut detector = DuplicateShareDetector::new(1024).unwrap();
+///
+/// let share = SubmitSharesStandard::new(1, 0, 1, 0xdead_beef, 0, 0x2000_0000);
+/// assert!(detector.check_standard(&share).is_none());
+///
+/// let resubmitted = SubmitSharesStandard::new(1, 1, 1, 0xdead_beef, 0, 0x2000_0000);
+/// let error = detector.check_standard(&resubmitted).unwrap();
+/// assert_eq!(error.sequence_number, 1);
+/// assert_eq!(error.error_code, SubmitSharesErrorCodes::DuplicateShare);
+///
+/// // Shares are forgotten on a new prev hash.
+/// detector.reset();
+/// assert!(detector.check_standard(&resubmitted).is_none());
+/// ```
+#[derive(Debug)]
+pub struct DuplicateShareDetector {
+    capacity: usize,
+    hasher: RandomState,
+    seen: HashSet<u64>,
+    order: VecDeque<u64>,
+}
+
+impl DuplicateShareDetector {
+    /// Creates a detector remembering up to `capacity` shares. The memory for
+    /// every share is allocated upfront.
+    ///
+    /// A RequirementError is returned if the capacity is zero.
+    pub fn new(capacity: usize) -> Result<DuplicateShareDetector> {
+        if capacity == 0 {
+            return Err(Error::RequirementError(
+                "the duplicate share detector capacity must be greater than 0".into(),
+            ));
+        }
+
+        Ok(DuplicateShareDetector {
+            capacity,
+            hasher: RandomState::new(),
+            seen: HashSet::with_capacity(capacity),
+            order: VecDeque::with_capacity(capacity),
+        })
+    }
+
+    /// Returns the number of shares remembered by the detector, at most its
+    /// capacity.
+    pub fn remembered(&self) -> usize {
+        self.order.len()
+    }
+
+    /// Returns an estimate in bytes of the heap memory used by the detector.
+    pub fn memory_usage(&self) -> usize {
+        // Every slot of the set has a one byte control tag.
+        self.seen.capacity() * (mem::size_of::<u64>() + 1)
+            + self.order.capacity() * mem::size_of::<u64>()
+    }
+
+    /// Checks a share submitted on a standard channel, recording it if it was
+    /// not seen before. Returns the SubmitSharesError to send if the share is
+    /// a duplicate.
+    pub fn check_standard(&mut self, share: &SubmitSharesStandard) -> Option<SubmitSharesError> {
+        let key = self.key(
+            share.channel_id,
+            share.job_id,
+            share.nonce,
+            share.ntime,
+            share.version,
+            &[],
+        );
+
+        self.check(key, share.channel_id, share.sequence_number)
+    }
+
+    /// Checks a share submitted on an extended channel, recording it if it was
+    /// not seen before. Returns the SubmitSharesError to send if the share is
+    /// a duplicate.
+    pub fn check_extended(&mut self, share: &SubmitSharesExtended) -> Option<SubmitSharesError> {
+        let key = self.key(
+            share.channel_id,
+            share.job_id,
+            share.nonce,
+            share.ntime,
+            share.version,
+            &share.extranonce.0,
+        );
+
+        self.check(key, share.channel_id, share.sequence_number)
+    }
+
+    /// Forgets every recorded share, keeping the allocated memory.
+    pub fn reset(&mut self) {
+        self.seen.clear();
+        self.order.clear();
+    }
+
+    fn key(
+        &self,
+        channel_id: u32,
+        job_id: u32,
+        nonce: u32,
+        ntime: u32,
+        version: u32,
+        extranonce: &[u8],
+    ) -> u64 {
+        self.hasher
+            .hash_one((channel_id, job_id, nonce, ntime, version, extranonce))
+    }
+
+    fn check(
+        &mut self,
+        key: u64,
+        channel_id: u32,
+        sequence_number: u32,
+    ) -> Option<SubmitSharesError> {
+        if self.seen.contains(&key) {
+            return Some(SubmitSharesError::new(
+                channel_id,
+                sequence_number,
+                SubmitSharesErrorCodes::DuplicateShare,
+            ));
+        }
+
+        if self.order.len() == self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+
+        self.seen.insert(key);
+        self.order.push_back(key);
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_duplicate_shares() {
+        let mut detector = DuplicateShareDetector::new(16).unwrap();
+
+        assert!(detector
+            .check_standard(&SubmitSharesStandard::new(1, 0, 1, 1, 1, 1))
+            .is_none());
+
+        // Each field of the key makes a distinct share.
+        let distinct = [
+            SubmitSharesStandard::new(2, 1, 1, 1, 1, 1),
+            SubmitSharesStandard::new(1, 2, 2, 1, 1, 1),
+            SubmitSharesStandard::new(1, 3, 1, 2, 1, 1),
+            SubmitSharesStandard::new(1, 4, 1, 1, 2, 1),
+            SubmitSharesStandard::new(1, 5, 1, 1, 1, 2),
+        ];
+        for share in distinct.iter() {
+            assert!(detector.check_standard(share).is_none());
+        }
+
+        // The sequence number is not part of the key.
+        let error = detector
+            .check_standard(&SubmitSharesStandard::new(1, 6, 1, 1, 1, 1))
+            .unwrap();
+        assert_eq!(error.channel_id, 1);
+        assert_eq!(error.sequence_number, 6);
+        assert_eq!(error.error_code, SubmitSharesErrorCodes::DuplicateShare);
+        assert_eq!(detector.remembered(), 6);
+    }
+
+    #[test]
+    fn detect_duplicate_extended_shares() {
+        let mut detector = DuplicateShareDetector::new(16).unwrap();
+
+        let share = SubmitSharesExtended::new(1, 0, 1, 1, 1, 1, vec![0x01, 0x02]).unwrap();
+        assert!(detector.check_extended(&share).is_none());
+        assert!(detector.check_extended(&share).is_some());
+
+        let share = SubmitSharesExtended::new(1, 1, 1, 1, 1, 1, vec![0x01, 0x03]).unwrap();
+        assert!(detector.check_extended(&share).is_none());
+    }
+
+    #[test]
+    fn evict_oldest_shares() {
+        let mut detector = DuplicateShareDetector::new(2).unwrap();
+        let memory_usage = detector.memory_usage();
+
+        for nonce in 0..3 {
+            let share = SubmitSharesStandard::new(1, nonce, 1, nonce, 1, 1);
+            assert!(detector.check_standard(&share).is_none());
+        }
+        assert_eq!(detector.remembered(), 2);
+        assert_eq!(detector.memory_usage(), memory_usage);
+
+        // The first share was evicted, the last one is remembered.
+        assert!(detector
+            .check_standard(&SubmitSharesStandard::new(1, 3, 1, 2, 1, 1))
+            .is_some());
+        assert!(detector
+            .check_standard(&SubmitSharesStandard::new(1, 4, 1, 0, 1, 1))
+            .is_none());
+    }
+
+    #[test]
+    fn reset_detector() {
+        let mut detector = DuplicateShareDetector::new(2).unwrap();
+        let share = SubmitSharesStandard::new(1, 0, 1, 1, 1, 1);
+
+        assert!(detector.check_standard(&share).is_none());
+        detector.reset();
+        assert_eq!(detector.remembered(), 0);
+        assert!(detector.check_standard(&share).is_none());
+    }
+
+    #[test]
+    fn zero_capacity() {
+        assert!(DuplicateShareDetector::new(0).is_err());
+    }
+}
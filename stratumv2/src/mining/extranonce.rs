@@ -0,0 +1,339 @@
+use crate::error::{Error, Result};
+use std::collections::{BTreeSet, HashSet};
+
+/// The maximum size of an extranonce_prefix, serialized as a
+/// [B0_32](../types/struct.B0_32.html).
+const MAX_EXTRANONCE_SIZE: usize = 32;
+
+/// The maximum number of bytes used to number prefixes in an allocator.
+const MAX_PREFIX_SIZE: usize = 8;
+
+/// ExtranonceAllocator hands out non-overlapping extranonce_prefixes to the
+/// channels of a Server.
+///
+/// The extranonce space of the coinbase transaction is split into three parts:
+/// - the `upstream_prefix` assigned to this node by its Upstream, empty for a
+///   pool.
+/// - a `prefix_size` bytes number, unique for each allocated prefix.
+/// - the remaining bytes searched by the Client of the channel, the
+///   `extranonce_size` sent in an
+///   [OpenExtendedMiningChannelSuccess](struct.OpenExtendedMiningChannelSuccess.html).
+///
+/// A proxy that opened an extended channel upstream can re-split the space it
+/// was assigned using [with_upstream_prefix](struct.ExtranonceAllocator.html#method.with_upstream_prefix)
+/// or [sub_allocator](struct.ExtranonceAllocator.html#method.sub_allocator).
+///
+/// # Examples
+///
+/// ```rust
+/// use stratumv2::mining::ExtranonceAllocator;
+///
+/// // 12 bytes of extranonce space, 4 bytes are used to number the prefixes.
+/// let mut allocator = ExtranonceAllocator::new(12, 4).unwrap();
+/// assert_eq!(allocator.extranonce_size(), 8);
+///
+/// let first = allocator.allocate(8).unwrap();
+/// let second = allocator.allocate(4).unwrap();
+/// assert_eq!(first, vec![0x00, 0x00, 0x00, 0x00]);
+/// assert_eq!(second, vec![0x00, 0x00, 0x00, 0x01]);
+///
+/// // Clients requesting more space than available are rejected.
+/// assert!(allocator.allocate(9).is_err());
+///
+/// // Standard channels do not search the extranonce, their prefix covers the
+/// // whole extranonce space.
+/// let standard = allocator.allocate_standard().unwrap();
+/// assert_eq!(standard.len(), 12);
+///
+/// assert!(allocator.release(&first));
+/// assert_eq!(allocator.allocate(8).unwrap(), first);
+/// ```
+#[derive(Debug)]
+pub struct ExtranonceAllocator {
+    upstream_prefix: Vec<u8>,
+    total_size: usize,
+    prefix_size: usize,
+    next: u64,
+    released: BTreeSet<u64>,
+    allocated: HashSet<u64>,
+}
+
+impl ExtranonceAllocator {
+    /// Creates an allocator splitting `total_size` bytes of extranonce space,
+    /// using `prefix_size` bytes to number the allocated prefixes.
+    ///
+    /// A RequirementError is returned if `prefix_size` is 0, larger than 8
+    /// bytes or larger than `total_size`, or if `total_size` is larger than
+    /// 32 bytes.
+    pub fn new(total_size: u16, prefix_size: u16) -> Result<ExtranonceAllocator> {
+        ExtranonceAllocator::with_upstream_prefix(vec![], total_size, prefix_size)
+    }
+
+    /// Creates an allocator for a proxy, splitting the `extranonce_size`
+    /// bytes of extranonce space assigned by the Upstream after its
+    /// `upstream_prefix`. Every allocated prefix starts with the
+    /// `upstream_prefix`.
+    pub fn with_upstream_prefix(
+        upstream_prefix: Vec<u8>,
+        extranonce_size: u16,
+        prefix_size: u16,
+    ) -> Result<ExtranonceAllocator> {
+        let total_size = extranonce_size as usize;
+        let prefix_size = prefix_size as usize;
+
+        if prefix_size == 0 || prefix_size > MAX_PREFIX_SIZE {
+            return Err(Error::RequirementError(
+                "prefix_size must be between 1 and 8 bytes".into(),
+            ));
+        }
+
+        if prefix_size > total_size {
+            return Err(Error::RequirementError(
+                "prefix_size cannot be greater than the extranonce size".into(),
+            ));
+        }
+
+        if upstream_prefix.len() + total_size > MAX_EXTRANONCE_SIZE {
+            return Err(Error::RequirementError(
+                "the extranonce space cannot be greater than 32 bytes".into(),
+            ));
+        }
+
+        Ok(ExtranonceAllocator {
+            upstream_prefix,
+            total_size,
+            prefix_size,
+            next: 0,
+            released: BTreeSet::new(),
+            allocated: HashSet::new(),
+        })
+    }
+
+    /// The prefix assigned to this allocator by its Upstream.
+    pub fn upstream_prefix(&self) -> &[u8] {
+        &self.upstream_prefix
+    }
+
+    /// The extranonce space left to the Client of an extended channel after
+    /// its allocated prefix.
+    pub fn extranonce_size(&self) -> u16 {
+        (self.total_size - self.prefix_size) as u16
+    }
+
+    /// The number of prefixes currently allocated.
+    pub fn allocated(&self) -> usize {
+        self.allocated.len()
+    }
+
+    /// Allocates a prefix for an extended channel requesting at least
+    /// `min_extranonce_size` bytes of extranonce space.
+    ///
+    /// A RequirementError is returned if the requested space is greater than
+    /// the [extranonce_size](struct.ExtranonceAllocator.html#method.extranonce_size)
+    /// or if every prefix is allocated.
+    pub fn allocate(&mut self, min_extranonce_size: u16) -> Result<Vec<u8>> {
+        if min_extranonce_size > self.extranonce_size() {
+            return Err(Error::RequirementError(
+                "min_extranonce_size is greater than the available extranonce space".into(),
+            ));
+        }
+
+        let id = self.next_id()?;
+        Ok(self.prefix(id))
+    }
+
+    /// Allocates a prefix for a standard channel. The Client of a standard
+    /// channel does not search the extranonce, so the prefix is padded with
+    /// zeros to cover the whole extranonce space.
+    pub fn allocate_standard(&mut self) -> Result<Vec<u8>> {
+        let id = self.next_id()?;
+
+        let mut prefix = self.prefix(id);
+        prefix.resize(self.upstream_prefix.len() + self.total_size, 0);
+
+        Ok(prefix)
+    }
+
+    /// Reclaims a prefix, e.g. when its channel is closed. Returns false if
+    /// the prefix was not allocated by this allocator.
+    pub fn release(&mut self, prefix: &[u8]) -> bool {
+        let id = match self.id(prefix) {
+            Some(id) => id,
+            None => return false,
+        };
+
+        if !self.allocated.remove(&id) {
+            return false;
+        }
+
+        self.released.insert(id);
+        true
+    }
+
+    /// Creates an allocator re-splitting the extranonce space of an allocated
+    /// `prefix`, e.g. for a proxy assigning prefixes to its downstream
+    /// channels. The prefixes of the returned allocator start with `prefix`
+    /// and never overlap with the prefixes of this allocator.
+    ///
+    /// A RequirementError is returned if the prefix was not allocated by this
+    /// allocator or if the remaining space cannot fit `prefix_size`.
+    pub fn sub_allocator(&self, prefix: &[u8], prefix_size: u16) -> Result<ExtranonceAllocator> {
+        let allocated = matches!(self.id(prefix), Some(id) if self.allocated.contains(&id));
+
+        if !allocated || prefix.len() != self.upstream_prefix.len() + self.prefix_size {
+            return Err(Error::RequirementError(
+                "cannot sub allocate a prefix that was not allocated by the allocator".into(),
+            ));
+        }
+
+        ExtranonceAllocator::with_upstream_prefix(
+            prefix.to_vec(),
+            self.extranonce_size(),
+            prefix_size,
+        )
+    }
+
+    /// Returns the lowest released id, or the next unused id.
+    fn next_id(&mut self) -> Result<u64> {
+        let id = match self.released.iter().next().copied() {
+            Some(id) => {
+                self.released.remove(&id);
+                id
+            }
+            None => {
+                if self.next as u128 >= 1u128 << (8 * self.prefix_size) {
+                    return Err(Error::RequirementError(
+                        "every extranonce prefix is allocated".into(),
+                    ));
+                }
+
+                self.next += 1;
+                self.next - 1
+            }
+        };
+
+        self.allocated.insert(id);
+        Ok(id)
+    }
+
+    /// Serializes an id after the upstream prefix, in big endian so that
+    /// prefixes are allocated in lexicographic order.
+    fn prefix(&self, id: u64) -> Vec<u8> {
+        let bytes = id.to_be_bytes();
+        serialize_slices!(
+            &self.upstream_prefix,
+            &bytes[MAX_PREFIX_SIZE - self.prefix_size..]
+        )
+    }
+
+    /// Parses the id of a prefix allocated by this allocator.
+    fn id(&self, prefix: &[u8]) -> Option<u64> {
+        if !prefix.starts_with(&self.upstream_prefix) {
+            return None;
+        }
+
+        let start = self.upstream_prefix.len();
+        let bytes = prefix.get(start..start + self.prefix_size)?;
+
+        let mut id = [0u8; MAX_PREFIX_SIZE];
+        id[MAX_PREFIX_SIZE - self.prefix_size..].copy_from_slice(bytes);
+        Some(u64::from_be_bytes(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_sizes() {
+        assert!(ExtranonceAllocator::new(8, 0).is_err());
+        assert!(ExtranonceAllocator::new(16, 9).is_err());
+        assert!(ExtranonceAllocator::new(2, 4).is_err());
+        assert!(ExtranonceAllocator::new(33, 4).is_err());
+        assert!(ExtranonceAllocator::with_upstream_prefix(vec![0; 4], 29, 4).is_err());
+
+        assert!(ExtranonceAllocator::new(4, 4).is_ok());
+        assert!(ExtranonceAllocator::new(32, 8).is_ok());
+    }
+
+    #[test]
+    fn allocate_unique_prefixes() {
+        let mut allocator = ExtranonceAllocator::new(3, 1).unwrap();
+
+        let mut prefixes = HashSet::new();
+        for _ in 0..256 {
+            assert!(prefixes.insert(allocator.allocate(2).unwrap()));
+        }
+
+        assert_eq!(allocator.allocated(), 256);
+        assert!(allocator.allocate(0).is_err());
+        assert!(allocator.allocate_standard().is_err());
+    }
+
+    #[test]
+    fn honor_min_extranonce_size() {
+        let mut allocator = ExtranonceAllocator::new(8, 2).unwrap();
+        assert_eq!(allocator.extranonce_size(), 6);
+
+        assert!(allocator.allocate(7).is_err());
+        assert_eq!(allocator.allocated(), 0);
+
+        assert_eq!(allocator.allocate(6).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn allocate_standard_prefix() {
+        let mut allocator = ExtranonceAllocator::with_upstream_prefix(vec![0xaa], 4, 2).unwrap();
+
+        assert_eq!(allocator.allocate(2).unwrap(), vec![0xaa, 0x00, 0x00]);
+        assert_eq!(
+            allocator.allocate_standard().unwrap(),
+            vec![0xaa, 0x00, 0x01, 0x00, 0x00]
+        );
+
+        assert!(allocator.release(&[0xaa, 0x00, 0x01, 0x00, 0x00]));
+        assert_eq!(allocator.allocate(0).unwrap(), vec![0xaa, 0x00, 0x01]);
+    }
+
+    #[test]
+    fn release_prefix() {
+        let mut allocator = ExtranonceAllocator::new(8, 1).unwrap();
+        let first = allocator.allocate(0).unwrap();
+        let second = allocator.allocate(0).unwrap();
+
+        assert!(allocator.release(&first));
+        assert!(!allocator.release(&first));
+        assert!(!allocator.release(&[0x05]));
+        assert!(!allocator.release(&[]));
+        assert_eq!(allocator.allocated(), 1);
+
+        // Released prefixes are reused before new prefixes.
+        assert_eq!(allocator.allocate(0).unwrap(), first);
+        assert_eq!(allocator.allocate(0).unwrap(), vec![0x02]);
+        assert!(allocator.release(&second));
+    }
+
+    #[test]
+    fn hierarchical_allocation() {
+        let mut pool = ExtranonceAllocator::new(16, 4).unwrap();
+        let proxy_prefix = pool.allocate(8).unwrap();
+        let other_prefix = pool.allocate(8).unwrap();
+
+        let mut proxy = pool.sub_allocator(&proxy_prefix, 2).unwrap();
+        assert_eq!(proxy.upstream_prefix(), &proxy_prefix[..]);
+        assert_eq!(proxy.extranonce_size(), 10);
+
+        let downstream = proxy.allocate(10).unwrap();
+        assert_eq!(downstream.len(), 6);
+        assert!(downstream.starts_with(&proxy_prefix));
+        assert!(!downstream.starts_with(&other_prefix));
+
+        // A standard channel of the proxy covers the extranonce space of the
+        // pool.
+        assert_eq!(proxy.allocate_standard().unwrap().len(), 16);
+
+        assert!(pool.sub_allocator(&[0xff; 4], 2).is_err());
+        assert!(pool.sub_allocator(&proxy_prefix, 13).is_err());
+    }
+}
@@ -2,7 +2,8 @@ use crate::BitFlag;
 
 /// Feature flags that can be passed to a SetupConnection message in the Mining
 /// Protocol. Each flag corresponds to a set bit.
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SetupConnectionFlags {
     /// Flag indicating the Client requires Standard Jobs. The Client doesn't
     /// undestand group channels and extended jobs.
@@ -26,7 +27,8 @@ impl_message_flag!(
 
 /// Feature flags for the SetupConnectionSuccess message from the Server to
 /// the Client for the Mining Protocol.
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SetupConnectionSuccessFlags {
     // TODO: Link everthing between ``
     /// Flag indicating the upstream node (Server) does not accept any changes
@@ -48,15 +50,14 @@ impl_message_flag!(
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::types::FlagSet;
 
     #[test]
     fn u32_deserialize_flags() {
-        let set_flags = 7;
-        let flags = SetupConnectionFlags::deserialize_flags(set_flags);
-
-        assert_eq!(flags.len(), 3);
+        let flags = FlagSet::<SetupConnectionFlags>::from_bits(7);
+        assert_eq!(flags.iter().count(), 3);
         assert_eq!(
-            flags,
+            flags.iter().collect::<Vec<_>>(),
             &[
                 SetupConnectionFlags::RequiresStandardJobs,
                 SetupConnectionFlags::RequiresWorkSelection,
@@ -64,23 +65,23 @@ mod test {
             ]
         );
 
-        let set_flags = 3;
-        let flags = SetupConnectionFlags::deserialize_flags(set_flags);
+        let flags = FlagSet::<SetupConnectionFlags>::from_bits(3);
         assert_eq!(
-            flags,
+            flags.iter().collect::<Vec<_>>(),
             &[
                 SetupConnectionFlags::RequiresStandardJobs,
                 SetupConnectionFlags::RequiresWorkSelection,
             ]
         );
 
-        let set_flags = 2;
-        let flags = SetupConnectionFlags::deserialize_flags(set_flags);
-        assert_eq!(flags.len(), 1);
-        assert_eq!(flags[0], SetupConnectionFlags::RequiresWorkSelection);
+        let flags = FlagSet::<SetupConnectionFlags>::from_bits(2);
+        assert_eq!(flags.iter().count(), 1);
+        assert!(flags.contains(SetupConnectionFlags::RequiresWorkSelection));
 
-        let set_flags = 8;
-        let flags = SetupConnectionFlags::deserialize_flags(set_flags);
-        assert_eq!(flags.len(), 0);
+        // Unknown bits are not dropped.
+        let flags = FlagSet::<SetupConnectionFlags>::from_bits(8);
+        assert_eq!(flags.iter().count(), 0);
+        assert_eq!(flags.bits(), 8);
+        assert_eq!(flags.unknown_bits(), 8);
     }
 }
@@ -0,0 +1,319 @@
+use crate::error::{Error, Result};
+use crate::mining::{NewExtendedMiningJob, NewMiningJob, SetNewPrevHash};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// A job sent on a standard or extended channel.
+#[derive(Debug, Clone)]
+pub enum Job {
+    Standard(NewMiningJob),
+    Extended(NewExtendedMiningJob),
+}
+
+impl Job {
+    /// Returns the channel the job was sent on.
+    pub fn channel_id(&self) -> u32 {
+        match self {
+            Job::Standard(job) => job.channel_id,
+            Job::Extended(job) => job.channel_id,
+        }
+    }
+
+    /// Returns the id of the job.
+    pub fn job_id(&self) -> u32 {
+        match self {
+            Job::Standard(job) => job.job_id,
+            Job::Extended(job) => job.job_id,
+        }
+    }
+
+    /// Returns true if the job was sent for a future SetNewPrevHash.
+    pub fn future_job(&self) -> bool {
+        match self {
+            Job::Standard(job) => job.future_job,
+            Job::Extended(job) => job.future_job,
+        }
+    }
+}
+
+impl From<NewMiningJob> for Job {
+    fn from(job: NewMiningJob) -> Self {
+        Job::Standard(job)
+    }
+}
+
+impl From<NewExtendedMiningJob> for Job {
+    fn from(job: NewExtendedMiningJob) -> Self {
+        Job::Extended(job)
+    }
+}
+
+/// The state of a job relative to the last
+/// [SetNewPrevHash](struct.SetNewPrevHash.html).
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum JobState {
+    /// The job was sent for a future SetNewPrevHash and cannot be mined yet.
+    Future,
+
+    /// The job is built on the current prev hash, shares can be submitted.
+    Active,
+
+    /// The job was replaced by a SetNewPrevHash, shares are stale.
+    Stale,
+}
+
+/// The result of a [JobStore](struct.JobStore.html) lookup, containing the
+/// data required to validate a share.
+#[derive(Debug)]
+pub struct JobLookup<'a> {
+    /// The job as it was sent.
+    pub job: &'a Job,
+
+    /// The state of the job relative to the current prev hash.
+    pub state: JobState,
+
+    /// The current prev hash. Only an `Active` job is built on it.
+    pub prev_hash: Option<&'a SetNewPrevHash>,
+}
+
+#[derive(Debug)]
+struct StoredJob {
+    job: Job,
+    state: JobState,
+    stale_since: Option<Instant>,
+}
+
+/// JobStore records the jobs sent on a channel or group channel and tracks
+/// their lifecycle:
+/// - jobs sent with `future_job` set are `Future` until a
+///   [SetNewPrevHash](struct.SetNewPrevHash.html) references them.
+/// - jobs sent for the current prev hash, or activated by it, are `Active`.
+/// - every other job becomes `Stale` on a SetNewPrevHash and is removed by
+///   [expire](#method.expire) once `stale_window` elapsed, after which shares
+///   for the job are reported as unknown instead of stale.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::{Duration, Instant};
+/// use stratumv2::mining::{JobState, JobStore, NewMiningJob, SetNewPrevHash};
+///
+/// let now = Instant::now();
+/// let mut jobs = JobStore::new(Duration::from_secs(30));
+///
+/// jobs.add_job(NewMiningJob::new(1, 1, true, 0x2000_0000, [0u8; 32]), now).unwrap();
+/// assert_eq!(jobs.lookup(1).unwrap().state, JobState::Future);
+///
+/// jobs.set_new_prev_hash(SetNewPrevHash::new(1, 1, [0u8; 32], 0, 0x1d00ffff), now).unwrap();
+/// assert_eq!(jobs.lookup(1).unwrap().state, JobState::Active);
+/// assert!(jobs.lookup(1).unwrap().prev_hash.is_some());
+/// ```
+#[derive(Debug)]
+pub struct JobStore {
+    stale_window: Duration,
+    jobs: HashMap<u32, StoredJob>,
+    prev_hash: Option<SetNewPrevHash>,
+}
+
+impl JobStore {
+    /// Creates an empty JobStore, keeping stale jobs for `stale_window`.
+    pub fn new(stale_window: Duration) -> JobStore {
+        JobStore {
+            stale_window,
+            jobs: HashMap::new(),
+            prev_hash: None,
+        }
+    }
+
+    /// Returns the last SetNewPrevHash.
+    pub fn prev_hash(&self) -> Option<&SetNewPrevHash> {
+        self.prev_hash.as_ref()
+    }
+
+    /// Returns the number of jobs in the store, including stale jobs.
+    pub fn len(&self) -> usize {
+        self.jobs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    /// Records a sent job. A job with `future_job` set is `Future`, any other
+    /// job is `Active` on the current prev hash.
+    ///
+    /// A RequirementError is returned if the job id was already used, or if
+    /// a job that is not a future job is added before any SetNewPrevHash.
+    pub fn add_job<T: Into<Job>>(&mut self, job: T, now: Instant) -> Result<()> {
+        self.expire(now);
+
+        let job = job.into();
+        if self.jobs.contains_key(&job.job_id()) {
+            return Err(Error::RequirementError(
+                "the job id is already used by another job".into(),
+            ));
+        }
+
+        let state = if job.future_job() {
+            JobState::Future
+        } else if self.prev_hash.is_some() {
+            JobState::Active
+        } else {
+            return Err(Error::RequirementError(
+                "a job cannot be active before a SetNewPrevHash".into(),
+            ));
+        };
+
+        self.jobs.insert(
+            job.job_id(),
+            StoredJob {
+                job,
+                state,
+                stale_since: None,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Applies a SetNewPrevHash, activating the future job it references.
+    /// Every other job becomes stale.
+    ///
+    /// A RequirementError is returned if the referenced job is not a known
+    /// future job.
+    pub fn set_new_prev_hash(&mut self, prev_hash: SetNewPrevHash, now: Instant) -> Result<()> {
+        self.expire(now);
+
+        match self.jobs.get(&prev_hash.job_id) {
+            Some(stored) if stored.state == JobState::Future => (),
+            _ => {
+                return Err(Error::RequirementError(
+                    "SetNewPrevHash must reference a future job".into(),
+                ))
+            }
+        }
+
+        for (job_id, stored) in self.jobs.iter_mut() {
+            if *job_id == prev_hash.job_id {
+                stored.state = JobState::Active;
+            } else if stored.state != JobState::Stale {
+                stored.state = JobState::Stale;
+                stored.stale_since = Some(now);
+            }
+        }
+
+        self.prev_hash = Some(prev_hash);
+        Ok(())
+    }
+
+    /// Looks up a job by its id. Returns None if the job is unknown or was
+    /// removed by [expire](#method.expire). Stale jobs that have not been
+    /// expired yet are returned as `Stale`.
+    pub fn lookup(&self, job_id: u32) -> Option<JobLookup<'_>> {
+        self.jobs.get(&job_id).map(|stored| JobLookup {
+            job: &stored.job,
+            state: stored.state,
+            prev_hash: self.prev_hash.as_ref(),
+        })
+    }
+
+    /// Removes the jobs that were stale for longer than the stale window.
+    pub fn expire(&mut self, now: Instant) {
+        let stale_window = self.stale_window;
+        self.jobs.retain(|_, stored| match stored.stale_since {
+            Some(stale_since) => now.saturating_duration_since(stale_since) < stale_window,
+            None => true,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn standard_job(job_id: u32, future_job: bool) -> NewMiningJob {
+        NewMiningJob::new(1, job_id, future_job, 0x2000_0000, [0u8; 32])
+    }
+
+    fn prev_hash(job_id: u32) -> SetNewPrevHash {
+        SetNewPrevHash::new(1, job_id, [job_id as u8; 32], 0, 0x1d00ffff)
+    }
+
+    #[test]
+    fn job_lifecycle() {
+        let now = Instant::now();
+        let mut jobs = JobStore::new(Duration::from_secs(30));
+
+        jobs.add_job(standard_job(1, true), now).unwrap();
+        jobs.set_new_prev_hash(prev_hash(1), now).unwrap();
+        jobs.add_job(standard_job(2, false), now).unwrap();
+        jobs.add_job(standard_job(3, true), now).unwrap();
+
+        assert_eq!(jobs.lookup(1).unwrap().state, JobState::Active);
+        assert_eq!(jobs.lookup(2).unwrap().state, JobState::Active);
+        assert_eq!(jobs.lookup(3).unwrap().state, JobState::Future);
+        assert!(jobs.lookup(4).is_none());
+
+        jobs.set_new_prev_hash(prev_hash(3), now).unwrap();
+        assert_eq!(jobs.lookup(1).unwrap().state, JobState::Stale);
+        assert_eq!(jobs.lookup(2).unwrap().state, JobState::Stale);
+
+        let lookup = jobs.lookup(3).unwrap();
+        assert_eq!(lookup.state, JobState::Active);
+        assert_eq!(lookup.job.job_id(), 3);
+        assert_eq!(lookup.prev_hash.unwrap().prev_hash, [3; 32]);
+    }
+
+    #[test]
+    fn extended_jobs() {
+        let now = Instant::now();
+        let mut jobs = JobStore::new(Duration::from_secs(30));
+
+        let job = NewExtendedMiningJob::new(1, 1, true, 1, true, vec![], vec![], vec![]).unwrap();
+        jobs.add_job(job, now).unwrap();
+        jobs.set_new_prev_hash(prev_hash(1), now).unwrap();
+
+        assert!(matches!(jobs.lookup(1).unwrap().job, Job::Extended(_)));
+    }
+
+    #[test]
+    fn expire_stale_jobs() {
+        let now = Instant::now();
+        let mut jobs = JobStore::new(Duration::from_secs(30));
+
+        jobs.add_job(standard_job(1, true), now).unwrap();
+        jobs.add_job(standard_job(2, true), now).unwrap();
+        jobs.set_new_prev_hash(prev_hash(1), now).unwrap();
+
+        // The unused future job is stale.
+        assert_eq!(jobs.lookup(2).unwrap().state, JobState::Stale);
+
+        jobs.expire(now + Duration::from_secs(29));
+        assert_eq!(jobs.len(), 2);
+
+        jobs.expire(now + Duration::from_secs(30));
+        assert_eq!(jobs.len(), 1);
+        assert!(jobs.lookup(2).is_none());
+
+        // Active jobs are never expired.
+        jobs.expire(now + Duration::from_secs(3600));
+        assert_eq!(jobs.lookup(1).unwrap().state, JobState::Active);
+    }
+
+    #[test]
+    fn invalid_jobs() {
+        let now = Instant::now();
+        let mut jobs = JobStore::new(Duration::from_secs(30));
+
+        // A job for the current prev hash before any prev hash.
+        assert!(jobs.add_job(standard_job(1, false), now).is_err());
+
+        jobs.add_job(standard_job(1, true), now).unwrap();
+        assert!(jobs.add_job(standard_job(1, true), now).is_err());
+
+        // SetNewPrevHash must reference a future job.
+        assert!(jobs.set_new_prev_hash(prev_hash(2), now).is_err());
+        jobs.set_new_prev_hash(prev_hash(1), now).unwrap();
+        assert!(jobs.set_new_prev_hash(prev_hash(1), now).is_err());
+    }
+}
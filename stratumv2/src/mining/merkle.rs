@@ -0,0 +1,117 @@
+use crate::types::U256;
+use crate::util::double_sha256;
+
+/// Computes the merkle root of a block by folding the txid of the coinbase
+/// transaction through its merkle path. Each step hashes the current node
+/// with the next hash of the path, the coinbase always being the left-most
+/// leaf of the tree.
+///
+/// # Examples
+///
+/// ```rust
+/// use stratumv2::mining::merkle_root;
+///
+/// // A block with only a coinbase transaction has the txid as merkle root.
+/// assert_eq!(merkle_root(&[0xaa; 32], &[]), [0xaa; 32]);
+/// ```
+pub fn merkle_root(coinbase_txid: &U256, merkle_path: &[U256]) -> U256 {
+    merkle_path.iter().fold(*coinbase_txid, |node, hash| {
+        double_sha256(&serialize_slices!(&node, hash))
+    })
+}
+
+/// Computes the merkle path of the coinbase transaction given the txids of
+/// all the transactions of a block, excluding the coinbase. The returned path
+/// can be sent in a [NewExtendedMiningJob](struct.NewExtendedMiningJob.html).
+///
+/// As in Bitcoin, the last node of a level with an odd number of nodes is
+/// paired with itself.
+pub fn merkle_path(txids: &[U256]) -> Vec<U256> {
+    let mut path = Vec::new();
+
+    // The first slot stands for the node on the path of the coinbase, its
+    // value is never used to compute the path.
+    let mut level = Vec::with_capacity(txids.len() + 1);
+    level.push([0u8; 32]);
+    level.extend_from_slice(txids);
+
+    while level.len() > 1 {
+        path.push(level[1]);
+
+        if level.len() % 2 == 1 {
+            level.push(level[level.len() - 1]);
+        }
+
+        level = level
+            .chunks(2)
+            .enumerate()
+            .map(|(i, pair)| {
+                if i == 0 {
+                    [0u8; 32]
+                } else {
+                    double_sha256(&serialize_slices!(&pair[0], &pair[1]))
+                }
+            })
+            .collect();
+    }
+
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::hex;
+    use std::convert::TryInto;
+
+    fn reversed(hex: &str) -> U256 {
+        let mut bytes = hex::decode(hex).unwrap();
+        bytes.reverse();
+        bytes.try_into().unwrap()
+    }
+
+    // The transactions of mainnet block 100000.
+    const COINBASE_TXID: &str = "8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87";
+    const TXIDS: [&str; 3] = [
+        "fff2525b8931402dd09222c50775608f75787bd2b87e56995a7bdd30f79702c4",
+        "6359f0868171b1d194cbee1af2f16ea598ae8fad666d9b012c8ed2b79a236ec4",
+        "e9a66845e05d5abc0ad04ec80f774a7e585c6e8db975962d069a522137b80c1d",
+    ];
+    const MERKLE_ROOT: &str = "f3e94742aca4b5ef85488dc37c06c3282295ffec960994b2c0d5ac2a25a95766";
+
+    #[test]
+    fn mainnet_merkle_root() {
+        let txids: Vec<U256> = TXIDS.iter().map(|txid| reversed(txid)).collect();
+        let path = merkle_path(&txids);
+
+        assert_eq!(path.len(), 2);
+        assert_eq!(path[0], txids[0]);
+        assert_eq!(
+            path[1],
+            double_sha256(&serialize_slices!(&txids[1], &txids[2]))
+        );
+
+        assert_eq!(
+            merkle_root(&reversed(COINBASE_TXID), &path),
+            reversed(MERKLE_ROOT)
+        );
+    }
+
+    #[test]
+    fn merkle_path_odd_transactions() {
+        let txids = [[0x01; 32], [0x02; 32]];
+        let path = merkle_path(&txids);
+
+        assert_eq!(path.len(), 2);
+        assert_eq!(path[0], [0x01; 32]);
+        assert_eq!(
+            path[1],
+            double_sha256(&serialize_slices!(&[0x02; 32], &[0x02; 32]))
+        );
+    }
+
+    #[test]
+    fn merkle_path_coinbase_only() {
+        assert!(merkle_path(&[]).is_empty());
+    }
+}
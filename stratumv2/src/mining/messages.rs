@@ -1,11 +1,11 @@
 use crate::common::SetupConnectionErrorCodes;
 use crate::error::{Error, Result};
 use crate::mining::{SetupConnectionFlags, SetupConnectionSuccessFlags};
-use crate::types::{MessageTypes, B0_32, STR0_255, STR0_32, U256};
-use crate::util::ByteParser;
-use crate::{BitFlag, Deserializable, Frameable, Protocol, Serializable};
-use std::borrow::Cow;
+use crate::types::{FlagSet, MessageTypes, B0_32, B0_64K, STR0_255, STR0_32, U256};
+use crate::util::{deserialize_bool, ByteParser};
+use crate::{Deserializable, Frameable, Protocol, Serializable};
 use std::fmt;
+use std::str::FromStr;
 use std::{io, str};
 
 // Implementation of the SetupConenction, SetupConnectionSuccess and SetupConnectionError
@@ -19,6 +19,8 @@ impl_setup_connection_error!(SetupConnectionFlags);
 /// sent from the Server. This message is used to request opening a standard
 /// channel to the upstream server. A standard mining channel indicates `header-only`
 /// mining.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OpenStandardMiningChannel {
     /// A Client-specified unique identifier across all client connections.
     /// The request_id is not interpreted by the Server.
@@ -39,6 +41,7 @@ pub struct OpenStandardMiningChannel {
     /// target or respond by sending a
     /// [OpenStandardMiningChannel.Error](struct.OpenStandardMiningChannelError.html)
     /// or [OpenExtendedMiningChannel.Error](struct.OpenExtendedMiningChannelError.html)
+    #[cfg_attr(feature = "serde", serde(with = "crate::util::hex::array"))]
     pub max_target: U256,
 }
 
@@ -99,6 +102,8 @@ impl_frameable_trait!(
 /// OpenExtendedMiningChannel is a message sent by the Client to the Server
 /// to open a mining channe that has additional capabilities such as
 /// difficulty aggregate and custom search space splitting.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OpenExtendedMiningChannel {
     /// A Client-specified unique identifier across all client connections.
     /// The request_id is not interpreted by the Server.
@@ -119,6 +124,7 @@ pub struct OpenExtendedMiningChannel {
     /// target or respond by sending a
     /// [OpenStandardMiningChannel.Error](struct.OpenStandardMiningChannelError.html)
     /// or [OpenExtendedMiningChannel.Error](struct.OpenExtendedMiningChannelError.html)
+    #[cfg_attr(feature = "serde", serde(with = "crate::util::hex::array"))]
     pub max_target: U256,
 
     /// The minimum size of extranonce space required by the Downstream node.
@@ -186,25 +192,31 @@ impl_frameable_trait!(
 
 /// OpenStandardMiningChannelSuccess is a message sent by the Server to the Client
 /// in response to a successful opening of a standard mining channel.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OpenStandardMiningChannelSuccess {
     /// The request_id received in the
     /// [OpenStandardMiningChannel](struct.OpenStandardMiningChannel.html) message.
     /// This is returned to the Client so that they can pair the responses with the
     /// initial request.
-    request_id: u32,
+    pub request_id: u32,
 
     /// Assigned by the Server to uniquely identify the channel, the id is stable
     /// for the whole lifetime of the connection.
-    channel_id: u32,
+    pub channel_id: u32,
 
     /// The initial target difficulty target for the mining channel.
-    target: U256,
+    #[cfg_attr(feature = "serde", serde(with = "crate::util::hex::array"))]
+    pub target: U256,
 
-    // TODO: I don't understand the purpose of the extranonce_prefix.
-    extranonce_prefix: B0_32,
+    /// The bytes placed at the start of the extranonce of the coinbase
+    /// transaction, unique for each channel so that Clients never search the
+    /// same space. A standard channel does not search the extranonce, so the
+    /// prefix covers the whole extranonce space.
+    pub extranonce_prefix: B0_32,
 
     /// Group channel that the channel belongs to.
-    group_channel_id: u32,
+    pub group_channel_id: u32,
 }
 
 impl OpenStandardMiningChannelSuccess {
@@ -268,113 +280,957 @@ impl_frameable_trait!(
 
 /// OpenExtendedMiningChannelSuccess is a message sent by the Server to the Client
 /// in response to a successful opening of a standard mining channel.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OpenExtendedMiningChannelSuccess {
     /// The request_id received in the
     /// [OpenExtendedMiningChannel](struct.OpenExtendedMiningChannel.html) message.
     /// This is returned to the Client so that they can pair the responses with the
     /// initial request.
-    request_id: u32,
+    pub request_id: u32,
+
+    /// Assigned by the Server to uniquely identify the channel, the id is stable
+    /// for the whole lifetime of the connection.
+    pub channel_id: u32,
+
+    /// The initial target difficulty target for the mining channel.
+    #[cfg_attr(feature = "serde", serde(with = "crate::util::hex::array"))]
+    pub target: U256,
+
+    /// The extranonce space available to the Client after the
+    /// `extranonce_prefix`, at least the `min_extranonce_size` requested in
+    /// the [OpenExtendedMiningChannel](struct.OpenExtendedMiningChannel.html).
+    pub extranonce_size: u16,
+
+    /// The bytes placed at the start of the extranonce of the coinbase
+    /// transaction, unique for each channel so that Clients never search the
+    /// same space.
+    pub extranonce_prefix: B0_32,
+}
+
+impl OpenExtendedMiningChannelSuccess {
+    pub fn new<T: Into<Vec<u8>>>(
+        request_id: u32,
+        channel_id: u32,
+        target: U256,
+        extranonce_size: u16,
+        extranonce_prefix: T,
+    ) -> Result<OpenExtendedMiningChannelSuccess> {
+        Ok(OpenExtendedMiningChannelSuccess {
+            request_id,
+            channel_id,
+            target,
+            extranonce_size,
+            extranonce_prefix: B0_32::new(extranonce_prefix.into())?,
+        })
+    }
+}
+
+impl Serializable for OpenExtendedMiningChannelSuccess {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<usize> {
+        let buffer = serialize_slices!(
+            &self.request_id.to_le_bytes(),
+            &self.channel_id.to_le_bytes(),
+            &self.target,
+            &self.extranonce_size.to_le_bytes(),
+            &self.extranonce_prefix.as_bytes()
+        );
+
+        Ok(writer.write(&buffer)?)
+    }
+}
+
+impl Deserializable for OpenExtendedMiningChannelSuccess {
+    fn deserialize(bytes: &[u8]) -> Result<OpenExtendedMiningChannelSuccess> {
+        let mut parser = ByteParser::new(bytes, 0);
+
+        let request_id = parser.next_by(4)?;
+        let channel_id = parser.next_by(4)?;
+        let target = parser.next_by(32)?;
+        let extranonce_size = parser.next_by(2)?;
+        let extranonce_prefix_length = parser.next_by(1)?[0] as usize;
+        let extranonce_prefix = parser.next_by(extranonce_prefix_length)?;
+
+        OpenExtendedMiningChannelSuccess::new(
+            u32::from_le_bytes(request_id.try_into()?),
+            u32::from_le_bytes(channel_id.try_into()?),
+            target.try_into()?,
+            u16::from_le_bytes(extranonce_size.try_into()?),
+            extranonce_prefix.to_vec(),
+        )
+    }
+}
+
+impl_frameable_trait!(
+    OpenExtendedMiningChannelSuccess,
+    MessageTypes::OpenExtendedMiningChannelSuccess,
+    false
+);
+
+// Implementation of the OpenMiningChannelError messages for Standard and Extended
+// mining.
+impl_open_mining_channel_error!(
+    OpenStandardMiningChannelError,
+    MessageTypes::OpenStandardMiningChannelError
+);
+
+impl_open_mining_channel_error!(
+    OpenExtendedMiningChannelError,
+    MessageTypes::OpenExtendedMiningChannelError
+);
+
+/// Contains the error codes for the [OpenStandardMiningChannelError](struct.OpenStandardMiningChannelError.html)
+/// and [OpenExtendedMiningChannelError](struct.OpenExtendedMiningChannelError.html)
+/// message. Each error code is serialized according to constraints of a
+/// [STR0_32](../types/struct.STR0_32.html).
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum OpenMiningChannelErrorCodes {
+    UnknownUser,
+    MaxTargetOutOfRange,
+
+    /// Indicates the Server cannot provide the extranonce space requested by
+    /// the `min_extranonce_size` of an
+    /// [OpenExtendedMiningChannel](struct.OpenExtendedMiningChannel.html).
+    MinExtranonceSizeTooLarge,
+}
+
+impl_error_codes_enum!(
+    OpenMiningChannelErrorCodes,
+    OpenMiningChannelErrorCodes::UnknownUser => "unknown-user",
+    OpenMiningChannelErrorCodes::MaxTargetOutOfRange => "max-target-out-of-range",
+    OpenMiningChannelErrorCodes::MinExtranonceSizeTooLarge => "min-extranonce-size-too-large"
+);
+
+/// NewMiningJob is a message sent by the Server to the Client to provide an
+/// updated mining job for a standard channel. The merkle root is provided
+/// directly, so the Client only needs to roll the header fields.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NewMiningJob {
+    /// The channel the job is intended for.
+    pub channel_id: u32,
+
+    /// A Server-specified identifier for the job, unique within the channel.
+    pub job_id: u32,
+
+    /// True if the job is intended for a future
+    /// [SetNewPrevHash](struct.SetNewPrevHash.html) message sent on the
+    /// channel. False if the job relates to the last sent SetNewPrevHash.
+    pub future_job: bool,
+
+    /// The version field of the block header.
+    pub version: u32,
+
+    /// The merkle root field of the block header.
+    #[cfg_attr(feature = "serde", serde(with = "crate::util::hex::array"))]
+    pub merkle_root: U256,
+}
+
+impl NewMiningJob {
+    pub fn new(
+        channel_id: u32,
+        job_id: u32,
+        future_job: bool,
+        version: u32,
+        merkle_root: U256,
+    ) -> NewMiningJob {
+        NewMiningJob {
+            channel_id,
+            job_id,
+            future_job,
+            version,
+            merkle_root,
+        }
+    }
+}
+
+impl Serializable for NewMiningJob {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<usize> {
+        let buffer = serialize_slices!(
+            &self.channel_id.to_le_bytes(),
+            &self.job_id.to_le_bytes(),
+            &[self.future_job as u8],
+            &self.version.to_le_bytes(),
+            &self.merkle_root
+        );
+
+        Ok(writer.write(&buffer)?)
+    }
+}
+
+impl Deserializable for NewMiningJob {
+    fn deserialize(bytes: &[u8]) -> Result<NewMiningJob> {
+        let mut parser = ByteParser::new(bytes, 0);
+
+        let channel_id = parser.next_by(4)?;
+        let job_id = parser.next_by(4)?;
+        let future_job = deserialize_bool(parser.next_by(1)?[0])?;
+        let version = parser.next_by(4)?;
+        let merkle_root = parser.next_by(32)?;
+
+        Ok(NewMiningJob::new(
+            u32::from_le_bytes(channel_id.try_into()?),
+            u32::from_le_bytes(job_id.try_into()?),
+            future_job,
+            u32::from_le_bytes(version.try_into()?),
+            merkle_root.try_into()?,
+        ))
+    }
+}
+
+impl_frameable_trait!(NewMiningJob, MessageTypes::NewMiningJob, true);
+
+/// NewExtendedMiningJob is a message sent by the Server to the Client to
+/// provide an updated mining job for an extended channel. The Client builds
+/// the coinbase transaction from the prefix, its extranonce and the suffix,
+/// and computes the merkle root using the merkle path.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NewExtendedMiningJob {
+    /// The channel the job is intended for.
+    pub channel_id: u32,
+
+    /// A Server-specified identifier for the job, unique within the channel.
+    pub job_id: u32,
+
+    /// True if the job is intended for a future
+    /// [SetNewPrevHash](struct.SetNewPrevHash.html) message sent on the
+    /// channel. False if the job relates to the last sent SetNewPrevHash.
+    pub future_job: bool,
+
+    /// The version field of the block header.
+    pub version: u32,
+
+    /// If true, the Client is allowed to roll the version bits of the block
+    /// header.
+    pub version_rolling_allowed: bool,
+
+    /// The merkle path of the coinbase transaction, used to compute the
+    /// merkle root of the block header.
+    #[cfg_attr(feature = "serde", serde(with = "crate::util::hex::array_list"))]
+    pub merkle_path: Vec<U256>,
+
+    /// The serialized coinbase transaction up to the start of the extranonce,
+    /// without the segwit marker, flag and witness data.
+    pub coinbase_tx_prefix: B0_64K,
+
+    /// The serialized coinbase transaction following the extranonce, without
+    /// the witness data.
+    pub coinbase_tx_suffix: B0_64K,
+}
+
+impl NewExtendedMiningJob {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new<T: Into<Vec<u8>>>(
+        channel_id: u32,
+        job_id: u32,
+        future_job: bool,
+        version: u32,
+        version_rolling_allowed: bool,
+        merkle_path: Vec<U256>,
+        coinbase_tx_prefix: T,
+        coinbase_tx_suffix: T,
+    ) -> Result<NewExtendedMiningJob> {
+        if merkle_path.len() > 255 {
+            return Err(Error::RequirementError(
+                "merkle_path cannot contain more than 255 hashes".into(),
+            ));
+        }
+
+        Ok(NewExtendedMiningJob {
+            channel_id,
+            job_id,
+            future_job,
+            version,
+            version_rolling_allowed,
+            merkle_path,
+            coinbase_tx_prefix: B0_64K::new(coinbase_tx_prefix)?,
+            coinbase_tx_suffix: B0_64K::new(coinbase_tx_suffix)?,
+        })
+    }
+}
+
+impl Serializable for NewExtendedMiningJob {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<usize> {
+        let mut merkle_path = vec![self.merkle_path.len() as u8];
+        for hash in &self.merkle_path {
+            merkle_path.extend_from_slice(hash);
+        }
+
+        let buffer = serialize_slices!(
+            &self.channel_id.to_le_bytes(),
+            &self.job_id.to_le_bytes(),
+            &[self.future_job as u8],
+            &self.version.to_le_bytes(),
+            &[self.version_rolling_allowed as u8],
+            &merkle_path,
+            &self.coinbase_tx_prefix.as_bytes(),
+            &self.coinbase_tx_suffix.as_bytes()
+        );
+
+        Ok(writer.write(&buffer)?)
+    }
+}
+
+impl Deserializable for NewExtendedMiningJob {
+    fn deserialize(bytes: &[u8]) -> Result<NewExtendedMiningJob> {
+        let mut parser = ByteParser::new(bytes, 0);
+
+        let channel_id = parser.next_by(4)?;
+        let job_id = parser.next_by(4)?;
+        let future_job = deserialize_bool(parser.next_by(1)?[0])?;
+        let version = parser.next_by(4)?;
+        let version_rolling_allowed = deserialize_bool(parser.next_by(1)?[0])?;
+
+        let merkle_path_length = parser.next_by(1)?[0] as usize;
+        let mut merkle_path = Vec::with_capacity(merkle_path_length);
+        for _ in 0..merkle_path_length {
+            merkle_path.push(parser.next_by(32)?.try_into()?);
+        }
+
+        let coinbase_tx_prefix_length = u16::from_le_bytes(parser.next_by(2)?.try_into()?);
+        let coinbase_tx_prefix = parser.next_by(coinbase_tx_prefix_length as usize)?;
+        let coinbase_tx_suffix_length = u16::from_le_bytes(parser.next_by(2)?.try_into()?);
+        let coinbase_tx_suffix = parser.next_by(coinbase_tx_suffix_length as usize)?;
+
+        NewExtendedMiningJob::new(
+            u32::from_le_bytes(channel_id.try_into()?),
+            u32::from_le_bytes(job_id.try_into()?),
+            future_job,
+            u32::from_le_bytes(version.try_into()?),
+            version_rolling_allowed,
+            merkle_path,
+            coinbase_tx_prefix.to_vec(),
+            coinbase_tx_suffix.to_vec(),
+        )
+    }
+}
+
+impl_frameable_trait!(
+    NewExtendedMiningJob,
+    MessageTypes::NewExtendedMiningJob,
+    true
+);
+
+/// SetNewPrevHash is a message sent by the Server to the Client when a new
+/// block is found on the network. Jobs sent before this message are no longer
+/// valid, except for the job referenced by `job_id`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SetNewPrevHash {
+    /// The channel the message is intended for.
+    pub channel_id: u32,
+
+    /// The job that should be used to mine on top of the new prev hash. This
+    /// is usually a job previously sent with `future_job` set to true.
+    pub job_id: u32,
+
+    /// The prev hash field of the block header.
+    #[cfg_attr(feature = "serde", serde(with = "crate::util::hex::array"))]
+    pub prev_hash: U256,
+
+    /// The smallest nTime value available for hashing.
+    pub min_ntime: u32,
+
+    /// The block header field `nBits`, the compact encoding of the network
+    /// target.
+    pub nbits: u32,
+}
+
+impl SetNewPrevHash {
+    pub fn new(
+        channel_id: u32,
+        job_id: u32,
+        prev_hash: U256,
+        min_ntime: u32,
+        nbits: u32,
+    ) -> SetNewPrevHash {
+        SetNewPrevHash {
+            channel_id,
+            job_id,
+            prev_hash,
+            min_ntime,
+            nbits,
+        }
+    }
+}
+
+impl Serializable for SetNewPrevHash {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<usize> {
+        let buffer = serialize_slices!(
+            &self.channel_id.to_le_bytes(),
+            &self.job_id.to_le_bytes(),
+            &self.prev_hash,
+            &self.min_ntime.to_le_bytes(),
+            &self.nbits.to_le_bytes()
+        );
+
+        Ok(writer.write(&buffer)?)
+    }
+}
+
+impl Deserializable for SetNewPrevHash {
+    fn deserialize(bytes: &[u8]) -> Result<SetNewPrevHash> {
+        let mut parser = ByteParser::new(bytes, 0);
+
+        let channel_id = parser.next_by(4)?;
+        let job_id = parser.next_by(4)?;
+        let prev_hash = parser.next_by(32)?;
+        let min_ntime = parser.next_by(4)?;
+        let nbits = parser.next_by(4)?;
+
+        Ok(SetNewPrevHash::new(
+            u32::from_le_bytes(channel_id.try_into()?),
+            u32::from_le_bytes(job_id.try_into()?),
+            prev_hash.try_into()?,
+            u32::from_le_bytes(min_ntime.try_into()?),
+            u32::from_le_bytes(nbits.try_into()?),
+        ))
+    }
+}
+
+impl_frameable_trait!(SetNewPrevHash, MessageTypes::SetNewPrevHash, true);
+
+/// UpdateChannel is a message sent by the Client to the Server to notify of
+/// a change of the hash rate or maximum target of a channel.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UpdateChannel {
+    /// The channel being updated.
+    pub channel_id: u32,
+
+    /// The updated expected [h/s] (hash rate/per second) of the device or the
+    /// cumulative on the channel if multiple devices are connected downstream.
+    pub nominal_hash_rate: f32,
+
+    /// The Maximum Target that can be accepted by the connected device or
+    /// multiple devices downstream. The Server MUST send a
+    /// [SetTarget](struct.SetTarget.html) if the current target of the channel
+    /// is above the maximum target.
+    #[cfg_attr(feature = "serde", serde(with = "crate::util::hex::array"))]
+    pub maximum_target: U256,
+}
+
+impl UpdateChannel {
+    pub fn new(channel_id: u32, nominal_hash_rate: f32, maximum_target: U256) -> UpdateChannel {
+        UpdateChannel {
+            channel_id,
+            nominal_hash_rate,
+            maximum_target,
+        }
+    }
+}
+
+impl Serializable for UpdateChannel {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<usize> {
+        let buffer = serialize_slices!(
+            &self.channel_id.to_le_bytes(),
+            &self.nominal_hash_rate.to_le_bytes(),
+            &self.maximum_target
+        );
+
+        Ok(writer.write(&buffer)?)
+    }
+}
+
+impl Deserializable for UpdateChannel {
+    fn deserialize(bytes: &[u8]) -> Result<UpdateChannel> {
+        let mut parser = ByteParser::new(bytes, 0);
+
+        let channel_id = parser.next_by(4)?;
+        let nominal_hash_rate = parser.next_by(4)?;
+        let maximum_target = parser.next_by(32)?;
+
+        Ok(UpdateChannel::new(
+            u32::from_le_bytes(channel_id.try_into()?),
+            f32::from_le_bytes(nominal_hash_rate.try_into()?),
+            maximum_target.try_into()?,
+        ))
+    }
+}
+
+impl_frameable_trait!(UpdateChannel, MessageTypes::UpdateChannel, true);
+
+/// SetTarget is a message sent by the Server to the Client to change the
+/// target of a channel. Shares submitted after receiving the message are
+/// checked against the new target.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SetTarget {
+    /// The channel the target applies to.
+    pub channel_id: u32,
+
+    /// The new target of the channel.
+    #[cfg_attr(feature = "serde", serde(with = "crate::util::hex::array"))]
+    pub maximum_target: U256,
+}
+
+impl SetTarget {
+    pub fn new(channel_id: u32, maximum_target: U256) -> SetTarget {
+        SetTarget {
+            channel_id,
+            maximum_target,
+        }
+    }
+}
+
+impl Serializable for SetTarget {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<usize> {
+        let buffer = serialize_slices!(&self.channel_id.to_le_bytes(), &self.maximum_target);
+
+        Ok(writer.write(&buffer)?)
+    }
+}
+
+impl Deserializable for SetTarget {
+    fn deserialize(bytes: &[u8]) -> Result<SetTarget> {
+        let mut parser = ByteParser::new(bytes, 0);
+
+        let channel_id = parser.next_by(4)?;
+        let maximum_target = parser.next_by(32)?;
+
+        Ok(SetTarget::new(
+            u32::from_le_bytes(channel_id.try_into()?),
+            maximum_target.try_into()?,
+        ))
+    }
+}
+
+impl_frameable_trait!(SetTarget, MessageTypes::SetTarget, true);
+
+/// Reconnect is a message sent by the Server to the Client to ask it to
+/// reconnect to a new endpoint. An empty `new_host` means the Client reconnects
+/// to the current host, and a `new_port` of 0 means the current port.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Reconnect {
+    /// The host of the new endpoint.
+    pub new_host: STR0_255,
+
+    /// The port of the new endpoint.
+    pub new_port: u16,
+}
+
+impl Reconnect {
+    pub fn new<T: Into<String>>(new_host: T, new_port: u16) -> Result<Reconnect> {
+        Ok(Reconnect {
+            new_host: STR0_255::new(new_host)?,
+            new_port,
+        })
+    }
+}
+
+impl Serializable for Reconnect {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<usize> {
+        let buffer = serialize_slices!(&self.new_host.as_bytes(), &self.new_port.to_le_bytes());
+
+        Ok(writer.write(&buffer)?)
+    }
+}
+
+impl Deserializable for Reconnect {
+    fn deserialize(bytes: &[u8]) -> Result<Reconnect> {
+        let mut parser = ByteParser::new(bytes, 0);
+
+        let new_host_length = parser.next_by(1)?[0] as usize;
+        let new_host = parser.next_by(new_host_length)?;
+        let new_port = parser.next_by(2)?;
+
+        Reconnect::new(
+            str::from_utf8(new_host)?,
+            u16::from_le_bytes(new_port.try_into()?),
+        )
+    }
+}
+
+impl_frameable_trait!(Reconnect, MessageTypes::Reconnect, false);
+
+/// CloseChannel is a message sent by the Client to the Server, or by the
+/// Server to the Client, to end the operation of a channel. The id of a
+/// closed channel can be reused for a new channel.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CloseChannel {
+    /// The channel being closed.
+    pub channel_id: u32,
+
+    /// The reason for closing the channel.
+    pub reason_code: STR0_32,
+}
+
+impl CloseChannel {
+    pub fn new<T: Into<String>>(channel_id: u32, reason_code: T) -> Result<CloseChannel> {
+        Ok(CloseChannel {
+            channel_id,
+            reason_code: STR0_32::new(reason_code)?,
+        })
+    }
+}
+
+impl Serializable for CloseChannel {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<usize> {
+        let buffer =
+            serialize_slices!(&self.channel_id.to_le_bytes(), &self.reason_code.as_bytes());
+
+        Ok(writer.write(&buffer)?)
+    }
+}
+
+impl Deserializable for CloseChannel {
+    fn deserialize(bytes: &[u8]) -> Result<CloseChannel> {
+        let mut parser = ByteParser::new(bytes, 0);
+
+        let channel_id = parser.next_by(4)?;
+        let reason_code_length = parser.next_by(1)?[0] as usize;
+        let reason_code = parser.next_by(reason_code_length)?;
+
+        CloseChannel::new(
+            u32::from_le_bytes(channel_id.try_into()?),
+            str::from_utf8(reason_code)?,
+        )
+    }
+}
+
+impl_frameable_trait!(CloseChannel, MessageTypes::CloseChannel, true);
+
+/// SubmitSharesStandard is a message sent by the Client to the Server to
+/// submit the result of hashing a job on a standard channel.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SubmitSharesStandard {
+    /// The channel the share was mined on.
+    pub channel_id: u32,
+
+    /// A Client-specified sequence number, used by the Server to acknowledge
+    /// a range of submitted shares.
+    pub sequence_number: u32,
+
+    /// The job the share was mined on.
+    pub job_id: u32,
+
+    /// The nonce field of the block header.
+    pub nonce: u32,
+
+    /// The nTime field of the block header.
+    pub ntime: u32,
+
+    /// The full version field of the block header, including any rolled bits.
+    pub version: u32,
+}
+
+impl SubmitSharesStandard {
+    pub fn new(
+        channel_id: u32,
+        sequence_number: u32,
+        job_id: u32,
+        nonce: u32,
+        ntime: u32,
+        version: u32,
+    ) -> SubmitSharesStandard {
+        SubmitSharesStandard {
+            channel_id,
+            sequence_number,
+            job_id,
+            nonce,
+            ntime,
+            version,
+        }
+    }
+}
+
+impl Serializable for SubmitSharesStandard {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<usize> {
+        let buffer = serialize_slices!(
+            &self.channel_id.to_le_bytes(),
+            &self.sequence_number.to_le_bytes(),
+            &self.job_id.to_le_bytes(),
+            &self.nonce.to_le_bytes(),
+            &self.ntime.to_le_bytes(),
+            &self.version.to_le_bytes()
+        );
+
+        Ok(writer.write(&buffer)?)
+    }
+}
+
+impl Deserializable for SubmitSharesStandard {
+    fn deserialize(bytes: &[u8]) -> Result<SubmitSharesStandard> {
+        let mut parser = ByteParser::new(bytes, 0);
+
+        let channel_id = parser.next_by(4)?;
+        let sequence_number = parser.next_by(4)?;
+        let job_id = parser.next_by(4)?;
+        let nonce = parser.next_by(4)?;
+        let ntime = parser.next_by(4)?;
+        let version = parser.next_by(4)?;
+
+        Ok(SubmitSharesStandard::new(
+            u32::from_le_bytes(channel_id.try_into()?),
+            u32::from_le_bytes(sequence_number.try_into()?),
+            u32::from_le_bytes(job_id.try_into()?),
+            u32::from_le_bytes(nonce.try_into()?),
+            u32::from_le_bytes(ntime.try_into()?),
+            u32::from_le_bytes(version.try_into()?),
+        ))
+    }
+}
+
+impl_frameable_trait!(
+    SubmitSharesStandard,
+    MessageTypes::SubmitSharesStandard,
+    true
+);
+
+/// SubmitSharesExtended is a message sent by the Client to the Server to
+/// submit the result of hashing a job on an extended channel. It contains the
+/// same fields as [SubmitSharesStandard](struct.SubmitSharesStandard.html)
+/// and the extranonce used to build the coinbase transaction.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SubmitSharesExtended {
+    /// The channel the share was mined on.
+    pub channel_id: u32,
+
+    /// A Client-specified sequence number, used by the Server to acknowledge
+    /// a range of submitted shares.
+    pub sequence_number: u32,
+
+    /// The job the share was mined on.
+    pub job_id: u32,
+
+    /// The nonce field of the block header.
+    pub nonce: u32,
+
+    /// The nTime field of the block header.
+    pub ntime: u32,
+
+    /// The full version field of the block header, including any rolled bits.
+    pub version: u32,
+
+    /// The extranonce searched by the Client. The size MUST be equal to the
+    /// `extranonce_size` of the channel.
+    pub extranonce: B0_32,
+}
+
+impl SubmitSharesExtended {
+    pub fn new<T: Into<Vec<u8>>>(
+        channel_id: u32,
+        sequence_number: u32,
+        job_id: u32,
+        nonce: u32,
+        ntime: u32,
+        version: u32,
+        extranonce: T,
+    ) -> Result<SubmitSharesExtended> {
+        Ok(SubmitSharesExtended {
+            channel_id,
+            sequence_number,
+            job_id,
+            nonce,
+            ntime,
+            version,
+            extranonce: B0_32::new(extranonce)?,
+        })
+    }
+}
+
+impl Serializable for SubmitSharesExtended {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<usize> {
+        let buffer = serialize_slices!(
+            &self.channel_id.to_le_bytes(),
+            &self.sequence_number.to_le_bytes(),
+            &self.job_id.to_le_bytes(),
+            &self.nonce.to_le_bytes(),
+            &self.ntime.to_le_bytes(),
+            &self.version.to_le_bytes(),
+            &self.extranonce.as_bytes()
+        );
+
+        Ok(writer.write(&buffer)?)
+    }
+}
+
+impl Deserializable for SubmitSharesExtended {
+    fn deserialize(bytes: &[u8]) -> Result<SubmitSharesExtended> {
+        let mut parser = ByteParser::new(bytes, 0);
+
+        let channel_id = parser.next_by(4)?;
+        let sequence_number = parser.next_by(4)?;
+        let job_id = parser.next_by(4)?;
+        let nonce = parser.next_by(4)?;
+        let ntime = parser.next_by(4)?;
+        let version = parser.next_by(4)?;
+        let extranonce_length = parser.next_by(1)?[0] as usize;
+        let extranonce = parser.next_by(extranonce_length)?;
+
+        SubmitSharesExtended::new(
+            u32::from_le_bytes(channel_id.try_into()?),
+            u32::from_le_bytes(sequence_number.try_into()?),
+            u32::from_le_bytes(job_id.try_into()?),
+            u32::from_le_bytes(nonce.try_into()?),
+            u32::from_le_bytes(ntime.try_into()?),
+            u32::from_le_bytes(version.try_into()?),
+            extranonce.to_vec(),
+        )
+    }
+}
+
+impl_frameable_trait!(
+    SubmitSharesExtended,
+    MessageTypes::SubmitSharesExtended,
+    true
+);
+
+/// SubmitSharesSuccess is a message sent by the Server to the Client to
+/// acknowledge a batch of accepted shares. The Server MAY acknowledge several
+/// shares at once, up to the share with `last_sequence_number`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SubmitSharesSuccess {
+    /// The channel the shares were submitted on.
+    pub channel_id: u32,
+
+    /// The sequence number of the last acknowledged share.
+    pub last_sequence_number: u32,
+
+    /// The number of shares accepted since the last SubmitSharesSuccess.
+    pub new_submits_accepted_count: u32,
+
+    /// The sum of the difficulty of the shares accepted since the last
+    /// SubmitSharesSuccess.
+    pub new_shares_sum: u64,
+}
+
+impl SubmitSharesSuccess {
+    pub fn new(
+        channel_id: u32,
+        last_sequence_number: u32,
+        new_submits_accepted_count: u32,
+        new_shares_sum: u64,
+    ) -> SubmitSharesSuccess {
+        SubmitSharesSuccess {
+            channel_id,
+            last_sequence_number,
+            new_submits_accepted_count,
+            new_shares_sum,
+        }
+    }
+}
+
+impl Serializable for SubmitSharesSuccess {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<usize> {
+        let buffer = serialize_slices!(
+            &self.channel_id.to_le_bytes(),
+            &self.last_sequence_number.to_le_bytes(),
+            &self.new_submits_accepted_count.to_le_bytes(),
+            &self.new_shares_sum.to_le_bytes()
+        );
+
+        Ok(writer.write(&buffer)?)
+    }
+}
+
+impl Deserializable for SubmitSharesSuccess {
+    fn deserialize(bytes: &[u8]) -> Result<SubmitSharesSuccess> {
+        let mut parser = ByteParser::new(bytes, 0);
+
+        let channel_id = parser.next_by(4)?;
+        let last_sequence_number = parser.next_by(4)?;
+        let new_submits_accepted_count = parser.next_by(4)?;
+        let new_shares_sum = parser.next_by(8)?;
+
+        Ok(SubmitSharesSuccess::new(
+            u32::from_le_bytes(channel_id.try_into()?),
+            u32::from_le_bytes(last_sequence_number.try_into()?),
+            u32::from_le_bytes(new_submits_accepted_count.try_into()?),
+            u64::from_le_bytes(new_shares_sum.try_into()?),
+        ))
+    }
+}
 
-    /// Assigned by the Server to uniquely identify the channel, the id is stable
-    /// for the whole lifetime of the connection.
-    channel_id: u32,
+impl_frameable_trait!(SubmitSharesSuccess, MessageTypes::SubmitSharesSuccess, true);
 
-    /// The initial target difficulty target for the mining channel.
-    target: U256,
+/// SubmitSharesError is a message sent by the Server to the Client when a
+/// submitted share is rejected.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SubmitSharesError {
+    /// The channel the share was submitted on.
+    pub channel_id: u32,
 
-    // TODO: I don't understand the purpose of the extranonce size.
-    extranonce_size: u16,
+    /// The sequence number of the rejected share.
+    pub sequence_number: u32,
 
-    // TODO: I don't understand the purpose of the extranonce prefix.
-    extranonce_prefix: B0_32,
+    pub error_code: SubmitSharesErrorCodes,
 }
 
-impl OpenExtendedMiningChannelSuccess {
-    pub fn new<T: Into<Vec<u8>>>(
-        request_id: u32,
+impl SubmitSharesError {
+    pub fn new(
         channel_id: u32,
-        target: U256,
-        extranonce_size: u16,
-        extranonce_prefix: T,
-    ) -> Result<OpenExtendedMiningChannelSuccess> {
-        Ok(OpenExtendedMiningChannelSuccess {
-            request_id,
+        sequence_number: u32,
+        error_code: SubmitSharesErrorCodes,
+    ) -> SubmitSharesError {
+        SubmitSharesError {
             channel_id,
-            target,
-            extranonce_size,
-            extranonce_prefix: B0_32::new(extranonce_prefix.into())?,
-        })
+            sequence_number,
+            error_code,
+        }
     }
 }
 
-impl Serializable for OpenExtendedMiningChannelSuccess {
+impl Serializable for SubmitSharesError {
     fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<usize> {
         let buffer = serialize_slices!(
-            &self.request_id.to_le_bytes(),
             &self.channel_id.to_le_bytes(),
-            &self.target,
-            &self.extranonce_size.to_le_bytes(),
-            &self.extranonce_prefix.as_bytes()
+            &self.sequence_number.to_le_bytes(),
+            &STR0_32::new(self.error_code.to_string())?.as_bytes()
         );
 
         Ok(writer.write(&buffer)?)
     }
 }
 
-impl Deserializable for OpenExtendedMiningChannelSuccess {
-    fn deserialize(bytes: &[u8]) -> Result<OpenExtendedMiningChannelSuccess> {
+impl Deserializable for SubmitSharesError {
+    fn deserialize(bytes: &[u8]) -> Result<SubmitSharesError> {
         let mut parser = ByteParser::new(bytes, 0);
 
-        let request_id = parser.next_by(4)?;
         let channel_id = parser.next_by(4)?;
-        let target = parser.next_by(32)?;
-        let extranonce_size = parser.next_by(2)?;
-        let extranonce_prefix_length = parser.next_by(1)?[0] as usize;
-        let extranonce_prefix = parser.next_by(extranonce_prefix_length)?;
+        let sequence_number = parser.next_by(4)?;
+        let error_code_length = parser.next_by(1)?[0] as usize;
+        let error_code = str::from_utf8(parser.next_by(error_code_length)?)?;
 
-        OpenExtendedMiningChannelSuccess::new(
-            u32::from_le_bytes(request_id.try_into()?),
+        Ok(SubmitSharesError::new(
             u32::from_le_bytes(channel_id.try_into()?),
-            target.try_into()?,
-            u16::from_le_bytes(extranonce_size.try_into()?),
-            extranonce_prefix.to_vec(),
-        )
+            u32::from_le_bytes(sequence_number.try_into()?),
+            SubmitSharesErrorCodes::from_str(error_code)?,
+        ))
     }
 }
 
-impl_frameable_trait!(
-    OpenExtendedMiningChannelSuccess,
-    MessageTypes::OpenExtendedMiningChannelSuccess,
-    false
-);
-
-// Implementation of the OpenMiningChannelError messages for Standard and Extended
-// mining.
-impl_open_mining_channel_error!(
-    OpenStandardMiningChannelError,
-    MessageTypes::OpenStandardMiningChannelError
-);
-
-impl_open_mining_channel_error!(
-    OpenExtendedMiningChannelError,
-    MessageTypes::OpenExtendedMiningChannelError
-);
+impl_frameable_trait!(SubmitSharesError, MessageTypes::SubmitSharesError, true);
 
-/// Contains the error codes for the [OpenStandardMiningChannelError](struct.OpenStandardMiningChannelError.html)
-/// and [OpenExtendedMiningChannelError](struct.OpenExtendedMiningChannelError.html)
+/// Contains the error codes for the [SubmitSharesError](struct.SubmitSharesError.html)
 /// message. Each error code is serialized according to constraints of a
 /// [STR0_32](../types/struct.STR0_32.html).
-#[derive(Debug, PartialEq)]
-pub enum OpenMiningChannelErrorCodes {
-    UnknownUser,
-    MaxTargetOutOfRange,
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SubmitSharesErrorCodes {
+    InvalidChannelId,
+    StaleShare,
+    DifficultyTooLow,
+    InvalidJobId,
+
+    /// Indicates the share was already submitted on the channel, see
+    /// [DuplicateShareDetector](struct.DuplicateShareDetector.html).
+    DuplicateShare,
 }
 
 impl_error_codes_enum!(
-    OpenMiningChannelErrorCodes,
-    OpenMiningChannelErrorCodes::UnknownUser => "unknown-user",
-    OpenMiningChannelErrorCodes::MaxTargetOutOfRange => "max-target-out-of-range"
-
+    SubmitSharesErrorCodes,
+    SubmitSharesErrorCodes::InvalidChannelId => "invalid-channel-id",
+    SubmitSharesErrorCodes::StaleShare => "stale-share",
+    SubmitSharesErrorCodes::DifficultyTooLow => "difficulty-too-low",
+    SubmitSharesErrorCodes::InvalidJobId => "invalid-job-id",
+    SubmitSharesErrorCodes::DuplicateShare => "duplicate-share"
 );
 
 #[cfg(test)]
@@ -387,7 +1243,7 @@ mod setup_connection_tests {
         let message = SetupConnection::new(
             2,
             2,
-            Cow::Borrowed(&[SetupConnectionFlags::RequiresStandardJobs]),
+            FlagSet::from([SetupConnectionFlags::RequiresStandardJobs]),
             "0.0.0.0",
             8545,
             "Bitmain",
@@ -404,7 +1260,7 @@ mod setup_connection_tests {
         let message = SetupConnection::new(
             1,
             2,
-            Cow::Borrowed(&[SetupConnectionFlags::RequiresStandardJobs]),
+            FlagSet::from([SetupConnectionFlags::RequiresStandardJobs]),
             "0.0.0.0",
             8545,
             "Bitmain",
@@ -421,7 +1277,7 @@ mod setup_connection_tests {
         let message = SetupConnection::new(
             2,
             0,
-            Cow::Borrowed(&[SetupConnectionFlags::RequiresStandardJobs]),
+            FlagSet::from([SetupConnectionFlags::RequiresStandardJobs]),
             "0.0.0.0",
             8545,
             "Bitmain",
@@ -437,7 +1293,7 @@ mod setup_connection_tests {
         let message = SetupConnection::new(
             2,
             2,
-            Cow::Borrowed(&[SetupConnectionFlags::RequiresStandardJobs]),
+            FlagSet::from([SetupConnectionFlags::RequiresStandardJobs]),
             "0.0.0.0",
             8545,
             "",
@@ -454,7 +1310,7 @@ mod setup_connection_tests {
         let message = SetupConnection::new(
             2,
             2,
-            Cow::Borrowed(&[SetupConnectionFlags::RequiresStandardJobs]),
+            FlagSet::from([SetupConnectionFlags::RequiresStandardJobs]),
             "0.0.0.0",
             8545,
             "Bitmain",
@@ -471,7 +1327,7 @@ mod setup_connection_tests {
         let message = SetupConnection::new(
             2,
             2,
-            Cow::Borrowed(&[SetupConnectionFlags::RequiresStandardJobs]),
+            FlagSet::from([SetupConnectionFlags::RequiresStandardJobs]),
             "0.0.0.0",
             8545,
             "Bitmain",
@@ -514,7 +1370,7 @@ mod setup_connection_tests {
         let message = SetupConnection::new(
             2,
             2,
-            Cow::Borrowed(&[]),
+            FlagSet::empty(),
             "0.0.0.0",
             8545,
             "Bitmain",
@@ -536,7 +1392,7 @@ mod setup_connection_tests {
         let message = SetupConnection::new(
             2,
             2,
-            Cow::Borrowed(&[
+            FlagSet::from([
                 SetupConnectionFlags::RequiresStandardJobs,
                 SetupConnectionFlags::RequiresVersionRolling,
             ]),
@@ -560,7 +1416,7 @@ mod setup_connection_tests {
         let message = SetupConnection::new(
             2,
             2,
-            Cow::Borrowed(&[
+            FlagSet::from([
                 SetupConnectionFlags::RequiresStandardJobs,
                 SetupConnectionFlags::RequiresWorkSelection,
                 SetupConnectionFlags::RequiresVersionRolling,
@@ -605,7 +1461,9 @@ mod setup_connection_tests {
         let message = SetupConnection::deserialize(&input).unwrap();
         assert_eq!(message.min_version, 2);
         assert_eq!(message.max_version, 2);
-        assert_eq!(message.flags[0], SetupConnectionFlags::RequiresStandardJobs);
+        assert!(message
+            .flags
+            .contains(SetupConnectionFlags::RequiresStandardJobs));
         assert_eq!(message.endpoint_host, "0.0.0.0".to_string());
         assert_eq!(message.endpoint_port, 8545);
         assert_eq!(message.vendor, "Bitmain".to_string());
@@ -663,7 +1521,7 @@ mod setup_connection_tests {
         let message = SetupConnection::new(
             2,
             2,
-            Cow::Borrowed(&[SetupConnectionFlags::RequiresStandardJobs]),
+            FlagSet::from([SetupConnectionFlags::RequiresStandardJobs]),
             "0.0.0.0",
             8545,
             "Bitmain",
@@ -1099,7 +1957,7 @@ mod connection_success_tests {
 
     #[test]
     fn serialize_connection_success() {
-        let message = SetupConnectionSuccess::new(2, Cow::Borrowed(&[]));
+        let message = SetupConnectionSuccess::new(2, FlagSet::empty());
 
         let buffer = serialize(message).unwrap();
 
@@ -1114,7 +1972,7 @@ mod connection_success_tests {
     fn serialize_connection_sucess() {
         let message = SetupConnectionSuccess::new(
             2,
-            Cow::Borrowed(&[SetupConnectionSuccessFlags::RequiresFixedVersion]),
+            FlagSet::from([SetupConnectionSuccessFlags::RequiresFixedVersion]),
         );
 
         let buffer = serialize(message).unwrap();
@@ -1130,7 +1988,7 @@ mod connection_success_tests {
     fn frame_connection_success() {
         let message = SetupConnectionSuccess::new(
             2,
-            Cow::Borrowed(&[SetupConnectionSuccessFlags::RequiresFixedVersion]),
+            FlagSet::from([SetupConnectionSuccessFlags::RequiresFixedVersion]),
         );
 
         let buffer = frame(message).unwrap();
@@ -1149,7 +2007,7 @@ mod connection_success_tests {
     fn serialize_connection_success_all_flags() {
         let message = SetupConnectionSuccess::new(
             2,
-            Cow::Borrowed(&[
+            FlagSet::from([
                 SetupConnectionSuccessFlags::RequiresFixedVersion,
                 SetupConnectionSuccessFlags::RequiresExtendedChannels,
             ]),
@@ -1166,7 +2024,7 @@ mod connection_success_tests {
 
     #[test]
     fn serialize_connection_success_no_flags() {
-        let message = SetupConnectionSuccess::new(2, Cow::Borrowed(&[]));
+        let message = SetupConnectionSuccess::new(2, FlagSet::empty());
 
         let buffer = serialize(message).unwrap();
 
@@ -1186,7 +2044,7 @@ mod connection_error_tests {
     #[test]
     fn serialize_connection_error() {
         let message = SetupConnectionError::new(
-            Cow::Borrowed(&[SetupConnectionFlags::RequiresStandardJobs]),
+            FlagSet::from([SetupConnectionFlags::RequiresStandardJobs]),
             SetupConnectionErrorCodes::UnsupportedFeatureFlags,
         )
         .unwrap();
@@ -1203,7 +2061,7 @@ mod connection_error_tests {
     #[test]
     fn serialize_connection_error_empty_flags() {
         let message = SetupConnectionError::new(
-            Cow::Borrowed(&[]),
+            FlagSet::empty(),
             SetupConnectionErrorCodes::UnsupportedFeatureFlags,
         );
 
@@ -1213,7 +2071,7 @@ mod connection_error_tests {
     #[test]
     fn frame_connection_error() {
         let message = SetupConnectionError::new(
-            Cow::Borrowed(&[SetupConnectionFlags::RequiresStandardJobs]),
+            FlagSet::from([SetupConnectionFlags::RequiresStandardJobs]),
             SetupConnectionErrorCodes::UnsupportedFeatureFlags,
         )
         .unwrap();
@@ -1244,8 +2102,8 @@ mod connection_error_tests {
 
         let conn_error = SetupConnectionError::deserialize(&message).unwrap();
         assert_eq!(
-            conn_error.flags[0],
-            SetupConnectionFlags::RequiresStandardJobs
+            conn_error.flags,
+            FlagSet::from([SetupConnectionFlags::RequiresStandardJobs])
         );
         assert_eq!(
             conn_error.error_code,
@@ -1302,3 +2160,406 @@ mod connection_error_tests {
         assert!(SetupConnectionError::deserialize(&input).is_err());
     }
 }
+
+#[cfg(test)]
+mod new_mining_job_tests {
+    use super::*;
+    use crate::util::serialize;
+
+    #[test]
+    fn serialize_new_mining_job() {
+        let mut merkle_root = [0u8; 32];
+        merkle_root[0] = 0xaa;
+        merkle_root[31] = 0xbb;
+
+        let message = NewMiningJob::new(1, 2, true, 0x2000_0000, merkle_root);
+        let buffer = serialize(message).unwrap();
+        assert_eq!(buffer.len(), 45);
+
+        assert_eq!(buffer[0..4], [0x01, 0x00, 0x00, 0x00]); // channel_id
+        assert_eq!(buffer[4..8], [0x02, 0x00, 0x00, 0x00]); // job_id
+        assert_eq!(buffer[8], 0x01); // future_job
+        assert_eq!(buffer[9..13], [0x00, 0x00, 0x00, 0x20]); // version
+        assert_eq!(buffer[13..45], merkle_root); // merkle_root
+
+        let message = NewMiningJob::deserialize(&buffer).unwrap();
+        assert_eq!(message.channel_id, 1);
+        assert_eq!(message.job_id, 2);
+        assert!(message.future_job);
+        assert_eq!(message.version, 0x2000_0000);
+        assert_eq!(message.merkle_root, merkle_root);
+    }
+
+    #[test]
+    fn deserialize_new_mining_job_invalid_bool() {
+        let mut buffer = serialize(NewMiningJob::new(1, 2, false, 1, [0u8; 32])).unwrap();
+        buffer[8] = 0x02;
+
+        assert!(NewMiningJob::deserialize(&buffer).is_err());
+    }
+}
+
+#[cfg(test)]
+mod set_new_prev_hash_tests {
+    use super::*;
+    use crate::util::frame;
+
+    #[test]
+    fn frame_set_new_prev_hash() {
+        let message = SetNewPrevHash::new(1, 2, [0xff; 32], 1_600_000_000, 0x1d00_ffff);
+        let buffer = frame(message).unwrap();
+
+        assert_eq!(buffer[0..6], [0x00, 0x00, 0x20, 0x30, 0x00, 0x00]);
+
+        let message = SetNewPrevHash::deserialize(&buffer[6..]).unwrap();
+        assert_eq!(message.channel_id, 1);
+        assert_eq!(message.job_id, 2);
+        assert_eq!(message.prev_hash, [0xff; 32]);
+        assert_eq!(message.min_ntime, 1_600_000_000);
+        assert_eq!(message.nbits, 0x1d00_ffff);
+    }
+}
+
+#[cfg(test)]
+mod submit_shares_standard_tests {
+    use super::*;
+    use crate::util::serialize;
+
+    #[test]
+    fn serialize_submit_shares_standard() {
+        let message = SubmitSharesStandard::new(1, 2, 3, 4, 5, 6);
+        let buffer = serialize(message).unwrap();
+
+        let expected = [
+            0x01, 0x00, 0x00, 0x00, // channel_id
+            0x02, 0x00, 0x00, 0x00, // sequence_number
+            0x03, 0x00, 0x00, 0x00, // job_id
+            0x04, 0x00, 0x00, 0x00, // nonce
+            0x05, 0x00, 0x00, 0x00, // ntime
+            0x06, 0x00, 0x00, 0x00, // version
+        ];
+        assert_eq!(buffer, expected);
+
+        let message = SubmitSharesStandard::deserialize(&buffer).unwrap();
+        assert_eq!(message.sequence_number, 2);
+        assert_eq!(message.version, 6);
+    }
+}
+
+#[cfg(test)]
+mod new_extended_mining_job_tests {
+    use super::*;
+    use crate::util::serialize;
+
+    #[test]
+    fn serialize_new_extended_mining_job() {
+        let message = NewExtendedMiningJob::new(
+            1,
+            2,
+            false,
+            1,
+            true,
+            vec![[0x11; 32], [0x22; 32]],
+            vec![0xaa],
+            vec![0xbb, 0xcc],
+        )
+        .unwrap();
+        let buffer = serialize(message).unwrap();
+        assert_eq!(buffer.len(), 4 + 4 + 1 + 4 + 1 + 1 + 64 + 3 + 4);
+
+        assert_eq!(buffer[13], 0x01); // version_rolling_allowed
+        assert_eq!(buffer[14], 0x02); // merkle_path length
+        assert_eq!(buffer[79..82], [0x01, 0x00, 0xaa]); // coinbase_tx_prefix
+        assert_eq!(buffer[82..86], [0x02, 0x00, 0xbb, 0xcc]); // coinbase_tx_suffix
+
+        let message = NewExtendedMiningJob::deserialize(&buffer).unwrap();
+        assert!(message.version_rolling_allowed);
+        assert_eq!(message.merkle_path, vec![[0x11; 32], [0x22; 32]]);
+        assert_eq!(message.coinbase_tx_prefix, vec![0xaa]);
+        assert_eq!(message.coinbase_tx_suffix, vec![0xbb, 0xcc]);
+    }
+
+    #[test]
+    fn new_extended_mining_job_merkle_path_too_long() {
+        let message =
+            NewExtendedMiningJob::new(1, 2, false, 1, true, vec![[0u8; 32]; 256], vec![], vec![]);
+        assert!(message.is_err());
+    }
+}
+
+#[cfg(test)]
+mod submit_shares_extended_tests {
+    use super::*;
+    use crate::util::frame;
+
+    #[test]
+    fn serialize_submit_shares_extended() {
+        let message = SubmitSharesExtended::new(1, 2, 3, 4, 5, 6, vec![0xde, 0xad]).unwrap();
+        let buffer = frame(message).unwrap();
+
+        assert_eq!(buffer[0..6], [0x00, 0x00, 0x1b, 0x1b, 0x00, 0x00]);
+        assert_eq!(buffer[30..], [0x02, 0xde, 0xad]); // extranonce
+
+        let message = SubmitSharesExtended::deserialize(&buffer[6..]).unwrap();
+        assert_eq!(message.job_id, 3);
+        assert_eq!(message.extranonce, vec![0xde, 0xad]);
+    }
+}
+
+#[cfg(test)]
+mod update_channel_tests {
+    use super::*;
+    use crate::util::frame;
+
+    #[test]
+    fn serialize_update_channel() {
+        let message = UpdateChannel::new(1, 12.3, [0xff; 32]);
+        let buffer = frame(message).unwrap();
+
+        assert_eq!(buffer[0..6], [0x00, 0x00, 0x16, 0x28, 0x00, 0x00]);
+        assert_eq!(buffer[6..10], [0x01, 0x00, 0x00, 0x00]); // channel_id
+        assert_eq!(buffer[10..14], [0xcd, 0xcc, 0x44, 0x41]); // nominal_hash_rate
+
+        let message = UpdateChannel::deserialize(&buffer[6..]).unwrap();
+        assert_eq!(message.nominal_hash_rate, 12.3);
+        assert_eq!(message.maximum_target, [0xff; 32]);
+    }
+}
+
+#[cfg(test)]
+mod set_target_tests {
+    use super::*;
+    use crate::util::frame;
+
+    #[test]
+    fn serialize_set_target() {
+        let message = SetTarget::new(1, [0x11; 32]);
+        let buffer = frame(message).unwrap();
+
+        assert_eq!(buffer[0..6], [0x00, 0x00, 0x21, 0x24, 0x00, 0x00]);
+
+        let message = SetTarget::deserialize(&buffer[6..]).unwrap();
+        assert_eq!(message.channel_id, 1);
+        assert_eq!(message.maximum_target, [0x11; 32]);
+    }
+}
+
+#[cfg(test)]
+mod reconnect_tests {
+    use super::*;
+    use crate::util::frame;
+
+    #[test]
+    fn serialize_reconnect() {
+        let message = Reconnect::new("pool", 3333).unwrap();
+        let buffer = frame(message).unwrap();
+
+        let expected = [
+            0x00, 0x00, // extension_type
+            0x25, // msg_type
+            0x07, 0x00, 0x00, // msg_length
+            0x04, // length_new_host
+            0x70, 0x6f, 0x6f, 0x6c, // new_host
+            0x05, 0x0d, // new_port
+        ];
+        assert_eq!(buffer, expected);
+
+        let message = Reconnect::deserialize(&buffer[6..]).unwrap();
+        assert_eq!(message.new_host, "pool".to_string());
+        assert_eq!(message.new_port, 3333);
+    }
+}
+
+#[cfg(test)]
+mod close_channel_tests {
+    use super::*;
+    use crate::util::frame;
+
+    #[test]
+    fn serialize_close_channel() {
+        let message = CloseChannel::new(1, "shutdown").unwrap();
+        let buffer = frame(message).unwrap();
+
+        let expected = [
+            0x00, 0x00, // extension_type
+            0x18, // msg_type
+            0x0d, 0x00, 0x00, // msg_length
+            0x01, 0x00, 0x00, 0x00, // channel_id
+            0x08, // length_reason_code
+            0x73, 0x68, 0x75, 0x74, 0x64, 0x6f, 0x77, 0x6e, // reason_code
+        ];
+        assert_eq!(buffer, expected);
+
+        let message = CloseChannel::deserialize(&buffer[6..]).unwrap();
+        assert_eq!(message.channel_id, 1);
+        assert_eq!(message.reason_code, "shutdown".to_string());
+    }
+}
+
+#[cfg(test)]
+mod submit_shares_success_tests {
+    use super::*;
+    use crate::util::frame;
+
+    #[test]
+    fn serialize_submit_shares_success() {
+        let message = SubmitSharesSuccess::new(1, 2, 3, 0x0102_0304_0506_0708);
+        let buffer = frame(message).unwrap();
+
+        let expected = [
+            0x00, 0x00, // extension_type
+            0x1c, // msg_type
+            0x14, 0x00, 0x00, // msg_length
+            0x01, 0x00, 0x00, 0x00, // channel_id
+            0x02, 0x00, 0x00, 0x00, // last_sequence_number
+            0x03, 0x00, 0x00, 0x00, // new_submits_accepted_count
+            0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01, // new_shares_sum
+        ];
+        assert_eq!(buffer, expected);
+
+        let message = SubmitSharesSuccess::deserialize(&buffer[6..]).unwrap();
+        assert_eq!(message.channel_id, 1);
+        assert_eq!(message.last_sequence_number, 2);
+        assert_eq!(message.new_submits_accepted_count, 3);
+        assert_eq!(message.new_shares_sum, 0x0102_0304_0506_0708);
+    }
+}
+
+#[cfg(test)]
+mod submit_shares_error_tests {
+    use super::*;
+    use crate::util::frame;
+
+    #[test]
+    fn serialize_submit_shares_error() {
+        let message = SubmitSharesError::new(1, 2, SubmitSharesErrorCodes::DuplicateShare);
+        let buffer = frame(message).unwrap();
+
+        assert_eq!(buffer[0..6], [0x00, 0x00, 0x1d, 0x18, 0x00, 0x00]);
+        assert_eq!(buffer[14], 0x0f); // length_error_code
+        assert_eq!(buffer[15..], *b"duplicate-share");
+
+        let message = SubmitSharesError::deserialize(&buffer[6..]).unwrap();
+        assert_eq!(message.channel_id, 1);
+        assert_eq!(message.sequence_number, 2);
+        assert_eq!(message.error_code, SubmitSharesErrorCodes::DuplicateShare);
+    }
+
+    #[test]
+    fn deserialize_submit_shares_error_unknown_code() {
+        let mut buffer = vec![0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x03];
+        buffer.extend_from_slice(b"foo");
+
+        assert!(matches!(
+            SubmitSharesError::deserialize(&buffer),
+            Err(Error::UnknownErrorCode())
+        ));
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod serde_tests {
+    use super::*;
+
+    #[test]
+    fn setup_connection_json() {
+        let mut flags = FlagSet::from([
+            SetupConnectionFlags::RequiresStandardJobs,
+            SetupConnectionFlags::RequiresVersionRolling,
+        ]);
+        flags = flags.union(FlagSet::from_bits(0x10));
+
+        let message = SetupConnection::new(
+            2,
+            2,
+            flags,
+            "0.0.0.0",
+            8545,
+            "Bitmain",
+            "S9i 13.5",
+            "braiins-os-2018-09-22-1-hash",
+            "some-device-uuid",
+        )
+        .unwrap();
+
+        let json = serde_json::to_value(&message).unwrap();
+        assert_eq!(json["protocol"], "Mining");
+        assert_eq!(
+            json["flags"],
+            serde_json::json!(["RequiresStandardJobs", "RequiresVersionRolling", 16])
+        );
+        assert_eq!(json["endpoint_host"], "0.0.0.0");
+
+        let message: SetupConnection = serde_json::from_value(json).unwrap();
+        assert_eq!(message.flags.bits(), 0x15);
+        assert_eq!(message.vendor, "Bitmain".to_string());
+    }
+
+    #[test]
+    fn new_extended_mining_job_json() {
+        let message = NewExtendedMiningJob::new(
+            1,
+            2,
+            true,
+            0x2000_0000,
+            false,
+            vec![[0xab; 32]],
+            vec![0x01],
+            vec![0x02, 0x03],
+        )
+        .unwrap();
+
+        let json = serde_json::to_value(&message).unwrap();
+        assert_eq!(json["merkle_path"], serde_json::json!(["ab".repeat(32)]));
+        assert_eq!(json["coinbase_tx_prefix"], "01");
+        assert_eq!(json["coinbase_tx_suffix"], "0203");
+
+        let message: NewExtendedMiningJob = serde_json::from_value(json).unwrap();
+        assert_eq!(message.merkle_path, vec![[0xab; 32]]);
+        assert_eq!(message.coinbase_tx_suffix, vec![0x02, 0x03]);
+    }
+
+    #[test]
+    fn error_codes_json() {
+        let message = SubmitSharesError::new(1, 2, SubmitSharesErrorCodes::StaleShare);
+
+        let json = serde_json::to_string(&message).unwrap();
+        assert_eq!(
+            json,
+            r#"{"channel_id":1,"sequence_number":2,"error_code":"stale-share"}"#
+        );
+
+        let message: SubmitSharesError = serde_json::from_str(&json).unwrap();
+        assert_eq!(message.error_code, SubmitSharesErrorCodes::StaleShare);
+
+        let unknown = r#"{"channel_id":1,"sequence_number":2,"error_code":"unknown"}"#;
+        assert!(serde_json::from_str::<SubmitSharesError>(unknown).is_err());
+    }
+
+    #[test]
+    fn sizes_enforced_json() {
+        let message = SetTarget::new(1, [0x00; 32]);
+        let mut json = serde_json::to_value(&message).unwrap();
+        assert_eq!(json["maximum_target"], "00".repeat(32));
+
+        // A U256 must be exactly 32 bytes.
+        json["maximum_target"] = serde_json::json!("00".repeat(31));
+        assert!(serde_json::from_value::<SetTarget>(json).is_err());
+
+        let message = Reconnect::new("pool.example.com", 3336).unwrap();
+        let mut json = serde_json::to_value(&message).unwrap();
+        assert_eq!(json["new_host"], "pool.example.com");
+
+        // A STR0_255 cannot be longer than 255 bytes.
+        json["new_host"] = serde_json::json!("a".repeat(256));
+        assert!(serde_json::from_value::<Reconnect>(json).is_err());
+
+        let message = SubmitSharesExtended::new(1, 2, 3, 4, 5, 6, [0x01; 4]).unwrap();
+        let mut json = serde_json::to_value(&message).unwrap();
+        assert_eq!(json["extranonce"], "01010101");
+
+        // A B0_32 cannot be longer than 32 bytes.
+        json["extranonce"] = serde_json::json!("01".repeat(33));
+        assert!(serde_json::from_value::<SubmitSharesExtended>(json).is_err());
+    }
+}
@@ -17,13 +17,43 @@
 //!                     opened to a particular connection. The group is addressable
 //!                     through a common communication channel.
 
+mod acks;
+mod coinbase;
+mod duplicate;
+mod extranonce;
 mod flags;
+mod jobs;
+mod merkle;
 mod messages;
+mod negotiation;
+mod server;
+mod share;
+mod vardiff;
+mod version_rolling;
 
+pub use acks::{ShareAckAggregator, ShareStats, ShareTracker};
+pub use coinbase::{build_coinbase, coinbase_txid};
+pub use duplicate::DuplicateShareDetector;
+pub use extranonce::ExtranonceAllocator;
 pub use flags::{SetupConnectionFlags, SetupConnectionSuccessFlags};
+pub use jobs::{Job, JobLookup, JobState, JobStore};
+pub use merkle::{merkle_path, merkle_root};
 pub use messages::{
-    OpenExtendedMiningChannel, OpenExtendedMiningChannelError, OpenExtendedMiningChannelSuccess,
-    OpenMiningChannelErrorCodes, OpenStandardMiningChannel, OpenStandardMiningChannelError,
-    OpenStandardMiningChannelSuccess, SetupConnection, SetupConnectionError,
-    SetupConnectionSuccess,
+    CloseChannel, NewExtendedMiningJob, NewMiningJob, OpenExtendedMiningChannel,
+    OpenExtendedMiningChannelError, OpenExtendedMiningChannelSuccess, OpenMiningChannelErrorCodes,
+    OpenStandardMiningChannel, OpenStandardMiningChannelError, OpenStandardMiningChannelSuccess,
+    Reconnect, SetNewPrevHash, SetTarget, SetupConnection, SetupConnectionError,
+    SetupConnectionSuccess, SubmitSharesError, SubmitSharesErrorCodes, SubmitSharesExtended,
+    SubmitSharesStandard, SubmitSharesSuccess, UpdateChannel,
 };
+pub use negotiation::{negotiate_version, FlagNegotiator};
+pub use server::{
+    Channel, ChannelKind, ChannelPolicy, ConnectionState, MiningServer,
+    OpenExtendedMiningChannelResponse, OpenStandardMiningChannelResponse, SetupConnectionResponse,
+};
+pub use share::{
+    block_header, header_hash, validate_extended_share, validate_standard_share, BlockHeader,
+    ShareOutcome,
+};
+pub use vardiff::{VardiffConfig, VardiffController};
+pub use version_rolling::VersionRolling;
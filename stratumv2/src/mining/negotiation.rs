@@ -0,0 +1,382 @@
+use crate::common::{SetupConnectionErrorCodes, VersionRange};
+use crate::error::{Error, Result};
+use crate::mining::{
+    SetupConnection, SetupConnectionError, SetupConnectionFlags, SetupConnectionSuccessFlags,
+};
+use crate::types::FlagSet;
+use crate::Protocol;
+
+/// Agrees on the protocol version of a new Mining Protocol connection given
+/// the versions supported by the Server and a received SetupConnection.
+///
+/// The highest version supported by both nodes is returned, this should be
+/// sent as the `used_version` in the SetupConnectionSuccess. Otherwise the
+/// SetupConnectionError that MUST be sent to the Client is returned:
+/// - `unsupported-protocol` if the SetupConnection is not for the Mining Protocol.
+/// - `protocol-version-mismatch` if the version ranges do not overlap.
+///
+/// Neither error is caused by feature flags, so the flags of the returned
+/// error are always empty.
+///
+/// # Examples
+///
+/// ```rust
+/// use stratumv2::common::{SetupConnectionErrorCodes, VersionRange};
+/// use stratumv2::mining;
+/// use stratumv2::types::FlagSet;
+///
+/// let message = mining::SetupConnection::new(
+///    2,
+///    3,
+///    FlagSet::empty(),
+///    "0.0.0.0",
+///    8545,
+///    "Bitmain",
+///    "S9i 13.5",
+///    "braiins-os-2018-09-22-1-hash",
+///    "some-device-uuid",
+/// ).unwrap();
+///
+/// let supported = VersionRange::new(2, 2).unwrap();
+/// assert_eq!(mining::negotiate_version(&supported, &message).ok(), Some(2));
+///
+/// let supported = VersionRange::new(4, 4).unwrap();
+/// let error = mining::negotiate_version(&supported, &message).err().unwrap();
+/// assert_eq!(error.error_code, SetupConnectionErrorCodes::ProtocolVersionMismatch);
+/// ```
+pub fn negotiate_version(
+    supported: &VersionRange,
+    message: &SetupConnection,
+) -> std::result::Result<u16, SetupConnectionError> {
+    if message.protocol() != Protocol::Mining {
+        return Err(SetupConnectionError {
+            flags: FlagSet::empty(),
+            error_code: SetupConnectionErrorCodes::UnsupportedProtocol,
+        });
+    }
+
+    supported
+        .negotiate(message.min_version, message.max_version)
+        .ok_or_else(|| SetupConnectionError {
+            flags: FlagSet::empty(),
+            error_code: SetupConnectionErrorCodes::ProtocolVersionMismatch,
+        })
+}
+
+/// FlagNegotiator enforces the rules between the feature flags requested by a
+/// Client in a SetupConnection and the flags returned by the Server in a
+/// SetupConnectionSuccess:
+/// - If `RequiresVersionRolling` was requested, `RequiresFixedVersion` MUST NOT
+///   be set.
+/// - If `RequiresExtendedChannels` is set, the Server will not accept standard
+///   channels, so a Client that `RequiresStandardJobs` cannot be served.
+///
+/// # Examples
+///
+/// ```rust
+/// use stratumv2::mining::{FlagNegotiator, SetupConnectionFlags, SetupConnectionSuccessFlags};
+/// use stratumv2::types::FlagSet;
+///
+/// let negotiator = FlagNegotiator::new(
+///     FlagSet::from([SetupConnectionFlags::RequiresVersionRolling]),
+///     FlagSet::from([SetupConnectionSuccessFlags::RequiresFixedVersion]),
+/// );
+///
+/// // The Server prefers a fixed version, but allows version rolling if requested.
+/// let flags = negotiator.negotiate(FlagSet::empty()).ok().unwrap();
+/// assert!(flags.contains(SetupConnectionSuccessFlags::RequiresFixedVersion));
+///
+/// let flags = negotiator
+///     .negotiate(FlagSet::from([SetupConnectionFlags::RequiresVersionRolling]))
+///     .ok()
+///     .unwrap();
+/// assert!(flags.is_empty());
+/// ```
+#[derive(Debug, Clone)]
+pub struct FlagNegotiator {
+    supported_flags: FlagSet<SetupConnectionFlags>,
+    success_flags: FlagSet<SetupConnectionSuccessFlags>,
+}
+
+impl FlagNegotiator {
+    /// Constructor for the FlagNegotiator. The `supported_flags` are the
+    /// SetupConnectionFlags the Server is capable of serving and the
+    /// `success_flags` are the SetupConnectionSuccessFlags the Server sets when
+    /// they do not conflict with the flags requested by the Client.
+    pub fn new(
+        supported_flags: FlagSet<SetupConnectionFlags>,
+        success_flags: FlagSet<SetupConnectionSuccessFlags>,
+    ) -> FlagNegotiator {
+        let mut supported_flags = supported_flags;
+
+        // A Client that only understands standard jobs cannot be served if the
+        // Server only accepts extended channels.
+        if success_flags.contains(SetupConnectionSuccessFlags::RequiresExtendedChannels) {
+            supported_flags.remove(SetupConnectionFlags::RequiresStandardJobs);
+        }
+
+        FlagNegotiator {
+            supported_flags,
+            success_flags,
+        }
+    }
+
+    /// Returns all the requested flags that the Server cannot serve, including
+    /// any bits unknown to the Server.
+    pub fn unsupported_flags(
+        &self,
+        requested: FlagSet<SetupConnectionFlags>,
+    ) -> FlagSet<SetupConnectionFlags> {
+        requested.difference(self.supported_flags)
+    }
+
+    /// Returns the flags that MUST be sent in the SetupConnectionSuccess for
+    /// the requested flags. Otherwise the `unsupported-feature-flags`
+    /// SetupConnectionError containing every unsupported flag is returned.
+    pub fn negotiate(
+        &self,
+        requested: FlagSet<SetupConnectionFlags>,
+    ) -> std::result::Result<FlagSet<SetupConnectionSuccessFlags>, SetupConnectionError> {
+        let unsupported_flags = self.unsupported_flags(requested);
+        if !unsupported_flags.is_empty() {
+            return Err(SetupConnectionError {
+                flags: unsupported_flags,
+                error_code: SetupConnectionErrorCodes::UnsupportedFeatureFlags,
+            });
+        }
+
+        let mut success_flags = self.success_flags;
+        if requested.contains(SetupConnectionFlags::RequiresVersionRolling) {
+            success_flags.remove(SetupConnectionSuccessFlags::RequiresFixedVersion);
+        }
+
+        Ok(success_flags)
+    }
+
+    /// Used by the Client to check that a received SetupConnectionSuccess is
+    /// consistent with the flags sent in the SetupConnection.
+    pub fn validate_success(
+        requested: FlagSet<SetupConnectionFlags>,
+        success: FlagSet<SetupConnectionSuccessFlags>,
+    ) -> Result<()> {
+        if requested.contains(SetupConnectionFlags::RequiresVersionRolling)
+            && success.contains(SetupConnectionSuccessFlags::RequiresFixedVersion)
+        {
+            return Err(Error::RequirementError(
+                "RequiresFixedVersion MUST NOT be set if RequiresVersionRolling was requested"
+                    .into(),
+            ));
+        }
+
+        if requested.contains(SetupConnectionFlags::RequiresStandardJobs)
+            && success.contains(SetupConnectionSuccessFlags::RequiresExtendedChannels)
+        {
+            return Err(Error::RequirementError(
+                "RequiresExtendedChannels cannot be set if RequiresStandardJobs was requested"
+                    .into(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job_negotiation;
+    use crate::util::serialize;
+    use crate::Deserializable;
+
+    fn setup_connection(min_version: u16, max_version: u16) -> SetupConnection {
+        SetupConnection::new(
+            min_version,
+            max_version,
+            FlagSet::empty(),
+            "0.0.0.0",
+            8545,
+            "Bitmain",
+            "S9i 13.5",
+            "braiins-os-2018-09-22-1-hash",
+            "some-uuid",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn negotiate_highest_common_version() {
+        let supported = VersionRange::new(2, 3).unwrap();
+
+        assert_eq!(
+            negotiate_version(&supported, &setup_connection(2, 2)).ok(),
+            Some(2)
+        );
+        assert_eq!(
+            negotiate_version(&supported, &setup_connection(2, 5)).ok(),
+            Some(3)
+        );
+        assert_eq!(
+            negotiate_version(&supported, &setup_connection(3, 5)).ok(),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn negotiate_version_mismatch() {
+        let supported = VersionRange::new(2, 3).unwrap();
+        let error = negotiate_version(&supported, &setup_connection(4, 5))
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            error.error_code,
+            SetupConnectionErrorCodes::ProtocolVersionMismatch
+        );
+        assert!(error.flags.is_empty());
+    }
+
+    #[test]
+    fn negotiate_unsupported_protocol() {
+        let message = job_negotiation::SetupConnection::new(
+            2,
+            2,
+            FlagSet::empty(),
+            "0.0.0.0",
+            8545,
+            "Bitmain",
+            "S9i 13.5",
+            "braiins-os-2018-09-22-1-hash",
+            "some-uuid",
+        )
+        .unwrap();
+
+        let buffer = serialize(message).unwrap();
+        let message = SetupConnection::deserialize(&buffer).unwrap();
+
+        let supported = VersionRange::new(2, 2).unwrap();
+        let error = negotiate_version(&supported, &message).err().unwrap();
+
+        assert_eq!(
+            error.error_code,
+            SetupConnectionErrorCodes::UnsupportedProtocol
+        );
+        assert!(error.flags.is_empty());
+    }
+
+    #[test]
+    fn negotiate_flags() {
+        let negotiator = FlagNegotiator::new(
+            FlagSet::from([
+                SetupConnectionFlags::RequiresStandardJobs,
+                SetupConnectionFlags::RequiresVersionRolling,
+            ]),
+            FlagSet::from([SetupConnectionSuccessFlags::RequiresFixedVersion]),
+        );
+
+        let flags = negotiator
+            .negotiate(FlagSet::from([SetupConnectionFlags::RequiresStandardJobs]))
+            .ok()
+            .unwrap();
+        assert_eq!(
+            flags,
+            FlagSet::from([SetupConnectionSuccessFlags::RequiresFixedVersion])
+        );
+
+        let flags = negotiator
+            .negotiate(FlagSet::from([
+                SetupConnectionFlags::RequiresStandardJobs,
+                SetupConnectionFlags::RequiresVersionRolling,
+            ]))
+            .ok()
+            .unwrap();
+        assert!(flags.is_empty());
+    }
+
+    #[test]
+    fn negotiate_unsupported_flags() {
+        let negotiator = FlagNegotiator::new(
+            FlagSet::from([SetupConnectionFlags::RequiresStandardJobs]),
+            FlagSet::empty(),
+        );
+
+        let error = negotiator
+            .negotiate(FlagSet::from([
+                SetupConnectionFlags::RequiresStandardJobs,
+                SetupConnectionFlags::RequiresWorkSelection,
+                SetupConnectionFlags::RequiresVersionRolling,
+            ]))
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            error.error_code,
+            SetupConnectionErrorCodes::UnsupportedFeatureFlags
+        );
+        assert_eq!(
+            error.flags,
+            FlagSet::from([
+                SetupConnectionFlags::RequiresWorkSelection,
+                SetupConnectionFlags::RequiresVersionRolling,
+            ])
+        );
+    }
+
+    #[test]
+    fn negotiate_unknown_flags() {
+        let negotiator = FlagNegotiator::new(
+            FlagSet::from([SetupConnectionFlags::RequiresStandardJobs]),
+            FlagSet::empty(),
+        );
+
+        // Flags from a newer version of the protocol are reported back as
+        // unsupported instead of being dropped.
+        let error = negotiator
+            .negotiate(FlagSet::from_bits(0x81))
+            .err()
+            .unwrap();
+
+        assert_eq!(error.flags.bits(), 0x80);
+        assert_eq!(error.flags.unknown_bits(), 0x80);
+    }
+
+    #[test]
+    fn negotiate_standard_jobs_with_extended_channels() {
+        let negotiator = FlagNegotiator::new(
+            FlagSet::from([SetupConnectionFlags::RequiresStandardJobs]),
+            FlagSet::from([SetupConnectionSuccessFlags::RequiresExtendedChannels]),
+        );
+
+        assert_eq!(
+            negotiator
+                .unsupported_flags(FlagSet::from([SetupConnectionFlags::RequiresStandardJobs])),
+            FlagSet::from([SetupConnectionFlags::RequiresStandardJobs])
+        );
+
+        let flags = negotiator.negotiate(FlagSet::empty()).ok().unwrap();
+        assert_eq!(
+            flags,
+            FlagSet::from([SetupConnectionSuccessFlags::RequiresExtendedChannels])
+        );
+    }
+
+    #[test]
+    fn validate_success_flags() {
+        assert!(FlagNegotiator::validate_success(
+            FlagSet::from([SetupConnectionFlags::RequiresStandardJobs]),
+            FlagSet::from([SetupConnectionSuccessFlags::RequiresFixedVersion]),
+        )
+        .is_ok());
+
+        assert!(FlagNegotiator::validate_success(
+            FlagSet::from([SetupConnectionFlags::RequiresVersionRolling]),
+            FlagSet::from([SetupConnectionSuccessFlags::RequiresFixedVersion]),
+        )
+        .is_err());
+
+        assert!(FlagNegotiator::validate_success(
+            FlagSet::from([SetupConnectionFlags::RequiresStandardJobs]),
+            FlagSet::from([SetupConnectionSuccessFlags::RequiresExtendedChannels]),
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,922 @@
+use crate::common::VersionRange;
+use crate::error::{Error, Result};
+use crate::mining::{
+    negotiate_version, CloseChannel, FlagNegotiator, OpenExtendedMiningChannel,
+    OpenExtendedMiningChannelError, OpenExtendedMiningChannelSuccess, OpenMiningChannelErrorCodes,
+    OpenStandardMiningChannel, OpenStandardMiningChannelError, OpenStandardMiningChannelSuccess,
+    SetupConnection, SetupConnectionError, SetupConnectionSuccess, SetupConnectionSuccessFlags,
+    VersionRolling,
+};
+use crate::target::Target;
+use crate::types::{FlagSet, U256};
+use crate::util::{ChannelIdAllocator, ChannelIdStrategy};
+use std::collections::HashMap;
+
+/// ChannelPolicy is implemented by the user of a [MiningServer](struct.MiningServer.html)
+/// to make the pool specific decisions required when a Client requests to open
+/// a mining channel.
+pub trait ChannelPolicy {
+    /// Returns true if the `user_identity` is allowed to open a channel on
+    /// the Server.
+    fn authorize_user(&mut self, user_identity: &str) -> bool;
+
+    /// Returns the initial target for a new channel. The target MUST NOT be
+    /// greater than the `max_target` requested by the Client, a greater
+    /// target is clamped to the `max_target`. Returning None rejects the
+    /// channel with a `max-target-out-of-range` error.
+    fn choose_target(&mut self, nominal_hash_rate: f32, max_target: &U256) -> Option<U256>;
+
+    /// Returns the extranonce_prefix assigned to a new channel and the
+    /// extranonce size granted to the Client, which MUST be at least the
+    /// `min_extranonce_size` the Client requested. Standard Channels always
+    /// request 0 and their granted size is ignored. Returning None rejects
+    /// the channel with a `min-extranonce-size-too-large` error.
+    fn allocate_extranonce_prefix(
+        &mut self,
+        channel_id: u32,
+        kind: ChannelKind,
+        min_extranonce_size: u16,
+    ) -> Option<(Vec<u8>, u16)>;
+
+    /// Called when a channel is closed, allowing any resources allocated to
+    /// the channel to be reclaimed.
+    fn release_channel(&mut self, _channel: &Channel) {}
+}
+
+/// The state of a connection handled by a [MiningServer](struct.MiningServer.html).
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ConnectionState {
+    /// The Server is waiting for the first SetupConnection message.
+    AwaitingSetupConnection,
+
+    /// The SetupConnection was successful and channels can be opened.
+    Connected,
+
+    /// The SetupConnection was rejected and the connection MUST be closed.
+    Closed,
+}
+
+/// The type of a mining channel opened on a [MiningServer](struct.MiningServer.html).
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ChannelKind {
+    Standard,
+    Extended,
+}
+
+/// Channel contains the state of a mining channel opened on a
+/// [MiningServer](struct.MiningServer.html).
+#[derive(Debug, Clone)]
+pub struct Channel {
+    /// The id assigned to the channel, unique for the lifetime of the connection.
+    pub channel_id: u32,
+
+    /// Indicates whether this is a Standard or Extended channel.
+    pub kind: ChannelKind,
+
+    /// The user_identity received when opening the channel.
+    pub user_identity: String,
+
+    /// The last known hash rate of the channel.
+    pub nominal_hash_rate: f32,
+
+    /// The Maximum Target that can be accepted by the Client.
+    pub max_target: U256,
+
+    /// The current target of the channel.
+    pub target: U256,
+
+    /// The extranonce_prefix assigned to the channel.
+    pub extranonce_prefix: Vec<u8>,
+
+    /// The extranonce space available to the Client. This is always 0 for
+    /// Standard Channels.
+    pub extranonce_size: u16,
+}
+
+/// The response to a SetupConnection message.
+pub enum SetupConnectionResponse {
+    Success(SetupConnectionSuccess),
+    Error(SetupConnectionError),
+}
+
+/// The response to an OpenStandardMiningChannel message.
+pub enum OpenStandardMiningChannelResponse {
+    Success(OpenStandardMiningChannelSuccess),
+    Error(OpenStandardMiningChannelError),
+}
+
+/// The response to an OpenExtendedMiningChannel message.
+pub enum OpenExtendedMiningChannelResponse {
+    Success(OpenExtendedMiningChannelSuccess),
+    Error(OpenExtendedMiningChannelError),
+}
+
+/// MiningServer is a sans-IO implementation of the Server side of a single
+/// Mining Protocol connection. The caller is responsible for reading messages
+/// from the network, passing them to the Server and sending the returned
+/// responses.
+///
+/// # Examples
+///
+/// ```rust
+/// use stratumv2::common::VersionRange;
+/// use stratumv2::mining;
+/// use stratumv2::mining::{
+///     ChannelKind, ChannelPolicy, FlagNegotiator, MiningServer, SetupConnectionResponse,
+/// };
+/// use stratumv2::types::FlagSet;
+///
+/// struct AllowAll;
+///
+/// impl ChannelPolicy for AllowAll {
+///     fn authorize_user(&mut self, _user_identity: &str) -> bool {
+///         true
+///     }
+///
+///     fn choose_target(&mut self, _nominal_hash_rate: f32, max_target: &[u8; 32]) -> Option<[u8; 32]> {
+///         Some(*max_target)
+///     }
+///
+///     fn allocate_extranonce_prefix(
+///         &mut self,
+///         channel_id: u32,
+///         _kind: ChannelKind,
+///         min_extranonce_size: u16,
+///     ) -> Option<(Vec<u8>, u16)> {
+///         Some((channel_id.to_le_bytes().to_vec(), min_extranonce_size))
+///     }
+/// }
+///
+/// let versions = VersionRange::new(2, 2).unwrap();
+/// let flags = FlagNegotiator::new(FlagSet::empty(), FlagSet::empty());
+/// let mut server = MiningServer::new(AllowAll, versions, flags);
+///
+/// let setup_connection = mining::SetupConnection::new(
+///    2,
+///    2,
+///    FlagSet::empty(),
+///    "0.0.0.0",
+///    8545,
+///    "Bitmain",
+///    "S9i 13.5",
+///    "braiins-os-2018-09-22-1-hash",
+///    "some-device-uuid",
+/// ).unwrap();
+///
+/// let response = server.handle_setup_connection(&setup_connection).unwrap();
+/// assert!(matches!(response, SetupConnectionResponse::Success(_)));
+/// ```
+pub struct MiningServer<P: ChannelPolicy> {
+    policy: P,
+    versions: VersionRange,
+    flags: FlagNegotiator,
+    state: ConnectionState,
+    success_flags: FlagSet<SetupConnectionSuccessFlags>,
+    used_version: Option<u16>,
+    channel_ids: ChannelIdAllocator,
+    group_channel_id: u32,
+    channels: HashMap<u32, Channel>,
+}
+
+impl<P: ChannelPolicy> MiningServer<P> {
+    /// Constructor for the MiningServer. The `versions` are the protocol
+    /// versions supported by the Server and the `flags` decide the feature
+    /// flags accepted from the Client and sent in the SetupConnectionSuccess.
+    ///
+    /// Channel ids are picked at random, see
+    /// [with_channel_id_strategy](struct.MiningServer.html#method.with_channel_id_strategy).
+    pub fn new(policy: P, versions: VersionRange, flags: FlagNegotiator) -> MiningServer<P> {
+        let mut channel_ids = ChannelIdAllocator::new(ChannelIdStrategy::Random);
+        let group_channel_id = channel_ids
+            .allocate_group()
+            .expect("a new allocator has free channel ids");
+
+        MiningServer {
+            policy,
+            versions,
+            flags,
+            state: ConnectionState::AwaitingSetupConnection,
+            success_flags: FlagSet::empty(),
+            used_version: None,
+            channel_ids,
+            group_channel_id,
+            channels: HashMap::new(),
+        }
+    }
+
+    /// Sets the strategy used to allocate channel ids. This MUST be called
+    /// before any channel is opened, as the group channel id is reallocated.
+    pub fn with_channel_id_strategy(mut self, strategy: ChannelIdStrategy) -> MiningServer<P> {
+        self.channel_ids = ChannelIdAllocator::new(strategy);
+        self.group_channel_id = self
+            .channel_ids
+            .allocate_group()
+            .expect("a new allocator has free channel ids");
+
+        self
+    }
+
+    /// Returns the current state of the connection.
+    pub fn state(&self) -> ConnectionState {
+        self.state
+    }
+
+    /// Returns the protocol version agreed in the SetupConnectionSuccess.
+    pub fn used_version(&self) -> Option<u16> {
+        self.used_version
+    }
+
+    /// Returns the group channel that all Standard Channels on this connection
+    /// belong to.
+    pub fn group_channel_id(&self) -> u32 {
+        self.group_channel_id
+    }
+
+    /// Returns the feature flags sent in the SetupConnectionSuccess.
+    pub fn success_flags(&self) -> FlagSet<SetupConnectionSuccessFlags> {
+        self.success_flags
+    }
+
+    /// Returns the version rolling allowed on the connection, used to
+    /// validate the shares submitted by the Client.
+    pub fn version_rolling(&self) -> VersionRolling {
+        VersionRolling::from_success_flags(self.success_flags)
+    }
+
+    /// Returns a reference to the ChannelPolicy.
+    pub fn policy(&self) -> &P {
+        &self.policy
+    }
+
+    /// Returns a mutable reference to the ChannelPolicy.
+    pub fn policy_mut(&mut self) -> &mut P {
+        &mut self.policy
+    }
+
+    /// Returns an open channel by channel_id.
+    pub fn channel(&self, channel_id: u32) -> Option<&Channel> {
+        self.channels.get(&channel_id)
+    }
+
+    /// Returns an iterator over all open channels.
+    pub fn channels(&self) -> impl Iterator<Item = &Channel> {
+        self.channels.values()
+    }
+
+    /// Validates a SetupConnection message and returns either a
+    /// SetupConnectionSuccess or a SetupConnectionError. After an error the
+    /// connection is Closed and the caller MUST close the connection after
+    /// sending the response.
+    pub fn handle_setup_connection(
+        &mut self,
+        message: &SetupConnection,
+    ) -> Result<SetupConnectionResponse> {
+        if self.state != ConnectionState::AwaitingSetupConnection {
+            return Err(Error::RequirementError(
+                "SetupConnection MUST only be sent once at the start of a connection".into(),
+            ));
+        }
+
+        let used_version = match negotiate_version(&self.versions, message) {
+            Ok(version) => version,
+            Err(error) => {
+                self.state = ConnectionState::Closed;
+                return Ok(SetupConnectionResponse::Error(error));
+            }
+        };
+
+        let success_flags = match self.flags.negotiate(message.flags) {
+            Ok(flags) => flags,
+            Err(error) => {
+                self.state = ConnectionState::Closed;
+                return Ok(SetupConnectionResponse::Error(error));
+            }
+        };
+
+        self.success_flags = success_flags;
+        self.used_version = Some(used_version);
+        self.state = ConnectionState::Connected;
+
+        Ok(SetupConnectionResponse::Success(
+            SetupConnectionSuccess::new(used_version, self.success_flags),
+        ))
+    }
+
+    /// Opens a Standard Channel using the ChannelPolicy and returns either an
+    /// OpenStandardMiningChannelSuccess or an OpenStandardMiningChannelError.
+    pub fn handle_open_standard_mining_channel(
+        &mut self,
+        message: &OpenStandardMiningChannel,
+    ) -> Result<OpenStandardMiningChannelResponse> {
+        if self
+            .success_flags
+            .contains(SetupConnectionSuccessFlags::RequiresExtendedChannels)
+        {
+            return Err(Error::RequirementError(
+                "standard channels cannot be opened when RequiresExtendedChannels is set".into(),
+            ));
+        }
+
+        let request_id = message.request_id;
+        match self.open_channel(
+            ChannelKind::Standard,
+            &message.user_identity.0,
+            message.nominal_hash_rate,
+            message.max_target,
+            0,
+        )? {
+            Ok(channel) => Ok(OpenStandardMiningChannelResponse::Success(
+                OpenStandardMiningChannelSuccess::new(
+                    request_id,
+                    channel.channel_id,
+                    channel.target,
+                    channel.extranonce_prefix.clone(),
+                    self.group_channel_id,
+                )?,
+            )),
+            Err(error_code) => Ok(OpenStandardMiningChannelResponse::Error(
+                OpenStandardMiningChannelError::new(request_id, error_code),
+            )),
+        }
+    }
+
+    /// Opens an Extended Channel using the ChannelPolicy and returns either an
+    /// OpenExtendedMiningChannelSuccess or an OpenExtendedMiningChannelError.
+    pub fn handle_open_extended_mining_channel(
+        &mut self,
+        message: &OpenExtendedMiningChannel,
+    ) -> Result<OpenExtendedMiningChannelResponse> {
+        let request_id = message.request_id;
+        match self.open_channel(
+            ChannelKind::Extended,
+            &message.user_identity.0,
+            message.nominal_hash_rate,
+            message.max_target,
+            message.min_extranonce_size,
+        )? {
+            Ok(channel) => Ok(OpenExtendedMiningChannelResponse::Success(
+                OpenExtendedMiningChannelSuccess::new(
+                    request_id,
+                    channel.channel_id,
+                    channel.target,
+                    channel.extranonce_size,
+                    channel.extranonce_prefix.clone(),
+                )?,
+            )),
+            Err(error_code) => Ok(OpenExtendedMiningChannelResponse::Error(
+                OpenExtendedMiningChannelError::new(request_id, error_code),
+            )),
+        }
+    }
+
+    /// Closes an open channel and releases its resources in the ChannelPolicy.
+    /// The channel id is freed and can be reused by a later channel.
+    pub fn close_channel(&mut self, channel_id: u32) -> Result<Channel> {
+        let channel = self.channels.remove(&channel_id).ok_or_else(|| {
+            Error::RequirementError("cannot close a channel that is not open".into())
+        })?;
+
+        self.channel_ids.free(channel_id);
+        self.policy.release_channel(&channel);
+        Ok(channel)
+    }
+
+    /// Handles a CloseChannel message sent by the Client.
+    pub fn handle_close_channel(&mut self, message: &CloseChannel) -> Result<Channel> {
+        self.close_channel(message.channel_id)
+    }
+
+    fn open_channel(
+        &mut self,
+        kind: ChannelKind,
+        user_identity: &str,
+        nominal_hash_rate: f32,
+        max_target: U256,
+        min_extranonce_size: u16,
+    ) -> Result<std::result::Result<&Channel, OpenMiningChannelErrorCodes>> {
+        if self.state != ConnectionState::Connected {
+            return Err(Error::RequirementError(
+                "a channel cannot be opened before a successful SetupConnection".into(),
+            ));
+        }
+
+        if !self.policy.authorize_user(user_identity) {
+            return Ok(Err(OpenMiningChannelErrorCodes::UnknownUser));
+        }
+
+        let target = match self.policy.choose_target(nominal_hash_rate, &max_target) {
+            Some(target) if Target::from(target) > Target::from(max_target) => max_target,
+            Some(target) => target,
+            None => return Ok(Err(OpenMiningChannelErrorCodes::MaxTargetOutOfRange)),
+        };
+
+        let channel_id = self.channel_ids.allocate()?;
+
+        let (extranonce_prefix, extranonce_size) =
+            match self
+                .policy
+                .allocate_extranonce_prefix(channel_id, kind, min_extranonce_size)
+            {
+                Some((prefix, _)) if kind == ChannelKind::Standard => (prefix, 0),
+                Some(allocation) => allocation,
+                None => {
+                    self.channel_ids.free(channel_id);
+                    return Ok(Err(OpenMiningChannelErrorCodes::MinExtranonceSizeTooLarge));
+                }
+            };
+
+        let channel = Channel {
+            channel_id,
+            kind,
+            user_identity: user_identity.into(),
+            nominal_hash_rate,
+            max_target,
+            target,
+            extranonce_prefix,
+            extranonce_size,
+        };
+
+        if extranonce_size < min_extranonce_size {
+            self.channel_ids.free(channel_id);
+            self.policy.release_channel(&channel);
+            return Ok(Err(OpenMiningChannelErrorCodes::MinExtranonceSizeTooLarge));
+        }
+
+        Ok(Ok(self.channels.entry(channel_id).or_insert(channel)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::SetupConnectionErrorCodes;
+    use crate::job_negotiation;
+    use crate::mining::SetupConnectionFlags;
+    use crate::util::serialize;
+    use crate::{Deserializable, Protocol};
+
+    /// A ChannelPolicy that only authorizes a single user and requires the
+    /// max_target to be at least the pool target.
+    struct TestPolicy {
+        pool_target: U256,
+        check_max_target: bool,
+        extranonce_size: u16,
+        released: Vec<u32>,
+    }
+
+    impl ChannelPolicy for TestPolicy {
+        fn authorize_user(&mut self, user_identity: &str) -> bool {
+            user_identity == "braiintest.worker1"
+        }
+
+        fn choose_target(&mut self, _nominal_hash_rate: f32, max_target: &U256) -> Option<U256> {
+            // Targets are little endian, compare from the most significant byte.
+            if self.check_max_target && max_target.iter().rev().lt(self.pool_target.iter().rev()) {
+                return None;
+            }
+
+            Some(self.pool_target)
+        }
+
+        fn allocate_extranonce_prefix(
+            &mut self,
+            channel_id: u32,
+            _kind: ChannelKind,
+            min_extranonce_size: u16,
+        ) -> Option<(Vec<u8>, u16)> {
+            if min_extranonce_size > 28 {
+                return None;
+            }
+
+            Some((channel_id.to_le_bytes().to_vec(), self.extranonce_size))
+        }
+
+        fn release_channel(&mut self, channel: &Channel) {
+            self.released.push(channel.channel_id);
+        }
+    }
+
+    fn pool_target() -> U256 {
+        let mut target = [0u8; 32];
+        target[28] = 0xff;
+        target
+    }
+
+    fn new_server() -> MiningServer<TestPolicy> {
+        MiningServer::new(
+            TestPolicy {
+                pool_target: pool_target(),
+                check_max_target: true,
+                extranonce_size: 28,
+                released: vec![],
+            },
+            VersionRange::new(2, 2).unwrap(),
+            FlagNegotiator::new(
+                FlagSet::from([
+                    SetupConnectionFlags::RequiresStandardJobs,
+                    SetupConnectionFlags::RequiresVersionRolling,
+                ]),
+                FlagSet::from([SetupConnectionSuccessFlags::RequiresFixedVersion]),
+            ),
+        )
+    }
+
+    fn setup_connection(
+        min_version: u16,
+        max_version: u16,
+        flags: &[SetupConnectionFlags],
+    ) -> SetupConnection {
+        SetupConnection::new(
+            min_version,
+            max_version,
+            FlagSet::from(flags),
+            "0.0.0.0",
+            8545,
+            "Bitmain",
+            "S9i 13.5",
+            "braiins-os-2018-09-22-1-hash",
+            "some-uuid",
+        )
+        .unwrap()
+    }
+
+    fn connected_server() -> MiningServer<TestPolicy> {
+        let mut server = new_server();
+        server
+            .handle_setup_connection(&setup_connection(2, 2, &[]))
+            .unwrap();
+        server
+    }
+
+    #[test]
+    fn setup_connection_success() {
+        let mut server = new_server();
+        let response = server
+            .handle_setup_connection(&setup_connection(
+                2,
+                3,
+                &[SetupConnectionFlags::RequiresStandardJobs],
+            ))
+            .unwrap();
+
+        match response {
+            SetupConnectionResponse::Success(success) => {
+                assert_eq!(success.used_version, 2);
+                assert!(success
+                    .flags
+                    .contains(SetupConnectionSuccessFlags::RequiresFixedVersion));
+            }
+            _ => panic!("expected SetupConnectionSuccess"),
+        }
+
+        assert_eq!(server.state(), ConnectionState::Connected);
+        assert_eq!(server.used_version(), Some(2));
+        assert!(server.version_rolling().is_fixed());
+    }
+
+    #[test]
+    fn setup_connection_unsupported_flags() {
+        let mut server = new_server();
+        let response = server
+            .handle_setup_connection(&setup_connection(
+                2,
+                2,
+                &[
+                    SetupConnectionFlags::RequiresStandardJobs,
+                    SetupConnectionFlags::RequiresWorkSelection,
+                ],
+            ))
+            .unwrap();
+
+        match response {
+            SetupConnectionResponse::Error(error) => {
+                assert_eq!(
+                    error.error_code,
+                    SetupConnectionErrorCodes::UnsupportedFeatureFlags
+                );
+                assert_eq!(
+                    error.flags,
+                    FlagSet::from([SetupConnectionFlags::RequiresWorkSelection])
+                );
+            }
+            _ => panic!("expected SetupConnectionError"),
+        }
+
+        assert_eq!(server.state(), ConnectionState::Closed);
+    }
+
+    #[test]
+    fn setup_connection_version_rolling() {
+        let mut server = new_server();
+        let response = server
+            .handle_setup_connection(&setup_connection(
+                2,
+                2,
+                &[SetupConnectionFlags::RequiresVersionRolling],
+            ))
+            .unwrap();
+
+        match response {
+            SetupConnectionResponse::Success(success) => assert!(success.flags.is_empty()),
+            _ => panic!("expected SetupConnectionSuccess"),
+        }
+
+        assert!(server.success_flags().is_empty());
+        assert_eq!(server.version_rolling(), VersionRolling::bip320());
+    }
+
+    #[test]
+    fn setup_connection_version_mismatch() {
+        let mut server = new_server();
+        let response = server
+            .handle_setup_connection(&setup_connection(3, 4, &[]))
+            .unwrap();
+
+        match response {
+            SetupConnectionResponse::Error(error) => {
+                assert_eq!(
+                    error.error_code,
+                    SetupConnectionErrorCodes::ProtocolVersionMismatch
+                );
+                assert!(error.flags.is_empty());
+            }
+            _ => panic!("expected SetupConnectionError"),
+        }
+    }
+
+    #[test]
+    fn setup_connection_unsupported_protocol() {
+        let job_negotiation_connection = job_negotiation::SetupConnection::new(
+            2,
+            2,
+            FlagSet::empty(),
+            "0.0.0.0",
+            8545,
+            "Bitmain",
+            "S9i 13.5",
+            "braiins-os-2018-09-22-1-hash",
+            "some-uuid",
+        )
+        .unwrap();
+
+        // A Job Negotiation SetupConnection received on a Mining Server.
+        let buffer = serialize(job_negotiation_connection).unwrap();
+        let message = SetupConnection::deserialize(&buffer).unwrap();
+        assert_eq!(message.protocol(), Protocol::JobNegotiation);
+
+        let mut server = new_server();
+        match server.handle_setup_connection(&message).unwrap() {
+            SetupConnectionResponse::Error(error) => assert_eq!(
+                error.error_code,
+                SetupConnectionErrorCodes::UnsupportedProtocol
+            ),
+            _ => panic!("expected SetupConnectionError"),
+        }
+    }
+
+    #[test]
+    fn setup_connection_only_once() {
+        let mut server = connected_server();
+        assert!(server
+            .handle_setup_connection(&setup_connection(2, 2, &[]))
+            .is_err());
+    }
+
+    #[test]
+    fn open_channel_before_setup_connection() {
+        let mut server = new_server();
+        let message =
+            OpenStandardMiningChannel::new(1, "braiintest.worker1", 12.3, [0xff; 32]).unwrap();
+
+        assert!(server
+            .handle_open_standard_mining_channel(&message)
+            .is_err());
+    }
+
+    #[test]
+    fn open_standard_channel() {
+        let mut server = connected_server();
+        let message =
+            OpenStandardMiningChannel::new(1, "braiintest.worker1", 12.3, [0xff; 32]).unwrap();
+
+        let success = match server
+            .handle_open_standard_mining_channel(&message)
+            .unwrap()
+        {
+            OpenStandardMiningChannelResponse::Success(success) => success,
+            _ => panic!("expected OpenStandardMiningChannelSuccess"),
+        };
+
+        assert_eq!(success.request_id, 1);
+        assert_eq!(success.target, pool_target());
+        assert_eq!(success.group_channel_id, server.group_channel_id());
+        assert!(success.extranonce_prefix == success.channel_id.to_le_bytes().to_vec());
+
+        let channel = server.channel(success.channel_id).unwrap();
+        assert_eq!(channel.kind, ChannelKind::Standard);
+        assert_eq!(channel.user_identity, "braiintest.worker1");
+        assert_eq!(channel.extranonce_size, 0);
+    }
+
+    #[test]
+    fn open_standard_channel_requires_extended_channels() {
+        let mut server = MiningServer::new(
+            TestPolicy {
+                pool_target: pool_target(),
+                check_max_target: true,
+                extranonce_size: 28,
+                released: vec![],
+            },
+            VersionRange::new(2, 2).unwrap(),
+            FlagNegotiator::new(
+                FlagSet::empty(),
+                FlagSet::from([SetupConnectionSuccessFlags::RequiresExtendedChannels]),
+            ),
+        );
+        server
+            .handle_setup_connection(&setup_connection(2, 2, &[]))
+            .unwrap();
+
+        let message =
+            OpenStandardMiningChannel::new(1, "braiintest.worker1", 12.3, [0xff; 32]).unwrap();
+        assert!(server
+            .handle_open_standard_mining_channel(&message)
+            .is_err());
+
+        let message =
+            OpenExtendedMiningChannel::new(2, "braiintest.worker1", 12.3, [0xff; 32], 8).unwrap();
+        assert!(server.handle_open_extended_mining_channel(&message).is_ok());
+    }
+
+    #[test]
+    fn open_standard_channel_unknown_user() {
+        let mut server = connected_server();
+        let message = OpenStandardMiningChannel::new(1, "unknown", 12.3, [0xff; 32]).unwrap();
+
+        match server
+            .handle_open_standard_mining_channel(&message)
+            .unwrap()
+        {
+            OpenStandardMiningChannelResponse::Error(error) => {
+                assert_eq!(error.request_id, 1);
+                assert_eq!(error.error_code, OpenMiningChannelErrorCodes::UnknownUser);
+            }
+            _ => panic!("expected OpenStandardMiningChannelError"),
+        }
+
+        assert_eq!(server.channels().count(), 0);
+    }
+
+    #[test]
+    fn open_standard_channel_max_target_out_of_range() {
+        let mut server = connected_server();
+        let message =
+            OpenStandardMiningChannel::new(1, "braiintest.worker1", 12.3, [0x00; 32]).unwrap();
+
+        match server
+            .handle_open_standard_mining_channel(&message)
+            .unwrap()
+        {
+            OpenStandardMiningChannelResponse::Error(error) => assert_eq!(
+                error.error_code,
+                OpenMiningChannelErrorCodes::MaxTargetOutOfRange
+            ),
+            _ => panic!("expected OpenStandardMiningChannelError"),
+        }
+    }
+
+    #[test]
+    fn open_extended_channel() {
+        let mut server = connected_server();
+        let message =
+            OpenExtendedMiningChannel::new(2, "braiintest.worker1", 12.3, [0xff; 32], 8).unwrap();
+
+        let success = match server
+            .handle_open_extended_mining_channel(&message)
+            .unwrap()
+        {
+            OpenExtendedMiningChannelResponse::Success(success) => success,
+            _ => panic!("expected OpenExtendedMiningChannelSuccess"),
+        };
+
+        assert_eq!(success.request_id, 2);
+        assert_eq!(success.extranonce_size, 28);
+
+        let channel = server.channel(success.channel_id).unwrap();
+        assert_eq!(channel.kind, ChannelKind::Extended);
+        assert_eq!(channel.extranonce_size, 28);
+    }
+
+    #[test]
+    fn open_extended_channel_extranonce_not_granted() {
+        let mut server = connected_server();
+        server.policy_mut().extranonce_size = 4;
+        let message =
+            OpenExtendedMiningChannel::new(2, "braiintest.worker1", 12.3, [0xff; 32], 8).unwrap();
+
+        match server
+            .handle_open_extended_mining_channel(&message)
+            .unwrap()
+        {
+            OpenExtendedMiningChannelResponse::Error(error) => assert_eq!(
+                error.error_code,
+                OpenMiningChannelErrorCodes::MinExtranonceSizeTooLarge
+            ),
+            _ => panic!("expected OpenExtendedMiningChannelError"),
+        }
+        assert_eq!(server.policy().released.len(), 1);
+        assert_eq!(server.channels().count(), 0);
+    }
+
+    #[test]
+    fn open_channel_clamps_target() {
+        let mut server = connected_server();
+        server.policy_mut().pool_target = [0xff; 32];
+        server.policy_mut().check_max_target = false;
+        let message =
+            OpenStandardMiningChannel::new(1, "braiintest.worker1", 12.3, pool_target()).unwrap();
+
+        let success = match server
+            .handle_open_standard_mining_channel(&message)
+            .unwrap()
+        {
+            OpenStandardMiningChannelResponse::Success(success) => success,
+            _ => panic!("expected OpenStandardMiningChannelSuccess"),
+        };
+        assert_eq!(success.target, pool_target());
+    }
+
+    #[test]
+    fn open_extended_channel_extranonce_too_large() {
+        let mut server = connected_server();
+        let message =
+            OpenExtendedMiningChannel::new(2, "braiintest.worker1", 12.3, [0xff; 32], 32).unwrap();
+
+        match server
+            .handle_open_extended_mining_channel(&message)
+            .unwrap()
+        {
+            OpenExtendedMiningChannelResponse::Error(error) => assert_eq!(
+                error.error_code,
+                OpenMiningChannelErrorCodes::MinExtranonceSizeTooLarge
+            ),
+            _ => panic!("expected OpenExtendedMiningChannelError"),
+        }
+    }
+
+    #[test]
+    fn close_channel() {
+        let mut server = connected_server();
+        let message =
+            OpenStandardMiningChannel::new(1, "braiintest.worker1", 12.3, [0xff; 32]).unwrap();
+
+        let channel_id = match server
+            .handle_open_standard_mining_channel(&message)
+            .unwrap()
+        {
+            OpenStandardMiningChannelResponse::Success(success) => success.channel_id,
+            _ => panic!("expected OpenStandardMiningChannelSuccess"),
+        };
+
+        let message = CloseChannel::new(channel_id, "shutdown").unwrap();
+        assert!(server.handle_close_channel(&message).is_ok());
+        assert!(server.channel(channel_id).is_none());
+        assert_eq!(server.policy().released, [channel_id]);
+
+        assert!(server.close_channel(channel_id).is_err());
+    }
+
+    #[test]
+    fn sequential_channel_ids() {
+        let mut server = connected_server().with_channel_id_strategy(ChannelIdStrategy::Sequential);
+        assert_eq!(server.group_channel_id(), 0);
+
+        let message =
+            OpenStandardMiningChannel::new(1, "braiintest.worker1", 12.3, [0xff; 32]).unwrap();
+
+        let open_channel = |server: &mut MiningServer<TestPolicy>| match server
+            .handle_open_standard_mining_channel(&message)
+            .unwrap()
+        {
+            OpenStandardMiningChannelResponse::Success(success) => success.channel_id,
+            _ => panic!("expected OpenStandardMiningChannelSuccess"),
+        };
+
+        assert_eq!(open_channel(&mut server), 1);
+        assert_eq!(open_channel(&mut server), 2);
+
+        // The id of a rejected channel is freed.
+        let message =
+            OpenExtendedMiningChannel::new(2, "braiintest.worker1", 12.3, [0xff; 32], 29).unwrap();
+        assert!(matches!(
+            server
+                .handle_open_extended_mining_channel(&message)
+                .unwrap(),
+            OpenExtendedMiningChannelResponse::Error(_)
+        ));
+        assert!(!server.channel_ids.is_allocated(3));
+
+        assert!(server.close_channel(1).is_ok());
+        assert_eq!(open_channel(&mut server), 4);
+    }
+}
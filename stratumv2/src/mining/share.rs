@@ -0,0 +1,592 @@
+use crate::error::{Error, Result};
+use crate::mining::{
+    build_coinbase, coinbase_txid, merkle_root, NewExtendedMiningJob, NewMiningJob, SetNewPrevHash,
+    SubmitSharesExtended, SubmitSharesStandard, VersionRolling,
+};
+use crate::target::Target;
+use crate::types::U256;
+use crate::util::double_sha256;
+use std::convert::TryInto;
+
+/// The outcome of validating a submitted share against its job.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ShareOutcome {
+    /// The share meets the channel target.
+    Valid { hash: U256 },
+
+    /// The share meets the network target, the header is a valid block.
+    BlockFound { hash: U256 },
+
+    /// The share does not meet the channel target.
+    LowDifficulty { hash: U256 },
+
+    /// The share was mined on a job that is not built on the current prev
+    /// hash.
+    Stale,
+
+    /// The share uses an nTime earlier than the `min_ntime` of the current
+    /// prev hash.
+    InvalidTime,
+
+    /// The version of the share differs from the version of the job in bits
+    /// that are not allowed to be rolled.
+    InvalidVersion,
+}
+
+/// Validates a share submitted on a standard channel.
+///
+/// The 80-byte block header is built from the `version`, `nonce` and `ntime`
+/// of the share, the `merkle_root` of the job and the `prev_hash` and `nbits`
+/// of the current [SetNewPrevHash](struct.SetNewPrevHash.html). The double
+/// SHA256 of the header is compared against the channel `target` and the
+/// network target encoded in `nbits`.
+///
+/// The `version` of the share may only differ from the version of the job in
+/// the bits allowed by `version_rolling`, see
+/// [VersionRolling::from_success_flags](struct.VersionRolling.html#method.from_success_flags).
+///
+/// A RequirementError is returned if the share does not reference the job or
+/// if `nbits` does not encode a valid target.
+///
+/// # Examples
+///
+/// ```rust
+/// use stratumv2::mining::{
+///     validate_standard_share, NewMiningJob, SetNewPrevHash, ShareOutcome, SubmitSharesStandard,
+///     VersionRolling,
+/// };
+///
+/// let job = NewMiningJob::new(1, 1, false, 0x2000_0000, [0u8; 32]);
+/// let prev_hash = SetNewPrevHash::new(1, 1, [0u8; 32], 1_600_000_000, 0x1d00_ffff);
+/// let share = SubmitSharesStandard::new(1, 0, 1, 0, 1_600_000_000, 0x2000_0000);
+///
+/// let version_rolling = VersionRolling::bip320();
+/// let outcome =
+///     validate_standard_share(&job, &prev_hash, &share, version_rolling, &[0xff; 32]).unwrap();
+/// assert!(matches!(outcome, ShareOutcome::Valid { .. }));
+/// ```
+pub fn validate_standard_share(
+    job: &NewMiningJob,
+    prev_hash: &SetNewPrevHash,
+    share: &SubmitSharesStandard,
+    version_rolling: VersionRolling,
+    target: &U256,
+) -> Result<ShareOutcome> {
+    if share.job_id != job.job_id {
+        return Err(Error::RequirementError(
+            "the share does not reference the job".into(),
+        ));
+    }
+
+    if is_stale(job.job_id, job.future_job, prev_hash) {
+        return Ok(ShareOutcome::Stale);
+    }
+
+    if share.ntime < prev_hash.min_ntime {
+        return Ok(ShareOutcome::InvalidTime);
+    }
+
+    if !version_rolling.is_valid(job.version, share.version) {
+        return Ok(ShareOutcome::InvalidVersion);
+    }
+
+    let header = block_header(
+        share.version,
+        &prev_hash.prev_hash,
+        &job.merkle_root,
+        share.ntime,
+        prev_hash.nbits,
+        share.nonce,
+    );
+
+    check_header(&header, prev_hash.nbits, target)
+}
+
+/// Validates a share submitted on an extended channel.
+///
+/// The coinbase transaction is rebuilt from the `coinbase_tx_prefix` of the
+/// job, the `extranonce_prefix` of the channel, the `extranonce` of the share
+/// and the `coinbase_tx_suffix` of the job. Its txid is folded through the
+/// `merkle_path` of the job to compute the merkle root of the block header,
+/// which is then checked as in [validate_standard_share](fn.validate_standard_share.html).
+/// The version can only be rolled if the `version_rolling_allowed` field of
+/// the job is set.
+///
+/// A RequirementError is returned if the share does not reference the job, if
+/// the extranonce of the share is not `extranonce_size` bytes or if `nbits`
+/// does not encode a valid target.
+pub fn validate_extended_share(
+    job: &NewExtendedMiningJob,
+    prev_hash: &SetNewPrevHash,
+    share: &SubmitSharesExtended,
+    extranonce_prefix: &[u8],
+    extranonce_size: u16,
+    version_rolling: VersionRolling,
+    target: &U256,
+) -> Result<ShareOutcome> {
+    if share.job_id != job.job_id {
+        return Err(Error::RequirementError(
+            "the share does not reference the job".into(),
+        ));
+    }
+
+    let coinbase = build_coinbase(
+        &job.coinbase_tx_prefix.0,
+        extranonce_prefix,
+        &share.extranonce.0,
+        extranonce_size,
+        &job.coinbase_tx_suffix.0,
+    )?;
+
+    if is_stale(job.job_id, job.future_job, prev_hash) {
+        return Ok(ShareOutcome::Stale);
+    }
+
+    if share.ntime < prev_hash.min_ntime {
+        return Ok(ShareOutcome::InvalidTime);
+    }
+
+    if !version_rolling
+        .for_job(job.version_rolling_allowed)
+        .is_valid(job.version, share.version)
+    {
+        return Ok(ShareOutcome::InvalidVersion);
+    }
+
+    let header = block_header(
+        share.version,
+        &prev_hash.prev_hash,
+        &merkle_root(&coinbase_txid(&coinbase), &job.merkle_path),
+        share.ntime,
+        prev_hash.nbits,
+        share.nonce,
+    );
+
+    check_header(&header, prev_hash.nbits, target)
+}
+
+/// Returns true if a future job was not activated by the current prev hash.
+fn is_stale(job_id: u32, future_job: bool, prev_hash: &SetNewPrevHash) -> bool {
+    future_job && job_id != prev_hash.job_id
+}
+
+/// Compares the hash of a block header against the network target encoded
+/// in `nbits` and the channel `target`.
+fn check_header(header: &[u8; 80], nbits: u32, target: &U256) -> Result<ShareOutcome> {
+    let hash = header_hash(header);
+
+    if Target::from_compact(nbits)?.is_met_by(&hash) {
+        return Ok(ShareOutcome::BlockFound { hash });
+    }
+
+    if !Target::from(*target).is_met_by(&hash) {
+        return Ok(ShareOutcome::LowDifficulty { hash });
+    }
+
+    Ok(ShareOutcome::Valid { hash })
+}
+
+/// Serializes the fields of a Bitcoin block header into its 80-byte
+/// representation. The `prev_hash` and `merkle_root` are expected in the
+/// internal byte order used by the header.
+pub fn block_header(
+    version: u32,
+    prev_hash: &U256,
+    merkle_root: &U256,
+    ntime: u32,
+    nbits: u32,
+    nonce: u32,
+) -> [u8; 80] {
+    let mut header = [0u8; 80];
+    header[0..4].copy_from_slice(&version.to_le_bytes());
+    header[4..36].copy_from_slice(prev_hash);
+    header[36..68].copy_from_slice(merkle_root);
+    header[68..72].copy_from_slice(&ntime.to_le_bytes());
+    header[72..76].copy_from_slice(&nbits.to_le_bytes());
+    header[76..80].copy_from_slice(&nonce.to_le_bytes());
+
+    header
+}
+
+/// BlockHeader holds the fields of a Bitcoin block header. The `prev_hash`
+/// and `merkle_root` are in the internal byte order used by the header.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct BlockHeader {
+    pub version: u32,
+    pub prev_hash: U256,
+    pub merkle_root: U256,
+    pub ntime: u32,
+    pub nbits: u32,
+    pub nonce: u32,
+}
+
+impl BlockHeader {
+    /// Parses the 80-byte representation of a block header.
+    pub fn from_bytes(header: &[u8; 80]) -> BlockHeader {
+        let u32_at = |i: usize| u32::from_le_bytes(header[i..i + 4].try_into().unwrap());
+
+        BlockHeader {
+            version: u32_at(0),
+            prev_hash: header[4..36].try_into().unwrap(),
+            merkle_root: header[36..68].try_into().unwrap(),
+            ntime: u32_at(68),
+            nbits: u32_at(72),
+            nonce: u32_at(76),
+        }
+    }
+
+    /// Returns the 80-byte representation of the block header.
+    pub fn to_bytes(&self) -> [u8; 80] {
+        block_header(
+            self.version,
+            &self.prev_hash,
+            &self.merkle_root,
+            self.ntime,
+            self.nbits,
+            self.nonce,
+        )
+    }
+
+    /// Returns the hash of the block header.
+    pub fn hash(&self) -> U256 {
+        header_hash(&self.to_bytes())
+    }
+}
+
+/// Returns the double SHA256 of a block header as a little endian U256.
+pub fn header_hash(header: &[u8; 80]) -> U256 {
+    double_sha256(header)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::hex;
+
+    fn reversed(hex: &str) -> U256 {
+        let mut bytes = hex::decode(hex).unwrap();
+        bytes.reverse();
+        bytes.try_into().unwrap()
+    }
+
+    // The Bitcoin mainnet genesis block.
+    const GENESIS_MERKLE_ROOT: &str =
+        "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b";
+    const GENESIS_HASH: &str = "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f";
+    const GENESIS_NTIME: u32 = 1231006505;
+    const GENESIS_NBITS: u32 = 0x1d00ffff;
+    const GENESIS_NONCE: u32 = 2083236893;
+
+    fn genesis_job(future_job: bool) -> (NewMiningJob, SetNewPrevHash) {
+        (
+            NewMiningJob::new(1, 1, future_job, 1, reversed(GENESIS_MERKLE_ROOT)),
+            SetNewPrevHash::new(1, 1, [0u8; 32], GENESIS_NTIME, GENESIS_NBITS),
+        )
+    }
+
+    #[test]
+    fn genesis_block_header() {
+        let header = block_header(
+            1,
+            &[0u8; 32],
+            &reversed(GENESIS_MERKLE_ROOT),
+            GENESIS_NTIME,
+            GENESIS_NBITS,
+            GENESIS_NONCE,
+        );
+
+        let expected = hex::decode(concat!(
+            "01000000",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            "3ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a",
+            "29ab5f49",
+            "ffff001d",
+            "1dac2b7c",
+        ))
+        .unwrap();
+        assert_eq!(header.to_vec(), expected);
+        assert_eq!(header_hash(&header), reversed(GENESIS_HASH));
+
+        let parsed = BlockHeader::from_bytes(&header);
+        assert_eq!(parsed.nonce, GENESIS_NONCE);
+        assert_eq!(parsed.merkle_root, reversed(GENESIS_MERKLE_ROOT));
+        assert_eq!(parsed.to_bytes(), header);
+        assert_eq!(parsed.hash(), reversed(GENESIS_HASH));
+    }
+
+    #[test]
+    fn validate_block_found() {
+        let (job, prev_hash) = genesis_job(false);
+        let share = SubmitSharesStandard::new(1, 0, 1, GENESIS_NONCE, GENESIS_NTIME, 1);
+
+        assert_eq!(
+            validate_standard_share(
+                &job,
+                &prev_hash,
+                &share,
+                VersionRolling::bip320(),
+                &[0xff; 32]
+            )
+            .unwrap(),
+            ShareOutcome::BlockFound {
+                hash: reversed(GENESIS_HASH)
+            }
+        );
+    }
+
+    #[test]
+    fn validate_share_against_channel_target() {
+        let (job, prev_hash) = genesis_job(false);
+        let share = SubmitSharesStandard::new(1, 0, 1, GENESIS_NONCE + 1, GENESIS_NTIME, 1);
+
+        let outcome = validate_standard_share(
+            &job,
+            &prev_hash,
+            &share,
+            VersionRolling::bip320(),
+            &[0xff; 32],
+        )
+        .unwrap();
+        assert!(matches!(outcome, ShareOutcome::Valid { .. }));
+
+        let outcome = validate_standard_share(
+            &job,
+            &prev_hash,
+            &share,
+            VersionRolling::bip320(),
+            &[0x00; 32],
+        )
+        .unwrap();
+        assert!(matches!(outcome, ShareOutcome::LowDifficulty { .. }));
+    }
+
+    #[test]
+    fn validate_stale_share_and_time() {
+        let (job, mut prev_hash) = genesis_job(true);
+        prev_hash.job_id = 2;
+        let share = SubmitSharesStandard::new(1, 0, 1, GENESIS_NONCE, GENESIS_NTIME, 1);
+
+        assert_eq!(
+            validate_standard_share(
+                &job,
+                &prev_hash,
+                &share,
+                VersionRolling::bip320(),
+                &[0xff; 32]
+            )
+            .unwrap(),
+            ShareOutcome::Stale
+        );
+
+        let (job, prev_hash) = genesis_job(false);
+        let share = SubmitSharesStandard::new(1, 0, 1, GENESIS_NONCE, GENESIS_NTIME - 1, 1);
+
+        assert_eq!(
+            validate_standard_share(
+                &job,
+                &prev_hash,
+                &share,
+                VersionRolling::bip320(),
+                &[0xff; 32]
+            )
+            .unwrap(),
+            ShareOutcome::InvalidTime
+        );
+    }
+
+    #[test]
+    fn validate_share_version_rolling() {
+        let (job, prev_hash) = genesis_job(false);
+
+        // The rolled header is not a valid block but meets the channel target.
+        let share = SubmitSharesStandard::new(1, 0, 1, GENESIS_NONCE, GENESIS_NTIME, 0x0000_2001);
+        let outcome = validate_standard_share(
+            &job,
+            &prev_hash,
+            &share,
+            VersionRolling::bip320(),
+            &[0xff; 32],
+        )
+        .unwrap();
+        assert!(matches!(outcome, ShareOutcome::Valid { .. }));
+
+        let outcome = validate_standard_share(
+            &job,
+            &prev_hash,
+            &share,
+            VersionRolling::fixed(),
+            &[0xff; 32],
+        )
+        .unwrap();
+        assert_eq!(outcome, ShareOutcome::InvalidVersion);
+
+        let share = SubmitSharesStandard::new(1, 0, 1, GENESIS_NONCE, GENESIS_NTIME, 0x2000_0001);
+        let outcome = validate_standard_share(
+            &job,
+            &prev_hash,
+            &share,
+            VersionRolling::bip320(),
+            &[0xff; 32],
+        )
+        .unwrap();
+        assert_eq!(outcome, ShareOutcome::InvalidVersion);
+    }
+
+    #[test]
+    fn validate_share_unknown_job() {
+        let (job, prev_hash) = genesis_job(false);
+        let share = SubmitSharesStandard::new(1, 0, 2, GENESIS_NONCE, GENESIS_NTIME, 1);
+
+        assert!(validate_standard_share(
+            &job,
+            &prev_hash,
+            &share,
+            VersionRolling::bip320(),
+            &[0xff; 32]
+        )
+        .is_err());
+    }
+
+    // Mainnet block 100000, with the coinbase split around the last two bytes
+    // pushed by its scriptSig to act as the extranonce_prefix and extranonce.
+    const BLOCK_HASH: &str = "000000000003ba27aa200b1cecaad478d2b00432346c3f1f3986da1afd33e506";
+    const PREV_HASH: &str = "000000000002d01c1fccc21636b607dfd930d31d01c3a62104612a1719011250";
+    const NTIME: u32 = 1293623863;
+    const NBITS: u32 = 0x1b04864c;
+    const NONCE: u32 = 274148111;
+    const COINBASE_TX_PREFIX: &str = concat!(
+        "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff",
+        "08044c86041b02"
+    );
+    const COINBASE_TX_SUFFIX: &str = concat!(
+        "ffffffff0100f2052a010000004341041b0e8c2567c12536aa13357b79a073dc4444acb83c4ec7a0e2",
+        "f99dd7457516c5817242da796924ca4e99947d087fedf9ce467cb9f7c6287078f801df276fdf84ac",
+        "00000000"
+    );
+    const TXIDS: [&str; 3] = [
+        "fff2525b8931402dd09222c50775608f75787bd2b87e56995a7bdd30f79702c4",
+        "6359f0868171b1d194cbee1af2f16ea598ae8fad666d9b012c8ed2b79a236ec4",
+        "e9a66845e05d5abc0ad04ec80f774a7e585c6e8db975962d069a522137b80c1d",
+    ];
+
+    fn mainnet_extended_job() -> (NewExtendedMiningJob, SetNewPrevHash) {
+        let txids: Vec<U256> = TXIDS.iter().map(|txid| reversed(txid)).collect();
+
+        (
+            NewExtendedMiningJob::new(
+                1,
+                1,
+                false,
+                1,
+                false,
+                crate::mining::merkle_path(&txids),
+                hex::decode(COINBASE_TX_PREFIX).unwrap(),
+                hex::decode(COINBASE_TX_SUFFIX).unwrap(),
+            )
+            .unwrap(),
+            SetNewPrevHash::new(1, 1, reversed(PREV_HASH), NTIME, NBITS),
+        )
+    }
+
+    #[test]
+    fn validate_extended_block_found() {
+        let (job, prev_hash) = mainnet_extended_job();
+        let share = SubmitSharesExtended::new(1, 0, 1, NONCE, NTIME, 1, vec![0x02]).unwrap();
+
+        assert_eq!(
+            validate_extended_share(
+                &job,
+                &prev_hash,
+                &share,
+                &[0x06],
+                1,
+                VersionRolling::bip320(),
+                &[0xff; 32]
+            )
+            .unwrap(),
+            ShareOutcome::BlockFound {
+                hash: reversed(BLOCK_HASH)
+            }
+        );
+    }
+
+    #[test]
+    fn validate_extended_share_against_channel_target() {
+        let (job, prev_hash) = mainnet_extended_job();
+
+        // A different extranonce changes the merkle root of the header.
+        let share = SubmitSharesExtended::new(1, 0, 1, NONCE, NTIME, 1, vec![0x03]).unwrap();
+
+        let outcome = validate_extended_share(
+            &job,
+            &prev_hash,
+            &share,
+            &[0x06],
+            1,
+            VersionRolling::bip320(),
+            &[0xff; 32],
+        )
+        .unwrap();
+        assert!(matches!(outcome, ShareOutcome::Valid { .. }));
+
+        let outcome = validate_extended_share(
+            &job,
+            &prev_hash,
+            &share,
+            &[0x06],
+            1,
+            VersionRolling::bip320(),
+            &[0x00; 32],
+        )
+        .unwrap();
+        assert!(matches!(outcome, ShareOutcome::LowDifficulty { .. }));
+    }
+
+    #[test]
+    fn validate_extended_share_version_rolling_not_allowed() {
+        let (mut job, prev_hash) = mainnet_extended_job();
+        let share = SubmitSharesExtended::new(1, 0, 1, NONCE, NTIME, 0x2001, vec![0x02]).unwrap();
+
+        let outcome = validate_extended_share(
+            &job,
+            &prev_hash,
+            &share,
+            &[0x06],
+            1,
+            VersionRolling::bip320(),
+            &[0xff; 32],
+        )
+        .unwrap();
+        assert_eq!(outcome, ShareOutcome::InvalidVersion);
+
+        job.version_rolling_allowed = true;
+        let outcome = validate_extended_share(
+            &job,
+            &prev_hash,
+            &share,
+            &[0x06],
+            1,
+            VersionRolling::bip320(),
+            &[0xff; 32],
+        )
+        .unwrap();
+        assert!(matches!(outcome, ShareOutcome::Valid { .. }));
+    }
+
+    #[test]
+    fn validate_extended_share_invalid_extranonce() {
+        let (job, prev_hash) = mainnet_extended_job();
+        let share = SubmitSharesExtended::new(1, 0, 1, NONCE, NTIME, 1, vec![0x02, 0x00]).unwrap();
+
+        assert!(validate_extended_share(
+            &job,
+            &prev_hash,
+            &share,
+            &[0x06],
+            1,
+            VersionRolling::bip320(),
+            &[0xff; 32]
+        )
+        .is_err());
+    }
+}
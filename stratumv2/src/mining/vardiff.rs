@@ -0,0 +1,438 @@
+use crate::error::{Error, Result};
+use crate::mining::{SetTarget, UpdateChannel};
+use crate::target::Target;
+use crate::types::U256;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// VardiffConfig contains the share rate goals used by a
+/// [VardiffController](struct.VardiffController.html).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VardiffConfig {
+    /// The number of shares per minute each channel should submit.
+    pub shares_per_minute: f64,
+
+    /// The duration over which shares are counted before a channel can be
+    /// retargeted.
+    pub retarget_interval: Duration,
+
+    /// The relative deviation from `shares_per_minute` tolerated before a
+    /// channel is retargeted, e.g. 0.3 allows between 70% and 130% of the
+    /// share rate goal.
+    pub tolerance: f64,
+}
+
+impl Default for VardiffConfig {
+    fn default() -> Self {
+        VardiffConfig {
+            shares_per_minute: 6.0,
+            retarget_interval: Duration::from_secs(120),
+            tolerance: 0.3,
+        }
+    }
+}
+
+/// The retarget state of a single channel.
+#[derive(Debug)]
+struct VardiffChannel {
+    target: Target,
+    max_target: Target,
+    shares: u32,
+    window_start: Instant,
+}
+
+/// VardiffController retargets mining channels so that each channel submits
+/// roughly `shares_per_minute` shares. The controller is sans-IO, the current
+/// time is provided by the caller and the returned
+/// [SetTarget](struct.SetTarget.html) messages must be sent to the Client.
+///
+/// The initial target of a channel is computed from its nominal hash rate.
+/// Accepted shares are counted over a window of `retarget_interval`, after
+/// which the hash rate of the channel is estimated from the share rate and a
+/// new target is computed. The target is only changed if the share rate is
+/// outside the `tolerance` of the goal and it never exceeds the `max_target`
+/// of the channel.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::{Duration, Instant};
+/// use stratumv2::mining::{VardiffConfig, VardiffController};
+///
+/// let mut vardiff = VardiffController::new(VardiffConfig::default()).unwrap();
+///
+/// let now = Instant::now();
+/// let target = vardiff.add_channel(1, 14e12, [0xff; 32], now).unwrap();
+/// assert_eq!(vardiff.target(1), Some(target));
+///
+/// // The channel submits a single share in two minutes, the target is raised.
+/// vardiff.on_share_accepted(1, now + Duration::from_secs(60));
+/// let set_target = vardiff.poll(now + Duration::from_secs(120));
+/// assert_eq!(set_target.len(), 1);
+/// assert_eq!(set_target[0].channel_id, 1);
+/// ```
+#[derive(Debug)]
+pub struct VardiffController {
+    config: VardiffConfig,
+    channels: HashMap<u32, VardiffChannel>,
+}
+
+impl VardiffController {
+    /// Creates a VardiffController. A RequirementError is returned if the
+    /// share rate goal, retarget interval or tolerance are not positive.
+    pub fn new(config: VardiffConfig) -> Result<VardiffController> {
+        if config.shares_per_minute.is_nan() || config.shares_per_minute <= 0.0 {
+            return Err(Error::RequirementError(
+                "shares_per_minute must be greater than 0".into(),
+            ));
+        }
+
+        if config.retarget_interval == Duration::from_secs(0) {
+            return Err(Error::RequirementError(
+                "retarget_interval must be greater than 0".into(),
+            ));
+        }
+
+        if config.tolerance.is_nan() || config.tolerance < 0.0 {
+            return Err(Error::RequirementError(
+                "tolerance cannot be negative".into(),
+            ));
+        }
+
+        Ok(VardiffController {
+            config,
+            channels: HashMap::new(),
+        })
+    }
+
+    /// Returns the configuration of the controller.
+    pub fn config(&self) -> &VardiffConfig {
+        &self.config
+    }
+
+    /// Starts retargeting a channel, returning its initial target computed
+    /// from the `nominal_hash_rate` and capped at `max_target`. A channel
+    /// with no nominal hash rate starts at its `max_target`.
+    pub fn add_channel(
+        &mut self,
+        channel_id: u32,
+        nominal_hash_rate: f32,
+        max_target: U256,
+        now: Instant,
+    ) -> Result<U256> {
+        let max_target = Target::from(max_target);
+        let target = self.target_for_hash_rate(nominal_hash_rate as f64, max_target)?;
+
+        self.channels.insert(
+            channel_id,
+            VardiffChannel {
+                target,
+                max_target,
+                shares: 0,
+                window_start: now,
+            },
+        );
+
+        Ok(target.into())
+    }
+
+    /// Stops retargeting a channel, e.g. when the channel is closed.
+    pub fn remove_channel(&mut self, channel_id: u32) {
+        self.channels.remove(&channel_id);
+    }
+
+    /// Returns the current target of a channel.
+    pub fn target(&self, channel_id: u32) -> Option<U256> {
+        self.channels
+            .get(&channel_id)
+            .map(|channel| channel.target.into())
+    }
+
+    /// Counts a share accepted on the channel, returning a SetTarget if the
+    /// retarget interval elapsed and the share rate is outside the tolerance.
+    pub fn on_share_accepted(&mut self, channel_id: u32, now: Instant) -> Option<SetTarget> {
+        let config = self.config;
+        let channel = self.channels.get_mut(&channel_id)?;
+        channel.shares += 1;
+
+        retarget(&config, channel_id, channel, now)
+    }
+
+    /// Applies an [UpdateChannel](struct.UpdateChannel.html) received from
+    /// the Client. The target is recomputed from the updated nominal hash
+    /// rate and capped at the updated maximum target, and the share window
+    /// is restarted.
+    ///
+    /// A RequirementError is returned if the channel is unknown.
+    pub fn on_update_channel(
+        &mut self,
+        message: &UpdateChannel,
+        now: Instant,
+    ) -> Result<Option<SetTarget>> {
+        let max_target = Target::from(message.maximum_target);
+        let target = self.target_for_hash_rate(message.nominal_hash_rate as f64, max_target)?;
+
+        let channel = self
+            .channels
+            .get_mut(&message.channel_id)
+            .ok_or_else(|| Error::RequirementError("cannot update an unknown channel".into()))?;
+
+        channel.max_target = max_target;
+        channel.shares = 0;
+        channel.window_start = now;
+
+        if channel.target == target {
+            return Ok(None);
+        }
+
+        channel.target = target;
+        Ok(Some(SetTarget::new(message.channel_id, target.into())))
+    }
+
+    /// Retargets every channel whose retarget interval elapsed. This allows
+    /// lowering the difficulty of channels that stopped submitting shares.
+    pub fn poll(&mut self, now: Instant) -> Vec<SetTarget> {
+        let config = self.config;
+        self.channels
+            .iter_mut()
+            .filter_map(|(channel_id, channel)| retarget(&config, *channel_id, channel, now))
+            .collect()
+    }
+
+    fn target_for_hash_rate(&self, hash_rate: f64, max_target: Target) -> Result<Target> {
+        if hash_rate < 0.0 {
+            return Err(Error::RequirementError(
+                "nominal hash rate cannot be negative".into(),
+            ));
+        }
+
+        if hash_rate == 0.0 {
+            return Ok(max_target);
+        }
+
+        let target = Target::from_hash_rate(hash_rate, self.config.shares_per_minute)?;
+        Ok(target.min(max_target))
+    }
+}
+
+/// Computes a new target for the channel if the retarget interval elapsed,
+/// restarting the share window.
+fn retarget(
+    config: &VardiffConfig,
+    channel_id: u32,
+    channel: &mut VardiffChannel,
+    now: Instant,
+) -> Option<SetTarget> {
+    let elapsed = now.checked_duration_since(channel.window_start)?;
+    if elapsed < config.retarget_interval {
+        return None;
+    }
+
+    let shares = channel.shares;
+    channel.shares = 0;
+    channel.window_start = now;
+
+    let minutes = elapsed.as_secs_f64() / 60.0;
+    let shares_per_minute = shares as f64 / minutes;
+    let deviation = shares_per_minute / config.shares_per_minute - 1.0;
+    if deviation.abs() <= config.tolerance {
+        return None;
+    }
+
+    let target = if shares == 0 {
+        // Without shares the hash rate cannot be estimated, lower the
+        // difficulty by increasing the target.
+        channel.target.checked_mul_u64(4).unwrap_or(Target::MAX)
+    } else {
+        let hash_rate = channel.target.hash_rate(shares_per_minute);
+        Target::from_hash_rate(hash_rate, config.shares_per_minute).ok()?
+    };
+
+    let target = target.min(channel.max_target);
+    if target == channel.target {
+        return None;
+    }
+
+    channel.target = target;
+    Some(SetTarget::new(channel_id, target.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX_TARGET: U256 = [0xff; 32];
+
+    fn config() -> VardiffConfig {
+        VardiffConfig {
+            shares_per_minute: 10.0,
+            retarget_interval: Duration::from_secs(60),
+            tolerance: 0.2,
+        }
+    }
+
+    fn seconds(start: Instant, seconds: u64) -> Instant {
+        start + Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn invalid_config() {
+        let mut config = config();
+        config.shares_per_minute = 0.0;
+        assert!(VardiffController::new(config).is_err());
+
+        let mut config = self::config();
+        config.retarget_interval = Duration::from_secs(0);
+        assert!(VardiffController::new(config).is_err());
+
+        let mut config = self::config();
+        config.tolerance = -1.0;
+        assert!(VardiffController::new(config).is_err());
+    }
+
+    #[test]
+    fn initial_target_from_hash_rate() {
+        let start = Instant::now();
+        let mut vardiff = VardiffController::new(config()).unwrap();
+
+        let target = vardiff.add_channel(1, 1e12, MAX_TARGET, start).unwrap();
+        assert_eq!(
+            target,
+            U256::from(Target::from_hash_rate(f64::from(1e12f32), 10.0).unwrap())
+        );
+
+        // The target is capped at the max target of the channel.
+        let max_target = Target::from_difficulty(1e6).unwrap();
+        let target = vardiff
+            .add_channel(2, 1.0, max_target.into(), start)
+            .unwrap();
+        assert_eq!(target, U256::from(max_target));
+
+        let target = vardiff.add_channel(3, 0.0, MAX_TARGET, start).unwrap();
+        assert_eq!(target, MAX_TARGET);
+
+        assert!(vardiff.add_channel(4, -1.0, MAX_TARGET, start).is_err());
+    }
+
+    #[test]
+    fn stable_share_rate() {
+        let start = Instant::now();
+        let mut vardiff = VardiffController::new(config()).unwrap();
+        let target = vardiff.add_channel(1, 1e12, MAX_TARGET, start).unwrap();
+
+        // 11 shares per minute is within the tolerance of the goal.
+        for minute in 0..5 {
+            for share in 0..11 {
+                let now = seconds(start, minute * 60 + share * 5 + 5);
+                assert!(vardiff.on_share_accepted(1, now).is_none());
+            }
+        }
+
+        assert_eq!(vardiff.target(1), Some(target));
+    }
+
+    #[test]
+    fn retarget_faster_channel() {
+        let start = Instant::now();
+        let mut vardiff = VardiffController::new(config()).unwrap();
+        let target = Target::from(vardiff.add_channel(1, 1e12, MAX_TARGET, start).unwrap());
+
+        // The channel hashes 4 times faster than its nominal hash rate.
+        let mut set_target = None;
+        for share in 1..=40 {
+            let now = seconds(start, share * 3 / 2);
+            set_target = vardiff.on_share_accepted(1, now);
+        }
+
+        let set_target = set_target.unwrap();
+        assert_eq!(set_target.channel_id, 1);
+
+        let new_target = Target::from(set_target.maximum_target);
+        assert!(new_target < target);
+
+        let hash_rate = new_target.hash_rate(10.0);
+        assert!((hash_rate - 4e12).abs() / 4e12 < 1e-6);
+    }
+
+    #[test]
+    fn retarget_idle_channel() {
+        let start = Instant::now();
+        let mut vardiff = VardiffController::new(config()).unwrap();
+        let target = Target::from(vardiff.add_channel(1, 1e12, MAX_TARGET, start).unwrap());
+
+        assert!(vardiff.poll(seconds(start, 59)).is_empty());
+
+        let set_target = vardiff.poll(seconds(start, 60));
+        assert_eq!(set_target.len(), 1);
+        assert_eq!(
+            Target::from(set_target[0].maximum_target),
+            target.checked_mul_u64(4).unwrap()
+        );
+
+        // The window restarted, the channel is not retargeted until the next
+        // interval elapses.
+        assert!(vardiff.poll(seconds(start, 90)).is_empty());
+    }
+
+    #[test]
+    fn retarget_capped_at_max_target() {
+        let start = Instant::now();
+        let mut vardiff = VardiffController::new(config()).unwrap();
+
+        let max_target = Target::from_hash_rate(1e12, 10.0).unwrap();
+        vardiff
+            .add_channel(1, 1e12, max_target.into(), start)
+            .unwrap();
+
+        // An idle channel is already at its max target.
+        assert!(vardiff.poll(seconds(start, 60)).is_empty());
+        assert_eq!(vardiff.target(1), Some(max_target.into()));
+    }
+
+    #[test]
+    fn update_channel() {
+        let start = Instant::now();
+        let mut vardiff = VardiffController::new(config()).unwrap();
+        vardiff.add_channel(1, 1e12, MAX_TARGET, start).unwrap();
+
+        let message = UpdateChannel::new(1, 2e12, MAX_TARGET);
+        let set_target = vardiff
+            .on_update_channel(&message, seconds(start, 10))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            set_target.maximum_target,
+            U256::from(Target::from_hash_rate(f64::from(2e12f32), 10.0).unwrap())
+        );
+
+        // The same update does not change the target.
+        assert!(vardiff
+            .on_update_channel(&message, seconds(start, 20))
+            .unwrap()
+            .is_none());
+
+        // Lowering the maximum target below the current target.
+        let max_target = Target::from_hash_rate(4e12, 10.0).unwrap();
+        let message = UpdateChannel::new(1, 2e12, max_target.into());
+        let set_target = vardiff
+            .on_update_channel(&message, seconds(start, 30))
+            .unwrap()
+            .unwrap();
+        assert_eq!(set_target.maximum_target, U256::from(max_target));
+
+        let message = UpdateChannel::new(2, 2e12, MAX_TARGET);
+        assert!(vardiff.on_update_channel(&message, start).is_err());
+    }
+
+    #[test]
+    fn remove_channel() {
+        let start = Instant::now();
+        let mut vardiff = VardiffController::new(config()).unwrap();
+        vardiff.add_channel(1, 1e12, MAX_TARGET, start).unwrap();
+        vardiff.remove_channel(1);
+
+        assert!(vardiff.target(1).is_none());
+        assert!(vardiff.on_share_accepted(1, seconds(start, 60)).is_none());
+        assert!(vardiff.poll(seconds(start, 60)).is_empty());
+    }
+}
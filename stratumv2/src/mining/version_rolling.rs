@@ -0,0 +1,171 @@
+use crate::error::{Error, Result};
+use crate::mining::SetupConnectionSuccessFlags;
+use crate::types::FlagSet;
+
+/// VersionRolling describes the bits of the block header version a Client is
+/// allowed to roll. BIP320 reserves the 16 bits `0x1fffe000` of the version
+/// field as general purpose bits that can be used as extra nonce space.
+///
+/// # Examples
+///
+/// ```rust
+/// use stratumv2::mining::VersionRolling;
+///
+/// let version_rolling = VersionRolling::bip320();
+///
+/// let version = version_rolling.apply(0x2000_0000, 0xffff_ffff);
+/// assert_eq!(version, 0x3fff_e000);
+/// assert!(version_rolling.is_valid(0x2000_0000, version));
+///
+/// // Bits outside of the mask cannot be rolled.
+/// assert!(!version_rolling.is_valid(0x2000_0000, 0x2000_0001));
+/// assert!(!VersionRolling::fixed().is_valid(0x2000_0000, version));
+/// ```
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct VersionRolling {
+    mask: u32,
+}
+
+impl VersionRolling {
+    /// The general purpose bits of the version field defined by BIP320.
+    pub const BIP320_MASK: u32 = 0x1fff_e000;
+
+    /// Creates a VersionRolling allowing the bits of `mask` to be rolled.
+    ///
+    /// A RequirementError is returned if the mask contains bits outside of
+    /// the [BIP320_MASK](#associatedconstant.BIP320_MASK).
+    pub fn new(mask: u32) -> Result<VersionRolling> {
+        if mask & !VersionRolling::BIP320_MASK != 0 {
+            return Err(Error::RequirementError(
+                "the version rolling mask can only contain BIP320 bits".into(),
+            ));
+        }
+
+        Ok(VersionRolling { mask })
+    }
+
+    /// Allows every BIP320 general purpose bit to be rolled.
+    pub fn bip320() -> VersionRolling {
+        VersionRolling {
+            mask: VersionRolling::BIP320_MASK,
+        }
+    }
+
+    /// Does not allow any bit of the version to be rolled.
+    pub fn fixed() -> VersionRolling {
+        VersionRolling { mask: 0 }
+    }
+
+    /// Returns the version rolling of a connection from the flags of its
+    /// SetupConnectionSuccess. If the Server sent `RequiresFixedVersion` the
+    /// version cannot be rolled.
+    pub fn from_success_flags(flags: FlagSet<SetupConnectionSuccessFlags>) -> VersionRolling {
+        if flags.contains(SetupConnectionSuccessFlags::RequiresFixedVersion) {
+            VersionRolling::fixed()
+        } else {
+            VersionRolling::bip320()
+        }
+    }
+
+    /// Returns the version rolling of an extended job, which can only roll
+    /// the version if its `version_rolling_allowed` field is set.
+    pub fn for_job(self, version_rolling_allowed: bool) -> VersionRolling {
+        if version_rolling_allowed {
+            self
+        } else {
+            VersionRolling::fixed()
+        }
+    }
+
+    /// Returns the bits of the version that can be rolled.
+    pub fn mask(&self) -> u32 {
+        self.mask
+    }
+
+    /// Returns true if no bit of the version can be rolled.
+    pub fn is_fixed(&self) -> bool {
+        self.mask == 0
+    }
+
+    /// Replaces the bits of the job `version` allowed by the mask with the
+    /// `rolled_bits`.
+    pub fn apply(&self, version: u32, rolled_bits: u32) -> u32 {
+        (version & !self.mask) | (rolled_bits & self.mask)
+    }
+
+    /// Returns true if the version of a share only differs from the version
+    /// of its job in bits allowed by the mask.
+    pub fn is_valid(&self, job_version: u32, share_version: u32) -> bool {
+        (job_version ^ share_version) & !self.mask == 0
+    }
+}
+
+impl Default for VersionRolling {
+    fn default() -> Self {
+        VersionRolling::bip320()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_rolling_mask() {
+        assert!(VersionRolling::new(0x0000_e000).is_ok());
+        assert!(VersionRolling::new(VersionRolling::BIP320_MASK).is_ok());
+        assert!(VersionRolling::new(0x2000_0000).is_err());
+        assert!(VersionRolling::new(0x0000_0001).is_err());
+
+        assert_eq!(VersionRolling::default(), VersionRolling::bip320());
+        assert!(VersionRolling::fixed().is_fixed());
+    }
+
+    #[test]
+    fn apply_version_rolling() {
+        let version_rolling = VersionRolling::new(0x0000_e000).unwrap();
+
+        assert_eq!(version_rolling.apply(0x2000_0000, 0xffff_ffff), 0x2000_e000);
+        assert_eq!(version_rolling.apply(0x2000_e000, 0), 0x2000_0000);
+        assert_eq!(
+            VersionRolling::fixed().apply(0x2000_0000, 0xffff_ffff),
+            0x2000_0000
+        );
+    }
+
+    #[test]
+    fn validate_share_version() {
+        let version_rolling = VersionRolling::new(0x0000_e000).unwrap();
+
+        assert!(version_rolling.is_valid(0x2000_0000, 0x2000_0000));
+        assert!(version_rolling.is_valid(0x2000_0000, 0x2000_a000));
+        assert!(!version_rolling.is_valid(0x2000_0000, 0x2001_0000));
+        assert!(!version_rolling.is_valid(0x2000_0000, 0x0000_0000));
+
+        // The job version may have bits set inside the mask.
+        assert!(version_rolling.is_valid(0x2000_e000, 0x2000_0000));
+    }
+
+    #[test]
+    fn version_rolling_from_flags() {
+        assert_eq!(
+            VersionRolling::from_success_flags(FlagSet::empty()),
+            VersionRolling::bip320()
+        );
+        assert_eq!(
+            VersionRolling::from_success_flags(FlagSet::from([
+                SetupConnectionSuccessFlags::RequiresFixedVersion
+            ])),
+            VersionRolling::fixed()
+        );
+
+        assert_eq!(
+            VersionRolling::bip320().for_job(false),
+            VersionRolling::fixed()
+        );
+        assert_eq!(
+            VersionRolling::bip320().for_job(true),
+            VersionRolling::bip320()
+        );
+    }
+}
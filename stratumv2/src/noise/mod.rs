@@ -79,12 +79,7 @@ impl<'a> SignedCertificate<'a> {
         not_valid_after: u32,
         public_key: &'a StaticPublicKey,
     ) -> Result<SignedCertificate<'a>> {
-        if valid_from >= not_valid_after {
-            return Err(Error::RequirementError(
-                "the valid_from time cannot be greater than or equal to the not_valid_after time"
-                    .into(),
-            ));
-        }
+        check_validity_period(valid_from, not_valid_after)?;
 
         Ok(SignedCertificate {
             version,
@@ -94,6 +89,19 @@ impl<'a> SignedCertificate<'a> {
         })
     }
 }
+
+/// Checks a certificate becomes valid before it expires.
+fn check_validity_period(valid_from: u32, not_valid_after: u32) -> Result<()> {
+    if valid_from >= not_valid_after {
+        return Err(Error::RequirementError(
+            "the valid_from time cannot be greater than or equal to the not_valid_after time"
+                .into(),
+        ));
+    }
+
+    Ok(())
+}
+
 impl Serializable for SignedCertificate<'_> {
     fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<usize> {
         // This should NOT serialize the authority_public_key and signature.
@@ -172,13 +180,65 @@ impl Serializable for CertificateFormat<'_> {
 /// the full certificate and validate the remote static public key ("s") has
 /// been signed by the AuthorityKeyPair of the Mining Pool.
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "signature_hex::Fields"))]
 pub struct SignatureNoiseMessage {
     pub version: u16,
     pub valid_from: u32,
     pub not_valid_after: u32,
+    #[cfg_attr(feature = "serde", serde(with = "signature_hex"))]
     pub signature: Signature,
 }
 
+/// Hex encoding of the signature of a SignatureNoiseMessage for the `serde`
+/// feature.
+#[cfg(feature = "serde")]
+mod signature_hex {
+    use super::{check_validity_period, Signature, SignatureNoiseMessage};
+    use crate::error::Error;
+    use crate::util::hex;
+    use std::convert::TryFrom;
+
+    /// The unchecked fields of a deserialized SignatureNoiseMessage.
+    #[derive(serde::Deserialize)]
+    pub(crate) struct Fields {
+        version: u16,
+        valid_from: u32,
+        not_valid_after: u32,
+        #[serde(with = "self")]
+        signature: Signature,
+    }
+
+    impl TryFrom<Fields> for SignatureNoiseMessage {
+        type Error = Error;
+
+        fn try_from(fields: Fields) -> std::result::Result<SignatureNoiseMessage, Error> {
+            check_validity_period(fields.valid_from, fields.not_valid_after)?;
+
+            Ok(SignatureNoiseMessage {
+                version: fields.version,
+                valid_from: fields.valid_from,
+                not_valid_after: fields.not_valid_after,
+                signature: fields.signature,
+            })
+        }
+    }
+
+    pub(crate) fn serialize<S: serde::Serializer>(
+        signature: &Signature,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        hex::array::serialize(&signature.to_bytes(), serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Signature, D::Error> {
+        let bytes: [u8; 64] = hex::array::deserialize(deserializer)?;
+        Signature::try_from(&bytes[..]).map_err(serde::de::Error::custom)
+    }
+}
+
 impl SignatureNoiseMessage {
     pub fn new(cert: &SignedCertificate, signature: Signature) -> SignatureNoiseMessage {
         SignatureNoiseMessage {
@@ -377,4 +437,25 @@ mod tests {
 
         assert!(cert.verify().is_ok());
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn signature_noise_message_json() {
+        let (_, _, signature_noise_message) = setup_keys_and_signature();
+
+        let json = serde_json::to_value(&signature_noise_message).unwrap();
+        assert_eq!(json["version"], 0);
+        assert_eq!(
+            json["signature"],
+            crate::util::hex::encode(&signature_noise_message.signature.to_bytes())
+        );
+
+        let message: SignatureNoiseMessage = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(message, signature_noise_message);
+
+        // The certificate must become valid before it expires.
+        let mut json = json;
+        json["valid_from"] = json["not_valid_after"].clone();
+        assert!(serde_json::from_value::<SignatureNoiseMessage>(json).is_err());
+    }
 }
@@ -0,0 +1,1185 @@
+//! The proxy aggregates the standard channels of many mining devices into a
+//! single extended channel opened upstream:
+//! - Every downstream channel is assigned a part of the extranonce space of
+//!   the upstream channel.
+//! - Extended jobs received from upstream are sent downstream as standard
+//!   jobs, with the merkle root computed for the extranonce of each channel.
+//! - Shares submitted downstream are sent upstream as `SubmitSharesExtended`
+//!   and their acknowledgements are sent back to the submitting channel.
+//! - The nominal hash rate of the upstream channel is the sum of the
+//!   downstream channels.
+//!
+//! The [Proxy](struct.Proxy.html) is sans-IO, messages are passed in by the
+//! caller and the messages to send are returned.
+use crate::error::{Error, Result};
+use crate::mining::{
+    build_coinbase, coinbase_txid, merkle_root, validate_standard_share, CloseChannel,
+    DuplicateShareDetector, ExtranonceAllocator, Job, JobState, JobStore, NewExtendedMiningJob,
+    NewMiningJob, OpenExtendedMiningChannel, OpenExtendedMiningChannelError,
+    OpenExtendedMiningChannelSuccess, OpenMiningChannelErrorCodes, OpenStandardMiningChannel,
+    OpenStandardMiningChannelError, OpenStandardMiningChannelResponse,
+    OpenStandardMiningChannelSuccess, SetNewPrevHash, SetTarget, ShareAckAggregator, ShareOutcome,
+    ShareTracker, SubmitSharesError, SubmitSharesErrorCodes, SubmitSharesExtended,
+    SubmitSharesStandard, SubmitSharesSuccess, UpdateChannel, VersionRolling,
+};
+use crate::target::Target;
+use crate::types::U256;
+use crate::util::{ChannelIdAllocator, ChannelIdStrategy};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// ProxyConfig contains the parameters of the extended channel opened
+/// upstream by a [Proxy](struct.Proxy.html).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProxyConfig {
+    /// The user identity of the extended channel.
+    pub user_identity: String,
+
+    /// The number of bytes of extranonce used to number the downstream
+    /// channels.
+    pub downstream_prefix_size: u16,
+
+    /// The duration stale jobs are remembered, so late shares are rejected
+    /// as stale instead of unknown.
+    pub stale_window: Duration,
+
+    /// The interval the accepted shares of a downstream channel are
+    /// acknowledged.
+    pub ack_interval: Duration,
+
+    /// The bits of the version the downstream devices are allowed to roll.
+    pub version_rolling: VersionRolling,
+
+    /// The number of shares remembered to reject duplicates, see
+    /// [DuplicateShareDetector](../mining/struct.DuplicateShareDetector.html).
+    pub duplicate_capacity: usize,
+}
+
+impl ProxyConfig {
+    /// Creates a ProxyConfig for the `user_identity` of the upstream channel,
+    /// with a 4 byte downstream prefix, a 30 second stale window, a 1 second
+    /// ack interval, BIP320 version rolling and 65536 remembered shares.
+    pub fn new<T: Into<String>>(user_identity: T) -> ProxyConfig {
+        ProxyConfig {
+            user_identity: user_identity.into(),
+            downstream_prefix_size: 4,
+            stale_window: Duration::from_secs(30),
+            ack_interval: Duration::from_secs(1),
+            version_rolling: VersionRolling::bip320(),
+            duplicate_capacity: 1 << 16,
+        }
+    }
+}
+
+/// The response to a SubmitSharesStandard received from downstream. Valid
+/// shares are forwarded upstream, invalid shares are rejected by the proxy.
+#[derive(Debug, Clone)]
+pub enum SubmitSharesStandardResponse {
+    Forward(SubmitSharesExtended),
+    Error(SubmitSharesError),
+}
+
+/// The extended channel opened upstream.
+#[derive(Debug)]
+struct UpstreamChannel {
+    channel_id: u32,
+    target: U256,
+    extranonce_size: u16,
+    extranonce: ExtranonceAllocator,
+}
+
+/// A share forwarded upstream, waiting for its acknowledgement.
+#[derive(Debug)]
+struct PendingShare {
+    channel_id: u32,
+    sequence_number: u32,
+    difficulty: u64,
+}
+
+/// A standard channel opened by a downstream device.
+#[derive(Debug)]
+struct DownstreamChannel {
+    extranonce_prefix: Vec<u8>,
+    nominal_hash_rate: f32,
+    max_target: U256,
+    target: U256,
+}
+
+/// Proxy translates between the standard channels opened downstream and the
+/// extended channel opened upstream.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Instant;
+/// use stratumv2::mining::{
+///     NewExtendedMiningJob, OpenExtendedMiningChannelSuccess, OpenStandardMiningChannel,
+///     OpenStandardMiningChannelResponse, SetNewPrevHash, SubmitSharesStandard,
+/// };
+/// use stratumv2::proxy::{Proxy, ProxyConfig, SubmitSharesStandardResponse};
+///
+/// let now = Instant::now();
+/// let mut proxy = Proxy::new(ProxyConfig::new("farm")).unwrap();
+///
+/// // Open the extended channel upstream, it is reused by every device.
+/// let open_channel = proxy.open_extended_mining_channel(1).unwrap().unwrap();
+/// assert_eq!(open_channel.min_extranonce_size, 4);
+/// assert!(proxy.open_extended_mining_channel(2).unwrap().is_none());
+///
+/// let success = OpenExtendedMiningChannelSuccess::new(1, 7, [0xff; 32], 4, vec![0xaa]).unwrap();
+/// proxy.handle_open_extended_mining_channel_success(&success).unwrap();
+///
+/// // A device opens a standard channel and is assigned an extranonce prefix.
+/// let open_channel = OpenStandardMiningChannel::new(1, "device1", 1e12, [0xff; 32]).unwrap();
+/// let channel = match proxy.handle_open_standard_mining_channel(&open_channel).unwrap() {
+///     OpenStandardMiningChannelResponse::Success(success) => success,
+///     OpenStandardMiningChannelResponse::Error(_) => panic!("expected a success"),
+/// };
+/// assert_eq!(channel.extranonce_prefix, vec![0xaa, 0x00, 0x00, 0x00, 0x00]);
+///
+/// // The hash rate of the device is announced upstream.
+/// assert_eq!(proxy.update_channel().unwrap().nominal_hash_rate, 1e12);
+///
+/// // Extended jobs are sent to every device as standard jobs.
+/// let job = NewExtendedMiningJob::new(7, 1, true, 0x2000_0000, true, vec![], vec![], vec![]).unwrap();
+/// let jobs = proxy.handle_new_extended_mining_job(job, now).unwrap();
+/// assert_eq!(jobs[0].channel_id, channel.channel_id);
+///
+/// let prev_hash = SetNewPrevHash::new(7, 1, [0u8; 32], 1_600_000_000, 0x1d00_ffff);
+/// proxy.handle_set_new_prev_hash(prev_hash, now).unwrap();
+///
+/// // Shares are sent upstream with the extranonce of the device.
+/// let share = SubmitSharesStandard::new(channel.channel_id, 0, 1, 0, 1_600_000_000, 0x2000_0000);
+/// match proxy.handle_submit_shares_standard(&share).unwrap() {
+///     SubmitSharesStandardResponse::Forward(share) => {
+///         assert_eq!(share.channel_id, 7);
+///         assert_eq!(share.extranonce, vec![0x00, 0x00, 0x00, 0x00]);
+///     }
+///     SubmitSharesStandardResponse::Error(_) => panic!("expected the share to be forwarded"),
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Proxy {
+    config: ProxyConfig,
+    upstream: Option<UpstreamChannel>,
+    upstream_requested: bool,
+    announced_hash_rate: f32,
+    channel_ids: ChannelIdAllocator,
+    group_channel_id: u32,
+    downstreams: HashMap<u32, DownstreamChannel>,
+    jobs: JobStore,
+    shares: ShareTracker,
+    duplicates: DuplicateShareDetector,
+    pending_shares: HashMap<u32, PendingShare>,
+    acks: ShareAckAggregator,
+}
+
+impl Proxy {
+    /// Creates a Proxy. A RequirementError is returned if the
+    /// `downstream_prefix_size` is not between 1 and 8 bytes or if the
+    /// `duplicate_capacity` is 0.
+    pub fn new(config: ProxyConfig) -> Result<Proxy> {
+        if config.downstream_prefix_size == 0 || config.downstream_prefix_size > 8 {
+            return Err(Error::RequirementError(
+                "downstream_prefix_size must be between 1 and 8 bytes".into(),
+            ));
+        }
+
+        let mut channel_ids = ChannelIdAllocator::new(ChannelIdStrategy::Sequential);
+        let group_channel_id = channel_ids.allocate_group()?;
+
+        Ok(Proxy {
+            jobs: JobStore::new(config.stale_window),
+            duplicates: DuplicateShareDetector::new(config.duplicate_capacity)?,
+            acks: ShareAckAggregator::new(config.ack_interval),
+            config,
+            upstream: None,
+            upstream_requested: false,
+            announced_hash_rate: 0.0,
+            channel_ids,
+            group_channel_id,
+            downstreams: HashMap::new(),
+            shares: ShareTracker::new(),
+            pending_shares: HashMap::new(),
+        })
+    }
+
+    /// Returns the configuration of the Proxy.
+    pub fn config(&self) -> &ProxyConfig {
+        &self.config
+    }
+
+    /// Returns the id of the extended channel opened upstream.
+    pub fn channel_id(&self) -> Option<u32> {
+        self.upstream.as_ref().map(|upstream| upstream.channel_id)
+    }
+
+    /// Returns the number of open downstream channels.
+    pub fn downstream_channels(&self) -> usize {
+        self.downstreams.len()
+    }
+
+    /// Returns the combined nominal hash rate of the downstream channels.
+    pub fn nominal_hash_rate(&self) -> f32 {
+        self.downstreams
+            .values()
+            .map(|downstream| downstream.nominal_hash_rate)
+            .sum()
+    }
+
+    /// Returns the OpenExtendedMiningChannel to send upstream, or None if the
+    /// upstream channel is already open or requested, in which case it is
+    /// reused.
+    pub fn open_extended_mining_channel(
+        &mut self,
+        request_id: u32,
+    ) -> Result<Option<OpenExtendedMiningChannel>> {
+        if self.upstream.is_some() || self.upstream_requested {
+            return Ok(None);
+        }
+
+        let nominal_hash_rate = self.nominal_hash_rate();
+        let message = OpenExtendedMiningChannel::new(
+            request_id,
+            self.config.user_identity.as_str(),
+            nominal_hash_rate,
+            [0xff; 32],
+            self.config.downstream_prefix_size,
+        )?;
+
+        self.upstream_requested = true;
+        self.announced_hash_rate = nominal_hash_rate;
+        Ok(Some(message))
+    }
+
+    /// Applies the OpenExtendedMiningChannelSuccess received from upstream.
+    ///
+    /// A RequirementError is returned if the channel is already open or if
+    /// the extranonce space assigned by the Upstream is too small.
+    pub fn handle_open_extended_mining_channel_success(
+        &mut self,
+        message: &OpenExtendedMiningChannelSuccess,
+    ) -> Result<()> {
+        if self.upstream.is_some() {
+            return Err(Error::RequirementError(
+                "the upstream channel is already open".into(),
+            ));
+        }
+
+        if message.extranonce_size < self.config.downstream_prefix_size {
+            return Err(Error::RequirementError(
+                "the upstream extranonce space is too small for the downstream channels".into(),
+            ));
+        }
+
+        let extranonce = ExtranonceAllocator::with_upstream_prefix(
+            message.extranonce_prefix.0.clone(),
+            message.extranonce_size,
+            self.config.downstream_prefix_size,
+        )?;
+
+        self.shares.add_channel(message.channel_id);
+        self.upstream_requested = false;
+        self.upstream = Some(UpstreamChannel {
+            channel_id: message.channel_id,
+            target: message.target,
+            extranonce_size: message.extranonce_size,
+            extranonce,
+        });
+
+        Ok(())
+    }
+
+    /// Applies the OpenExtendedMiningChannelError received from upstream, so
+    /// the channel can be requested again.
+    pub fn handle_open_extended_mining_channel_error(
+        &mut self,
+        _message: &OpenExtendedMiningChannelError,
+    ) {
+        self.upstream_requested = false;
+    }
+
+    /// Opens a downstream standard channel, assigning its extranonce prefix.
+    /// The target of the channel is the upstream target, or the
+    /// `max_target` of the device if it is harder.
+    ///
+    /// The channel is rejected with a `min-extranonce-size-too-large` error
+    /// if the extranonce space or the channel ids are exhausted. A
+    /// RequirementError is returned if the upstream channel is not open.
+    pub fn handle_open_standard_mining_channel(
+        &mut self,
+        message: &OpenStandardMiningChannel,
+    ) -> Result<OpenStandardMiningChannelResponse> {
+        // The specification has no error code for a Server out of channels,
+        // the proxy cannot provide the extranonce space of another channel.
+        let exhausted = || {
+            Ok(OpenStandardMiningChannelResponse::Error(
+                OpenStandardMiningChannelError::new(
+                    message.request_id,
+                    OpenMiningChannelErrorCodes::MinExtranonceSizeTooLarge,
+                ),
+            ))
+        };
+
+        let upstream = self.upstream_mut()?;
+        let target = downstream_target(upstream.target, message.max_target);
+        let extranonce_prefix = match upstream.extranonce.allocate_standard() {
+            Ok(extranonce_prefix) => extranonce_prefix,
+            Err(_) => return exhausted(),
+        };
+
+        let channel_id = match self.channel_ids.allocate() {
+            Ok(channel_id) => channel_id,
+            Err(_) => {
+                self.upstream_mut()?.extranonce.release(&extranonce_prefix);
+                return exhausted();
+            }
+        };
+
+        self.downstreams.insert(
+            channel_id,
+            DownstreamChannel {
+                extranonce_prefix: extranonce_prefix.clone(),
+                nominal_hash_rate: message.nominal_hash_rate,
+                max_target: message.max_target,
+                target,
+            },
+        );
+
+        Ok(OpenStandardMiningChannelResponse::Success(
+            OpenStandardMiningChannelSuccess::new(
+                message.request_id,
+                channel_id,
+                target,
+                extranonce_prefix,
+                self.group_channel_id,
+            )?,
+        ))
+    }
+
+    /// Applies an UpdateChannel received from a downstream channel. Returns
+    /// the SetTarget to send to the channel if its target changed.
+    ///
+    /// A RequirementError is returned if the channel is not open.
+    pub fn handle_update_channel(&mut self, message: &UpdateChannel) -> Result<Option<SetTarget>> {
+        let upstream_target = self.upstream_mut()?.target;
+        let downstream = self.downstream_mut(message.channel_id)?;
+
+        downstream.nominal_hash_rate = message.nominal_hash_rate;
+        downstream.max_target = message.maximum_target;
+
+        let target = downstream_target(upstream_target, message.maximum_target);
+        if target == downstream.target {
+            return Ok(None);
+        }
+
+        downstream.target = target;
+        Ok(Some(SetTarget::new(message.channel_id, target)))
+    }
+
+    /// Closes a downstream channel, releasing its extranonce prefix and
+    /// discarding its pending shares.
+    ///
+    /// A RequirementError is returned if the channel is not open.
+    pub fn handle_close_channel(&mut self, message: &CloseChannel) -> Result<()> {
+        let channel_id = message.channel_id;
+        let downstream = self.downstreams.remove(&channel_id).ok_or_else(|| {
+            Error::RequirementError("cannot close a channel that is not open".into())
+        })?;
+
+        if let Some(upstream) = self.upstream.as_mut() {
+            upstream.extranonce.release(&downstream.extranonce_prefix);
+        }
+
+        self.channel_ids.free(channel_id);
+        self.acks.remove_channel(channel_id);
+        self.pending_shares
+            .retain(|_, share| share.channel_id != channel_id);
+
+        Ok(())
+    }
+
+    /// Returns the UpdateChannel to send upstream if the combined nominal
+    /// hash rate of the downstream channels changed since it was last
+    /// announced.
+    pub fn update_channel(&mut self) -> Option<UpdateChannel> {
+        let channel_id = self.channel_id()?;
+        let nominal_hash_rate = self.nominal_hash_rate();
+
+        if (nominal_hash_rate - self.announced_hash_rate).abs() <= f32::EPSILON {
+            return None;
+        }
+
+        self.announced_hash_rate = nominal_hash_rate;
+        Some(UpdateChannel::new(
+            channel_id,
+            nominal_hash_rate,
+            [0xff; 32],
+        ))
+    }
+
+    /// Returns the active job of a downstream channel as a future job,
+    /// followed by the SetNewPrevHash activating it. These are sent to a
+    /// channel after it is opened.
+    ///
+    /// A RequirementError is returned if the channel is not open.
+    pub fn current_job(&self, channel_id: u32) -> Result<Option<(NewMiningJob, SetNewPrevHash)>> {
+        let upstream = self.upstream()?;
+        let downstream = self
+            .downstreams
+            .get(&channel_id)
+            .ok_or_else(|| Error::RequirementError("the downstream channel is not open".into()))?;
+
+        let prev_hash = match self.jobs.prev_hash() {
+            Some(prev_hash) => prev_hash,
+            None => return Ok(None),
+        };
+
+        let job = match self.jobs.lookup(prev_hash.job_id).map(|lookup| lookup.job) {
+            Some(Job::Extended(job)) => job,
+            _ => return Ok(None),
+        };
+
+        let mut job = standard_job(job, upstream, channel_id, downstream)?;
+        job.future_job = true;
+
+        Ok(Some((job, copy_prev_hash(prev_hash, channel_id))))
+    }
+
+    /// Applies a NewExtendedMiningJob received from upstream. Returns the
+    /// NewMiningJob to send to every downstream channel.
+    pub fn handle_new_extended_mining_job(
+        &mut self,
+        job: NewExtendedMiningJob,
+        now: Instant,
+    ) -> Result<Vec<NewMiningJob>> {
+        self.check_channel_id(job.channel_id)?;
+
+        let upstream = self.upstream()?;
+        let messages = self
+            .downstreams
+            .iter()
+            .map(|(channel_id, downstream)| standard_job(&job, upstream, *channel_id, downstream))
+            .collect::<Result<Vec<NewMiningJob>>>()?;
+
+        self.jobs.add_job(job, now)?;
+        Ok(messages)
+    }
+
+    /// Applies a SetNewPrevHash received from upstream. Returns the
+    /// SetNewPrevHash to send to every downstream channel. Jobs stale for
+    /// longer than the `stale_window` are forgotten.
+    pub fn handle_set_new_prev_hash(
+        &mut self,
+        prev_hash: SetNewPrevHash,
+        now: Instant,
+    ) -> Result<Vec<SetNewPrevHash>> {
+        self.check_channel_id(prev_hash.channel_id)?;
+
+        let messages = self
+            .downstreams
+            .keys()
+            .map(|channel_id| copy_prev_hash(&prev_hash, *channel_id))
+            .collect();
+
+        self.jobs.set_new_prev_hash(prev_hash, now)?;
+        self.jobs.expire(now);
+        self.duplicates.reset();
+        Ok(messages)
+    }
+
+    /// Applies a SetTarget received from upstream. Returns the SetTarget to
+    /// send to every downstream channel whose target changed.
+    pub fn handle_set_target(&mut self, message: &SetTarget) -> Result<Vec<SetTarget>> {
+        self.check_channel_id(message.channel_id)?;
+
+        let upstream = self.upstream_mut()?;
+        upstream.target = message.maximum_target;
+
+        Ok(self
+            .downstreams
+            .iter_mut()
+            .filter_map(|(channel_id, downstream)| {
+                let target = downstream_target(message.maximum_target, downstream.max_target);
+                if target == downstream.target {
+                    return None;
+                }
+
+                downstream.target = target;
+                Some(SetTarget::new(*channel_id, target))
+            })
+            .collect())
+    }
+
+    /// Translates a SubmitSharesStandard received from a downstream channel
+    /// into the SubmitSharesExtended to send upstream. The extranonce of the
+    /// share is the extranonce prefix of the channel, without the prefix of
+    /// the upstream channel.
+    ///
+    /// Shares are validated against the target of the downstream channel
+    /// before they are forwarded. Shares on an unknown channel or job, stale
+    /// shares, duplicates and shares that do not meet the target are
+    /// rejected with a SubmitSharesError. A RequirementError is returned if
+    /// the upstream channel is not open.
+    pub fn handle_submit_shares_standard(
+        &mut self,
+        share: &SubmitSharesStandard,
+    ) -> Result<SubmitSharesStandardResponse> {
+        let upstream = self.upstream()?;
+        let channel_id = upstream.channel_id;
+        let upstream_prefix_size = upstream.extranonce.upstream_prefix().len();
+
+        let reject = |error_code| {
+            Ok(SubmitSharesStandardResponse::Error(SubmitSharesError::new(
+                share.channel_id,
+                share.sequence_number,
+                error_code,
+            )))
+        };
+
+        let downstream = match self.downstreams.get(&share.channel_id) {
+            Some(downstream) => downstream,
+            None => return reject(SubmitSharesErrorCodes::InvalidChannelId),
+        };
+
+        let (job, prev_hash) = match self.jobs.lookup(share.job_id) {
+            Some(lookup) if lookup.state != JobState::Active => {
+                return reject(SubmitSharesErrorCodes::StaleShare)
+            }
+            Some(lookup) => match (lookup.job, lookup.prev_hash) {
+                (Job::Extended(job), Some(prev_hash)) => (job, prev_hash),
+                _ => return reject(SubmitSharesErrorCodes::InvalidJobId),
+            },
+            None => return reject(SubmitSharesErrorCodes::InvalidJobId),
+        };
+
+        let version_rolling = self
+            .config
+            .version_rolling
+            .for_job(job.version_rolling_allowed);
+        let job = standard_job(job, upstream, share.channel_id, downstream)?;
+        let outcome =
+            validate_standard_share(&job, prev_hash, share, version_rolling, &downstream.target)?;
+
+        match outcome {
+            ShareOutcome::Valid { .. } | ShareOutcome::BlockFound { .. } => (),
+            ShareOutcome::LowDifficulty { .. } => {
+                return reject(SubmitSharesErrorCodes::DifficultyTooLow)
+            }
+            ShareOutcome::Stale => return reject(SubmitSharesErrorCodes::StaleShare),
+            // The specification has no error code for an invalid version or
+            // nTime, the share does not match the job it references.
+            ShareOutcome::InvalidVersion | ShareOutcome::InvalidTime => {
+                return reject(SubmitSharesErrorCodes::InvalidJobId)
+            }
+        }
+
+        let extranonce = downstream.extranonce_prefix[upstream_prefix_size..].to_vec();
+        let difficulty = (Target::from(downstream.target).pool_difficulty() as u64).max(1);
+
+        if let Some(error) = self.duplicates.check_standard(share) {
+            return Ok(SubmitSharesStandardResponse::Error(error));
+        }
+
+        let sequence_number = self.shares.next_sequence_number(channel_id)?;
+        self.pending_shares.insert(
+            sequence_number,
+            PendingShare {
+                channel_id: share.channel_id,
+                sequence_number: share.sequence_number,
+                difficulty,
+            },
+        );
+
+        Ok(SubmitSharesStandardResponse::Forward(
+            SubmitSharesExtended::new(
+                channel_id,
+                sequence_number,
+                share.job_id,
+                share.nonce,
+                share.ntime,
+                share.version,
+                extranonce,
+            )?,
+        ))
+    }
+
+    /// Applies a SubmitSharesSuccess received from upstream. The accepted
+    /// shares are acknowledged to their downstream channels by
+    /// [poll_acks](#method.poll_acks), at the difficulty of the downstream
+    /// channel when the share was submitted.
+    pub fn handle_submit_shares_success(
+        &mut self,
+        message: &SubmitSharesSuccess,
+        now: Instant,
+    ) -> Result<()> {
+        self.check_channel_id(message.channel_id)?;
+        self.shares.on_success(message)?;
+
+        let last_sequence_number = message.last_sequence_number;
+        let accepted: Vec<u32> = self
+            .pending_shares
+            .keys()
+            .filter(|sequence_number| {
+                last_sequence_number.wrapping_sub(**sequence_number) < 1 << 31
+            })
+            .copied()
+            .collect();
+
+        for sequence_number in accepted {
+            if let Some(share) = self.pending_shares.remove(&sequence_number) {
+                self.acks.on_share_accepted(
+                    share.channel_id,
+                    share.sequence_number,
+                    share.difficulty,
+                    now,
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Applies a SubmitSharesError received from upstream. Returns the
+    /// SubmitSharesError to send to the downstream channel that submitted the
+    /// rejected share, if it is still open. The pending acknowledgements of
+    /// the channel SHOULD be sent first, see [flush_acks](#method.flush_acks).
+    pub fn handle_submit_shares_error(
+        &mut self,
+        message: &SubmitSharesError,
+    ) -> Result<Option<SubmitSharesError>> {
+        self.check_channel_id(message.channel_id)?;
+        self.shares.on_error(message)?;
+
+        Ok(self
+            .pending_shares
+            .remove(&message.sequence_number)
+            .map(|share| {
+                SubmitSharesError::new(share.channel_id, share.sequence_number, message.error_code)
+            }))
+    }
+
+    /// Returns the SubmitSharesSuccess to send to every downstream channel
+    /// whose accepted shares are due to be acknowledged.
+    pub fn poll_acks(&mut self, now: Instant) -> Vec<SubmitSharesSuccess> {
+        self.acks.poll(now)
+    }
+
+    /// Returns the SubmitSharesSuccess of the accepted shares of a
+    /// downstream channel immediately.
+    pub fn flush_acks(&mut self, channel_id: u32) -> Option<SubmitSharesSuccess> {
+        self.acks.flush(channel_id)
+    }
+
+    fn upstream(&self) -> Result<&UpstreamChannel> {
+        self.upstream
+            .as_ref()
+            .ok_or_else(|| Error::RequirementError("the upstream channel is not open".into()))
+    }
+
+    fn upstream_mut(&mut self) -> Result<&mut UpstreamChannel> {
+        self.upstream
+            .as_mut()
+            .ok_or_else(|| Error::RequirementError("the upstream channel is not open".into()))
+    }
+
+    fn downstream_mut(&mut self, channel_id: u32) -> Result<&mut DownstreamChannel> {
+        self.downstreams
+            .get_mut(&channel_id)
+            .ok_or_else(|| Error::RequirementError("the downstream channel is not open".into()))
+    }
+
+    fn check_channel_id(&self, channel_id: u32) -> Result<()> {
+        match self.channel_id() {
+            Some(id) if id == channel_id => Ok(()),
+            _ => Err(Error::RequirementError(
+                "the message is not for the upstream channel".into(),
+            )),
+        }
+    }
+}
+
+/// Returns the harder of the upstream target and the maximum target of a
+/// downstream device.
+fn downstream_target(upstream_target: U256, max_target: U256) -> U256 {
+    if Target::from(max_target) < Target::from(upstream_target) {
+        max_target
+    } else {
+        upstream_target
+    }
+}
+
+/// Builds the standard job of a downstream channel from an extended job, the
+/// merkle root committing to the extranonce prefix of the channel.
+fn standard_job(
+    job: &NewExtendedMiningJob,
+    upstream: &UpstreamChannel,
+    channel_id: u32,
+    downstream: &DownstreamChannel,
+) -> Result<NewMiningJob> {
+    let upstream_prefix = upstream.extranonce.upstream_prefix();
+    let coinbase = build_coinbase(
+        &job.coinbase_tx_prefix.0,
+        upstream_prefix,
+        &downstream.extranonce_prefix[upstream_prefix.len()..],
+        upstream.extranonce_size,
+        &job.coinbase_tx_suffix.0,
+    )?;
+
+    Ok(NewMiningJob::new(
+        channel_id,
+        job.job_id,
+        job.future_job,
+        job.version,
+        merkle_root(&coinbase_txid(&coinbase), &job.merkle_path),
+    ))
+}
+
+fn copy_prev_hash(prev_hash: &SetNewPrevHash, channel_id: u32) -> SetNewPrevHash {
+    SetNewPrevHash::new(
+        channel_id,
+        prev_hash.job_id,
+        prev_hash.prev_hash,
+        prev_hash.min_ntime,
+        prev_hash.nbits,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open_proxy() -> Proxy {
+        let mut proxy = Proxy::new(ProxyConfig::new("farm")).unwrap();
+        proxy.open_extended_mining_channel(1).unwrap().unwrap();
+
+        let success =
+            OpenExtendedMiningChannelSuccess::new(1, 7, [0xff; 32], 6, vec![0xaa, 0xbb]).unwrap();
+        proxy
+            .handle_open_extended_mining_channel_success(&success)
+            .unwrap();
+
+        proxy
+    }
+
+    fn open_channel(proxy: &mut Proxy, hash_rate: f32) -> OpenStandardMiningChannelSuccess {
+        let message = OpenStandardMiningChannel::new(1, "device", hash_rate, [0xff; 32]).unwrap();
+
+        match proxy.handle_open_standard_mining_channel(&message).unwrap() {
+            OpenStandardMiningChannelResponse::Success(success) => success,
+            OpenStandardMiningChannelResponse::Error(_) => panic!("expected a success"),
+        }
+    }
+
+    fn extended_job(job_id: u32, future_job: bool) -> NewExtendedMiningJob {
+        NewExtendedMiningJob::new(
+            7,
+            job_id,
+            future_job,
+            0x2000_0000,
+            true,
+            vec![[0x11; 32]],
+            vec![0x01, 0x02],
+            vec![0x03, 0x04],
+        )
+        .unwrap()
+    }
+
+    fn prev_hash(job_id: u32) -> SetNewPrevHash {
+        SetNewPrevHash::new(7, job_id, [0x22; 32], 1_600_000_000, 0x1d00_ffff)
+    }
+
+    fn activate_job(proxy: &mut Proxy, job_id: u32, now: Instant) {
+        proxy
+            .handle_new_extended_mining_job(extended_job(job_id, true), now)
+            .unwrap();
+        proxy
+            .handle_set_new_prev_hash(prev_hash(job_id), now)
+            .unwrap();
+    }
+
+    fn forward(proxy: &mut Proxy, share: &SubmitSharesStandard) -> SubmitSharesExtended {
+        match proxy.handle_submit_shares_standard(share).unwrap() {
+            SubmitSharesStandardResponse::Forward(share) => share,
+            SubmitSharesStandardResponse::Error(_) => panic!("expected the share to be forwarded"),
+        }
+    }
+
+    fn reject(proxy: &mut Proxy, share: &SubmitSharesStandard) -> SubmitSharesErrorCodes {
+        match proxy.handle_submit_shares_standard(share).unwrap() {
+            SubmitSharesStandardResponse::Forward(_) => panic!("expected the share to be rejected"),
+            SubmitSharesStandardResponse::Error(error) => error.error_code,
+        }
+    }
+
+    #[test]
+    fn open_upstream_channel() {
+        let mut proxy = Proxy::new(ProxyConfig::new("farm")).unwrap();
+
+        // Downstream channels cannot be opened before the upstream channel.
+        let message = OpenStandardMiningChannel::new(1, "device", 1.0, [0xff; 32]).unwrap();
+        assert!(proxy.handle_open_standard_mining_channel(&message).is_err());
+
+        let open_channel = proxy.open_extended_mining_channel(1).unwrap().unwrap();
+        assert_eq!(open_channel.nominal_hash_rate, 0.0);
+        assert!(proxy.open_extended_mining_channel(2).unwrap().is_none());
+
+        // The channel can be requested again after an error.
+        proxy.handle_open_extended_mining_channel_error(&OpenExtendedMiningChannelError::new(
+            1,
+            crate::mining::OpenMiningChannelErrorCodes::MinExtranonceSizeTooLarge,
+        ));
+        assert!(proxy.open_extended_mining_channel(3).unwrap().is_some());
+
+        // The upstream extranonce space must fit the downstream prefixes.
+        let success = OpenExtendedMiningChannelSuccess::new(3, 7, [0xff; 32], 2, vec![]).unwrap();
+        assert!(proxy
+            .handle_open_extended_mining_channel_success(&success)
+            .is_err());
+
+        assert!(Proxy::new(ProxyConfig {
+            downstream_prefix_size: 0,
+            ..ProxyConfig::new("farm")
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn open_downstream_channels() {
+        let mut proxy = open_proxy();
+
+        let first = open_channel(&mut proxy, 1e12);
+        let second = open_channel(&mut proxy, 2e12);
+        assert_ne!(first.channel_id, second.channel_id);
+        assert_eq!(first.group_channel_id, second.group_channel_id);
+        assert_eq!(
+            first.extranonce_prefix,
+            vec![0xaa, 0xbb, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]
+        );
+        assert_eq!(
+            second.extranonce_prefix,
+            vec![0xaa, 0xbb, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00]
+        );
+        assert_eq!(proxy.downstream_channels(), 2);
+
+        // The extranonce prefix of a closed channel is reused.
+        proxy
+            .handle_close_channel(&CloseChannel::new(first.channel_id, "").unwrap())
+            .unwrap();
+        assert!(proxy
+            .handle_close_channel(&CloseChannel::new(first.channel_id, "").unwrap())
+            .is_err());
+        assert_eq!(
+            open_channel(&mut proxy, 1e12).extranonce_prefix,
+            first.extranonce_prefix.0
+        );
+    }
+
+    #[test]
+    fn aggregate_hash_rate() {
+        let mut proxy = open_proxy();
+        assert!(proxy.update_channel().is_none());
+
+        let first = open_channel(&mut proxy, 1e12);
+        open_channel(&mut proxy, 2e12);
+
+        let update = proxy.update_channel().unwrap();
+        assert_eq!(update.channel_id, 7);
+        assert_eq!(update.nominal_hash_rate, 3e12);
+        assert!(proxy.update_channel().is_none());
+
+        let update = UpdateChannel::new(first.channel_id, 2e12, [0xff; 32]);
+        assert!(proxy.handle_update_channel(&update).unwrap().is_none());
+        assert_eq!(proxy.update_channel().unwrap().nominal_hash_rate, 4e12);
+
+        proxy
+            .handle_close_channel(&CloseChannel::new(first.channel_id, "").unwrap())
+            .unwrap();
+        assert_eq!(proxy.update_channel().unwrap().nominal_hash_rate, 2e12);
+    }
+
+    #[test]
+    fn translate_jobs() {
+        let now = Instant::now();
+        let mut proxy = open_proxy();
+        let first = open_channel(&mut proxy, 1e12);
+        open_channel(&mut proxy, 1e12);
+
+        let mut jobs = proxy
+            .handle_new_extended_mining_job(extended_job(1, true), now)
+            .unwrap();
+        jobs.sort_by_key(|job| job.channel_id);
+        assert_eq!(jobs.len(), 2);
+        assert_eq!(jobs[0].channel_id, first.channel_id);
+        assert_eq!(jobs[0].job_id, 1);
+        assert!(jobs[0].future_job);
+
+        // The merkle root commits to the extranonce prefix of each channel.
+        let coinbase = [
+            &[0x01, 0x02][..],
+            &first.extranonce_prefix.0,
+            &[0x03, 0x04][..],
+        ]
+        .concat();
+        assert_eq!(
+            jobs[0].merkle_root,
+            merkle_root(&coinbase_txid(&coinbase), &[[0x11; 32]])
+        );
+        assert_ne!(jobs[0].merkle_root, jobs[1].merkle_root);
+
+        let prev_hashes = proxy.handle_set_new_prev_hash(prev_hash(1), now).unwrap();
+        assert_eq!(prev_hashes.len(), 2);
+        assert!(prev_hashes.iter().all(|prev_hash| prev_hash.job_id == 1));
+
+        // A channel opened later receives the active job.
+        let third = open_channel(&mut proxy, 1e12);
+        let (job, prev_hash) = proxy.current_job(third.channel_id).unwrap().unwrap();
+        assert_eq!(job.channel_id, third.channel_id);
+        assert!(job.future_job);
+        assert_eq!(prev_hash.channel_id, third.channel_id);
+        assert_eq!(prev_hash.job_id, job.job_id);
+
+        // Messages must be sent on the upstream channel.
+        assert!(proxy
+            .handle_new_extended_mining_job(
+                NewExtendedMiningJob::new(8, 2, true, 0, true, vec![], vec![], vec![]).unwrap(),
+                now
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn translate_targets() {
+        let mut proxy = open_proxy();
+        let easy = open_channel(&mut proxy, 1e12);
+
+        let hard_target = Target::from_pool_difficulty(1024.0).unwrap().to_le_bytes();
+        let message = OpenStandardMiningChannel::new(2, "device", 1e12, hard_target).unwrap();
+        let hard = match proxy.handle_open_standard_mining_channel(&message).unwrap() {
+            OpenStandardMiningChannelResponse::Success(success) => success,
+            OpenStandardMiningChannelResponse::Error(_) => panic!("expected a success"),
+        };
+        assert_eq!(easy.target, [0xff; 32]);
+        assert_eq!(hard.target, hard_target);
+
+        // Only the channels whose target changed are updated.
+        let target = Target::from_pool_difficulty(16.0).unwrap().to_le_bytes();
+        let updates = proxy.handle_set_target(&SetTarget::new(7, target)).unwrap();
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].channel_id, easy.channel_id);
+        assert_eq!(updates[0].maximum_target, target);
+    }
+
+    #[test]
+    fn forward_shares() {
+        let now = Instant::now();
+        let mut proxy = open_proxy();
+        let channel = open_channel(&mut proxy, 1e12);
+        activate_job(&mut proxy, 1, now);
+
+        let share =
+            SubmitSharesStandard::new(channel.channel_id, 5, 1, 0xdead, 1_600_000_000, 0x2000_0000);
+        let forwarded = forward(&mut proxy, &share);
+        assert_eq!(forwarded.channel_id, 7);
+        assert_eq!(forwarded.sequence_number, 0);
+        assert_eq!(forwarded.job_id, 1);
+        assert_eq!(forwarded.nonce, 0xdead);
+        assert_eq!(
+            forwarded.extranonce,
+            vec![0x00, 0x00, 0x00, 0x00, 0x00, 0x00]
+        );
+
+        let share =
+            SubmitSharesStandard::new(channel.channel_id, 6, 1, 0xbeef, 1_600_000_000, 0x2000_0000);
+        assert_eq!(forward(&mut proxy, &share).sequence_number, 1);
+
+        // Accepted shares are acknowledged with the downstream sequence numbers.
+        proxy
+            .handle_submit_shares_success(&SubmitSharesSuccess::new(7, 1, 2, 2), now)
+            .unwrap();
+        let acks = proxy.poll_acks(now + Duration::from_secs(1));
+        assert_eq!(acks.len(), 1);
+        assert_eq!(acks[0].channel_id, channel.channel_id);
+        assert_eq!(acks[0].last_sequence_number, 6);
+        assert_eq!(acks[0].new_submits_accepted_count, 2);
+
+        // Rejected shares are returned to the submitting channel.
+        let share =
+            SubmitSharesStandard::new(channel.channel_id, 7, 1, 0xf00d, 1_600_000_000, 0x2000_0000);
+        let forwarded = forward(&mut proxy, &share);
+        let error = proxy
+            .handle_submit_shares_error(&SubmitSharesError::new(
+                7,
+                forwarded.sequence_number,
+                SubmitSharesErrorCodes::DifficultyTooLow,
+            ))
+            .unwrap()
+            .unwrap();
+        assert_eq!(error.channel_id, channel.channel_id);
+        assert_eq!(error.sequence_number, 7);
+        assert_eq!(error.error_code, SubmitSharesErrorCodes::DifficultyTooLow);
+    }
+
+    #[test]
+    fn reject_shares() {
+        let now = Instant::now();
+        let mut proxy = open_proxy();
+        let channel = open_channel(&mut proxy, 1e12);
+        activate_job(&mut proxy, 1, now);
+
+        let share = SubmitSharesStandard::new(99, 0, 1, 0, 1_600_000_000, 0x2000_0000);
+        assert_eq!(
+            reject(&mut proxy, &share),
+            SubmitSharesErrorCodes::InvalidChannelId
+        );
+
+        let share =
+            SubmitSharesStandard::new(channel.channel_id, 0, 9, 0, 1_600_000_000, 0x2000_0000);
+        assert_eq!(
+            reject(&mut proxy, &share),
+            SubmitSharesErrorCodes::InvalidJobId
+        );
+
+        let share = SubmitSharesStandard::new(channel.channel_id, 0, 1, 0, 1, 0x2000_0000);
+        assert_eq!(
+            reject(&mut proxy, &share),
+            SubmitSharesErrorCodes::InvalidJobId
+        );
+
+        let share =
+            SubmitSharesStandard::new(channel.channel_id, 0, 1, 0, 1_600_000_000, 0x2000_0001);
+        assert_eq!(
+            reject(&mut proxy, &share),
+            SubmitSharesErrorCodes::InvalidJobId
+        );
+
+        let share =
+            SubmitSharesStandard::new(channel.channel_id, 1, 1, 0, 1_600_000_000, 0x2000_0000);
+        forward(&mut proxy, &share);
+        let share =
+            SubmitSharesStandard::new(channel.channel_id, 2, 1, 0, 1_600_000_000, 0x2000_0000);
+        assert_eq!(
+            reject(&mut proxy, &share),
+            SubmitSharesErrorCodes::DuplicateShare
+        );
+
+        // Shares are validated against the target of the downstream channel.
+        let hard_target = Target::from_pool_difficulty(1024.0).unwrap().to_le_bytes();
+        let message = OpenStandardMiningChannel::new(2, "device", 1e12, hard_target).unwrap();
+        let hard = match proxy.handle_open_standard_mining_channel(&message).unwrap() {
+            OpenStandardMiningChannelResponse::Success(success) => success,
+            OpenStandardMiningChannelResponse::Error(_) => panic!("expected a success"),
+        };
+        let share = SubmitSharesStandard::new(hard.channel_id, 0, 1, 0, 1_600_000_000, 0x2000_0000);
+        assert_eq!(
+            reject(&mut proxy, &share),
+            SubmitSharesErrorCodes::DifficultyTooLow
+        );
+
+        activate_job(&mut proxy, 2, now);
+        let share =
+            SubmitSharesStandard::new(channel.channel_id, 0, 1, 0, 1_600_000_000, 0x2000_0000);
+        assert_eq!(
+            reject(&mut proxy, &share),
+            SubmitSharesErrorCodes::StaleShare
+        );
+        // Jobs stale for longer than the stale window are unknown.
+        activate_job(&mut proxy, 3, now + Duration::from_secs(31));
+        assert_eq!(
+            reject(&mut proxy, &share),
+            SubmitSharesErrorCodes::InvalidJobId
+        );
+    }
+
+    #[test]
+    fn reject_rolled_versions_when_not_allowed() {
+        let now = Instant::now();
+        let mut proxy = open_proxy();
+        let channel = open_channel(&mut proxy, 1e12);
+
+        let job = NewExtendedMiningJob::new(
+            7,
+            1,
+            true,
+            0x2000_0000,
+            false,
+            vec![[0x11; 32]],
+            vec![0x01, 0x02],
+            vec![0x03, 0x04],
+        )
+        .unwrap();
+        proxy.handle_new_extended_mining_job(job, now).unwrap();
+        proxy.handle_set_new_prev_hash(prev_hash(1), now).unwrap();
+
+        // The upstream job does not allow the version to be rolled.
+        let share =
+            SubmitSharesStandard::new(channel.channel_id, 0, 1, 0, 1_600_000_000, 0x2000_2000);
+        assert_eq!(
+            reject(&mut proxy, &share),
+            SubmitSharesErrorCodes::InvalidJobId
+        );
+
+        let share =
+            SubmitSharesStandard::new(channel.channel_id, 1, 1, 0, 1_600_000_000, 0x2000_0000);
+        forward(&mut proxy, &share);
+
+        // Jobs allowing version rolling accept the BIP320 bits.
+        activate_job(&mut proxy, 2, now);
+        let share =
+            SubmitSharesStandard::new(channel.channel_id, 2, 2, 0, 1_600_000_000, 0x2000_2000);
+        forward(&mut proxy, &share);
+    }
+
+    #[test]
+    fn ack_at_submitted_difficulty() {
+        let now = Instant::now();
+        let mut proxy = open_proxy();
+        let channel = open_channel(&mut proxy, 1e12);
+        activate_job(&mut proxy, 1, now);
+
+        let share =
+            SubmitSharesStandard::new(channel.channel_id, 0, 1, 0, 1_600_000_000, 0x2000_0000);
+        forward(&mut proxy, &share);
+
+        // The target of the channel changes before the share is accepted.
+        let target = Target::from_pool_difficulty(16.0).unwrap().to_le_bytes();
+        proxy.handle_set_target(&SetTarget::new(7, target)).unwrap();
+
+        proxy
+            .handle_submit_shares_success(&SubmitSharesSuccess::new(7, 0, 1, 1), now)
+            .unwrap();
+        let ack = proxy.flush_acks(channel.channel_id).unwrap();
+        assert_eq!(ack.new_shares_sum, 1);
+    }
+
+    #[test]
+    fn reject_channels_when_exhausted() {
+        let mut proxy = Proxy::new(ProxyConfig {
+            downstream_prefix_size: 1,
+            ..ProxyConfig::new("farm")
+        })
+        .unwrap();
+        proxy.open_extended_mining_channel(1).unwrap().unwrap();
+
+        let success = OpenExtendedMiningChannelSuccess::new(1, 7, [0xff; 32], 1, vec![]).unwrap();
+        proxy
+            .handle_open_extended_mining_channel_success(&success)
+            .unwrap();
+
+        for _ in 0..256 {
+            open_channel(&mut proxy, 1e12);
+        }
+
+        let message = OpenStandardMiningChannel::new(9, "device", 1e12, [0xff; 32]).unwrap();
+        match proxy.handle_open_standard_mining_channel(&message).unwrap() {
+            OpenStandardMiningChannelResponse::Success(_) => panic!("expected an error"),
+            OpenStandardMiningChannelResponse::Error(error) => {
+                assert_eq!(error.request_id, 9);
+                assert_eq!(
+                    error.error_code,
+                    OpenMiningChannelErrorCodes::MinExtranonceSizeTooLarge
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,504 @@
+//! A target is sent in messages as a `U256`, the little endian byte
+//! representation of a 256-bit unsigned integer. A hash meets a target if its
+//! little endian integer value is less than or equal to the target.
+//! [Target](struct.Target.html) provides the conversions between the byte
+//! representation, the compact `nBits` representation used in block headers
+//! and floating-point difficulties, so that a channel target can be computed
+//! from a `nominal_hash_rate`.
+use crate::error::{Error, Result};
+use crate::types::U256;
+use std::cmp::Ordering;
+use std::fmt;
+
+/// The number of u64 limbs in a Target.
+const LIMBS: usize = 4;
+
+/// Target is a 256-bit unsigned integer stored as little endian u64 limbs.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Target([u64; LIMBS]);
+
+impl Target {
+    /// The zero target, which can only be met by a zero hash.
+    pub const ZERO: Target = Target([0; LIMBS]);
+
+    /// The maximum target, which is met by every hash.
+    pub const MAX: Target = Target([u64::MAX; LIMBS]);
+
+    /// The target of difficulty 1 as used by the network, encoded as
+    /// `0x1d00ffff` in compact form.
+    pub const DIFFICULTY_1: Target = Target([0, 0, 0, 0x0000_0000_ffff_0000]);
+
+    /// The target of a difficulty 1 share as used by pools,
+    /// `0x00000000ffffffff...ffff`.
+    pub const POOL_DIFFICULTY_1: Target =
+        Target([u64::MAX, u64::MAX, u64::MAX, 0x0000_0000_ffff_ffff]);
+
+    /// Creates a Target from its little endian byte representation.
+    pub fn from_le_bytes(bytes: U256) -> Target {
+        let mut limbs = [0u64; LIMBS];
+        for (i, limb) in limbs.iter_mut().enumerate() {
+            let mut chunk = [0u8; 8];
+            chunk.copy_from_slice(&bytes[i * 8..(i + 1) * 8]);
+            *limb = u64::from_le_bytes(chunk);
+        }
+
+        Target(limbs)
+    }
+
+    /// Returns the little endian byte representation of the Target, as sent in
+    /// messages.
+    pub fn to_le_bytes(&self) -> U256 {
+        let mut bytes = [0u8; 32];
+        for (i, limb) in self.0.iter().enumerate() {
+            bytes[i * 8..(i + 1) * 8].copy_from_slice(&limb.to_le_bytes());
+        }
+
+        bytes
+    }
+
+    /// Decodes the compact `nBits` representation of a target.
+    ///
+    /// A RequirementError is returned if the encoded target is negative or
+    /// does not fit in 256 bits.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use stratumv2::target::Target;
+    ///
+    /// let target = Target::from_compact(0x1d00ffff).unwrap();
+    /// assert_eq!(target, Target::DIFFICULTY_1);
+    /// assert_eq!(target.to_compact(), 0x1d00ffff);
+    /// ```
+    pub fn from_compact(nbits: u32) -> Result<Target> {
+        let exponent = (nbits >> 24) as usize;
+        let mantissa = nbits & 0x007f_ffff;
+
+        if nbits & 0x0080_0000 != 0 && mantissa != 0 {
+            return Err(Error::RequirementError(
+                "nbits cannot encode a negative target".into(),
+            ));
+        }
+
+        let mut bytes = [0u8; 32];
+        if exponent <= 3 {
+            let value = mantissa >> (8 * (3 - exponent));
+            bytes[0..4].copy_from_slice(&value.to_le_bytes());
+            return Ok(Target::from_le_bytes(bytes));
+        }
+
+        for (i, byte) in mantissa.to_le_bytes()[0..3].iter().enumerate() {
+            let position = exponent - 3 + i;
+            if position < 32 {
+                bytes[position] = *byte;
+            } else if *byte != 0 {
+                return Err(Error::RequirementError(
+                    "nbits encodes a target larger than 256 bits".into(),
+                ));
+            }
+        }
+
+        Ok(Target::from_le_bytes(bytes))
+    }
+
+    /// Encodes the Target in the compact `nBits` representation. The encoding
+    /// only keeps the 3 most significant bytes of the Target.
+    pub fn to_compact(&self) -> u32 {
+        let bytes = self.to_le_bytes();
+        let size = match bytes.iter().rposition(|byte| *byte != 0) {
+            Some(position) => position + 1,
+            None => return 0,
+        };
+
+        let mut mantissa = if size <= 3 {
+            let mut value = [0u8; 4];
+            value[0..size].copy_from_slice(&bytes[0..size]);
+            u32::from_le_bytes(value) << (8 * (3 - size))
+        } else {
+            let mut value = [0u8; 4];
+            value[0..3].copy_from_slice(&bytes[size - 3..size]);
+            u32::from_le_bytes(value)
+        };
+
+        // The sign bit of the mantissa must not be set, shift the mantissa
+        // into the next byte instead.
+        let mut size = size as u32;
+        if mantissa & 0x0080_0000 != 0 {
+            mantissa >>= 8;
+            size += 1;
+        }
+
+        (size << 24) | mantissa
+    }
+
+    /// Creates a Target from a floating-point value. Values are truncated
+    /// towards zero.
+    ///
+    /// A RequirementError is returned if the value is negative, not a number
+    /// or does not fit in 256 bits.
+    pub fn from_f64(value: f64) -> Result<Target> {
+        if value.is_nan() || value < 0.0 {
+            return Err(Error::RequirementError(
+                "target cannot be negative or NaN".into(),
+            ));
+        }
+
+        if value >= 2f64.powi(256) {
+            return Err(Error::RequirementError(
+                "target cannot be larger than 256 bits".into(),
+            ));
+        }
+
+        let mut remaining = value.trunc();
+        let mut limbs = [0u64; LIMBS];
+        for i in (0..LIMBS).rev() {
+            let base = 2f64.powi(64 * i as i32);
+            let limb = (remaining / base).trunc();
+            limbs[i] = limb as u64;
+            remaining -= limb * base;
+        }
+
+        Ok(Target(limbs))
+    }
+
+    /// Returns the Target as a floating-point value, losing precision beyond
+    /// the 53 most significant bits.
+    pub fn to_f64(&self) -> f64 {
+        self.0
+            .iter()
+            .enumerate()
+            .map(|(i, limb)| *limb as f64 * 2f64.powi(64 * i as i32))
+            .sum()
+    }
+
+    /// Creates the Target corresponding to a network difficulty, as
+    /// `DIFFICULTY_1 / difficulty`.
+    ///
+    /// A RequirementError is returned if the difficulty is not positive.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use stratumv2::target::Target;
+    ///
+    /// let target = Target::from_difficulty(2.0).unwrap();
+    /// assert_eq!(target.to_compact(), 0x1c7fff80);
+    /// assert_eq!(target.difficulty(), 2.0);
+    /// ```
+    pub fn from_difficulty(difficulty: f64) -> Result<Target> {
+        Target::from_difficulty_1(&Target::DIFFICULTY_1, difficulty)
+    }
+
+    /// Returns the network difficulty of the Target, as
+    /// `DIFFICULTY_1 / target`.
+    pub fn difficulty(&self) -> f64 {
+        Target::DIFFICULTY_1.to_f64() / self.to_f64()
+    }
+
+    /// Creates the Target corresponding to a pool share difficulty, as
+    /// `POOL_DIFFICULTY_1 / difficulty`.
+    ///
+    /// A RequirementError is returned if the difficulty is not positive.
+    pub fn from_pool_difficulty(difficulty: f64) -> Result<Target> {
+        Target::from_difficulty_1(&Target::POOL_DIFFICULTY_1, difficulty)
+    }
+
+    /// Returns the pool share difficulty of the Target, as
+    /// `POOL_DIFFICULTY_1 / target`.
+    pub fn pool_difficulty(&self) -> f64 {
+        Target::POOL_DIFFICULTY_1.to_f64() / self.to_f64()
+    }
+
+    fn from_difficulty_1(difficulty_1: &Target, difficulty: f64) -> Result<Target> {
+        if difficulty.is_nan() || difficulty <= 0.0 {
+            return Err(Error::RequirementError(
+                "difficulty must be greater than 0".into(),
+            ));
+        }
+
+        // A difficulty lower than 1 can exceed the maximum target.
+        Ok(Target::from_f64(difficulty_1.to_f64() / difficulty).unwrap_or(Target::MAX))
+    }
+
+    /// Creates the Target for a device hashing at `hash_rate` [h/s] to submit
+    /// on average `shares_per_minute` shares. The expected number of hashes to
+    /// find a share meeting a target is `2^256 / (target + 1)`.
+    ///
+    /// A RequirementError is returned if the hash rate or shares per minute
+    /// are not positive.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use stratumv2::target::Target;
+    ///
+    /// // 14 TH/s submitting 6 shares per minute.
+    /// let target = Target::from_hash_rate(14e12, 6.0).unwrap();
+    ///
+    /// let hash_rate = target.hash_rate(6.0);
+    /// assert!((hash_rate - 14e12).abs() / 14e12 < 1e-9);
+    /// ```
+    pub fn from_hash_rate(hash_rate: f64, shares_per_minute: f64) -> Result<Target> {
+        if hash_rate.is_nan() || hash_rate <= 0.0 {
+            return Err(Error::RequirementError(
+                "hash rate must be greater than 0".into(),
+            ));
+        }
+
+        if shares_per_minute.is_nan() || shares_per_minute <= 0.0 {
+            return Err(Error::RequirementError(
+                "shares per minute must be greater than 0".into(),
+            ));
+        }
+
+        let hashes_per_share = hash_rate * 60.0 / shares_per_minute;
+        if hashes_per_share <= 1.0 {
+            return Ok(Target::MAX);
+        }
+
+        Ok(Target::from_f64(2f64.powi(256) / hashes_per_share)?.saturating_sub_one())
+    }
+
+    /// Returns the hash rate [h/s] a device must have to submit on average
+    /// `shares_per_minute` shares meeting the Target.
+    pub fn hash_rate(&self, shares_per_minute: f64) -> f64 {
+        let hashes_per_share = 2f64.powi(256) / (self.to_f64() + 1.0);
+        hashes_per_share * shares_per_minute / 60.0
+    }
+
+    /// Multiplies the Target by `value`, returning None on overflow.
+    pub fn checked_mul_u64(&self, value: u64) -> Option<Target> {
+        let mut limbs = [0u64; LIMBS];
+        let mut carry = 0u128;
+        for (i, limb) in self.0.iter().enumerate() {
+            let product = *limb as u128 * value as u128 + carry;
+            limbs[i] = product as u64;
+            carry = product >> 64;
+        }
+
+        if carry != 0 {
+            return None;
+        }
+
+        Some(Target(limbs))
+    }
+
+    /// Divides the Target by `value`, returning None if `value` is 0.
+    pub fn checked_div_u64(&self, value: u64) -> Option<Target> {
+        if value == 0 {
+            return None;
+        }
+
+        let mut limbs = [0u64; LIMBS];
+        let mut remainder = 0u128;
+        for i in (0..LIMBS).rev() {
+            let dividend = (remainder << 64) | self.0[i] as u128;
+            limbs[i] = (dividend / value as u128) as u64;
+            remainder = dividend % value as u128;
+        }
+
+        Some(Target(limbs))
+    }
+
+    /// Returns true if the little endian `hash` is less than or equal to the
+    /// Target.
+    pub fn is_met_by(&self, hash: &U256) -> bool {
+        Target::from_le_bytes(*hash) <= *self
+    }
+
+    fn saturating_sub_one(&self) -> Target {
+        let mut limbs = self.0;
+        for limb in limbs.iter_mut() {
+            let (value, borrow) = limb.overflowing_sub(1);
+            *limb = value;
+            if !borrow {
+                return Target(limbs);
+            }
+        }
+
+        Target::ZERO
+    }
+}
+
+impl Ord for Target {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.iter().rev().cmp(other.0.iter().rev())
+    }
+}
+
+impl PartialOrd for Target {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl From<U256> for Target {
+    fn from(bytes: U256) -> Self {
+        Target::from_le_bytes(bytes)
+    }
+}
+
+impl From<Target> for U256 {
+    fn from(target: Target) -> Self {
+        target.to_le_bytes()
+    }
+}
+
+/// Formats the Target as a big endian hex string, the order used to display
+/// hashes and targets.
+impl fmt::Debug for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Target(")?;
+        for limb in self.0.iter().rev() {
+            write!(f, "{:016x}", limb)?;
+        }
+        write!(f, ")")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn byte_conversion() {
+        let mut bytes = [0u8; 32];
+        bytes[0] = 0x01;
+        bytes[9] = 0x02;
+        bytes[31] = 0x03;
+
+        let target = Target::from_le_bytes(bytes);
+        assert_eq!(target.0, [0x01, 0x0200, 0, 0x0300_0000_0000_0000]);
+        assert_eq!(target.to_le_bytes(), bytes);
+        assert_eq!(U256::from(target), bytes);
+    }
+
+    #[test]
+    fn compact_conversion() {
+        assert_eq!(
+            Target::from_compact(0x1d00ffff).unwrap(),
+            Target::DIFFICULTY_1
+        );
+
+        // Mainnet block 100000.
+        let target = Target::from_compact(0x1b04864c).unwrap();
+        assert_eq!(target.0, [0, 0, 0, 0x0004_864c]);
+        assert_eq!(target.to_compact(), 0x1b04864c);
+
+        assert_eq!(
+            Target::from_compact(0x0112_3456).unwrap().0,
+            [0x12, 0, 0, 0]
+        );
+        assert_eq!(
+            Target::from_compact(0x0112_3456).unwrap().to_compact(),
+            0x0112_0000
+        );
+        assert_eq!(Target::ZERO.to_compact(), 0);
+
+        // A mantissa with the sign bit set is shifted into the next byte.
+        assert_eq!(Target([0x80, 0, 0, 0]).to_compact(), 0x0200_8000);
+
+        assert!(Target::from_compact(0x0492_3456).is_err());
+        assert!(Target::from_compact(0x2101_0000).is_err());
+    }
+
+    #[test]
+    fn compare_targets() {
+        assert!(Target::ZERO < Target::DIFFICULTY_1);
+        assert!(Target::DIFFICULTY_1 < Target::POOL_DIFFICULTY_1);
+        assert!(Target::POOL_DIFFICULTY_1 < Target::MAX);
+        assert!(Target([u64::MAX, 0, 0, 0]) < Target([0, 1, 0, 0]));
+
+        let mut hash = [0u8; 32];
+        hash[27] = 0x01;
+        assert!(Target::DIFFICULTY_1.is_met_by(&hash));
+
+        hash[28] = 0x01;
+        assert!(!Target::DIFFICULTY_1.is_met_by(&hash));
+    }
+
+    #[test]
+    fn f64_conversion() {
+        assert_eq!(Target::from_f64(0.0).unwrap(), Target::ZERO);
+        assert_eq!(Target::from_f64(1.9).unwrap(), Target([1, 0, 0, 0]));
+        assert_eq!(
+            Target::from_f64(2f64.powi(64)).unwrap(),
+            Target([0, 1, 0, 0])
+        );
+        assert_eq!(Target::DIFFICULTY_1.to_f64(), 65535.0 * 2f64.powi(208));
+
+        assert!(Target::from_f64(-1.0).is_err());
+        assert!(Target::from_f64(f64::NAN).is_err());
+        assert!(Target::from_f64(2f64.powi(256)).is_err());
+    }
+
+    #[test]
+    fn difficulty_conversion() {
+        assert_eq!(Target::from_difficulty(1.0).unwrap(), Target::DIFFICULTY_1);
+        assert_eq!(Target::DIFFICULTY_1.difficulty(), 1.0);
+
+        // Mainnet block 100000 has a difficulty of 14484.16.
+        let difficulty = Target::from_compact(0x1b04864c).unwrap().difficulty();
+        assert_eq!(difficulty.floor(), 14484.0);
+
+        let target = Target::from_pool_difficulty(1024.0).unwrap();
+        assert!((target.pool_difficulty() - 1024.0).abs() < 1e-9);
+        assert!(
+            target
+                < Target::from_difficulty(1024.0)
+                    .unwrap()
+                    .checked_mul_u64(2)
+                    .unwrap()
+        );
+
+        assert_eq!(Target::from_difficulty(1e-80).unwrap(), Target::MAX);
+        assert!(Target::from_difficulty(0.0).is_err());
+        assert!(Target::from_pool_difficulty(-1.0).is_err());
+    }
+
+    #[test]
+    fn hash_rate_conversion() {
+        // One hash per share is met by any hash.
+        assert_eq!(Target::from_hash_rate(1.0, 60.0).unwrap(), Target::MAX);
+
+        // 2^32 hashes per share is a target of 2^224 - 1.
+        let target = Target::from_hash_rate(2f64.powi(32), 60.0).unwrap();
+        assert_eq!(
+            target,
+            Target([u64::MAX, u64::MAX, u64::MAX, 0x0000_0000_ffff_ffff])
+        );
+        assert_eq!(target.hash_rate(60.0), 2f64.powi(32));
+
+        // Doubling the hash rate halves the target.
+        let double = Target::from_hash_rate(2f64.powi(33), 60.0).unwrap();
+        assert_eq!(target.checked_div_u64(2).unwrap(), double);
+
+        assert!(Target::from_hash_rate(0.0, 6.0).is_err());
+        assert!(Target::from_hash_rate(1e12, 0.0).is_err());
+    }
+
+    #[test]
+    fn integer_arithmetic() {
+        let target = Target([u64::MAX, 0, 0, 0]);
+        assert_eq!(
+            target.checked_mul_u64(2).unwrap(),
+            Target([u64::MAX - 1, 1, 0, 0])
+        );
+        assert_eq!(
+            target
+                .checked_mul_u64(2)
+                .unwrap()
+                .checked_div_u64(2)
+                .unwrap(),
+            target
+        );
+
+        assert!(Target::MAX.checked_mul_u64(2).is_none());
+        assert!(Target::MAX.checked_div_u64(0).is_none());
+        assert_eq!(Target::ZERO.saturating_sub_one(), Target::ZERO);
+        assert_eq!(
+            Target([0, 1, 0, 0]).saturating_sub_one(),
+            Target([u64::MAX, 0, 0, 0])
+        );
+    }
+}
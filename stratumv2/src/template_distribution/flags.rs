@@ -0,0 +1,17 @@
+use crate::BitFlag;
+
+/// Feature flags that can be passed to a SetupConnection message for the
+/// template distribution protocol. The protocol does not define any flags,
+/// bits set by newer versions of the protocol are kept by the
+/// [FlagSet](../types/struct.FlagSet.html) as unknown bits.
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SetupConnectionFlags {}
+
+impl BitFlag for SetupConnectionFlags {
+    const FLAGS: &'static [SetupConnectionFlags] = &[];
+
+    fn as_bit_flag(&self) -> u32 {
+        match *self {}
+    }
+}
@@ -0,0 +1,749 @@
+use crate::common::SetupConnectionErrorCodes;
+use crate::error::{Error, Result};
+use crate::template_distribution::SetupConnectionFlags;
+use crate::types::{FlagSet, MessageTypes, B0_16M, B0_255, B0_64K, STR0_255, U256};
+use crate::util::{deserialize_bool, ByteParser};
+use crate::{Deserializable, Frameable, Protocol, Serializable};
+use std::fmt;
+use std::str::FromStr;
+use std::{io, str};
+
+// Implementation of the SetupConnection, SetupConnectionSuccess and
+// SetupConnectionError messages for the Template Distribution Protocol.
+impl_setup_connection!(Protocol::TemplateDistribution, SetupConnectionFlags);
+impl_setup_connection_success!(SetupConnectionFlags);
+impl_setup_connection_error!(SetupConnectionFlags);
+
+/// CoinbaseOutputDataSize is a message sent by the Client to the Template
+/// Provider to announce the maximum size of the coinbase outputs it will add
+/// to the templates. The Template Provider MUST reduce the transactions of
+/// the templates to leave enough space in the block.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CoinbaseOutputDataSize {
+    /// The maximum additional serialized bytes the Client will add to the
+    /// coinbase outputs.
+    pub coinbase_output_max_additional_size: u32,
+}
+
+impl CoinbaseOutputDataSize {
+    pub fn new(coinbase_output_max_additional_size: u32) -> CoinbaseOutputDataSize {
+        CoinbaseOutputDataSize {
+            coinbase_output_max_additional_size,
+        }
+    }
+}
+
+impl Serializable for CoinbaseOutputDataSize {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<usize> {
+        Ok(writer.write(&self.coinbase_output_max_additional_size.to_le_bytes())?)
+    }
+}
+
+impl Deserializable for CoinbaseOutputDataSize {
+    fn deserialize(bytes: &[u8]) -> Result<CoinbaseOutputDataSize> {
+        let mut parser = ByteParser::new(bytes, 0);
+
+        let coinbase_output_max_additional_size = parser.next_by(4)?;
+
+        Ok(CoinbaseOutputDataSize::new(u32::from_le_bytes(
+            coinbase_output_max_additional_size.try_into()?,
+        )))
+    }
+}
+
+impl_frameable_trait!(
+    CoinbaseOutputDataSize,
+    MessageTypes::CoinbaseOutputDataSize,
+    false
+);
+
+/// NewTemplate is a message sent by the Template Provider to the Client with
+/// a new block template. The coinbase transaction is described by its fields
+/// so the Client can add its own outputs, the other transactions are only
+/// committed to by the `merkle_path`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NewTemplate {
+    /// A Template Provider identifier for the template, unique for the
+    /// connection.
+    pub template_id: u64,
+
+    /// True if the template is intended for a future
+    /// [SetNewPrevHash](struct.SetNewPrevHash.html), false if it can be mined
+    /// on the current prev hash immediately.
+    pub future_template: bool,
+
+    /// The version field of the block header.
+    pub version: u32,
+
+    /// The version field of the coinbase transaction.
+    pub coinbase_tx_version: u32,
+
+    /// Up to 8 bytes placed at the beginning of the script of the coinbase
+    /// input, such as the block height required by BIP34.
+    pub coinbase_prefix: B0_255,
+
+    /// The sequence field of the coinbase input.
+    pub coinbase_tx_input_sequence: u32,
+
+    /// The value in satoshis available to the outputs added by the Client.
+    pub coinbase_tx_value_remaining: u64,
+
+    /// The number of outputs serialized in `coinbase_tx_outputs`.
+    pub coinbase_tx_outputs_count: u32,
+
+    /// The serialized outputs the Template Provider requires in the coinbase
+    /// transaction, such as the witness commitment.
+    pub coinbase_tx_outputs: B0_64K,
+
+    /// The lock time field of the coinbase transaction.
+    pub coinbase_tx_locktime: u32,
+
+    /// The merkle path of the coinbase transaction, the hashes needed to
+    /// compute the merkle root from the coinbase txid.
+    #[cfg_attr(feature = "serde", serde(with = "crate::util::hex::array_list"))]
+    pub merkle_path: Vec<U256>,
+}
+
+impl NewTemplate {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new<T: Into<Vec<u8>>>(
+        template_id: u64,
+        future_template: bool,
+        version: u32,
+        coinbase_tx_version: u32,
+        coinbase_prefix: T,
+        coinbase_tx_input_sequence: u32,
+        coinbase_tx_value_remaining: u64,
+        coinbase_tx_outputs_count: u32,
+        coinbase_tx_outputs: T,
+        coinbase_tx_locktime: u32,
+        merkle_path: Vec<U256>,
+    ) -> Result<NewTemplate> {
+        let coinbase_prefix = coinbase_prefix.into();
+        if coinbase_prefix.len() > 8 {
+            return Err(Error::RequirementError(
+                "coinbase_prefix cannot be greater than 8 bytes".into(),
+            ));
+        }
+
+        if merkle_path.len() > 255 {
+            return Err(Error::RequirementError(
+                "merkle_path cannot contain more than 255 hashes".into(),
+            ));
+        }
+
+        Ok(NewTemplate {
+            template_id,
+            future_template,
+            version,
+            coinbase_tx_version,
+            coinbase_prefix: B0_255::new(coinbase_prefix)?,
+            coinbase_tx_input_sequence,
+            coinbase_tx_value_remaining,
+            coinbase_tx_outputs_count,
+            coinbase_tx_outputs: B0_64K::new(coinbase_tx_outputs)?,
+            coinbase_tx_locktime,
+            merkle_path,
+        })
+    }
+}
+
+impl Serializable for NewTemplate {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<usize> {
+        let mut merkle_path = vec![self.merkle_path.len() as u8];
+        for hash in &self.merkle_path {
+            merkle_path.extend_from_slice(hash);
+        }
+
+        let buffer = serialize_slices!(
+            &self.template_id.to_le_bytes(),
+            &[self.future_template as u8],
+            &self.version.to_le_bytes(),
+            &self.coinbase_tx_version.to_le_bytes(),
+            &self.coinbase_prefix.as_bytes(),
+            &self.coinbase_tx_input_sequence.to_le_bytes(),
+            &self.coinbase_tx_value_remaining.to_le_bytes(),
+            &self.coinbase_tx_outputs_count.to_le_bytes(),
+            &self.coinbase_tx_outputs.as_bytes(),
+            &self.coinbase_tx_locktime.to_le_bytes(),
+            &merkle_path
+        );
+
+        Ok(writer.write(&buffer)?)
+    }
+}
+
+impl Deserializable for NewTemplate {
+    fn deserialize(bytes: &[u8]) -> Result<NewTemplate> {
+        let mut parser = ByteParser::new(bytes, 0);
+
+        let template_id = parser.next_by(8)?;
+        let future_template = deserialize_bool(parser.next_by(1)?[0])?;
+        let version = parser.next_by(4)?;
+        let coinbase_tx_version = parser.next_by(4)?;
+        let coinbase_prefix_length = parser.next_by(1)?[0] as usize;
+        let coinbase_prefix = parser.next_by(coinbase_prefix_length)?;
+        let coinbase_tx_input_sequence = parser.next_by(4)?;
+        let coinbase_tx_value_remaining = parser.next_by(8)?;
+        let coinbase_tx_outputs_count = parser.next_by(4)?;
+        let coinbase_tx_outputs_length = u16::from_le_bytes(parser.next_by(2)?.try_into()?);
+        let coinbase_tx_outputs = parser.next_by(coinbase_tx_outputs_length as usize)?;
+        let coinbase_tx_locktime = parser.next_by(4)?;
+
+        let merkle_path_length = parser.next_by(1)?[0] as usize;
+        let mut merkle_path = Vec::with_capacity(merkle_path_length);
+        for _ in 0..merkle_path_length {
+            merkle_path.push(parser.next_by(32)?.try_into()?);
+        }
+
+        NewTemplate::new(
+            u64::from_le_bytes(template_id.try_into()?),
+            future_template,
+            u32::from_le_bytes(version.try_into()?),
+            u32::from_le_bytes(coinbase_tx_version.try_into()?),
+            coinbase_prefix.to_vec(),
+            u32::from_le_bytes(coinbase_tx_input_sequence.try_into()?),
+            u64::from_le_bytes(coinbase_tx_value_remaining.try_into()?),
+            u32::from_le_bytes(coinbase_tx_outputs_count.try_into()?),
+            coinbase_tx_outputs.to_vec(),
+            u32::from_le_bytes(coinbase_tx_locktime.try_into()?),
+            merkle_path,
+        )
+    }
+}
+
+impl_frameable_trait!(NewTemplate, MessageTypes::NewTemplate, false);
+
+/// SetNewPrevHash is a message sent by the Template Provider to the Client
+/// when the chain tip changes. Templates sent before this message are no
+/// longer valid, except for the future template referenced by `template_id`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SetNewPrevHash {
+    /// The future template activated by the new prev hash.
+    pub template_id: u64,
+
+    /// The hash of the new chain tip.
+    #[cfg_attr(feature = "serde", serde(with = "crate::util::hex::array"))]
+    pub prev_hash: U256,
+
+    /// The nTime field of the block header, valid for the new chain tip.
+    pub header_timestamp: u32,
+
+    /// The nBits field of the block header.
+    pub nbits: u32,
+
+    /// The network target of the block, as encoded by `nbits`.
+    #[cfg_attr(feature = "serde", serde(with = "crate::util::hex::array"))]
+    pub target: U256,
+}
+
+impl SetNewPrevHash {
+    pub fn new(
+        template_id: u64,
+        prev_hash: U256,
+        header_timestamp: u32,
+        nbits: u32,
+        target: U256,
+    ) -> SetNewPrevHash {
+        SetNewPrevHash {
+            template_id,
+            prev_hash,
+            header_timestamp,
+            nbits,
+            target,
+        }
+    }
+}
+
+impl Serializable for SetNewPrevHash {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<usize> {
+        let buffer = serialize_slices!(
+            &self.template_id.to_le_bytes(),
+            &self.prev_hash,
+            &self.header_timestamp.to_le_bytes(),
+            &self.nbits.to_le_bytes(),
+            &self.target
+        );
+
+        Ok(writer.write(&buffer)?)
+    }
+}
+
+impl Deserializable for SetNewPrevHash {
+    fn deserialize(bytes: &[u8]) -> Result<SetNewPrevHash> {
+        let mut parser = ByteParser::new(bytes, 0);
+
+        let template_id = parser.next_by(8)?;
+        let prev_hash = parser.next_by(32)?;
+        let header_timestamp = parser.next_by(4)?;
+        let nbits = parser.next_by(4)?;
+        let target = parser.next_by(32)?;
+
+        Ok(SetNewPrevHash::new(
+            u64::from_le_bytes(template_id.try_into()?),
+            prev_hash.try_into()?,
+            u32::from_le_bytes(header_timestamp.try_into()?),
+            u32::from_le_bytes(nbits.try_into()?),
+            target.try_into()?,
+        ))
+    }
+}
+
+impl_frameable_trait!(
+    SetNewPrevHash,
+    MessageTypes::SetNewPrevHashTemplateDistribution,
+    false
+);
+
+/// RequestTransactionData is a message sent by the Client to the Template
+/// Provider to request the transactions of a template, for example to
+/// negotiate the template with a Mining Pool.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RequestTransactionData {
+    /// The template the transactions are requested for.
+    pub template_id: u64,
+}
+
+impl RequestTransactionData {
+    pub fn new(template_id: u64) -> RequestTransactionData {
+        RequestTransactionData { template_id }
+    }
+}
+
+impl Serializable for RequestTransactionData {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<usize> {
+        Ok(writer.write(&self.template_id.to_le_bytes())?)
+    }
+}
+
+impl Deserializable for RequestTransactionData {
+    fn deserialize(bytes: &[u8]) -> Result<RequestTransactionData> {
+        let mut parser = ByteParser::new(bytes, 0);
+
+        let template_id = parser.next_by(8)?;
+
+        Ok(RequestTransactionData::new(u64::from_le_bytes(
+            template_id.try_into()?,
+        )))
+    }
+}
+
+impl_frameable_trait!(
+    RequestTransactionData,
+    MessageTypes::RequestTransactionData,
+    false
+);
+
+/// RequestTransactionDataSuccess is a message sent by the Template Provider
+/// to the Client with the transactions of a template.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RequestTransactionDataSuccess {
+    /// The template the transactions belong to.
+    pub template_id: u64,
+
+    /// Extra data required to build the block, such as the witness reserved
+    /// value of the coinbase transaction.
+    pub excess_data: B0_64K,
+
+    /// The serialized transactions of the template, excluding the coinbase
+    /// transaction, in block order.
+    pub transaction_list: Vec<B0_16M>,
+}
+
+impl RequestTransactionDataSuccess {
+    pub fn new<T: Into<Vec<u8>>>(
+        template_id: u64,
+        excess_data: T,
+        transaction_list: Vec<Vec<u8>>,
+    ) -> Result<RequestTransactionDataSuccess> {
+        if transaction_list.len() > 65535 {
+            return Err(Error::RequirementError(
+                "transaction_list cannot contain more than 65535 transactions".into(),
+            ));
+        }
+
+        Ok(RequestTransactionDataSuccess {
+            template_id,
+            excess_data: B0_64K::new(excess_data)?,
+            transaction_list: transaction_list
+                .into_iter()
+                .map(B0_16M::new)
+                .collect::<Result<Vec<B0_16M>>>()?,
+        })
+    }
+}
+
+impl Serializable for RequestTransactionDataSuccess {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<usize> {
+        let mut transaction_list = (self.transaction_list.len() as u16).to_le_bytes().to_vec();
+        for transaction in &self.transaction_list {
+            transaction_list.extend_from_slice(&transaction.as_bytes());
+        }
+
+        let buffer = serialize_slices!(
+            &self.template_id.to_le_bytes(),
+            &self.excess_data.as_bytes(),
+            &transaction_list
+        );
+
+        Ok(writer.write(&buffer)?)
+    }
+}
+
+impl Deserializable for RequestTransactionDataSuccess {
+    fn deserialize(bytes: &[u8]) -> Result<RequestTransactionDataSuccess> {
+        let mut parser = ByteParser::new(bytes, 0);
+
+        let template_id = parser.next_by(8)?;
+        let excess_data_length = u16::from_le_bytes(parser.next_by(2)?.try_into()?);
+        let excess_data = parser.next_by(excess_data_length as usize)?;
+
+        let transaction_list_length = u16::from_le_bytes(parser.next_by(2)?.try_into()?);
+        let mut transaction_list = Vec::with_capacity(transaction_list_length as usize);
+        for _ in 0..transaction_list_length {
+            let length = parser.next_by(3)?;
+            let length = u32::from_le_bytes([length[0], length[1], length[2], 0]);
+            transaction_list.push(parser.next_by(length as usize)?.to_vec());
+        }
+
+        RequestTransactionDataSuccess::new(
+            u64::from_le_bytes(template_id.try_into()?),
+            excess_data.to_vec(),
+            transaction_list,
+        )
+    }
+}
+
+impl_frameable_trait!(
+    RequestTransactionDataSuccess,
+    MessageTypes::RequestTransactionDataSuccess,
+    false
+);
+
+/// RequestTransactionDataError is a message sent by the Template Provider to
+/// the Client when the transactions of a template cannot be provided.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RequestTransactionDataError {
+    /// The template the transactions were requested for.
+    pub template_id: u64,
+
+    /// The reason the transactions cannot be provided.
+    pub error_code: RequestTransactionDataErrorCodes,
+}
+
+impl RequestTransactionDataError {
+    pub fn new(
+        template_id: u64,
+        error_code: RequestTransactionDataErrorCodes,
+    ) -> RequestTransactionDataError {
+        RequestTransactionDataError {
+            template_id,
+            error_code,
+        }
+    }
+}
+
+impl Serializable for RequestTransactionDataError {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<usize> {
+        let buffer = serialize_slices!(
+            &self.template_id.to_le_bytes(),
+            &STR0_255::new(self.error_code.to_string())?.as_bytes()
+        );
+
+        Ok(writer.write(&buffer)?)
+    }
+}
+
+impl Deserializable for RequestTransactionDataError {
+    fn deserialize(bytes: &[u8]) -> Result<RequestTransactionDataError> {
+        let mut parser = ByteParser::new(bytes, 0);
+
+        let template_id = parser.next_by(8)?;
+        let error_code_length = parser.next_by(1)?[0] as usize;
+        let error_code = str::from_utf8(parser.next_by(error_code_length)?)?;
+
+        Ok(RequestTransactionDataError::new(
+            u64::from_le_bytes(template_id.try_into()?),
+            RequestTransactionDataErrorCodes::from_str(error_code)?,
+        ))
+    }
+}
+
+impl_frameable_trait!(
+    RequestTransactionDataError,
+    MessageTypes::RequestTransactionDataError,
+    false
+);
+
+/// Contains the error codes for the
+/// [RequestTransactionData.Error](struct.RequestTransactionDataError.html)
+/// message. Each error code is serialized according to constraints of a
+/// [STR0_255](../types/struct.STR0_255.html).
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum RequestTransactionDataErrorCodes {
+    /// The template is not known by the Template Provider.
+    TemplateIdNotFound,
+
+    /// The template is known but was built on a previous chain tip.
+    StaleTemplateId,
+}
+
+impl_error_codes_enum!(
+    RequestTransactionDataErrorCodes,
+    RequestTransactionDataErrorCodes::TemplateIdNotFound => "template-id-not-found",
+    RequestTransactionDataErrorCodes::StaleTemplateId => "stale-template-id"
+);
+
+/// SubmitSolution is a message sent by the Client to the Template Provider
+/// when a block is found on a template. The Template Provider assembles the
+/// block from the template and the fields of the solution.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SubmitSolution {
+    /// The template the block was found on.
+    pub template_id: u64,
+
+    /// The version field of the block header, including any rolled bits.
+    pub version: u32,
+
+    /// The nTime field of the block header.
+    pub header_timestamp: u32,
+
+    /// The nonce field of the block header.
+    pub header_nonce: u32,
+
+    /// The full serialized coinbase transaction of the block.
+    pub coinbase_tx: B0_64K,
+}
+
+impl SubmitSolution {
+    pub fn new<T: Into<Vec<u8>>>(
+        template_id: u64,
+        version: u32,
+        header_timestamp: u32,
+        header_nonce: u32,
+        coinbase_tx: T,
+    ) -> Result<SubmitSolution> {
+        Ok(SubmitSolution {
+            template_id,
+            version,
+            header_timestamp,
+            header_nonce,
+            coinbase_tx: B0_64K::new(coinbase_tx)?,
+        })
+    }
+}
+
+impl Serializable for SubmitSolution {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<usize> {
+        let buffer = serialize_slices!(
+            &self.template_id.to_le_bytes(),
+            &self.version.to_le_bytes(),
+            &self.header_timestamp.to_le_bytes(),
+            &self.header_nonce.to_le_bytes(),
+            &self.coinbase_tx.as_bytes()
+        );
+
+        Ok(writer.write(&buffer)?)
+    }
+}
+
+impl Deserializable for SubmitSolution {
+    fn deserialize(bytes: &[u8]) -> Result<SubmitSolution> {
+        let mut parser = ByteParser::new(bytes, 0);
+
+        let template_id = parser.next_by(8)?;
+        let version = parser.next_by(4)?;
+        let header_timestamp = parser.next_by(4)?;
+        let header_nonce = parser.next_by(4)?;
+        let coinbase_tx_length = u16::from_le_bytes(parser.next_by(2)?.try_into()?);
+        let coinbase_tx = parser.next_by(coinbase_tx_length as usize)?;
+
+        SubmitSolution::new(
+            u64::from_le_bytes(template_id.try_into()?),
+            u32::from_le_bytes(version.try_into()?),
+            u32::from_le_bytes(header_timestamp.try_into()?),
+            u32::from_le_bytes(header_nonce.try_into()?),
+            coinbase_tx.to_vec(),
+        )
+    }
+}
+
+impl_frameable_trait!(SubmitSolution, MessageTypes::SubmitSolution, false);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::frame;
+
+    #[test]
+    fn init_template_distribution_connection() {
+        let message = SetupConnection::new(
+            2,
+            2,
+            FlagSet::empty(),
+            "0.0.0.0",
+            8442,
+            "Bitcoin Core",
+            "",
+            "0.21.0",
+            "",
+        )
+        .unwrap();
+
+        assert_eq!(message.protocol(), Protocol::TemplateDistribution);
+
+        let buffer = frame(message).unwrap();
+        let message = SetupConnection::deserialize(&buffer[6..]).unwrap();
+        assert_eq!(message.protocol(), Protocol::TemplateDistribution);
+        assert_eq!(message.endpoint_port, 8442);
+    }
+
+    #[test]
+    fn serialize_coinbase_output_data_size() {
+        let message = CoinbaseOutputDataSize::new(100);
+        let buffer = frame(message).unwrap();
+
+        assert_eq!(
+            buffer,
+            [0x00, 0x00, 0x70, 0x04, 0x00, 0x00, 0x64, 0x00, 0x00, 0x00]
+        );
+
+        let message = CoinbaseOutputDataSize::deserialize(&buffer[6..]).unwrap();
+        assert_eq!(message.coinbase_output_max_additional_size, 100);
+    }
+
+    #[test]
+    fn serialize_new_template() {
+        let message = NewTemplate::new(
+            7,
+            true,
+            0x2000_0000,
+            2,
+            vec![0x03, 0x01, 0x02, 0x03],
+            0xffff_ffff,
+            625_000_000,
+            1,
+            vec![0xaa; 10],
+            0,
+            vec![[0x11; 32], [0x22; 32]],
+        )
+        .unwrap();
+        let buffer = frame(message).unwrap();
+
+        assert_eq!(buffer[0..3], [0x00, 0x00, 0x71]);
+        assert_eq!(buffer[6..14], 7u64.to_le_bytes());
+
+        let message = NewTemplate::deserialize(&buffer[6..]).unwrap();
+        assert_eq!(message.template_id, 7);
+        assert!(message.future_template);
+        assert_eq!(message.version, 0x2000_0000);
+        assert_eq!(message.coinbase_tx_version, 2);
+        assert_eq!(message.coinbase_prefix, vec![0x03, 0x01, 0x02, 0x03]);
+        assert_eq!(message.coinbase_tx_input_sequence, 0xffff_ffff);
+        assert_eq!(message.coinbase_tx_value_remaining, 625_000_000);
+        assert_eq!(message.coinbase_tx_outputs_count, 1);
+        assert_eq!(message.coinbase_tx_outputs, vec![0xaa; 10]);
+        assert_eq!(message.coinbase_tx_locktime, 0);
+        assert_eq!(message.merkle_path, vec![[0x11; 32], [0x22; 32]]);
+    }
+
+    #[test]
+    fn new_template_coinbase_prefix_size() {
+        let message = NewTemplate::new(1, false, 0, 2, vec![0x00; 9], 0, 0, 0, vec![], 0, vec![]);
+
+        assert!(message.is_err());
+    }
+
+    #[test]
+    fn serialize_set_new_prev_hash() {
+        let message = SetNewPrevHash::new(7, [0x33; 32], 1_600_000_000, 0x1d00ffff, [0x44; 32]);
+        let buffer = frame(message).unwrap();
+
+        assert_eq!(buffer[0..6], [0x00, 0x00, 0x72, 0x50, 0x00, 0x00]);
+
+        let message = SetNewPrevHash::deserialize(&buffer[6..]).unwrap();
+        assert_eq!(message.template_id, 7);
+        assert_eq!(message.prev_hash, [0x33; 32]);
+        assert_eq!(message.header_timestamp, 1_600_000_000);
+        assert_eq!(message.nbits, 0x1d00ffff);
+        assert_eq!(message.target, [0x44; 32]);
+    }
+
+    #[test]
+    fn serialize_request_transaction_data() {
+        let message = RequestTransactionData::new(7);
+        let buffer = frame(message).unwrap();
+
+        assert_eq!(buffer[0..6], [0x00, 0x00, 0x73, 0x08, 0x00, 0x00]);
+        assert_eq!(
+            RequestTransactionData::deserialize(&buffer[6..])
+                .unwrap()
+                .template_id,
+            7
+        );
+    }
+
+    #[test]
+    fn serialize_request_transaction_data_success() {
+        let message = RequestTransactionDataSuccess::new(
+            7,
+            vec![0x00; 32],
+            vec![vec![0x01; 300], vec![0x02]],
+        )
+        .unwrap();
+        let buffer = frame(message).unwrap();
+
+        assert_eq!(buffer[0..3], [0x00, 0x00, 0x74]);
+
+        let message = RequestTransactionDataSuccess::deserialize(&buffer[6..]).unwrap();
+        assert_eq!(message.template_id, 7);
+        assert_eq!(message.excess_data, vec![0x00; 32]);
+        assert_eq!(message.transaction_list.len(), 2);
+        assert_eq!(message.transaction_list[0], vec![0x01; 300]);
+        assert_eq!(message.transaction_list[1], vec![0x02]);
+    }
+
+    #[test]
+    fn serialize_request_transaction_data_error() {
+        let message = RequestTransactionDataError::new(
+            7,
+            RequestTransactionDataErrorCodes::TemplateIdNotFound,
+        );
+        let buffer = frame(message).unwrap();
+
+        let mut expected = vec![0x00, 0x00, 0x75, 0x1e, 0x00, 0x00];
+        expected.extend_from_slice(&7u64.to_le_bytes());
+        expected.push(0x15);
+        expected.extend_from_slice(b"template-id-not-found");
+        assert_eq!(buffer, expected);
+
+        let message = RequestTransactionDataError::deserialize(&buffer[6..]).unwrap();
+        assert_eq!(
+            message.error_code,
+            RequestTransactionDataErrorCodes::TemplateIdNotFound
+        );
+    }
+
+    #[test]
+    fn serialize_submit_solution() {
+        let message =
+            SubmitSolution::new(7, 0x2000_0000, 1_600_000_000, 42, vec![0x01; 100]).unwrap();
+        let buffer = frame(message).unwrap();
+
+        assert_eq!(buffer[0..6], [0x00, 0x00, 0x76, 0x7a, 0x00, 0x00]);
+
+        let message = SubmitSolution::deserialize(&buffer[6..]).unwrap();
+        assert_eq!(message.template_id, 7);
+        assert_eq!(message.version, 0x2000_0000);
+        assert_eq!(message.header_timestamp, 1_600_000_000);
+        assert_eq!(message.header_nonce, 42);
+        assert_eq!(message.coinbase_tx, vec![0x01; 100]);
+    }
+}
@@ -0,0 +1,21 @@
+//! The sub protocol allows a Template Provider, typically a Bitcoin node, to
+//! distribute block templates to a Mining Pool or a Job Negotiator. It is
+//! intended to replace getblocktemplate.
+//!
+//! A Template Provider sends a future [NewTemplate](struct.NewTemplate.html)
+//! followed by a [SetNewPrevHash](struct.SetNewPrevHash.html) activating it
+//! when the chain tip changes. The transactions of a template can be
+//! requested with [RequestTransactionData](struct.RequestTransactionData.html)
+//! and a block found on a template is returned with
+//! [SubmitSolution](struct.SubmitSolution.html).
+
+mod flags;
+mod messages;
+
+/// Template Distribution subprotocol messages.
+pub use flags::SetupConnectionFlags;
+pub use messages::{
+    CoinbaseOutputDataSize, NewTemplate, RequestTransactionData, RequestTransactionDataError,
+    RequestTransactionDataErrorCodes, RequestTransactionDataSuccess, SetNewPrevHash,
+    SetupConnection, SetupConnectionError, SetupConnectionSuccess, SubmitSolution,
+};
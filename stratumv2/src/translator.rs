@@ -0,0 +1,765 @@
+//! The translator bridges Stratum V1 miners to a Stratum V2 Upstream. The
+//! translator opens a single extended channel upstream and splits its
+//! extranonce space between the downstream V1 miners:
+//! - V2 jobs are sent downstream as `mining.notify`, the `coinb1` ending at
+//!   the start of the extranonce assigned to each miner.
+//! - `SetTarget` is sent downstream as `mining.set_difficulty`.
+//! - `mining.submit` shares are sent upstream as `SubmitSharesExtended`.
+//! - `mining.configure` negotiates the BIP320 version bits each miner is
+//!   allowed to roll.
+//!
+//! The [Translator](struct.Translator.html) is sans-IO, messages are passed
+//! in by the caller and the messages to send are returned.
+use crate::error::{Error, Result};
+use crate::mining::{
+    ExtranonceAllocator, Job, JobState, JobStore, NewExtendedMiningJob, OpenExtendedMiningChannel,
+    OpenExtendedMiningChannelSuccess, SetNewPrevHash, SetTarget, ShareTracker, SubmitSharesError,
+    SubmitSharesExtended, SubmitSharesSuccess, VersionRolling,
+};
+use crate::types::U256;
+use crate::v1::{
+    Authorize, Configure, ConfigureResult, Notify, SetDifficulty, Submit, Subscribe,
+    SubscribeResult,
+};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// TranslatorConfig contains the parameters of the extended channel opened
+/// upstream by a [Translator](struct.Translator.html).
+#[derive(Debug, Clone, PartialEq)]
+pub struct TranslatorConfig {
+    /// The user identity of the extended channel.
+    pub user_identity: String,
+
+    /// The expected hash rate of every downstream miner combined.
+    pub nominal_hash_rate: f32,
+
+    /// The number of bytes of extranonce used to number the downstream
+    /// miners.
+    pub downstream_prefix_size: u16,
+
+    /// The number of bytes of extranonce searched by each downstream miner.
+    pub extranonce2_size: u16,
+
+    /// The duration stale jobs are remembered, so late shares are rejected
+    /// as stale instead of unknown.
+    pub stale_window: Duration,
+}
+
+impl TranslatorConfig {
+    /// Creates a TranslatorConfig for the `user_identity` and expected
+    /// `nominal_hash_rate` of the upstream channel, with a 2 byte downstream
+    /// prefix, a 4 byte extranonce2 and a 30 second stale window.
+    pub fn new<T: Into<String>>(user_identity: T, nominal_hash_rate: f32) -> TranslatorConfig {
+        TranslatorConfig {
+            user_identity: user_identity.into(),
+            nominal_hash_rate,
+            downstream_prefix_size: 2,
+            extranonce2_size: 4,
+            stale_window: Duration::from_secs(30),
+        }
+    }
+}
+
+/// The extended channel opened upstream.
+#[derive(Debug)]
+struct UpstreamChannel {
+    channel_id: u32,
+    target: U256,
+    extranonce: ExtranonceAllocator,
+}
+
+/// A downstream V1 miner.
+#[derive(Debug)]
+struct Downstream {
+    extranonce1: Vec<u8>,
+    workers: Vec<String>,
+}
+
+/// Translator translates between the V1 miners connected downstream and the
+/// extended channel opened upstream.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Instant;
+/// use stratumv2::mining::{NewExtendedMiningJob, OpenExtendedMiningChannelSuccess, SetNewPrevHash};
+/// use stratumv2::translator::{Translator, TranslatorConfig};
+/// use stratumv2::v1::{Authorize, Subscribe, Submit};
+///
+/// let now = Instant::now();
+/// let mut translator = Translator::new(TranslatorConfig::new("pool.worker", 1e12)).unwrap();
+///
+/// // Open the extended channel upstream.
+/// let open_channel = translator.open_extended_mining_channel(1).unwrap();
+/// assert_eq!(open_channel.min_extranonce_size, 6);
+///
+/// let success = OpenExtendedMiningChannelSuccess::new(1, 7, [0xff; 32], 6, vec![0xaa]).unwrap();
+/// translator.handle_open_extended_mining_channel_success(&success).unwrap();
+///
+/// // A V1 miner subscribes and is assigned a part of the extranonce space.
+/// let subscribed = translator.handle_subscribe(1, &Subscribe::new(None)).unwrap();
+/// assert_eq!(subscribed.extranonce1, vec![0xaa, 0x00, 0x00]);
+/// assert_eq!(subscribed.extranonce2_size, 4);
+/// assert!(translator.handle_authorize(1, &Authorize::new("miner", "x")).unwrap());
+///
+/// // V2 jobs are sent to the miner as mining.notify.
+/// let job = NewExtendedMiningJob::new(7, 1, true, 0x2000_0000, true, vec![], vec![], vec![]).unwrap();
+/// assert!(translator.handle_new_extended_mining_job(job, now).unwrap().is_none());
+///
+/// let prev_hash = SetNewPrevHash::new(7, 1, [0u8; 32], 1_600_000_000, 0x1d00_ffff);
+/// let notify = translator.handle_set_new_prev_hash(prev_hash, now).unwrap();
+/// assert_eq!(notify.job_id, "1");
+/// assert!(notify.clean_jobs);
+///
+/// // Shares are sent upstream with the extranonce of the miner.
+/// let submit = Submit::new("miner", "1", vec![0x01, 0x02, 0x03, 0x04], 1_600_000_000, 0, None);
+/// let share = translator.handle_submit(1, &submit).unwrap();
+/// assert_eq!(share.channel_id, 7);
+/// assert_eq!(share.extranonce, vec![0x00, 0x00, 0x01, 0x02, 0x03, 0x04]);
+/// ```
+#[derive(Debug)]
+pub struct Translator {
+    config: TranslatorConfig,
+    upstream: Option<UpstreamChannel>,
+    downstreams: HashMap<u32, Downstream>,
+    jobs: JobStore,
+    shares: ShareTracker,
+    pending_shares: HashMap<u32, u32>,
+    version_rolling: HashMap<u32, VersionRolling>,
+}
+
+impl Translator {
+    /// Creates a Translator. A RequirementError is returned if the
+    /// `downstream_prefix_size` is not between 1 and 8 bytes.
+    pub fn new(config: TranslatorConfig) -> Result<Translator> {
+        if config.downstream_prefix_size == 0 || config.downstream_prefix_size > 8 {
+            return Err(Error::RequirementError(
+                "downstream_prefix_size must be between 1 and 8 bytes".into(),
+            ));
+        }
+
+        Ok(Translator {
+            jobs: JobStore::new(config.stale_window),
+            config,
+            upstream: None,
+            downstreams: HashMap::new(),
+            shares: ShareTracker::new(),
+            pending_shares: HashMap::new(),
+            version_rolling: HashMap::new(),
+        })
+    }
+
+    /// Returns the configuration of the Translator.
+    pub fn config(&self) -> &TranslatorConfig {
+        &self.config
+    }
+
+    /// Returns the id of the extended channel opened upstream.
+    pub fn channel_id(&self) -> Option<u32> {
+        self.upstream.as_ref().map(|upstream| upstream.channel_id)
+    }
+
+    /// Returns the OpenExtendedMiningChannel to send upstream, requesting
+    /// enough extranonce space for every downstream miner.
+    pub fn open_extended_mining_channel(
+        &self,
+        request_id: u32,
+    ) -> Result<OpenExtendedMiningChannel> {
+        OpenExtendedMiningChannel::new(
+            request_id,
+            self.config.user_identity.as_str(),
+            self.config.nominal_hash_rate,
+            [0xff; 32],
+            self.config.downstream_prefix_size + self.config.extranonce2_size,
+        )
+    }
+
+    /// Applies the OpenExtendedMiningChannelSuccess received from upstream.
+    ///
+    /// A RequirementError is returned if the channel is already open or if
+    /// the extranonce space assigned by the Upstream is too small.
+    pub fn handle_open_extended_mining_channel_success(
+        &mut self,
+        message: &OpenExtendedMiningChannelSuccess,
+    ) -> Result<()> {
+        if self.upstream.is_some() {
+            return Err(Error::RequirementError(
+                "the upstream channel is already open".into(),
+            ));
+        }
+
+        if message.extranonce_size
+            < self.config.downstream_prefix_size + self.config.extranonce2_size
+        {
+            return Err(Error::RequirementError(
+                "the upstream extranonce space is too small for the downstream miners".into(),
+            ));
+        }
+
+        let extranonce = ExtranonceAllocator::with_upstream_prefix(
+            message.extranonce_prefix.0.clone(),
+            message.extranonce_size,
+            self.config.downstream_prefix_size,
+        )?;
+
+        self.shares.add_channel(message.channel_id);
+        self.upstream = Some(UpstreamChannel {
+            channel_id: message.channel_id,
+            target: message.target,
+            extranonce,
+        });
+
+        Ok(())
+    }
+
+    /// Answers the mining.configure of a V1 miner, which is usually sent
+    /// before mining.subscribe. The negotiated version rolling mask is kept
+    /// for the shares of the miner, a miner that did not negotiate version
+    /// rolling cannot roll any bit.
+    pub fn handle_configure(
+        &mut self,
+        downstream_id: u32,
+        message: &Configure,
+    ) -> Result<ConfigureResult> {
+        let result = ConfigureResult::from_version_rolling(message, VersionRolling::bip320());
+        self.version_rolling
+            .insert(downstream_id, result.version_rolling()?);
+
+        Ok(result)
+    }
+
+    /// Registers a V1 miner, assigning its extranonce1. The extranonce2_size
+    /// of the miner covers the remaining space of the upstream channel.
+    ///
+    /// A RequirementError is returned if the upstream channel is not open, if
+    /// the miner already subscribed or if the extranonce space is exhausted.
+    pub fn handle_subscribe(
+        &mut self,
+        downstream_id: u32,
+        _message: &Subscribe,
+    ) -> Result<SubscribeResult> {
+        if self.downstreams.contains_key(&downstream_id) {
+            return Err(Error::RequirementError(
+                "the downstream miner already subscribed".into(),
+            ));
+        }
+
+        let upstream = self.upstream_mut()?;
+        let extranonce1 = upstream.extranonce.allocate(0)?;
+        let extranonce2_size = upstream.extranonce.extranonce_size();
+
+        self.downstreams.insert(
+            downstream_id,
+            Downstream {
+                extranonce1: extranonce1.clone(),
+                workers: vec![],
+            },
+        );
+
+        Ok(SubscribeResult::new(
+            downstream_id.to_string(),
+            extranonce1,
+            extranonce2_size,
+        ))
+    }
+
+    /// Authorizes a worker of a subscribed V1 miner. Workers share the
+    /// upstream channel, so every worker is authorized.
+    pub fn handle_authorize(&mut self, downstream_id: u32, message: &Authorize) -> Result<bool> {
+        let downstream = self.downstream_mut(downstream_id)?;
+
+        if !downstream.workers.contains(&message.username) {
+            downstream.workers.push(message.username.clone());
+        }
+
+        Ok(true)
+    }
+
+    /// Removes a disconnected V1 miner, releasing its extranonce1.
+    pub fn remove_downstream(&mut self, downstream_id: u32) {
+        self.version_rolling.remove(&downstream_id);
+        if let Some(downstream) = self.downstreams.remove(&downstream_id) {
+            if let Some(upstream) = self.upstream.as_mut() {
+                upstream.extranonce.release(&downstream.extranonce1);
+            }
+        }
+    }
+
+    /// Returns the mining.set_difficulty of the upstream channel target, sent
+    /// to every miner after it is authorized.
+    pub fn set_difficulty(&self) -> Option<SetDifficulty> {
+        self.upstream
+            .as_ref()
+            .map(|upstream| SetDifficulty::from_target(&upstream.target))
+    }
+
+    /// Returns the mining.notify of the last active job, sent to every miner
+    /// after it is authorized.
+    pub fn current_notify(&self) -> Option<Notify> {
+        let prev_hash = self.jobs.prev_hash()?;
+        let lookup = self.jobs.lookup(prev_hash.job_id)?;
+
+        match lookup.job {
+            Job::Extended(job) => Some(notify(job, prev_hash, true)),
+            Job::Standard(_) => None,
+        }
+    }
+
+    /// Applies a NewExtendedMiningJob received from upstream. Returns the
+    /// mining.notify to send to every miner, or None for a future job, which
+    /// is sent once it is activated by a SetNewPrevHash.
+    pub fn handle_new_extended_mining_job(
+        &mut self,
+        job: NewExtendedMiningJob,
+        now: Instant,
+    ) -> Result<Option<Notify>> {
+        self.check_channel_id(job.channel_id)?;
+
+        let message = match (job.future_job, self.jobs.prev_hash()) {
+            (false, Some(prev_hash)) => Some(notify(&job, prev_hash, false)),
+            _ => None,
+        };
+
+        self.jobs.add_job(job, now)?;
+        Ok(message)
+    }
+
+    /// Applies a SetNewPrevHash received from upstream. Returns the
+    /// mining.notify of the activated job, with `clean_jobs` set so miners
+    /// abandon the stale jobs. Jobs stale for longer than the `stale_window`
+    /// are forgotten.
+    pub fn handle_set_new_prev_hash(
+        &mut self,
+        prev_hash: SetNewPrevHash,
+        now: Instant,
+    ) -> Result<Notify> {
+        self.check_channel_id(prev_hash.channel_id)?;
+        self.jobs.set_new_prev_hash(prev_hash, now)?;
+        self.jobs.expire(now);
+
+        self.current_notify().ok_or_else(|| {
+            Error::RequirementError("SetNewPrevHash must reference an extended job".into())
+        })
+    }
+
+    /// Applies a SetTarget received from upstream. Returns the
+    /// mining.set_difficulty to send to every miner.
+    pub fn handle_set_target(&mut self, message: &SetTarget) -> Result<SetDifficulty> {
+        self.check_channel_id(message.channel_id)?;
+
+        let upstream = self.upstream_mut()?;
+        upstream.target = message.maximum_target;
+
+        Ok(SetDifficulty::from_target(&message.maximum_target))
+    }
+
+    /// Translates a mining.submit from a V1 miner into the
+    /// SubmitSharesExtended to send upstream. The extranonce of the share is
+    /// the extranonce1 of the miner, without the prefix of the upstream
+    /// channel, followed by the extranonce2 of the share.
+    ///
+    /// A RequirementError is returned if the worker is not authorized, if the
+    /// job is unknown or stale, if the extranonce2 is not `extranonce2_size`
+    /// bytes or if the miner rolled version bits outside of the mask
+    /// negotiated with mining.configure.
+    pub fn handle_submit(
+        &mut self,
+        downstream_id: u32,
+        message: &Submit,
+    ) -> Result<SubmitSharesExtended> {
+        let upstream = self
+            .upstream
+            .as_ref()
+            .ok_or_else(|| Error::RequirementError("the upstream channel is not open".into()))?;
+        let channel_id = upstream.channel_id;
+        let upstream_prefix_size = upstream.extranonce.upstream_prefix().len();
+        let extranonce2_size = upstream.extranonce.extranonce_size() as usize;
+
+        let downstream = self.downstreams.get(&downstream_id).ok_or_else(|| {
+            Error::RequirementError("the downstream miner did not subscribe".into())
+        })?;
+
+        if !downstream.workers.contains(&message.worker_name) {
+            return Err(Error::RequirementError(
+                "the worker is not authorized".into(),
+            ));
+        }
+
+        if message.extranonce2.len() != extranonce2_size {
+            return Err(Error::RequirementError(
+                "the extranonce2 does not match the extranonce2_size".into(),
+            ));
+        }
+
+        let job = message
+            .job_id
+            .parse::<u32>()
+            .ok()
+            .and_then(|job_id| self.jobs.lookup(job_id))
+            .ok_or_else(|| Error::RequirementError("the job is unknown".into()))?;
+
+        let job = match (job.state, job.job) {
+            (JobState::Active, Job::Extended(job)) => job,
+            _ => return Err(Error::RequirementError("the job is stale".into())),
+        };
+
+        let version_rolling = self
+            .version_rolling
+            .get(&downstream_id)
+            .copied()
+            .unwrap_or_else(VersionRolling::fixed)
+            .for_job(job.version_rolling_allowed);
+        let version = match message.version_bits {
+            Some(bits) if bits & !version_rolling.mask() != 0 => {
+                return Err(Error::RequirementError(
+                    "the version bits cannot be rolled".into(),
+                ))
+            }
+            Some(bits) => version_rolling.apply(job.version, bits),
+            None => job.version,
+        };
+
+        let mut extranonce = downstream.extranonce1[upstream_prefix_size..].to_vec();
+        extranonce.extend_from_slice(&message.extranonce2);
+        let job_id = job.job_id;
+
+        let sequence_number = self.shares.next_sequence_number(channel_id)?;
+        self.pending_shares.insert(sequence_number, downstream_id);
+
+        SubmitSharesExtended::new(
+            channel_id,
+            sequence_number,
+            job_id,
+            message.nonce,
+            message.ntime,
+            version,
+            extranonce,
+        )
+    }
+
+    /// Applies a SubmitSharesSuccess received from upstream.
+    pub fn handle_submit_shares_success(&mut self, message: &SubmitSharesSuccess) -> Result<()> {
+        self.check_channel_id(message.channel_id)?;
+        self.shares.on_success(message)?;
+
+        let last_sequence_number = message.last_sequence_number;
+        self.pending_shares.retain(|sequence_number, _| {
+            last_sequence_number.wrapping_sub(*sequence_number) >= 1 << 31
+        });
+
+        Ok(())
+    }
+
+    /// Applies a SubmitSharesError received from upstream. Returns the id of
+    /// the downstream miner that submitted the rejected share, if known.
+    pub fn handle_submit_shares_error(
+        &mut self,
+        message: &SubmitSharesError,
+    ) -> Result<Option<u32>> {
+        self.check_channel_id(message.channel_id)?;
+        self.shares.on_error(message)?;
+
+        Ok(self.pending_shares.remove(&message.sequence_number))
+    }
+
+    fn upstream_mut(&mut self) -> Result<&mut UpstreamChannel> {
+        self.upstream
+            .as_mut()
+            .ok_or_else(|| Error::RequirementError("the upstream channel is not open".into()))
+    }
+
+    fn downstream_mut(&mut self, downstream_id: u32) -> Result<&mut Downstream> {
+        self.downstreams
+            .get_mut(&downstream_id)
+            .ok_or_else(|| Error::RequirementError("the downstream miner did not subscribe".into()))
+    }
+
+    fn check_channel_id(&self, channel_id: u32) -> Result<()> {
+        match self.channel_id() {
+            Some(id) if id == channel_id => Ok(()),
+            _ => Err(Error::RequirementError(
+                "the message is not for the upstream channel".into(),
+            )),
+        }
+    }
+}
+
+/// Builds the mining.notify of an extended job on a prev hash.
+fn notify(job: &NewExtendedMiningJob, prev_hash: &SetNewPrevHash, clean_jobs: bool) -> Notify {
+    Notify {
+        job_id: job.job_id.to_string(),
+        prev_hash: prev_hash.prev_hash,
+        coinb1: job.coinbase_tx_prefix.0.clone(),
+        coinb2: job.coinbase_tx_suffix.0.clone(),
+        merkle_branch: job.merkle_path.clone(),
+        version: job.version,
+        nbits: prev_hash.nbits,
+        ntime: prev_hash.min_ntime,
+        clean_jobs,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mining::SubmitSharesErrorCodes;
+    use crate::target::Target;
+
+    fn open_translator() -> Translator {
+        let mut translator = Translator::new(TranslatorConfig::new("pool.worker", 1e12)).unwrap();
+        let success =
+            OpenExtendedMiningChannelSuccess::new(1, 7, [0xff; 32], 8, vec![0xaa, 0xbb]).unwrap();
+        translator
+            .handle_open_extended_mining_channel_success(&success)
+            .unwrap();
+
+        translator
+    }
+
+    fn subscribe(translator: &mut Translator, downstream_id: u32) -> SubscribeResult {
+        let result = translator
+            .handle_subscribe(downstream_id, &Subscribe::new(None))
+            .unwrap();
+        translator
+            .handle_authorize(downstream_id, &Authorize::new("miner", "x"))
+            .unwrap();
+
+        result
+    }
+
+    fn extended_job(job_id: u32, future_job: bool) -> NewExtendedMiningJob {
+        NewExtendedMiningJob::new(
+            7,
+            job_id,
+            future_job,
+            0x2000_0000,
+            true,
+            vec![[0x11; 32]],
+            vec![0x01, 0x02],
+            vec![0x03, 0x04],
+        )
+        .unwrap()
+    }
+
+    fn activate_job(translator: &mut Translator, job_id: u32, now: Instant) -> Notify {
+        translator
+            .handle_new_extended_mining_job(extended_job(job_id, true), now)
+            .unwrap();
+        translator
+            .handle_set_new_prev_hash(
+                SetNewPrevHash::new(7, job_id, [0x22; 32], 1_600_000_000, 0x1d00_ffff),
+                now,
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn open_upstream_channel() {
+        let mut translator = Translator::new(TranslatorConfig::new("pool.worker", 1e12)).unwrap();
+
+        let message = translator.open_extended_mining_channel(3).unwrap();
+        assert_eq!(message.request_id, 3);
+        assert_eq!(message.user_identity, "pool.worker".to_string());
+        assert_eq!(message.min_extranonce_size, 6);
+
+        // Miners cannot subscribe before the channel is open.
+        assert!(translator
+            .handle_subscribe(1, &Subscribe::new(None))
+            .is_err());
+
+        let too_small = OpenExtendedMiningChannelSuccess::new(3, 7, [0xff; 32], 5, vec![]).unwrap();
+        assert!(translator
+            .handle_open_extended_mining_channel_success(&too_small)
+            .is_err());
+
+        let success = OpenExtendedMiningChannelSuccess::new(3, 7, [0xff; 32], 6, vec![]).unwrap();
+        translator
+            .handle_open_extended_mining_channel_success(&success)
+            .unwrap();
+        assert_eq!(translator.channel_id(), Some(7));
+        assert!(translator
+            .handle_open_extended_mining_channel_success(&success)
+            .is_err());
+    }
+
+    #[test]
+    fn split_extranonce_between_miners() {
+        let mut translator = open_translator();
+
+        let first = subscribe(&mut translator, 1);
+        let second = subscribe(&mut translator, 2);
+        assert_eq!(first.extranonce1, vec![0xaa, 0xbb, 0x00, 0x00]);
+        assert_eq!(second.extranonce1, vec![0xaa, 0xbb, 0x00, 0x01]);
+        assert_eq!(first.extranonce2_size, 6);
+
+        assert!(translator
+            .handle_subscribe(1, &Subscribe::new(None))
+            .is_err());
+
+        // The extranonce1 of a disconnected miner is reused.
+        translator.remove_downstream(1);
+        assert_eq!(subscribe(&mut translator, 3).extranonce1, first.extranonce1);
+    }
+
+    #[test]
+    fn translate_jobs() {
+        let now = Instant::now();
+        let mut translator = open_translator();
+        assert!(translator.current_notify().is_none());
+
+        let notify = activate_job(&mut translator, 1, now);
+        assert_eq!(
+            notify,
+            Notify {
+                job_id: "1".into(),
+                prev_hash: [0x22; 32],
+                coinb1: vec![0x01, 0x02],
+                coinb2: vec![0x03, 0x04],
+                merkle_branch: vec![[0x11; 32]],
+                version: 0x2000_0000,
+                nbits: 0x1d00_ffff,
+                ntime: 1_600_000_000,
+                clean_jobs: true,
+            }
+        );
+        assert_eq!(translator.current_notify(), Some(notify));
+
+        // A job on the current prev hash is sent immediately.
+        let notify = translator
+            .handle_new_extended_mining_job(extended_job(2, false), now)
+            .unwrap()
+            .unwrap();
+        assert_eq!(notify.job_id, "2");
+        assert!(!notify.clean_jobs);
+
+        // Jobs for another channel are rejected.
+        let mut job = extended_job(3, true);
+        job.channel_id = 8;
+        assert!(translator.handle_new_extended_mining_job(job, now).is_err());
+    }
+
+    #[test]
+    fn translate_set_target() {
+        let mut translator = open_translator();
+        assert!(translator.set_difficulty().unwrap().difficulty < 1.0);
+
+        let target = Target::from_pool_difficulty(1024.0).unwrap();
+        let message = translator
+            .handle_set_target(&SetTarget::new(7, target.into()))
+            .unwrap();
+        assert_eq!(message.difficulty, 1024.0);
+        assert_eq!(translator.set_difficulty(), Some(message));
+
+        assert!(translator
+            .handle_set_target(&SetTarget::new(8, target.into()))
+            .is_err());
+    }
+
+    #[test]
+    fn translate_submit() {
+        let now = Instant::now();
+        let mut translator = open_translator();
+        for downstream_id in 1..=2 {
+            translator
+                .handle_configure(downstream_id, &Configure::version_rolling(0x0000_e000, 2))
+                .unwrap();
+            subscribe(&mut translator, downstream_id);
+        }
+        activate_job(&mut translator, 1, now);
+
+        let extranonce2 = vec![0x01, 0x02, 0x03, 0x04, 0x05, 0x06];
+        let submit = Submit::new("miner", "1", extranonce2.clone(), 1, 2, Some(0x0000_e000));
+        let share = translator.handle_submit(2, &submit).unwrap();
+
+        assert_eq!(share.channel_id, 7);
+        assert_eq!(share.sequence_number, 0);
+        assert_eq!(share.job_id, 1);
+        assert_eq!(share.ntime, 1);
+        assert_eq!(share.nonce, 2);
+        assert_eq!(share.version, 0x2000_e000);
+        assert_eq!(
+            share.extranonce,
+            vec![0x00, 0x01, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06]
+        );
+
+        let share = translator.handle_submit(1, &submit).unwrap();
+        assert_eq!(share.sequence_number, 1);
+
+        // Rejections are matched with the miner that submitted the share.
+        let error = SubmitSharesError::new(7, 0, SubmitSharesErrorCodes::DifficultyTooLow);
+        assert_eq!(
+            translator.handle_submit_shares_error(&error).unwrap(),
+            Some(2)
+        );
+        translator
+            .handle_submit_shares_success(&SubmitSharesSuccess::new(7, 1, 1, 1))
+            .unwrap();
+        assert!(translator.pending_shares.is_empty());
+    }
+
+    #[test]
+    fn negotiate_version_rolling() {
+        let now = Instant::now();
+        let mut translator = open_translator();
+
+        let result = translator
+            .handle_configure(1, &Configure::version_rolling(0xffff_ffff, 2))
+            .unwrap();
+        assert_eq!(
+            result.version_rolling_mask,
+            Some(VersionRolling::BIP320_MASK)
+        );
+        let result = translator
+            .handle_configure(2, &Configure::version_rolling(0x0000_e000, 2))
+            .unwrap();
+        assert_eq!(result.version_rolling_mask, Some(0x0000_e000));
+        for downstream_id in 1..=3 {
+            subscribe(&mut translator, downstream_id);
+        }
+        activate_job(&mut translator, 1, now);
+
+        let extranonce2 = vec![0u8; 6];
+        let submit = Submit::new("miner", "1", extranonce2, 1, 2, Some(0x0001_0000));
+        assert_eq!(
+            translator.handle_submit(1, &submit).unwrap().version,
+            0x2001_0000
+        );
+
+        // Bits outside of the negotiated mask are rejected.
+        assert!(translator.handle_submit(2, &submit).is_err());
+
+        // A miner that did not negotiate version rolling cannot roll.
+        assert!(translator.handle_submit(3, &submit).is_err());
+        let mut submit = submit;
+        submit.version_bits = None;
+        assert!(translator.handle_submit(3, &submit).is_ok());
+    }
+
+    #[test]
+    fn reject_invalid_submit() {
+        let now = Instant::now();
+        let mut translator = open_translator();
+        subscribe(&mut translator, 1);
+        activate_job(&mut translator, 1, now);
+
+        let extranonce2 = vec![0u8; 6];
+        let invalid = [
+            Submit::new("unknown", "1", extranonce2.clone(), 1, 2, None),
+            Submit::new("miner", "2", extranonce2.clone(), 1, 2, None),
+            Submit::new("miner", "job", extranonce2.clone(), 1, 2, None),
+            Submit::new("miner", "1", vec![0u8; 5], 1, 2, None),
+            Submit::new("miner", "1", extranonce2.clone(), 1, 2, Some(0x0000_0001)),
+        ];
+        for submit in invalid.iter() {
+            assert!(translator.handle_submit(1, submit).is_err());
+        }
+
+        let submit = Submit::new("miner", "1", extranonce2, 1, 2, None);
+        assert!(translator.handle_submit(2, &submit).is_err());
+
+        // Shares on a stale job are rejected.
+        activate_job(&mut translator, 2, now);
+        assert!(translator.handle_submit(1, &submit).is_err());
+    }
+}
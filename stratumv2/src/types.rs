@@ -1,5 +1,9 @@
 use crate::error::{Error, Result};
+use crate::BitFlag;
 use std::convert::TryFrom;
+use std::fmt;
+use std::iter::FromIterator;
+use std::marker::PhantomData;
 
 /// U256 is an unsigned integer type of 256-bits in little endian. This will
 /// usually be used to represent a raw SHA256 byte output.
@@ -18,7 +22,7 @@ impl_sized_STR0!(STR0_32, 32);
 /// Serialization is assumed with the following structure:
 /// <1-byte length L (u8) + variable length bytes>
 #[derive(Debug, Clone)]
-pub struct B0_32(Vec<u8>);
+pub struct B0_32(pub(crate) Vec<u8>);
 
 impl B0_32 {
     const MAX_SIZE: usize = 32;
@@ -46,9 +50,389 @@ impl PartialEq<Vec<u8>> for B0_32 {
     }
 }
 
+/// AsRef implementation giving access to the bytes of B0_32 without the
+/// length prefix.
+impl AsRef<[u8]> for B0_32 {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// B0_64K is a type representing a vector of bytes with a maximum size of
+/// 65535 bytes. Serialization has the following structure:
+/// <2-byte length L (u16) + variable length bytes>
+#[derive(Debug, Clone)]
+pub struct B0_64K(pub(crate) Vec<u8>);
+
+impl B0_64K {
+    const MAX_SIZE: usize = 65535;
+
+    pub fn new<T: Into<Vec<u8>>>(value: T) -> Result<B0_64K> {
+        let input = value.into();
+        if input.len() > Self::MAX_SIZE {
+            return Err(Error::RequirementError(
+                "length of bytes cannot be greater than 65535".into(),
+            ));
+        }
+
+        Ok(B0_64K(input))
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        serialize_slices!(&(self.0.len() as u16).to_le_bytes(), &self.0)
+    }
+}
+
+/// PartialEq implementation allowing direct comparison between B0_64K and Vec<u8>.
+impl PartialEq<Vec<u8>> for B0_64K {
+    fn eq(&self, other: &Vec<u8>) -> bool {
+        self.0 == *other
+    }
+}
+
+/// AsRef implementation giving access to the bytes of B0_64K without the
+/// length prefix.
+impl AsRef<[u8]> for B0_64K {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// B0_255 is a type representing a vector of bytes with a maximum size of 255
+/// bytes. Serialization has the following structure:
+/// <1-byte length L (u8) + variable length bytes>
+#[derive(Debug, Clone)]
+pub struct B0_255(pub(crate) Vec<u8>);
+
+impl B0_255 {
+    const MAX_SIZE: usize = 255;
+
+    pub fn new<T: Into<Vec<u8>>>(value: T) -> Result<B0_255> {
+        let input = value.into();
+        if input.len() > Self::MAX_SIZE {
+            return Err(Error::RequirementError(
+                "length of bytes cannot be greater than 255".into(),
+            ));
+        }
+
+        Ok(B0_255(input))
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        serialize_slices!(&[self.0.len() as u8], &self.0)
+    }
+}
+
+/// PartialEq implementation allowing direct comparison between B0_255 and Vec<u8>.
+impl PartialEq<Vec<u8>> for B0_255 {
+    fn eq(&self, other: &Vec<u8>) -> bool {
+        self.0 == *other
+    }
+}
+
+/// AsRef implementation giving access to the bytes of B0_255 without the
+/// length prefix.
+impl AsRef<[u8]> for B0_255 {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// B0_16M is a type representing a vector of bytes with a maximum size of
+/// 16777215 bytes. Serialization has the following structure:
+/// <3-byte length L (u24) + variable length bytes>
+#[derive(Debug, Clone)]
+pub struct B0_16M(pub(crate) Vec<u8>);
+
+impl B0_16M {
+    const MAX_SIZE: usize = 16_777_215;
+
+    pub fn new<T: Into<Vec<u8>>>(value: T) -> Result<B0_16M> {
+        let input = value.into();
+        if input.len() > Self::MAX_SIZE {
+            return Err(Error::RequirementError(
+                "length of bytes cannot be greater than 16777215".into(),
+            ));
+        }
+
+        Ok(B0_16M(input))
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        serialize_slices!(&(self.0.len() as u32).to_le_bytes()[0..=2], &self.0)
+    }
+}
+
+/// PartialEq implementation allowing direct comparison between B0_16M and Vec<u8>.
+impl PartialEq<Vec<u8>> for B0_16M {
+    fn eq(&self, other: &Vec<u8>) -> bool {
+        self.0 == *other
+    }
+}
+
+/// AsRef implementation giving access to the bytes of B0_16M without the
+/// length prefix.
+impl AsRef<[u8]> for B0_16M {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Implements hex string serialization of the B0 types for the `serde`
+/// feature. Deserialization enforces the maximum size of the type.
+#[cfg(feature = "serde")]
+macro_rules! impl_B0_serde {
+    ($($type:ident),*) => {
+        $(
+            impl serde::Serialize for $type {
+                fn serialize<S: serde::Serializer>(
+                    &self,
+                    serializer: S,
+                ) -> std::result::Result<S::Ok, S::Error> {
+                    serializer.serialize_str(&crate::util::hex::encode(&self.0))
+                }
+            }
+
+            impl<'de> serde::Deserialize<'de> for $type {
+                fn deserialize<D: serde::Deserializer<'de>>(
+                    deserializer: D,
+                ) -> std::result::Result<Self, D::Error> {
+                    let bytes = crate::util::hex::deserialize_bytes(deserializer)?;
+                    $type::new(bytes).map_err(serde::de::Error::custom)
+                }
+            }
+        )*
+    };
+}
+
+#[cfg(feature = "serde")]
+impl_B0_serde!(B0_32, B0_64K, B0_255, B0_16M);
+
+/// FlagSet is a set of feature flags stored as the raw u32 bit field sent in
+/// a message. Bits that do not correspond to a known flag of type `T` are
+/// preserved, so that flags defined by newer versions of the protocol are not
+/// silently dropped and can be reported back to the other node.
+///
+/// # Examples
+///
+/// ```rust
+/// use stratumv2::mining::SetupConnectionFlags;
+/// use stratumv2::types::FlagSet;
+///
+/// let mut flags = FlagSet::from([SetupConnectionFlags::RequiresStandardJobs]);
+/// flags.insert(SetupConnectionFlags::RequiresVersionRolling);
+///
+/// assert!(flags.contains(SetupConnectionFlags::RequiresStandardJobs));
+/// assert!(!flags.contains(SetupConnectionFlags::RequiresWorkSelection));
+/// assert_eq!(flags.bits(), 0x05);
+///
+/// // Unknown bits are kept when deserializing flags.
+/// let flags = FlagSet::<SetupConnectionFlags>::from_bits(0x09);
+/// assert!(flags.contains(SetupConnectionFlags::RequiresStandardJobs));
+/// assert_eq!(flags.unknown_bits(), 0x08);
+/// ```
+pub struct FlagSet<T> {
+    bits: u32,
+    flag_type: PhantomData<T>,
+}
+
+impl<T: BitFlag> FlagSet<T> {
+    /// Returns a FlagSet without any flags set.
+    pub fn empty() -> FlagSet<T> {
+        FlagSet::from_bits(0)
+    }
+
+    /// Returns a FlagSet from a raw bit field, including any unknown bits.
+    pub fn from_bits(bits: u32) -> FlagSet<T> {
+        FlagSet {
+            bits,
+            flag_type: PhantomData,
+        }
+    }
+
+    /// Returns the raw bit field, including any unknown bits.
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    /// Returns the bits that do not correspond to a known flag.
+    pub fn unknown_bits(&self) -> u32 {
+        self.bits & !Self::known_bits()
+    }
+
+    /// Returns true if any bits that do not correspond to a known flag are set.
+    pub fn has_unknown_bits(&self) -> bool {
+        self.unknown_bits() != 0
+    }
+
+    /// Returns true if no bits are set.
+    pub fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+
+    /// Returns true if the flag is set.
+    pub fn contains(&self, flag: T) -> bool {
+        self.bits & flag.as_bit_flag() != 0
+    }
+
+    /// Returns true if every bit set in other is also set in this FlagSet.
+    pub fn is_superset(&self, other: FlagSet<T>) -> bool {
+        other.bits & !self.bits == 0
+    }
+
+    /// Sets the flag.
+    pub fn insert(&mut self, flag: T) {
+        self.bits |= flag.as_bit_flag();
+    }
+
+    /// Unsets the flag.
+    pub fn remove(&mut self, flag: T) {
+        self.bits &= !flag.as_bit_flag();
+    }
+
+    /// Returns the bits set in either FlagSet.
+    pub fn union(&self, other: FlagSet<T>) -> FlagSet<T> {
+        FlagSet::from_bits(self.bits | other.bits)
+    }
+
+    /// Returns the bits set in both FlagSets.
+    pub fn intersection(&self, other: FlagSet<T>) -> FlagSet<T> {
+        FlagSet::from_bits(self.bits & other.bits)
+    }
+
+    /// Returns the bits set in this FlagSet but not in other.
+    pub fn difference(&self, other: FlagSet<T>) -> FlagSet<T> {
+        FlagSet::from_bits(self.bits & !other.bits)
+    }
+
+    /// Returns an iterator over the known flags that are set.
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        T::FLAGS
+            .iter()
+            .filter(move |flag| self.bits & flag.as_bit_flag() != 0)
+            .copied()
+    }
+
+    fn known_bits() -> u32 {
+        T::FLAGS
+            .iter()
+            .fold(0, |accumulator, flag| accumulator | flag.as_bit_flag())
+    }
+}
+
+impl<T: BitFlag> Default for FlagSet<T> {
+    fn default() -> Self {
+        FlagSet::empty()
+    }
+}
+
+// Clone, Copy and PartialEq are implemented manually since deriving would
+// require the same traits on T.
+impl<T> Clone for FlagSet<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for FlagSet<T> {}
+
+impl<T> PartialEq for FlagSet<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.bits == other.bits
+    }
+}
+
+impl<T> Eq for FlagSet<T> {}
+
+impl<T: BitFlag + fmt::Debug> fmt::Debug for FlagSet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut set = f.debug_set();
+        set.entries(self.iter());
+
+        if self.has_unknown_bits() {
+            set.entry(&format_args!("{:#x}", self.unknown_bits()));
+        }
+
+        set.finish()
+    }
+}
+
+impl<T: BitFlag> From<T> for FlagSet<T> {
+    fn from(flag: T) -> Self {
+        FlagSet::from_bits(flag.as_bit_flag())
+    }
+}
+
+impl<T: BitFlag> From<&[T]> for FlagSet<T> {
+    fn from(flags: &[T]) -> Self {
+        flags.iter().copied().collect()
+    }
+}
+
+impl<T: BitFlag, const N: usize> From<[T; N]> for FlagSet<T> {
+    fn from(flags: [T; N]) -> Self {
+        flags.iter().copied().collect()
+    }
+}
+
+impl<T: BitFlag> FromIterator<T> for FlagSet<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut set = FlagSet::empty();
+        for flag in iter {
+            set.insert(flag);
+        }
+
+        set
+    }
+}
+
+/// An entry of a serialized FlagSet, either the name of a known flag or the
+/// unknown bits.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+enum FlagSetEntry<T> {
+    Flag(T),
+    UnknownBits(u32),
+}
+
+/// Serializes a FlagSet as a list of the names of the set flags, followed by
+/// the unknown bits if any are set.
+#[cfg(feature = "serde")]
+impl<T: BitFlag + serde::Serialize> serde::Serialize for FlagSet<T> {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        let mut entries: Vec<FlagSetEntry<T>> = self.iter().map(FlagSetEntry::Flag).collect();
+        if self.has_unknown_bits() {
+            entries.push(FlagSetEntry::UnknownBits(self.unknown_bits()));
+        }
+
+        serde::Serialize::serialize(&entries, serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: BitFlag + serde::Deserialize<'de>> serde::Deserialize<'de> for FlagSet<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let entries: Vec<FlagSetEntry<T>> = serde::Deserialize::deserialize(deserializer)?;
+
+        Ok(FlagSet::from_bits(entries.iter().fold(0, |bits, entry| {
+            bits | match entry {
+                FlagSetEntry::Flag(flag) => flag.as_bit_flag(),
+                FlagSetEntry::UnknownBits(unknown_bits) => *unknown_bits,
+            }
+        })))
+    }
+}
+
 /// MessageTypes contain all the variations for the byte representation of
 /// messages used in message frames.
 #[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MessageTypes {
     SetupConnection,
     SetupConnectionSuccess,
@@ -59,6 +443,33 @@ pub enum MessageTypes {
     OpenExtendedMiningChannel,
     OpenExtendedMiningChannelSuccess,
     OpenExtendedMiningChannelError,
+    UpdateChannel,
+    CloseChannel,
+    SubmitSharesStandard,
+    SubmitSharesExtended,
+    SubmitSharesSuccess,
+    SubmitSharesError,
+    NewMiningJob,
+    NewExtendedMiningJob,
+    SetNewPrevHash,
+    SetTarget,
+    Reconnect,
+    AllocateMiningJobToken,
+    AllocateMiningJobTokenSuccess,
+    IdentifyTransactions,
+    IdentifyTransactionsSuccess,
+    ProvideMissingTransactions,
+    ProvideMissingTransactionsSuccess,
+    CommitMiningJob,
+    CommitMiningJobSuccess,
+    CommitMiningJobError,
+    CoinbaseOutputDataSize,
+    NewTemplate,
+    SetNewPrevHashTemplateDistribution,
+    RequestTransactionData,
+    RequestTransactionDataSuccess,
+    RequestTransactionDataError,
+    SubmitSolution,
 }
 
 impl From<MessageTypes> for u8 {
@@ -73,6 +484,33 @@ impl From<MessageTypes> for u8 {
             MessageTypes::OpenExtendedMiningChannel => 0x13,
             MessageTypes::OpenExtendedMiningChannelSuccess => 0x14,
             MessageTypes::OpenExtendedMiningChannelError => 0x15,
+            MessageTypes::UpdateChannel => 0x16,
+            MessageTypes::CloseChannel => 0x18,
+            MessageTypes::SubmitSharesStandard => 0x1a,
+            MessageTypes::SubmitSharesExtended => 0x1b,
+            MessageTypes::SubmitSharesSuccess => 0x1c,
+            MessageTypes::SubmitSharesError => 0x1d,
+            MessageTypes::NewMiningJob => 0x1e,
+            MessageTypes::NewExtendedMiningJob => 0x1f,
+            MessageTypes::SetNewPrevHash => 0x20,
+            MessageTypes::SetTarget => 0x21,
+            MessageTypes::Reconnect => 0x25,
+            MessageTypes::AllocateMiningJobToken => 0x50,
+            MessageTypes::AllocateMiningJobTokenSuccess => 0x51,
+            MessageTypes::IdentifyTransactions => 0x53,
+            MessageTypes::IdentifyTransactionsSuccess => 0x54,
+            MessageTypes::ProvideMissingTransactions => 0x55,
+            MessageTypes::ProvideMissingTransactionsSuccess => 0x56,
+            MessageTypes::CommitMiningJob => 0x57,
+            MessageTypes::CommitMiningJobSuccess => 0x58,
+            MessageTypes::CommitMiningJobError => 0x59,
+            MessageTypes::CoinbaseOutputDataSize => 0x70,
+            MessageTypes::NewTemplate => 0x71,
+            MessageTypes::SetNewPrevHashTemplateDistribution => 0x72,
+            MessageTypes::RequestTransactionData => 0x73,
+            MessageTypes::RequestTransactionDataSuccess => 0x74,
+            MessageTypes::RequestTransactionDataError => 0x75,
+            MessageTypes::SubmitSolution => 0x76,
         }
     }
 }
@@ -87,9 +525,37 @@ impl TryFrom<u8> for MessageTypes {
             0x03 => Ok(MessageTypes::SetupConnectionError),
             0x10 => Ok(MessageTypes::OpenStandardMiningChannel),
             0x11 => Ok(MessageTypes::OpenStandardMiningChannelSuccess),
+            0x12 => Ok(MessageTypes::OpenStandardMiningChannelError),
             0x13 => Ok(MessageTypes::OpenExtendedMiningChannel),
             0x14 => Ok(MessageTypes::OpenExtendedMiningChannelSuccess),
             0x15 => Ok(MessageTypes::OpenExtendedMiningChannelError),
+            0x16 => Ok(MessageTypes::UpdateChannel),
+            0x18 => Ok(MessageTypes::CloseChannel),
+            0x1a => Ok(MessageTypes::SubmitSharesStandard),
+            0x1b => Ok(MessageTypes::SubmitSharesExtended),
+            0x1c => Ok(MessageTypes::SubmitSharesSuccess),
+            0x1d => Ok(MessageTypes::SubmitSharesError),
+            0x1e => Ok(MessageTypes::NewMiningJob),
+            0x1f => Ok(MessageTypes::NewExtendedMiningJob),
+            0x20 => Ok(MessageTypes::SetNewPrevHash),
+            0x21 => Ok(MessageTypes::SetTarget),
+            0x25 => Ok(MessageTypes::Reconnect),
+            0x50 => Ok(MessageTypes::AllocateMiningJobToken),
+            0x51 => Ok(MessageTypes::AllocateMiningJobTokenSuccess),
+            0x53 => Ok(MessageTypes::IdentifyTransactions),
+            0x54 => Ok(MessageTypes::IdentifyTransactionsSuccess),
+            0x55 => Ok(MessageTypes::ProvideMissingTransactions),
+            0x56 => Ok(MessageTypes::ProvideMissingTransactionsSuccess),
+            0x57 => Ok(MessageTypes::CommitMiningJob),
+            0x58 => Ok(MessageTypes::CommitMiningJobSuccess),
+            0x59 => Ok(MessageTypes::CommitMiningJobError),
+            0x70 => Ok(MessageTypes::CoinbaseOutputDataSize),
+            0x71 => Ok(MessageTypes::NewTemplate),
+            0x72 => Ok(MessageTypes::SetNewPrevHashTemplateDistribution),
+            0x73 => Ok(MessageTypes::RequestTransactionData),
+            0x74 => Ok(MessageTypes::RequestTransactionDataSuccess),
+            0x75 => Ok(MessageTypes::RequestTransactionDataError),
+            0x76 => Ok(MessageTypes::SubmitSolution),
             _ => Err(Error::UnknownMessageType()),
         }
     }
@@ -151,4 +617,126 @@ mod tests {
         let c = STR0_255::new("bar").unwrap();
         assert!(a != c);
     }
+
+    #[test]
+    fn b0_as_ref() {
+        let b0_32 = B0_32::new(vec![0x01, 0x02]).unwrap();
+        assert_eq!(b0_32.as_ref(), &[0x01, 0x02]);
+        assert_eq!(b0_32.as_bytes(), vec![0x02, 0x01, 0x02]);
+
+        let b0_64k = B0_64K::new(vec![0x03]).unwrap();
+        assert_eq!(b0_64k.as_ref(), &[0x03]);
+    }
+
+    #[test]
+    fn b0_255_size_limit() {
+        assert!(B0_255::new(vec![0u8; 255]).is_ok());
+        assert!(B0_255::new(vec![0u8; 256]).is_err());
+        assert_eq!(
+            B0_255::new(vec![0x01]).unwrap().as_bytes(),
+            vec![0x01, 0x01]
+        );
+    }
+
+    #[test]
+    fn b0_16m_to_bytes() {
+        let b0_16m = B0_16M::new(vec![0xaa; 0x0102]).unwrap();
+        let bytes = b0_16m.as_bytes();
+
+        assert_eq!(bytes[0..3], [0x02, 0x01, 0x00]);
+        assert_eq!(bytes.len(), 3 + 0x0102);
+        assert!(B0_16M::new(vec![0u8; 16_777_216]).is_err());
+    }
+
+    #[test]
+    fn flag_set_iteration() {
+        use crate::mining::SetupConnectionFlags;
+
+        let flags = FlagSet::from([
+            SetupConnectionFlags::RequiresVersionRolling,
+            SetupConnectionFlags::RequiresStandardJobs,
+        ]);
+
+        // Flags are iterated in the order of BitFlag::FLAGS, unknown bits are
+        // skipped.
+        let flags = FlagSet::<SetupConnectionFlags>::from_bits(flags.bits() | 0x80);
+        assert_eq!(
+            flags.iter().collect::<Vec<_>>(),
+            vec![
+                SetupConnectionFlags::RequiresStandardJobs,
+                SetupConnectionFlags::RequiresVersionRolling
+            ]
+        );
+        assert_eq!(FlagSet::<SetupConnectionFlags>::empty().iter().count(), 0);
+        assert_eq!(flags.iter().collect::<FlagSet<_>>().bits(), 0x05);
+    }
+
+    #[test]
+    fn flag_set_unknown_bits() {
+        use crate::mining::SetupConnectionFlags;
+
+        let mut flags = FlagSet::<SetupConnectionFlags>::from_bits(0xf000_0002);
+        assert!(flags.contains(SetupConnectionFlags::RequiresWorkSelection));
+        assert!(flags.has_unknown_bits());
+        assert_eq!(flags.unknown_bits(), 0xf000_0000);
+
+        // Unknown bits are kept through set operations.
+        flags.remove(SetupConnectionFlags::RequiresWorkSelection);
+        assert_eq!(flags.bits(), 0xf000_0000);
+        assert!(!flags.is_empty());
+
+        let known = FlagSet::from(SetupConnectionFlags::RequiresStandardJobs);
+        assert_eq!(flags.union(known).bits(), 0xf000_0001);
+        assert_eq!(flags.intersection(known).bits(), 0);
+        assert_eq!(flags.union(known).difference(flags), known);
+        assert!(flags.union(known).is_superset(known));
+        assert!(!known.is_superset(flags));
+        assert_eq!(
+            format!("{:?}", flags.union(known)),
+            "{RequiresStandardJobs, 0xf0000000}"
+        );
+    }
+
+    #[test]
+    fn flag_set_serialize_round_trip() {
+        use crate::mining::{SetupConnection, SetupConnectionFlags};
+        use crate::{Deserializable, Protocol, Serializable};
+
+        let flags = FlagSet::<SetupConnectionFlags>::from_bits(0x8000_0005);
+        let message = SetupConnection::new(
+            2,
+            2,
+            flags,
+            "0.0.0.0",
+            8545,
+            "Bitmain",
+            "S9",
+            "braiins-os",
+            "",
+        )
+        .unwrap();
+
+        let mut bytes = vec![];
+        message.serialize(&mut bytes).unwrap();
+        assert_eq!(bytes[0], Protocol::Mining as u8);
+        assert_eq!(bytes[5..9], 0x8000_0005u32.to_le_bytes());
+
+        let deserialized = SetupConnection::deserialize(&bytes).unwrap();
+        assert_eq!(deserialized.flags, flags);
+        assert_eq!(deserialized.flags.unknown_bits(), 0x8000_0000);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn flag_set_json_round_trip() {
+        use crate::mining::SetupConnectionFlags;
+
+        let flags = FlagSet::<SetupConnectionFlags>::from_bits(0x8000_0004);
+        let json = serde_json::to_string(&flags).unwrap();
+        assert_eq!(json, r#"["RequiresVersionRolling",2147483648]"#);
+
+        let deserialized: FlagSet<SetupConnectionFlags> = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, flags);
+        assert!(serde_json::from_str::<FlagSet<SetupConnectionFlags>>(r#"["Unknown"]"#).is_err());
+    }
 }
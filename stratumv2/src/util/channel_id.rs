@@ -1,10 +1,150 @@
+use crate::error::{Error, Result};
 use rand::Rng;
+use std::collections::HashSet;
 
 /// Generate a new random channel_id.
 pub fn new_channel_id() -> u32 {
     rand::thread_rng().gen_range(0, u32::MAX)
 }
 
+/// The strategy used by a [ChannelIdAllocator](struct.ChannelIdAllocator.html)
+/// to pick new ids.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ChannelIdStrategy {
+    /// Ids are allocated in increasing order, wrapping around on overflow.
+    Sequential,
+
+    /// Ids are picked at random.
+    Random,
+}
+
+/// ChannelIdAllocator allocates the channel ids of a single connection. The
+/// specification requires channel ids to be unique and stable for the whole
+/// lifetime of a connection, so an id is never returned while it is live,
+/// either as a channel id or as a group channel id.
+///
+/// # Examples
+///
+/// ```rust
+/// use stratumv2::util::{ChannelIdAllocator, ChannelIdStrategy};
+///
+/// let mut ids = ChannelIdAllocator::new(ChannelIdStrategy::Sequential);
+///
+/// let group_channel_id = ids.allocate_group().unwrap();
+/// let channel_id = ids.allocate().unwrap();
+/// assert_eq!(group_channel_id, 0);
+/// assert_eq!(channel_id, 1);
+///
+/// // Ids of closed channels can be reused by later channels.
+/// assert!(ids.free(channel_id));
+/// assert!(!ids.is_allocated(channel_id));
+///
+/// // Group channel ids are not freed as channel ids.
+/// assert!(!ids.free(group_channel_id));
+/// assert!(ids.is_group(group_channel_id));
+/// ```
+#[derive(Debug)]
+pub struct ChannelIdAllocator {
+    strategy: ChannelIdStrategy,
+    next: u32,
+    channels: HashSet<u32>,
+    groups: HashSet<u32>,
+}
+
+impl ChannelIdAllocator {
+    /// Creates an allocator without any live ids, allocating new ids with
+    /// `strategy`.
+    pub fn new(strategy: ChannelIdStrategy) -> ChannelIdAllocator {
+        ChannelIdAllocator {
+            strategy,
+            next: 0,
+            channels: HashSet::new(),
+            groups: HashSet::new(),
+        }
+    }
+
+    /// Returns the strategy new ids are allocated with.
+    pub fn strategy(&self) -> ChannelIdStrategy {
+        self.strategy
+    }
+
+    /// Allocates an id for a new channel.
+    ///
+    /// A RequirementError is returned if every id is live.
+    pub fn allocate(&mut self) -> Result<u32> {
+        let id = self.next_id()?;
+        self.channels.insert(id);
+
+        Ok(id)
+    }
+
+    /// Allocates an id for a new group channel. Group channel ids share the
+    /// id space of channels but are tracked separately.
+    ///
+    /// A RequirementError is returned if every id is live.
+    pub fn allocate_group(&mut self) -> Result<u32> {
+        let id = self.next_id()?;
+        self.groups.insert(id);
+
+        Ok(id)
+    }
+
+    /// Frees the id of a closed channel. Returns false if the id is not a
+    /// live channel id.
+    pub fn free(&mut self, channel_id: u32) -> bool {
+        self.channels.remove(&channel_id)
+    }
+
+    /// Frees the id of a group channel. Returns false if the id is not a live
+    /// group channel id.
+    pub fn free_group(&mut self, group_channel_id: u32) -> bool {
+        self.groups.remove(&group_channel_id)
+    }
+
+    /// Returns true if the id is a live channel id.
+    pub fn is_allocated(&self, channel_id: u32) -> bool {
+        self.channels.contains(&channel_id)
+    }
+
+    /// Returns true if the id is a live group channel id.
+    pub fn is_group(&self, group_channel_id: u32) -> bool {
+        self.groups.contains(&group_channel_id)
+    }
+
+    fn is_live(&self, id: u32) -> bool {
+        self.channels.contains(&id) || self.groups.contains(&id)
+    }
+
+    fn next_id(&mut self) -> Result<u32> {
+        if self.channels.len() + self.groups.len() > u32::MAX as usize {
+            return Err(Error::RequirementError(
+                "every channel id is allocated".into(),
+            ));
+        }
+
+        match self.strategy {
+            ChannelIdStrategy::Sequential => {
+                while self.is_live(self.next) {
+                    self.next = self.next.wrapping_add(1);
+                }
+
+                let id = self.next;
+                self.next = self.next.wrapping_add(1);
+                Ok(id)
+            }
+            ChannelIdStrategy::Random => {
+                let mut rng = rand::thread_rng();
+                loop {
+                    let id = rng.gen::<u32>();
+                    if !self.is_live(id) {
+                        return Ok(id);
+                    }
+                }
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -13,4 +153,63 @@ mod tests {
     fn channel_id_generate() {
         assert!(new_channel_id() <= u32::MAX)
     }
+
+    #[test]
+    fn sequential_ids() {
+        let mut ids = ChannelIdAllocator::new(ChannelIdStrategy::Sequential);
+
+        assert_eq!(ids.allocate().unwrap(), 0);
+        assert_eq!(ids.allocate_group().unwrap(), 1);
+        assert_eq!(ids.allocate().unwrap(), 2);
+
+        // Freed ids are not reused until the sequence wraps around.
+        assert!(ids.free(0));
+        assert_eq!(ids.allocate().unwrap(), 3);
+    }
+
+    #[test]
+    fn sequential_ids_skip_live_ids() {
+        let mut ids = ChannelIdAllocator::new(ChannelIdStrategy::Sequential);
+        ids.next = u32::MAX;
+
+        assert_eq!(ids.allocate().unwrap(), u32::MAX);
+        assert_eq!(ids.allocate().unwrap(), 0);
+        assert_eq!(ids.allocate_group().unwrap(), 1);
+
+        ids.next = u32::MAX;
+        assert_eq!(ids.allocate().unwrap(), 2);
+    }
+
+    #[test]
+    fn random_ids_are_unique() {
+        let mut ids = ChannelIdAllocator::new(ChannelIdStrategy::Random);
+
+        let group_channel_id = ids.allocate_group().unwrap();
+        let mut allocated = HashSet::new();
+        for _ in 0..1000 {
+            let id = ids.allocate().unwrap();
+            assert_ne!(id, group_channel_id);
+            assert!(allocated.insert(id));
+        }
+
+        for id in allocated {
+            assert!(ids.is_allocated(id));
+            assert!(ids.free(id));
+        }
+    }
+
+    #[test]
+    fn free_ids() {
+        let mut ids = ChannelIdAllocator::new(ChannelIdStrategy::Sequential);
+        let channel_id = ids.allocate().unwrap();
+        let group_channel_id = ids.allocate_group().unwrap();
+
+        assert!(!ids.free_group(channel_id));
+        assert!(!ids.free(group_channel_id));
+
+        assert!(ids.free(channel_id));
+        assert!(!ids.free(channel_id));
+        assert!(ids.free_group(group_channel_id));
+        assert!(!ids.is_group(group_channel_id));
+    }
 }
@@ -0,0 +1,157 @@
+//! Hex encoding of byte strings. With the `serde` feature, the submodules are
+//! used with `#[serde(with = "...")]` on fields that are not a library type.
+use crate::error::{Error, Result};
+#[cfg(feature = "serde")]
+use serde::de::Error as _;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serializer};
+#[cfg(feature = "serde")]
+use std::convert::TryInto;
+
+/// Encodes bytes as a lowercase hex string.
+pub fn encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Decodes a hex string, a DeserializationError is returned for an odd
+/// length or a non hex character.
+pub fn decode(hex: &str) -> Result<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return Err(Error::DeserializationError(
+            "hex string must be an even number of hex characters".into(),
+        ));
+    }
+
+    Ok((0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect())
+}
+
+/// Deserializes a hex string into bytes.
+#[cfg(feature = "serde")]
+pub(crate) fn deserialize_bytes<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Vec<u8>, D::Error> {
+    decode(&String::deserialize(deserializer)?).map_err(D::Error::custom)
+}
+
+#[cfg(feature = "serde")]
+fn to_array<const N: usize, E: serde::de::Error>(
+    bytes: Vec<u8>,
+) -> std::result::Result<[u8; N], E> {
+    bytes
+        .try_into()
+        .map_err(|_| E::custom(format!("expected {} hex encoded bytes", N)))
+}
+
+/// Hex encoding of a `Vec<u8>`.
+#[cfg(feature = "serde")]
+pub(crate) mod bytes {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(
+        bytes: &[u8],
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&encode(bytes))
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Vec<u8>, D::Error> {
+        deserialize_bytes(deserializer)
+    }
+}
+
+/// Hex encoding of a fixed size byte array, such as a `U256`.
+#[cfg(feature = "serde")]
+pub(crate) mod array {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer, const N: usize>(
+        bytes: &[u8; N],
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&encode(bytes))
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>, const N: usize>(
+        deserializer: D,
+    ) -> std::result::Result<[u8; N], D::Error> {
+        to_array(deserialize_bytes(deserializer)?)
+    }
+}
+
+/// Hex encoding of a list of fixed size byte arrays, such as a `Vec<U256>`.
+#[cfg(feature = "serde")]
+pub(crate) mod array_list {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer, const N: usize>(
+        list: &[[u8; N]],
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_seq(list.iter().map(|bytes| encode(bytes)))
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>, const N: usize>(
+        deserializer: D,
+    ) -> std::result::Result<Vec<[u8; N]>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|hex| to_array(decode(hex).map_err(D::Error::custom)?))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "serde")]
+    use serde::Serialize;
+
+    #[cfg(feature = "serde")]
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Fields {
+        #[serde(with = "bytes")]
+        bytes: Vec<u8>,
+        #[serde(with = "array")]
+        array: [u8; 4],
+        #[serde(with = "array_list")]
+        array_list: Vec<[u8; 2]>,
+    }
+
+    #[test]
+    fn encode_decode() {
+        assert_eq!(encode(&[0x00, 0x0f, 0xab]), "000fab");
+        assert_eq!(decode("000fAB").unwrap(), vec![0x00, 0x0f, 0xab]);
+        assert_eq!(decode("").unwrap(), Vec::<u8>::new());
+
+        assert!(decode("abc").is_err());
+        assert!(decode("zz").is_err());
+        assert!(decode("éé").is_err());
+        assert!(decode("+f").is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn hex_fields() {
+        let fields = Fields {
+            bytes: vec![0x01, 0x02],
+            array: [0xde, 0xad, 0xbe, 0xef],
+            array_list: vec![[0x00, 0x01], [0xff, 0xfe]],
+        };
+
+        let json = serde_json::to_string(&fields).unwrap();
+        assert_eq!(
+            json,
+            r#"{"bytes":"0102","array":"deadbeef","array_list":["0001","fffe"]}"#
+        );
+        assert_eq!(serde_json::from_str::<Fields>(&json).unwrap(), fields);
+
+        // Arrays must have the exact length.
+        let short = r#"{"bytes":"","array":"dead","array_list":["00"]}"#;
+        assert!(serde_json::from_str::<Fields>(short).is_err());
+    }
+}
@@ -1,9 +1,14 @@
 use crate::error::{Error, Result};
+use crate::types::U256;
 use crate::{Frameable, Serializable};
+use sha2::{Digest, Sha256};
+use std::convert::TryInto;
 use std::time::SystemTime;
 
 mod channel_id;
-pub use channel_id::new_channel_id;
+pub use channel_id::{new_channel_id, ChannelIdAllocator, ChannelIdStrategy};
+
+pub mod hex;
 
 /// Convert SystemTime to a Unix timestamp as a u32.
 pub fn system_unix_time_to_u32(time: &SystemTime) -> Result<u32> {
@@ -30,6 +35,51 @@ pub fn serialize<T: Serializable>(val: T) -> Result<Vec<u8>> {
     Ok(buffer)
 }
 
+/// Returns the double SHA256 of the input, as used for block header hashes,
+/// transaction ids and merkle tree nodes.
+pub fn double_sha256(data: &[u8]) -> U256 {
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(&Sha256::digest(&Sha256::digest(data)));
+
+    hash
+}
+
+/// Serializes a Bitcoin compact size integer.
+pub fn compact_size(value: u64) -> Vec<u8> {
+    match value {
+        0..=0xfc => vec![value as u8],
+        0xfd..=0xffff => [&[0xfd], &(value as u16).to_le_bytes()[..]].concat(),
+        0x1_0000..=0xffff_ffff => [&[0xfe], &(value as u32).to_le_bytes()[..]].concat(),
+        _ => [&[0xff], &value.to_le_bytes()[..]].concat(),
+    }
+}
+
+/// Deserializes a Bitcoin compact size integer from the start of `bytes`,
+/// returning the integer and the number of bytes it was encoded with. A
+/// ParseError is returned if `bytes` ends before the integer.
+pub fn read_compact_size(bytes: &[u8]) -> Result<(u64, usize)> {
+    let mut parser = ByteParser::new(bytes, 0);
+    let value = match parser.next_by(1)?[0] {
+        0xfd => u16::from_le_bytes(parser.next_by(2)?.try_into()?) as u64,
+        0xfe => u32::from_le_bytes(parser.next_by(4)?.try_into()?) as u64,
+        0xff => u64::from_le_bytes(parser.next_by(8)?.try_into()?),
+        value => value as u64,
+    };
+
+    Ok((value, parser.offset()))
+}
+
+/// Deserializes a BOOL, a single byte that MUST be either 0 or 1.
+pub(crate) fn deserialize_bool(byte: u8) -> Result<bool> {
+    match byte {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(Error::DeserializationError(
+            "received an invalid value for a BOOL".into(),
+        )),
+    }
+}
+
 /// ByteParser is a custom iterator-like struct. It's used to extract segments
 /// from a slice using by providing an offset to return the bytes from start
 /// to step.
@@ -44,7 +94,10 @@ impl<'a> ByteParser<'a> {
     }
 
     pub(crate) fn next_by(&mut self, step: usize) -> Result<&'a [u8]> {
-        let offset = self.start + step;
+        let offset = self
+            .start
+            .checked_add(step)
+            .ok_or_else(|| Error::ParseError("out of bounds error".into()))?;
 
         let b = self.bytes.get(self.start..offset);
         if b.is_none() {
@@ -54,4 +107,22 @@ impl<'a> ByteParser<'a> {
         self.start = offset;
         Ok(b.unwrap())
     }
+
+    /// Parses a Bitcoin compact size integer.
+    pub(crate) fn next_compact_size(&mut self) -> Result<u64> {
+        let (value, size) = read_compact_size(self.bytes.get(self.start..).unwrap_or(&[]))?;
+        self.start += size;
+
+        Ok(value)
+    }
+
+    /// Returns the offset of the next byte to be parsed.
+    pub(crate) fn offset(&self) -> usize {
+        self.start
+    }
+
+    /// Returns the number of bytes left to be parsed.
+    pub(crate) fn remaining(&self) -> usize {
+        self.bytes.len().saturating_sub(self.start)
+    }
 }
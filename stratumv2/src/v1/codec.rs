@@ -0,0 +1,866 @@
+use crate::error::{Error, Result};
+use crate::mining::SubmitSharesErrorCodes;
+use crate::types::U256;
+use crate::util::hex;
+use crate::v1::messages::VERSION_ROLLING;
+use crate::v1::{
+    Authorize, Configure, ConfigureResult, Notify, SetDifficulty, SetExtranonce, Submit, Subscribe,
+    SubscribeResult,
+};
+use serde_json::{json, Map, Value};
+use std::convert::TryInto;
+
+/// The maximum length of a line, protecting the codec from peers that never
+/// send a newline.
+const MAX_LINE_LENGTH: usize = 64 * 1024;
+
+/// A request sent by a V1 miner.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    Subscribe(Subscribe),
+    Authorize(Authorize),
+    Configure(Configure),
+    Submit(Submit),
+}
+
+/// A notification sent to a V1 miner.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Notification {
+    Notify(Notify),
+    SetDifficulty(SetDifficulty),
+    SetExtranonce(SetExtranonce),
+}
+
+/// The error of a rejected request, serialized as `[code, message, null]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new<T: Into<String>>(code: i64, message: T) -> RpcError {
+        RpcError {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// Converts the error code of a rejected V2 share into the error codes
+/// commonly used by V1 pools.
+impl From<SubmitSharesErrorCodes> for RpcError {
+    fn from(error_code: SubmitSharesErrorCodes) -> Self {
+        let code = match error_code {
+            SubmitSharesErrorCodes::InvalidChannelId => 20,
+            SubmitSharesErrorCodes::StaleShare | SubmitSharesErrorCodes::InvalidJobId => 21,
+            SubmitSharesErrorCodes::DuplicateShare => 22,
+            SubmitSharesErrorCodes::DifficultyTooLow => 23,
+        };
+
+        RpcError::new(code, error_code.to_string())
+    }
+}
+
+/// The response to a [Request](enum.Request.html). `mining.authorize` and
+/// `mining.submit` are answered with a boolean.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Response {
+    Subscribe(SubscribeResult),
+    Configure(ConfigureResult),
+    Boolean(bool),
+    Error(RpcError),
+}
+
+/// The id of a JSON-RPC request, echoed in its response. An error response
+/// to a request whose id could not be read has a null id.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Id {
+    Number(u64),
+    String(String),
+    Null,
+}
+
+impl From<u64> for Id {
+    fn from(id: u64) -> Self {
+        Id::Number(id)
+    }
+}
+
+impl From<&str> for Id {
+    fn from(id: &str) -> Self {
+        Id::String(id.into())
+    }
+}
+
+/// A Stratum V1 JSON-RPC message.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    Request { id: Id, request: Request },
+    Response { id: Id, response: Response },
+    Notification(Notification),
+}
+
+/// Serializes a message as a single line of JSON, terminated by a newline.
+///
+/// # Examples
+///
+/// ```rust
+/// use stratumv2::v1::{decode, encode, Message, Request, Subscribe};
+///
+/// let message = Message::Request {
+///     id: 1.into(),
+///     request: Request::Subscribe(Subscribe::new(Some("cgminer/4.10.0".into()))),
+/// };
+///
+/// let line = encode(&message).unwrap();
+/// assert_eq!(
+///     line,
+///     "{\"id\":1,\"method\":\"mining.subscribe\",\"params\":[\"cgminer/4.10.0\"]}\n"
+/// );
+/// assert_eq!(decode(&line).unwrap(), message);
+/// ```
+pub fn encode(message: &Message) -> Result<String> {
+    let value = match message {
+        Message::Request { id, request } => {
+            let (method, params) = encode_request(request);
+            json!({ "id": encode_id(id), "method": method, "params": params })
+        }
+        Message::Response { id, response } => match response {
+            Response::Error(error) => json!({
+                "id": encode_id(id),
+                "result": null,
+                "error": [error.code, error.message, null],
+            }),
+            response => json!({
+                "id": encode_id(id),
+                "result": encode_response(response),
+                "error": null,
+            }),
+        },
+        Message::Notification(notification) => {
+            let (method, params) = encode_notification(notification);
+            json!({ "id": null, "method": method, "params": params })
+        }
+    };
+
+    let mut line = serde_json::to_string(&value)?;
+    line.push('\n');
+
+    Ok(line)
+}
+
+/// Deserializes a single line of JSON into a message.
+///
+/// A JsonError is returned if the line is not valid JSON and a ParseError is
+/// returned if it is not a valid Stratum V1 message.
+pub fn decode(line: &str) -> Result<Message> {
+    let value: Value = serde_json::from_str(line)?;
+    let object = value
+        .as_object()
+        .ok_or_else(|| parse_error("the message is not a JSON object"))?;
+
+    let method = match object.get("method") {
+        Some(method) => method
+            .as_str()
+            .ok_or_else(|| parse_error("the method is not a string"))?,
+        None => {
+            let response = decode_response(object)?;
+            let id = match (decode_id(object)?, &response) {
+                (Id::Null, Response::Error(_)) => Id::Null,
+                (Id::Null, _) => return Err(parse_error("the response id is null")),
+                (id, _) => id,
+            };
+
+            return Ok(Message::Response { id, response });
+        }
+    };
+
+    let empty = vec![];
+    let params = match object.get("params") {
+        Some(Value::Array(params)) => params,
+        Some(Value::Null) | None => &empty,
+        Some(_) => return Err(parse_error("the params are not an array")),
+    };
+
+    let request = match method {
+        "mining.subscribe" => Request::Subscribe(Subscribe::new(
+            params.first().and_then(Value::as_str).map(String::from),
+        )),
+        "mining.authorize" => Request::Authorize(Authorize::new(
+            string_param(params, 0)?,
+            params
+                .get(1)
+                .and_then(Value::as_str)
+                .unwrap_or("")
+                .to_string(),
+        )),
+        "mining.configure" => Request::Configure(decode_configure(params)?),
+        "mining.submit" => Request::Submit(Submit::new(
+            string_param(params, 0)?,
+            string_param(params, 1)?,
+            hex_param(params, 2)?,
+            u32_param(params, 3)?,
+            u32_param(params, 4)?,
+            match params.get(5) {
+                Some(Value::Null) | None => None,
+                Some(_) => Some(u32_param(params, 5)?),
+            },
+        )),
+        "mining.notify" => return Ok(Message::Notification(decode_notify(params)?)),
+        "mining.set_difficulty" => {
+            let difficulty = params
+                .first()
+                .and_then(Value::as_f64)
+                .ok_or_else(|| parse_error("the difficulty is not a number"))?;
+
+            return Ok(Message::Notification(Notification::SetDifficulty(
+                SetDifficulty::new(difficulty),
+            )));
+        }
+        "mining.set_extranonce" => {
+            return Ok(Message::Notification(Notification::SetExtranonce(
+                SetExtranonce::new(hex_param(params, 0)?, u16_param(params, 1)?),
+            )))
+        }
+        _ => return Err(parse_error("the method is unknown")),
+    };
+
+    match decode_id(object)? {
+        Id::Null => Err(parse_error("the request id is null")),
+        id => Ok(Message::Request { id, request }),
+    }
+}
+
+/// LineCodec splits the bytes received from a V1 peer into newline
+/// delimited messages.
+///
+/// # Examples
+///
+/// ```rust
+/// use stratumv2::v1::{LineCodec, Message, Notification};
+///
+/// let mut codec = LineCodec::new();
+///
+/// codec.push(b"{\"id\":null,\"method\":\"mining.set_difficulty\",");
+/// assert!(codec.next_message().is_none());
+///
+/// codec.push(b"\"params\":[1024]}\n");
+/// match codec.next_message().unwrap().unwrap() {
+///     Message::Notification(Notification::SetDifficulty(message)) => {
+///         assert_eq!(message.difficulty, 1024.0)
+///     }
+///     _ => panic!("expected mining.set_difficulty"),
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct LineCodec {
+    buffer: Vec<u8>,
+}
+
+impl LineCodec {
+    pub fn new() -> LineCodec {
+        LineCodec::default()
+    }
+
+    /// Appends received bytes to the codec.
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Returns the next complete message, or None if no complete line was
+    /// received. Empty lines are skipped.
+    ///
+    /// A ParseError is returned if a line is longer than 64 KiB, after which
+    /// the buffered bytes are discarded.
+    pub fn next_message(&mut self) -> Option<Result<Message>> {
+        loop {
+            let end = match self.buffer.iter().position(|byte| *byte == b'\n') {
+                Some(end) => end,
+                None if self.buffer.len() > MAX_LINE_LENGTH => {
+                    self.buffer.clear();
+                    return Some(Err(parse_error("the line exceeds the maximum length")));
+                }
+                None => return None,
+            };
+
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = match std::str::from_utf8(&line) {
+                Ok(line) => line.trim(),
+                Err(error) => return Some(Err(error.into())),
+            };
+
+            if !line.is_empty() {
+                return Some(decode(line));
+            }
+        }
+    }
+}
+
+fn encode_request(request: &Request) -> (&'static str, Value) {
+    match request {
+        Request::Subscribe(message) => (
+            "mining.subscribe",
+            match &message.user_agent {
+                Some(user_agent) => json!([user_agent]),
+                None => json!([]),
+            },
+        ),
+        Request::Authorize(message) => (
+            "mining.authorize",
+            json!([message.username, message.password]),
+        ),
+        Request::Configure(message) => {
+            let mut params = Map::new();
+            if let Some(mask) = message.version_rolling_mask {
+                params.insert("version-rolling.mask".into(), json!(encode_u32(mask)));
+            }
+            if let Some(min_bit_count) = message.version_rolling_min_bit_count {
+                params.insert("version-rolling.min-bit-count".into(), json!(min_bit_count));
+            }
+
+            ("mining.configure", json!([message.extensions, params]))
+        }
+        Request::Submit(message) => {
+            let mut params = vec![
+                json!(message.worker_name),
+                json!(message.job_id),
+                json!(hex::encode(&message.extranonce2)),
+                json!(encode_u32(message.ntime)),
+                json!(encode_u32(message.nonce)),
+            ];
+            if let Some(version_bits) = message.version_bits {
+                params.push(json!(encode_u32(version_bits)));
+            }
+
+            ("mining.submit", Value::Array(params))
+        }
+    }
+}
+
+fn encode_response(response: &Response) -> Value {
+    match response {
+        Response::Subscribe(result) => json!([
+            [
+                ["mining.set_difficulty", result.subscription_id],
+                ["mining.notify", result.subscription_id]
+            ],
+            hex::encode(&result.extranonce1),
+            result.extranonce2_size,
+        ]),
+        Response::Configure(result) => match result.version_rolling_mask {
+            Some(mask) => json!({
+                VERSION_ROLLING: true,
+                "version-rolling.mask": encode_u32(mask),
+            }),
+            None => json!({ VERSION_ROLLING: false }),
+        },
+        Response::Boolean(result) => json!(result),
+        Response::Error(error) => json!([error.code, error.message, null]),
+    }
+}
+
+fn encode_notification(notification: &Notification) -> (&'static str, Value) {
+    match notification {
+        Notification::Notify(message) => (
+            "mining.notify",
+            json!([
+                message.job_id,
+                encode_prev_hash(&message.prev_hash),
+                hex::encode(&message.coinb1),
+                hex::encode(&message.coinb2),
+                message
+                    .merkle_branch
+                    .iter()
+                    .map(|hash| hex::encode(hash))
+                    .collect::<Vec<String>>(),
+                encode_u32(message.version),
+                encode_u32(message.nbits),
+                encode_u32(message.ntime),
+                message.clean_jobs,
+            ]),
+        ),
+        Notification::SetDifficulty(message) => {
+            ("mining.set_difficulty", json!([message.difficulty]))
+        }
+        Notification::SetExtranonce(message) => (
+            "mining.set_extranonce",
+            json!([hex::encode(&message.extranonce1), message.extranonce2_size]),
+        ),
+    }
+}
+
+fn encode_id(id: &Id) -> Value {
+    match id {
+        Id::Number(id) => json!(id),
+        Id::String(id) => json!(id),
+        Id::Null => Value::Null,
+    }
+}
+
+fn decode_id(object: &Map<String, Value>) -> Result<Id> {
+    match object.get("id") {
+        Some(Value::Null) | None => Ok(Id::Null),
+        Some(Value::String(id)) => Ok(Id::String(id.clone())),
+        Some(id) => id
+            .as_u64()
+            .map(Id::Number)
+            .ok_or_else(|| parse_error("the id is not an unsigned integer or a string")),
+    }
+}
+
+fn decode_response(object: &Map<String, Value>) -> Result<Response> {
+    match object.get("error") {
+        Some(Value::Null) | None => (),
+        Some(Value::Array(error)) => {
+            return Ok(Response::Error(RpcError::new(
+                error.first().and_then(Value::as_i64).unwrap_or(20),
+                error.get(1).and_then(Value::as_str).unwrap_or(""),
+            )))
+        }
+        Some(Value::Object(error)) => {
+            return Ok(Response::Error(RpcError::new(
+                error.get("code").and_then(Value::as_i64).unwrap_or(20),
+                error.get("message").and_then(Value::as_str).unwrap_or(""),
+            )))
+        }
+        Some(_) => return Err(parse_error("the error is invalid")),
+    }
+
+    match object.get("result") {
+        Some(Value::Bool(result)) => Ok(Response::Boolean(*result)),
+        Some(Value::Array(result)) => {
+            let subscription_id = result
+                .first()
+                .and_then(Value::as_array)
+                .and_then(|subscriptions| subscriptions.first())
+                .and_then(Value::as_array)
+                .and_then(|subscription| subscription.get(1))
+                .and_then(Value::as_str)
+                .unwrap_or("");
+
+            Ok(Response::Subscribe(SubscribeResult::new(
+                subscription_id,
+                hex_param(result, 1)?,
+                u16_param(result, 2)?,
+            )))
+        }
+        Some(Value::Object(result)) => {
+            let version_rolling = result.get(VERSION_ROLLING).and_then(Value::as_bool);
+            let mask = match (version_rolling, result.get("version-rolling.mask")) {
+                (Some(true), Some(mask)) => Some(decode_u32(mask)?),
+                _ => None,
+            };
+
+            Ok(Response::Configure(ConfigureResult::new(mask)))
+        }
+        _ => Err(parse_error("the result is invalid")),
+    }
+}
+
+fn decode_configure(params: &[Value]) -> Result<Configure> {
+    let extensions = params
+        .first()
+        .and_then(Value::as_array)
+        .ok_or_else(|| parse_error("the extensions are not an array"))?
+        .iter()
+        .map(|name| {
+            name.as_str()
+                .map(String::from)
+                .ok_or_else(|| parse_error("the extension name is not a string"))
+        })
+        .collect::<Result<Vec<String>>>()?;
+
+    let empty = Map::new();
+    let extension_params = params.get(1).and_then(Value::as_object).unwrap_or(&empty);
+
+    Ok(Configure {
+        extensions,
+        version_rolling_mask: extension_params
+            .get("version-rolling.mask")
+            .map(decode_u32)
+            .transpose()?,
+        version_rolling_min_bit_count: extension_params
+            .get("version-rolling.min-bit-count")
+            .and_then(Value::as_u64)
+            .map(|count| count as u32),
+    })
+}
+
+fn decode_notify(params: &[Value]) -> Result<Notification> {
+    let merkle_branch = params
+        .get(4)
+        .and_then(Value::as_array)
+        .ok_or_else(|| parse_error("the merkle branch is not an array"))?
+        .iter()
+        .map(|hash| decode_u256(&decode_hex(hash)?))
+        .collect::<Result<Vec<U256>>>()?;
+
+    Ok(Notification::Notify(Notify {
+        job_id: string_param(params, 0)?,
+        prev_hash: decode_prev_hash(&hex_param(params, 1)?)?,
+        coinb1: hex_param(params, 2)?,
+        coinb2: hex_param(params, 3)?,
+        merkle_branch,
+        version: u32_param(params, 5)?,
+        nbits: u32_param(params, 6)?,
+        ntime: u32_param(params, 7)?,
+        clean_jobs: params
+            .get(8)
+            .and_then(Value::as_bool)
+            .ok_or_else(|| parse_error("clean_jobs is not a boolean"))?,
+    }))
+}
+
+fn parse_error(message: &str) -> Error {
+    Error::ParseError(message.into())
+}
+
+fn string_param(params: &[Value], index: usize) -> Result<String> {
+    params
+        .get(index)
+        .and_then(Value::as_str)
+        .map(String::from)
+        .ok_or_else(|| parse_error("a param is not a string"))
+}
+
+fn hex_param(params: &[Value], index: usize) -> Result<Vec<u8>> {
+    decode_hex(params.get(index).unwrap_or(&Value::Null))
+}
+
+fn u32_param(params: &[Value], index: usize) -> Result<u32> {
+    decode_u32(params.get(index).unwrap_or(&Value::Null))
+}
+
+fn u16_param(params: &[Value], index: usize) -> Result<u16> {
+    params
+        .get(index)
+        .and_then(Value::as_u64)
+        .and_then(|value| value.try_into().ok())
+        .ok_or_else(|| parse_error("a param is not a 16 bit integer"))
+}
+
+fn decode_hex(value: &Value) -> Result<Vec<u8>> {
+    value
+        .as_str()
+        .and_then(|hex| hex::decode(hex).ok())
+        .ok_or_else(|| parse_error("a param is not a hex string"))
+}
+
+/// Encodes a u32 as the 8 big endian hex characters used by V1.
+fn encode_u32(value: u32) -> String {
+    format!("{:08x}", value)
+}
+
+fn decode_u32(value: &Value) -> Result<u32> {
+    let hex = value
+        .as_str()
+        .ok_or_else(|| parse_error("a param is not a hex string"))?;
+
+    if hex.is_empty() || !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return Err(parse_error("a param is not a 32 bit hex string"));
+    }
+
+    u32::from_str_radix(hex, 16).map_err(|_| parse_error("a param is not a 32 bit hex string"))
+}
+
+fn decode_u256(bytes: &[u8]) -> Result<U256> {
+    bytes
+        .try_into()
+        .map_err(|_| parse_error("a param is not a 32 byte hash"))
+}
+
+/// V1 serializes the prev hash as eight 32 bit words in header order, each
+/// word in big endian.
+fn encode_prev_hash(prev_hash: &U256) -> String {
+    let mut bytes = *prev_hash;
+    bytes.chunks_mut(4).for_each(|word| word.reverse());
+
+    hex::encode(&bytes)
+}
+
+fn decode_prev_hash(bytes: &[u8]) -> Result<U256> {
+    let mut prev_hash = decode_u256(bytes)?;
+    prev_hash.chunks_mut(4).for_each(|word| word.reverse());
+
+    Ok(prev_hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_subscribe() {
+        let message = decode(r#"{"id": 1, "method": "mining.subscribe", "params": []}"#).unwrap();
+        assert_eq!(
+            message,
+            Message::Request {
+                id: 1.into(),
+                request: Request::Subscribe(Subscribe::new(None)),
+            }
+        );
+
+        let result = SubscribeResult::new(
+            "ae6812eb4cd7735a302a8a9dd95cf71f",
+            vec![0x08, 0x00, 0x00, 0x2a],
+            4,
+        );
+        let message = Message::Response {
+            id: 1.into(),
+            response: Response::Subscribe(result),
+        };
+        let line = encode(&message).unwrap();
+        assert_eq!(
+            line,
+            "{\"error\":null,\"id\":1,\"result\":[[[\"mining.set_difficulty\",\"ae6812eb4cd7735a302a8a9dd95cf71f\"],[\"mining.notify\",\"ae6812eb4cd7735a302a8a9dd95cf71f\"]],\"0800002a\",4]}\n"
+        );
+        assert_eq!(decode(&line).unwrap(), message);
+
+        // String ids are echoed back as strings.
+        let message =
+            decode(r#"{"id": "a1", "method": "mining.subscribe", "params": []}"#).unwrap();
+        assert_eq!(
+            message,
+            Message::Request {
+                id: "a1".into(),
+                request: Request::Subscribe(Subscribe::new(None)),
+            }
+        );
+        let message = Message::Response {
+            id: "a1".into(),
+            response: Response::Boolean(true),
+        };
+        let line = encode(&message).unwrap();
+        assert!(line.contains("\"id\":\"a1\""));
+        assert_eq!(decode(&line).unwrap(), message);
+    }
+
+    #[test]
+    fn decode_authorize() {
+        let message = decode(
+            r#"{"params": ["slush.miner1", "password"], "id": 2, "method": "mining.authorize"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            message,
+            Message::Request {
+                id: 2.into(),
+                request: Request::Authorize(Authorize::new("slush.miner1", "password")),
+            }
+        );
+
+        let message = decode(r#"{"error": null, "id": 2, "result": true}"#).unwrap();
+        assert_eq!(
+            message,
+            Message::Response {
+                id: 2.into(),
+                response: Response::Boolean(true),
+            }
+        );
+    }
+
+    // The mining.notify of the Stratum V1 documentation.
+    const NOTIFY: &str = r#"{"params": ["bf", "4d16b6f85af6e2198f44ae2a6de67f78487ae5611b77c6c0440b921e00000000",
+        "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff20020862062f503253482f04b8864e5008",
+        "072f736c7573682f000000000100f2052a010000001976a914d23fcdf86f7e756a64a7a9688ef9903327048ed988ac00000000", [],
+        "00000002", "1c2ac4af", "504e86b9", false], "id": null, "method": "mining.notify"}"#;
+
+    #[test]
+    fn decode_notify_message() {
+        let notify = match decode(NOTIFY).unwrap() {
+            Message::Notification(Notification::Notify(notify)) => notify,
+            _ => panic!("expected mining.notify"),
+        };
+
+        assert_eq!(notify.job_id, "bf");
+        assert_eq!(notify.prev_hash[0..4], [0xf8, 0xb6, 0x16, 0x4d]);
+        assert_eq!(notify.prev_hash[28..32], [0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(notify.coinb1.len(), 58);
+        assert!(notify.merkle_branch.is_empty());
+        assert_eq!(notify.version, 2);
+        assert_eq!(notify.nbits, 0x1c2a_c4af);
+        assert_eq!(notify.ntime, 0x504e_86b9);
+        assert!(!notify.clean_jobs);
+
+        let message = Message::Notification(Notification::Notify(notify));
+        assert_eq!(decode(&encode(&message).unwrap()).unwrap(), message);
+    }
+
+    #[test]
+    fn decode_submit() {
+        let message = decode(
+            r#"{"params": ["slush.miner1", "bf", "00000001", "504e86ed", "b2957c02"], "id": 4, "method": "mining.submit"}"#,
+        )
+        .unwrap();
+        let submit = Submit::new(
+            "slush.miner1",
+            "bf",
+            vec![0x00, 0x00, 0x00, 0x01],
+            0x504e_86ed,
+            0xb295_7c02,
+            None,
+        );
+        assert_eq!(
+            message,
+            Message::Request {
+                id: 4.into(),
+                request: Request::Submit(submit.clone()),
+            }
+        );
+
+        let mut submit = submit;
+        submit.version_bits = Some(0x0000_e000);
+        let message = Message::Request {
+            id: 5.into(),
+            request: Request::Submit(submit),
+        };
+        let line = encode(&message).unwrap();
+        assert!(line.contains("\"0000e000\"]"));
+        assert_eq!(decode(&line).unwrap(), message);
+    }
+
+    #[test]
+    fn decode_configure() {
+        let message = decode(
+            r#"{"method": "mining.configure", "id": 1, "params": [["minimum-difficulty", "version-rolling"],
+            {"minimum-difficulty.value": 2048, "version-rolling.mask": "00fff000", "version-rolling.min-bit-count": 2}]}"#,
+        )
+        .unwrap();
+
+        let configure = match message {
+            Message::Request {
+                request: Request::Configure(configure),
+                ..
+            } => configure,
+            _ => panic!("expected mining.configure"),
+        };
+        assert!(configure.requests_version_rolling());
+        assert_eq!(configure.version_rolling_mask, Some(0x00ff_f000));
+        assert_eq!(configure.version_rolling_min_bit_count, Some(2));
+
+        let message = decode(
+            r#"{"error": null, "id": 1, "result": {"version-rolling": true, "version-rolling.mask": "00ffe000"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            message,
+            Message::Response {
+                id: 1.into(),
+                response: Response::Configure(ConfigureResult::new(Some(0x00ff_e000))),
+            }
+        );
+
+        let message = Message::Response {
+            id: 1.into(),
+            response: Response::Configure(ConfigureResult::new(None)),
+        };
+        assert_eq!(decode(&encode(&message).unwrap()).unwrap(), message);
+    }
+
+    #[test]
+    fn decode_notifications() {
+        let message = Message::Notification(Notification::SetExtranonce(SetExtranonce::new(
+            vec![0xaa, 0xbb],
+            6,
+        )));
+        let line = encode(&message).unwrap();
+        assert_eq!(
+            line,
+            "{\"id\":null,\"method\":\"mining.set_extranonce\",\"params\":[\"aabb\",6]}\n"
+        );
+        assert_eq!(decode(&line).unwrap(), message);
+
+        let message =
+            decode(r#"{"id": null, "method": "mining.set_difficulty", "params": [0.5]}"#).unwrap();
+        assert_eq!(
+            message,
+            Message::Notification(Notification::SetDifficulty(SetDifficulty::new(0.5)))
+        );
+    }
+
+    #[test]
+    fn decode_errors() {
+        let message =
+            decode(r#"{"id": 4, "result": null, "error": [21, "Job not found", null]}"#).unwrap();
+        assert_eq!(
+            message,
+            Message::Response {
+                id: 4.into(),
+                response: Response::Error(RpcError::new(21, "Job not found")),
+            }
+        );
+
+        // An error response to a request whose id could not be read.
+        let message =
+            decode(r#"{"id": null, "result": null, "error": [20, "Other", null]}"#).unwrap();
+        assert_eq!(
+            message,
+            Message::Response {
+                id: Id::Null,
+                response: Response::Error(RpcError::new(20, "Other")),
+            }
+        );
+        assert_eq!(decode(&encode(&message).unwrap()).unwrap(), message);
+
+        let error = RpcError::from(SubmitSharesErrorCodes::DuplicateShare);
+        assert_eq!(error, RpcError::new(22, "duplicate-share"));
+        let message = Message::Response {
+            id: 4.into(),
+            response: Response::Error(error),
+        };
+        assert_eq!(decode(&encode(&message).unwrap()).unwrap(), message);
+    }
+
+    #[test]
+    fn decode_invalid_messages() {
+        assert!(matches!(decode("not json"), Err(Error::JsonError(_))));
+        assert!(matches!(decode("[]"), Err(Error::ParseError(_))));
+        assert!(decode(r#"{"id": 1, "method": "mining.unknown", "params": []}"#).is_err());
+        assert!(decode(r#"{"id": null, "method": "mining.subscribe", "params": []}"#).is_err());
+        assert!(decode(r#"{"id": -1, "method": "mining.subscribe", "params": []}"#).is_err());
+        assert!(decode(r#"{"id": null, "result": true, "error": null}"#).is_err());
+        assert!(decode(
+            r#"{"id": 4, "method": "mining.submit", "params": ["w", "bf", "0g", "504e86ed", "b2957c02"]}"#
+        )
+        .is_err());
+        assert!(decode(
+            r#"{"id": 4, "method": "mining.submit", "params": ["w", "bf", "00", "504e86ed"]}"#
+        )
+        .is_err());
+        for ntime in ["+04e86ed", "-04e86ed", ""].iter() {
+            let line = format!(
+                r#"{{"id": 4, "method": "mining.submit", "params": ["w", "bf", "00", "{}", "b2957c02"]}}"#,
+                ntime
+            );
+            assert!(decode(&line).is_err());
+        }
+    }
+
+    #[test]
+    fn line_codec() {
+        let mut codec = LineCodec::new();
+        codec.push(b"\n{\"id\":1,\"result\":true,\"error\":null}\n{\"id\":2,");
+        assert_eq!(
+            codec.next_message().unwrap().unwrap(),
+            Message::Response {
+                id: 1.into(),
+                response: Response::Boolean(true),
+            }
+        );
+        assert!(codec.next_message().is_none());
+
+        codec.push(b"\"result\":false,\"error\":null}\r\n");
+        assert_eq!(
+            codec.next_message().unwrap().unwrap(),
+            Message::Response {
+                id: 2.into(),
+                response: Response::Boolean(false),
+            }
+        );
+
+        codec.push(&[b' '; MAX_LINE_LENGTH + 1]);
+        assert!(codec.next_message().unwrap().is_err());
+        assert!(codec.next_message().is_none());
+    }
+}
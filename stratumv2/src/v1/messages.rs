@@ -0,0 +1,269 @@
+use crate::error::Result;
+use crate::mining::{SetupConnectionFlags, VersionRolling};
+use crate::target::Target;
+use crate::types::{FlagSet, U256};
+
+/// Subscribe is the `mining.subscribe` request, sent by a V1 miner to
+/// receive work.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subscribe {
+    /// The name and version of the mining software.
+    pub user_agent: Option<String>,
+}
+
+impl Subscribe {
+    pub fn new(user_agent: Option<String>) -> Subscribe {
+        Subscribe { user_agent }
+    }
+}
+
+/// SubscribeResult is the result of a `mining.subscribe` request. It assigns
+/// the extranonce space of the miner.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubscribeResult {
+    /// An identifier of the subscription.
+    pub subscription_id: String,
+
+    /// The bytes of the coinbase extranonce fixed for the miner, placed
+    /// between `coinb1` and the `extranonce2` searched by the miner.
+    pub extranonce1: Vec<u8>,
+
+    /// The number of bytes of extranonce searched by the miner.
+    pub extranonce2_size: u16,
+}
+
+impl SubscribeResult {
+    pub fn new<T: Into<String>>(
+        subscription_id: T,
+        extranonce1: Vec<u8>,
+        extranonce2_size: u16,
+    ) -> SubscribeResult {
+        SubscribeResult {
+            subscription_id: subscription_id.into(),
+            extranonce1,
+            extranonce2_size,
+        }
+    }
+}
+
+/// Authorize is the `mining.authorize` request, sent by a V1 miner to
+/// authorize a worker submitting shares.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Authorize {
+    pub username: String,
+    pub password: String,
+}
+
+impl Authorize {
+    pub fn new<T: Into<String>>(username: T, password: T) -> Authorize {
+        Authorize {
+            username: username.into(),
+            password: password.into(),
+        }
+    }
+}
+
+/// Notify is the `mining.notify` notification, providing a new job to a V1
+/// miner.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notify {
+    pub job_id: String,
+
+    /// The hash of the previous block, in the internal byte order used by the
+    /// block header.
+    pub prev_hash: U256,
+
+    /// The coinbase transaction up to the start of `extranonce1`.
+    pub coinb1: Vec<u8>,
+
+    /// The coinbase transaction following the end of `extranonce2`.
+    pub coinb2: Vec<u8>,
+
+    /// The merkle path of the coinbase transaction.
+    pub merkle_branch: Vec<U256>,
+
+    pub version: u32,
+    pub nbits: u32,
+    pub ntime: u32,
+
+    /// If true, the miner MUST abandon its previous jobs.
+    pub clean_jobs: bool,
+}
+
+/// SetDifficulty is the `mining.set_difficulty` notification, setting the
+/// share difficulty of the following jobs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SetDifficulty {
+    pub difficulty: f64,
+}
+
+impl SetDifficulty {
+    pub fn new(difficulty: f64) -> SetDifficulty {
+        SetDifficulty { difficulty }
+    }
+
+    /// Creates the SetDifficulty of a channel target, using the pool share
+    /// difficulty.
+    pub fn from_target(target: &U256) -> SetDifficulty {
+        SetDifficulty::new(Target::from(*target).pool_difficulty())
+    }
+
+    /// Returns the share target of the difficulty.
+    ///
+    /// A RequirementError is returned if the difficulty is not positive.
+    pub fn target(&self) -> Result<U256> {
+        Ok(Target::from_pool_difficulty(self.difficulty)?.into())
+    }
+}
+
+/// Submit is the `mining.submit` request, sent by a V1 miner to submit a
+/// share.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Submit {
+    /// The name of an authorized worker.
+    pub worker_name: String,
+
+    pub job_id: String,
+
+    /// The extranonce searched by the miner, `extranonce2_size` bytes.
+    pub extranonce2: Vec<u8>,
+
+    pub ntime: u32,
+    pub nonce: u32,
+
+    /// The rolled bits of the version, if the miner rolls the version.
+    pub version_bits: Option<u32>,
+}
+
+impl Submit {
+    pub fn new<T: Into<String>>(
+        worker_name: T,
+        job_id: T,
+        extranonce2: Vec<u8>,
+        ntime: u32,
+        nonce: u32,
+        version_bits: Option<u32>,
+    ) -> Submit {
+        Submit {
+            worker_name: worker_name.into(),
+            job_id: job_id.into(),
+            extranonce2,
+            ntime,
+            nonce,
+            version_bits,
+        }
+    }
+}
+
+/// SetExtranonce is the `mining.set_extranonce` notification, replacing the
+/// extranonce space assigned to a V1 miner by its SubscribeResult.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SetExtranonce {
+    pub extranonce1: Vec<u8>,
+    pub extranonce2_size: u16,
+}
+
+impl SetExtranonce {
+    pub fn new(extranonce1: Vec<u8>, extranonce2_size: u16) -> SetExtranonce {
+        SetExtranonce {
+            extranonce1,
+            extranonce2_size,
+        }
+    }
+}
+
+/// The name of the version rolling extension of `mining.configure`.
+pub(crate) const VERSION_ROLLING: &str = "version-rolling";
+
+/// Configure is the `mining.configure` request (BIP310), sent by a V1 miner
+/// to negotiate protocol extensions. Only the `version-rolling` extension is
+/// interpreted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Configure {
+    /// The names of the requested extensions.
+    pub extensions: Vec<String>,
+
+    /// The bits of the version the miner wants to roll.
+    pub version_rolling_mask: Option<u32>,
+
+    /// The minimum number of bits the miner needs to roll.
+    pub version_rolling_min_bit_count: Option<u32>,
+}
+
+impl Configure {
+    /// Creates a Configure requesting the `version-rolling` extension.
+    pub fn version_rolling(mask: u32, min_bit_count: u32) -> Configure {
+        Configure {
+            extensions: vec![VERSION_ROLLING.into()],
+            version_rolling_mask: Some(mask),
+            version_rolling_min_bit_count: Some(min_bit_count),
+        }
+    }
+
+    /// Returns true if the miner requested the `version-rolling` extension.
+    pub fn requests_version_rolling(&self) -> bool {
+        self.extensions.iter().any(|name| name == VERSION_ROLLING)
+    }
+
+    /// Returns the SetupConnection flags equivalent to the requested
+    /// extensions.
+    pub fn setup_connection_flags(&self) -> FlagSet<SetupConnectionFlags> {
+        let mut flags = FlagSet::empty();
+        if self.requests_version_rolling() {
+            flags.insert(SetupConnectionFlags::RequiresVersionRolling);
+        }
+
+        flags
+    }
+}
+
+/// ConfigureResult is the result of a `mining.configure` request.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConfigureResult {
+    /// The bits of the version the miner is allowed to roll, or None if the
+    /// `version-rolling` extension is not supported.
+    pub version_rolling_mask: Option<u32>,
+}
+
+impl ConfigureResult {
+    pub fn new(version_rolling_mask: Option<u32>) -> ConfigureResult {
+        ConfigureResult {
+            version_rolling_mask,
+        }
+    }
+
+    /// Answers a Configure request, allowing the bits of the requested mask
+    /// that are allowed by `version_rolling`. Miners requesting no mask are
+    /// allowed every bit of `version_rolling`.
+    ///
+    /// Version rolling is refused if it was not requested, if no bit can be
+    /// rolled or if fewer bits than the requested minimum can be rolled.
+    pub fn from_version_rolling(
+        request: &Configure,
+        version_rolling: VersionRolling,
+    ) -> ConfigureResult {
+        if !request.requests_version_rolling() {
+            return ConfigureResult::new(None);
+        }
+
+        let mask = request.version_rolling_mask.unwrap_or(u32::MAX) & version_rolling.mask();
+        let min_bit_count = request.version_rolling_min_bit_count.unwrap_or(1).max(1);
+
+        if mask.count_ones() < min_bit_count {
+            return ConfigureResult::new(None);
+        }
+
+        ConfigureResult::new(Some(mask))
+    }
+
+    /// Returns the version rolling negotiated with the `mining.configure`.
+    ///
+    /// A RequirementError is returned if the mask contains bits outside of
+    /// the BIP320 general purpose bits.
+    pub fn version_rolling(&self) -> Result<VersionRolling> {
+        match self.version_rolling_mask {
+            Some(mask) => VersionRolling::new(mask),
+            None => Ok(VersionRolling::fixed()),
+        }
+    }
+}
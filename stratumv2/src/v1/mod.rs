@@ -0,0 +1,17 @@
+//! Stratum V1 is the JSON-RPC protocol spoken by legacy mining devices. The
+//! messages in this module are used to bridge V1 devices to a Stratum V2
+//! Upstream, see the [translator](../translator/index.html).
+
+/// The Stratum V1 messages.
+mod messages;
+
+/// The line delimited JSON-RPC codec.
+mod codec;
+
+pub use codec::{
+    decode, encode, Id, LineCodec, Message, Notification, Request, Response, RpcError,
+};
+pub use messages::{
+    Authorize, Configure, ConfigureResult, Notify, SetDifficulty, SetExtranonce, Submit, Subscribe,
+    SubscribeResult,
+};